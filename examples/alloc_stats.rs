@@ -0,0 +1,24 @@
+use mysql_binlog::alloc_stats::CountingAllocator;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} /path/to/binlog/file", args[0]);
+        std::process::exit(2);
+    }
+    let mut events = mysql_binlog::parse_file(&args[1])?;
+    let count = events.by_ref().flatten().count();
+    let stats = events.stats();
+    println!(
+        "{} events from {} bytes: {} allocations ({} bytes, {:.1} allocations per event)",
+        count,
+        stats.bytes_read,
+        stats.allocations,
+        stats.bytes_allocated,
+        stats.allocations as f64 / count.max(1) as f64
+    );
+    Ok(())
+}
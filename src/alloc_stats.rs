@@ -0,0 +1,112 @@
+//! Allocation counting, for tracking the cost of decoding in benchmarks.
+//!
+//! Install a [`CountingAllocator`] as the global allocator (wrapping the system allocator or any
+//! other, such as mimalloc or jemalloc) and every
+//! [`ParserStats`](crate::stats::ParserStats) will include the number of allocations made, and
+//! bytes allocated, while producing events:
+//!
+//! ```no_run
+//! use mysql_binlog::alloc_stats::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+//! ```
+//!
+//! Allocations are counted per thread, so work done on checksum worker threads (see
+//! [`checksum_threads`](crate::BinlogFileParserBuilder::checksum_threads)) is not included.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static BYTES_ALLOCATED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A global allocator which counts the allocations made on each thread before passing them on
+/// to another allocator
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+impl CountingAllocator<System> {
+    /// Count allocations made with the system allocator
+    pub const fn system() -> Self {
+        CountingAllocator { inner: System }
+    }
+}
+
+impl<A> CountingAllocator<A> {
+    /// Count allocations made with another allocator
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator { inner }
+    }
+}
+
+fn count(bytes: usize) {
+    // the counters may already have been destroyed if this thread is exiting
+    let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+    let _ = BYTES_ALLOCATED.try_with(|b| b.set(b.get() + bytes as u64));
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        self.inner.realloc(ptr, layout, new_size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Allocation counters for the current thread
+pub(crate) struct Counters {
+    pub(crate) allocations: u64,
+    pub(crate) bytes_allocated: u64,
+}
+
+impl Counters {
+    pub(crate) fn current() -> Self {
+        Counters {
+            allocations: ALLOCATIONS.with(Cell::get),
+            bytes_allocated: BYTES_ALLOCATED.with(Cell::get),
+        }
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+
+#[cfg(test)]
+mod tests {
+    use super::Counters;
+    use crate::parse_file;
+
+    #[test]
+    fn test_counts_allocations() {
+        let before = Counters::current();
+        let _ = vec![0u8; 100];
+        let after = Counters::current();
+        assert_eq!(after.allocations - before.allocations, 1);
+        assert_eq!(after.bytes_allocated - before.bytes_allocated, 100);
+
+        let mut iter = parse_file("test_data/bin-log.000001").unwrap();
+        assert_eq!(iter.by_ref().count(), 7);
+        let stats = iter.stats();
+        assert!(stats.allocations > 0);
+        assert!(stats.bytes_allocated > stats.bytes_read);
+    }
+}
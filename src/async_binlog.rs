@@ -0,0 +1,521 @@
+//! Reading binlog files from an [`AsyncRead`] + [`AsyncSeek`] source, for use inside async
+//! services without wrapping the blocking reader in `spawn_blocking`.
+//!
+//! Only available with the `async` feature. [`AsyncEventIterator`] is a [`Stream`] of [`BinlogEvent`]s decoded exactly as by an
+//! [`EventIterator`](crate::EventIterator), with the options of a
+//! [`BinlogFileParserBuilder`] (see [`AsyncBinlogFile::events_with`]); it reads a single file,
+//! stopping at its end or after a RotateEvent.
+
+use std::io::{self, Cursor};
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use byteorder::{ByteOrder, LittleEndian};
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, ReadBuf};
+
+use crate::binlog_file::first_event_description;
+use crate::checksum::{self, ChecksumVerifier, Verification};
+use crate::errors::{BinlogParseError, EventParseError};
+use crate::event::{DecodeOptions, Event, EventFormat, FormatDescription, TypeCode, V4_HEADER_LEN};
+use crate::session::ParseSession;
+use crate::stats::ParserStats;
+use crate::table_map::TableMap;
+use crate::{BinlogEvent, BinlogFileParserBuilder, EventProcessor, IteratorOptions};
+
+// the event length is the fourth field of the common header
+const EVENT_LENGTH_OFFSET: usize = 9;
+
+/// A binlog file read asynchronously; the counterpart of
+/// [`BinlogFile`](crate::binlog_file::BinlogFile)
+pub struct AsyncBinlogFile<R> {
+    reader: R,
+    // the magic bytes and the FormatDescriptionEvent, which a builder is made from
+    header: Vec<u8>,
+    first_event_offset: u64,
+    format: EventFormat,
+    description: FormatDescription,
+    max_event_size: Option<u32>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncBinlogFile<R> {
+    /// Check the magic bytes and read the FormatDescriptionEvent at the start of the file
+    pub async fn try_from_reader(mut reader: R) -> Result<Self, BinlogParseError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).await?;
+        if magic != [0xfeu8, 0x62, 0x69, 0x6e] {
+            return Err(BinlogParseError::BadMagic(magic));
+        }
+        let mut header = [0u8; V4_HEADER_LEN as usize];
+        reader.read_exact(&mut header).await?;
+        let event_length = LittleEndian::read_u32(&header[EVENT_LENGTH_OFFSET..]) as usize;
+        let mut data = magic.to_vec();
+        data.extend_from_slice(&header);
+        // read incrementally rather than trusting the length to allocate the buffer
+        let rest = event_length.saturating_sub(header.len());
+        (&mut reader)
+            .take(rest as u64)
+            .read_to_end(&mut data)
+            .await?;
+        if data.len() < magic.len() + event_length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let fde = Event::read(&mut &data[magic.len()..], 4)?;
+        let description = first_event_description(&fde)?;
+        Ok(AsyncBinlogFile {
+            reader,
+            header: data,
+            first_event_offset: fde.end_offset(),
+            format: description.format(),
+            description,
+            max_event_size: None,
+        })
+    }
+
+    /// Fail with [`EventTooLarge`](EventParseError::EventTooLarge) rather than reading an event
+    /// longer than `limit` bytes, which guards against a corrupt length
+    pub fn set_max_event_size(&mut self, limit: Option<u32>) {
+        self.max_event_size = limit;
+    }
+
+    pub fn format(&self) -> EventFormat {
+        self.format
+    }
+
+    /// The FormatDescriptionEvent at the start of this file
+    pub fn format_description(&self) -> &FormatDescription {
+        &self.description
+    }
+
+    /// Stream the events in this file with the default options, optionally from the given
+    /// starting offset
+    pub async fn events(self, offset: Option<u64>) -> io::Result<AsyncEventIterator<R>> {
+        let options = IteratorOptions {
+            start_position: offset,
+            max_event_size: self.max_event_size,
+            decode: DecodeOptions {
+                max_event_size: self.max_event_size,
+                ..DecodeOptions::default()
+            },
+            ..IteratorOptions::default()
+        };
+        self.stream(ParseSession::new(), options).await
+    }
+
+    /// A builder over this file's FormatDescriptionEvent, to configure the stream returned by
+    /// [`events_with`](Self::events_with). It starts with this file's
+    /// [`set_max_event_size`](Self::set_max_event_size) limit.
+    pub fn parser_builder(
+        &self,
+    ) -> Result<BinlogFileParserBuilder<Cursor<Vec<u8>>>, BinlogParseError> {
+        let builder = BinlogFileParserBuilder::try_from_reader(Cursor::new(self.header.clone()))?;
+        Ok(match self.max_event_size {
+            Some(limit) => builder.max_event_size(limit),
+            None => builder,
+        })
+    }
+
+    /// Stream the events in this file with the options and session of a builder from
+    /// [`parser_builder`](Self::parser_builder), beginning at its
+    /// [`start_position`](BinlogFileParserBuilder::start_position) if one was set.
+    ///
+    /// Checksums are verified inline, whatever the number of
+    /// [`checksum_threads`](BinlogFileParserBuilder::checksum_threads). The options which
+    /// concern reading files rather than decoding their events are ignored: `resume_from`,
+    /// `recover_corruption`, `io_errors_end_stream`, `active_file`, and following rotations.
+    pub async fn events_with(
+        self,
+        builder: BinlogFileParserBuilder<Cursor<Vec<u8>>>,
+    ) -> io::Result<AsyncEventIterator<R>> {
+        self.stream(builder.session, builder.options).await
+    }
+
+    async fn stream(
+        mut self,
+        session: ParseSession,
+        options: IteratorOptions,
+    ) -> io::Result<AsyncEventIterator<R>> {
+        let offset = options.start_position.unwrap_or(self.first_event_offset);
+        self.reader.seek(io::SeekFrom::Start(offset)).await?;
+        let verifier = options.checksum_threads.map(|_| {
+            ChecksumVerifier::new(
+                0,
+                checksum::resolve(self.format.checksum_algorithm, &options.checksums),
+            )
+        });
+        let max_event_size = options.max_event_size;
+        let mut processor = EventProcessor::new(session, options);
+        processor.session.start_file(&self.description);
+        Ok(AsyncEventIterator {
+            reader: self.reader,
+            format: self.format,
+            description: self.description,
+            offset: Some(offset),
+            buf: Vec::new(),
+            filled: 0,
+            max_event_size,
+            verifier,
+            processor,
+        })
+    }
+}
+
+/// Stream of [`BinlogEvent`]s from an [`AsyncBinlogFile`]
+pub struct AsyncEventIterator<R> {
+    reader: R,
+    format: EventFormat,
+    // the FormatDescriptionEvent at the start of the file
+    description: FormatDescription,
+    // the offset of the next event, or None once the stream has finished
+    offset: Option<u64>,
+    // the next event, as much of it as has been read
+    buf: Vec<u8>,
+    filled: usize,
+    max_event_size: Option<u32>,
+    verifier: Option<ChecksumVerifier>,
+    processor: EventProcessor,
+}
+
+impl<R: AsyncRead + Unpin> AsyncEventIterator<R> {
+    /// Continue from the state of a previous iterator (see
+    /// [`EventIterator::into_session`](crate::EventIterator::into_session)), keeping its table
+    /// map, GTID tracking, and statistics
+    pub fn with_session(mut self, mut session: ParseSession) -> Self {
+        session.start_file(&self.description);
+        self.processor.session = session;
+        self
+    }
+
+    /// Counters describing the work done by this stream so far
+    pub fn stats(&self) -> ParserStats {
+        let mut stats = self.processor.session.stats();
+        if let Some(ref verifier) = self.verifier {
+            verifier.update_stats(&mut stats);
+        }
+        stats
+    }
+
+    /// The tables described by every Table Map event read so far
+    pub fn table_map(&self) -> &TableMap {
+        self.processor.session.table_map()
+    }
+
+    /// The state of the parser; see [`ParseSession`]
+    pub fn session(&self) -> &ParseSession {
+        &self.processor.session
+    }
+
+    /// Stop reading, returning the state of the parser
+    pub fn into_session(self) -> ParseSession {
+        self.processor.session
+    }
+
+    /// Whether the event is at or after the configured stop position or timestamp
+    fn reached_stop(&self, event: &Event) -> bool {
+        let options = &self.processor.options;
+        let past_position = options
+            .stop_position
+            .is_some_and(|position| event.offset() >= position);
+        let past_timestamp = options
+            .stop_timestamp
+            .is_some_and(|timestamp| event.timestamp() >= timestamp);
+        past_position || past_timestamp
+    }
+
+    /// Fill `buf` from the reader. Returns `Ok(false)` if the reader is at its end before
+    /// anything was read.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        while self.filled < self.buf.len() {
+            let mut read_buf = ReadBuf::new(&mut self.buf[self.filled..]);
+            match Pin::new(&mut self.reader).poll_read(cx, &mut read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(())) => {
+                    let read = read_buf.filled().len();
+                    if read == 0 {
+                        return Poll::Ready(if self.filled == 0 {
+                            Ok(false)
+                        } else {
+                            Err(io::ErrorKind::UnexpectedEof.into())
+                        });
+                    }
+                    self.filled += read;
+                }
+            }
+        }
+        Poll::Ready(Ok(true))
+    }
+
+    /// Read the next raw event; the asynchronous counterpart of
+    /// [`BinlogEvents`](crate::binlog_file::BinlogEvents)
+    fn poll_raw_event(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Event, EventParseError>>> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return Poll::Ready(None),
+        };
+        if self.buf.is_empty() {
+            self.buf.resize(usize::from(V4_HEADER_LEN), 0);
+        }
+        loop {
+            match self.poll_fill(cx) {
+                Poll::Pending => return Poll::Pending,
+                // a clean end of file, between two events
+                Poll::Ready(Ok(false)) => {
+                    self.offset = None;
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Err(e)) => {
+                    self.offset = None;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Ready(Ok(true)) => {}
+            }
+            let event_length = LittleEndian::read_u32(&self.buf[EVENT_LENGTH_OFFSET..]);
+            if let Some(limit) = self.max_event_size.filter(|limit| event_length > *limit) {
+                self.offset = None;
+                return Poll::Ready(Some(Err(EventParseError::EventTooLarge {
+                    offset,
+                    length: event_length,
+                    limit,
+                })));
+            }
+            let event_length = event_length as usize;
+            if self.buf.len() == usize::from(V4_HEADER_LEN) && event_length > self.buf.len() {
+                // now we know how much more to read
+                self.buf.resize(event_length, 0);
+            } else {
+                break;
+            }
+        }
+        let data = mem::take(&mut self.buf);
+        self.filled = 0;
+        let event = match Event::read_with_format(&mut data.as_slice(), offset, &self.format)
+            .and_then(|event| Ok((FormatDescription::from_event(&event)?, event)))
+        {
+            Ok((description, event)) => {
+                if let Some(description) = description {
+                    self.format = description.format();
+                }
+                event
+            }
+            Err(e) => {
+                self.offset = None;
+                return Poll::Ready(Some(Err(e)));
+            }
+        };
+        self.offset = if event.type_code() == TypeCode::RotateEvent {
+            None
+        } else {
+            Some(event.end_offset())
+        };
+        Poll::Ready(Some(Ok(event)))
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncEventIterator<R> {
+    type Item = Result<BinlogEvent, EventParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let result = loop {
+            if let Some(part) = this.processor.split_events.pop_front() {
+                break Ok(part);
+            }
+            if let Some(e) = this.verifier.as_mut().and_then(|v| v.take_failure()) {
+                break Err(e);
+            }
+            let event = match this.processor.payload_events.pop_front() {
+                Some(event) => Arc::new(event),
+                None => match this.poll_raw_event(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Ready(Some(Err(e))) => break Err(e),
+                    Poll::Ready(Some(Ok(event))) => {
+                        if this.reached_stop(&event) {
+                            this.offset = None;
+                            return Poll::Ready(None);
+                        }
+                        let event = Arc::new(event);
+                        let verification = match this.verifier {
+                            Some(ref mut verifier) => verifier.submit(&event),
+                            None => Verification::Unverified,
+                        };
+                        let stats = &mut this.processor.session.stats;
+                        stats.events_read += 1;
+                        stats.bytes_read += u64::from(event.event_length());
+                        match verification {
+                            // checksums are verified inline, so a mismatch is known before the
+                            // event is decoded, and is returned in its place
+                            Verification::Failed(e) => break Err(e),
+                            Verification::Unverified => stats.checksums_unverified += 1,
+                            Verification::Verified | Verification::Pending => {}
+                        }
+                        event
+                    }
+                },
+            };
+            if let Some(result) = this.processor.process(event) {
+                break result;
+            }
+        };
+        Poll::Ready(Some(result.map(|mut event| {
+            event.position = Some(this.processor.position_after(&event, this.offset));
+            event
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::io::Cursor;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use assert_matches::assert_matches;
+    use futures_core::Stream;
+
+    use super::{AsyncBinlogFile, AsyncEventIterator};
+    use crate::errors::{BinlogParseError, EventParseError};
+    use crate::event::TypeCode;
+
+    // an in-memory reader is always ready, so nothing needs to wake the task
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    fn collect<R: tokio::io::AsyncRead + Unpin>(
+        events: &mut AsyncEventIterator<R>,
+    ) -> Vec<crate::BinlogEvent> {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut collected = Vec::new();
+        loop {
+            match Pin::new(&mut *events).poll_next(&mut cx) {
+                Poll::Ready(Some(event)) => collected.push(event.unwrap()),
+                Poll::Ready(None) => return collected,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let file = block_on(AsyncBinlogFile::try_from_reader(Cursor::new(data.clone()))).unwrap();
+        let mut events = block_on(file.events(None)).unwrap();
+        let async_events = collect(&mut events);
+
+        let sync_events = crate::parse_reader(Cursor::new(data.clone()))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&async_events).unwrap(),
+            serde_json::to_value(&sync_events).unwrap()
+        );
+        assert_eq!(async_events[2].type_code, TypeCode::WriteRowsEventV2);
+        assert_eq!(events.stats().events_read, 13);
+        assert_eq!(events.table_map().find("bltest", "foo").unwrap().0, 203);
+
+        // from an offset, and stopping at a truncated event
+        let file = block_on(AsyncBinlogFile::try_from_reader(Cursor::new(
+            data[..1000].to_vec(),
+        )))
+        .unwrap();
+        let mut events = block_on(file.events(Some(749))).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut types = Vec::new();
+        let error = loop {
+            match Pin::new(&mut events).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(event))) => types.push(event.type_code),
+                Poll::Ready(Some(Err(e))) => break e,
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        };
+        assert_eq!(types, vec![TypeCode::QueryEvent]);
+        assert_eq!(error.code_name(), "event_io");
+
+        // events longer than the limit aren't read
+        let mut file =
+            block_on(AsyncBinlogFile::try_from_reader(Cursor::new(data.clone()))).unwrap();
+        file.set_max_event_size(Some(50));
+        let mut events = block_on(file.events(None)).unwrap();
+        match Pin::new(&mut events).poll_next(&mut cx) {
+            Poll::Ready(Some(Err(e))) => assert_eq!(e.code_name(), "event_too_large"),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        assert!(matches!(
+            block_on(AsyncBinlogFile::try_from_reader(Cursor::new(vec![0u8; 30]))),
+            Err(BinlogParseError::BadMagic(_))
+        ));
+    }
+
+    #[test]
+    fn test_stream_with_options() {
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let file = block_on(AsyncBinlogFile::try_from_reader(Cursor::new(data.clone()))).unwrap();
+        let builder = file
+            .parser_builder()
+            .unwrap()
+            .verify_checksums(true)
+            .summaries_only(true)
+            .stop_position(1000);
+        let mut events = block_on(file.events_with(builder)).unwrap();
+        let async_events = collect(&mut events);
+
+        let sync_events =
+            crate::BinlogFileParserBuilder::try_from_reader(Cursor::new(data.clone()))
+                .unwrap()
+                .summaries_only(true)
+                .stop_position(1000)
+                .build()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(
+            serde_json::to_value(&async_events).unwrap(),
+            serde_json::to_value(&sync_events).unwrap()
+        );
+        assert!(async_events[2].rows.is_empty());
+        let stats = events.stats();
+        assert_eq!(stats.checksums_verified, stats.events_read);
+        assert_eq!(stats.checksums_unverified, 0);
+
+        // a corrupt event is reported
+        let mut corrupt = data;
+        corrupt[1000] ^= 0xff;
+        let file = block_on(AsyncBinlogFile::try_from_reader(Cursor::new(corrupt))).unwrap();
+        let builder = file.parser_builder().unwrap().verify_checksums(true);
+        let mut events = block_on(file.events_with(builder)).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut offsets = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match Pin::new(&mut events).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(event))) => offsets.push(event.offset),
+                Poll::Ready(Some(Err(e))) => errors.push(e),
+                Poll::Ready(None) => break,
+                Poll::Pending => {}
+            }
+        }
+        // in place of the corrupt event, which is never returned
+        assert_matches!(
+            errors[..],
+            [EventParseError::ChecksumMismatch { offset: 942, .. }]
+        );
+        assert!(!offsets.contains(&942));
+        assert!(offsets.contains(&1008));
+        assert_eq!(events.stats().checksum_failures, 1);
+    }
+}
@@ -0,0 +1,459 @@
+//! Encoding rows events as [Avro](https://avro.apache.org/docs/current/specification/) datums,
+//! for producing directly into schema-registry-backed Kafka topics.
+//!
+//! Only available with the `avro` feature. Each table gets its own schema, derived from its
+//! [`SingleTableMap`]: [`row_schema`] is a record with a nullable field per column, and
+//! [`envelope_schema`] wraps the before- and after-images of a change with its operation, GTID,
+//! and position. [`encode_event`] encodes every row of a rows event against the envelope schema
+//! of its table; frame the datums with [`confluent_frame`] to produce them to a topic whose
+//! schema is registered with a Confluent-compatible schema registry.
+//!
+//! Columns are named as in the table's [`ColumnDescriptor`](crate::table_map::ColumnDescriptor)s
+//! (so the binlog should be written with `binlog_row_metadata=FULL` or read with a
+//! [`SchemaProvider`](crate::table_map::SchemaProvider)), or by their position, as `_0`, `_1`,
+//! and so on. Names are changed to be valid Avro names where needed.
+
+use std::convert::TryFrom;
+
+use bigdecimal::num_bigint::BigInt;
+use serde_json::{json, Value};
+
+use crate::column_types::ColumnType;
+use crate::errors::AvroEncodeError;
+use crate::event::RowEvent;
+use crate::table_map::SingleTableMap;
+use crate::value::{days_from_civil, MySQLValue};
+use crate::BinlogEvent;
+
+/// How a column is represented in Avro
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AvroType {
+    Long,
+    /// `BIGINT UNSIGNED`, which doesn't fit in a long
+    UnsignedLong,
+    Int,
+    Float,
+    Double,
+    Decimal {
+        precision: u8,
+        scale: u8,
+    },
+    /// Character and binary strings: a string if the value could be decoded, otherwise bytes
+    Text,
+    /// An `ENUM`: the member's name if it's known, otherwise its index
+    Enum,
+    Date,
+    Time,
+    DateTime,
+    Timestamp,
+    Json,
+    Geometry,
+    /// Types which can't be decoded, which are always null
+    Unsupported,
+}
+
+impl AvroType {
+    fn of(column_type: &ColumnType, unsigned: bool) -> Self {
+        match *column_type {
+            ColumnType::LongLong if unsigned => AvroType::UnsignedLong,
+            ColumnType::Tiny
+            | ColumnType::Short
+            | ColumnType::Int24
+            | ColumnType::Long
+            | ColumnType::LongLong
+            | ColumnType::Set(_)
+            | ColumnType::Bit(..) => AvroType::Long,
+            ColumnType::Year => AvroType::Int,
+            ColumnType::Float(_) => AvroType::Float,
+            ColumnType::Double(_) => AvroType::Double,
+            ColumnType::NewDecimal(precision, scale) => AvroType::Decimal { precision, scale },
+            ColumnType::VarChar(_)
+            | ColumnType::VarString
+            | ColumnType::MyString
+            | ColumnType::TinyBlob
+            | ColumnType::MediumBlob
+            | ColumnType::LongBlob
+            | ColumnType::Blob(_) => AvroType::Text,
+            ColumnType::Enum(_) => AvroType::Enum,
+            ColumnType::Date => AvroType::Date,
+            ColumnType::Time | ColumnType::Time2(_) => AvroType::Time,
+            ColumnType::DateTime | ColumnType::DateTime2(_) => AvroType::DateTime,
+            ColumnType::Timestamp | ColumnType::Timestamp2(_) => AvroType::Timestamp,
+            ColumnType::Json(_) => AvroType::Json,
+            ColumnType::Geometry(_) => AvroType::Geometry,
+            ColumnType::Decimal | ColumnType::NewDate | ColumnType::Null => AvroType::Unsupported,
+        }
+    }
+
+    /// The type of a field of this type, which is always a union including null
+    fn field_schema(self, namespace: &str, name: &str) -> Value {
+        let inner = match self {
+            AvroType::Long => json!("long"),
+            AvroType::UnsignedLong => {
+                json!({"type": "bytes", "logicalType": "decimal", "precision": 20, "scale": 0})
+            }
+            AvroType::Int => json!("int"),
+            AvroType::Float => json!("float"),
+            AvroType::Double => json!("double"),
+            AvroType::Decimal { precision, scale } => json!({
+                "type": "bytes",
+                "logicalType": "decimal",
+                "precision": precision,
+                "scale": scale,
+            }),
+            AvroType::Text => return json!(["null", "string", "bytes"]),
+            AvroType::Enum => return json!(["null", "string", "int"]),
+            AvroType::Date => json!({"type": "int", "logicalType": "date"}),
+            AvroType::Time => json!({"type": "long", "logicalType": "time-micros"}),
+            AvroType::DateTime => json!({"type": "long", "logicalType": "local-timestamp-micros"}),
+            AvroType::Timestamp => json!({"type": "long", "logicalType": "timestamp-micros"}),
+            AvroType::Json => json!("string"),
+            AvroType::Geometry => json!({
+                "type": "record",
+                "name": format!("{}_geometry", name),
+                "namespace": namespace,
+                "fields": [
+                    {"name": "srid", "type": "long"},
+                    {"name": "wkb", "type": "bytes"},
+                ],
+            }),
+            AvroType::Unsupported => return json!(["null"]),
+        };
+        json!(["null", inner])
+    }
+}
+
+/// Make `name` a valid Avro name: letters, digits, and underscores, not starting with a digit
+fn avro_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn column_name(table: &SingleTableMap, i: usize) -> String {
+    match table.descriptors.get(i).and_then(|d| d.name.as_deref()) {
+        Some(name) => avro_name(name),
+        None => format!("_{}", i),
+    }
+}
+
+fn column_types(table: &SingleTableMap) -> impl Iterator<Item = AvroType> + '_ {
+    table.columns.iter().enumerate().map(move |(i, column)| {
+        let unsigned = table
+            .descriptors
+            .get(i)
+            .is_some_and(|d| d.unsigned == Some(true));
+        AvroType::of(column, unsigned)
+    })
+}
+
+/// The namespace and name of a table's row record
+fn row_name(table: &SingleTableMap) -> (String, String) {
+    (avro_name(&table.schema_name), avro_name(&table.table_name))
+}
+
+/// The schema of a row of `table`: a record named after the table, in a namespace named after
+/// its schema, with a nullable field for each column
+pub fn row_schema(table: &SingleTableMap) -> Value {
+    let (namespace, name) = row_name(table);
+    let fields = column_types(table)
+        .enumerate()
+        .map(|(i, avro_type)| {
+            let column = column_name(table, i);
+            json!({
+                "name": column,
+                "type": avro_type.field_schema(&namespace, &column),
+                "default": null,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "type": "record",
+        "name": name,
+        "namespace": namespace,
+        "fields": fields,
+    })
+}
+
+/// The schema of a change to a row of `table`, which [`encode_event`] encodes
+pub fn envelope_schema(table: &SingleTableMap) -> Value {
+    let (namespace, name) = row_name(table);
+    json!({
+        "type": "record",
+        "name": "Envelope",
+        "namespace": format!("{}.{}", namespace, name),
+        "fields": [
+            {"name": "op", "type": {
+                "type": "enum",
+                "name": "Operation",
+                "symbols": ["INSERT", "UPDATE", "DELETE"],
+            }},
+            {"name": "before", "type": ["null", row_schema(table)], "default": null},
+            {"name": "after", "type": ["null", format!("{}.{}", namespace, name)], "default": null},
+            {"name": "gtid", "type": ["null", "string"], "default": null},
+            {"name": "timestamp", "type": "long"},
+            {"name": "server_id", "type": "long"},
+            {"name": "offset", "type": "long"},
+        ],
+    })
+}
+
+/// Prefix a datum with the magic byte and schema id expected by consumers using a
+/// Confluent-compatible schema registry
+pub fn confluent_frame(schema_id: u32, datum: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(datum.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&schema_id.to_be_bytes());
+    framed.extend_from_slice(datum);
+    framed
+}
+
+fn write_long(buf: &mut Vec<u8>, n: i64) {
+    let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+    while zigzag >= 0x80 {
+        buf.push(zigzag as u8 | 0x80);
+        zigzag >>= 7;
+    }
+    buf.push(zigzag as u8);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(buf, bytes.len() as i64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Write the branch of a field's union, and the value in it. Returns `false` if the value isn't
+/// of the field's type.
+fn write_value(buf: &mut Vec<u8>, avro_type: AvroType, value: &MySQLValue) -> bool {
+    if let MySQLValue::Null = value {
+        write_long(buf, 0);
+        return true;
+    }
+    let branch = buf.len();
+    write_long(buf, 1);
+    match (avro_type, value) {
+        (AvroType::Long, MySQLValue::SignedInteger(i)) => write_long(buf, *i),
+        // a BIGINT UNSIGNED column is mapped to `UnsignedLong`, but a SET or BIT(64) column can
+        // still hold a value that doesn't fit in a long
+        (AvroType::Long, MySQLValue::UnsignedInteger(n))
+        | (AvroType::Long, MySQLValue::Set(n))
+        | (AvroType::Long, MySQLValue::Bit { value: n, .. }) => match i64::try_from(*n) {
+            Ok(n) => write_long(buf, n),
+            Err(_) => return false,
+        },
+        (AvroType::UnsignedLong, MySQLValue::UnsignedInteger(u)) => {
+            write_bytes(buf, &BigInt::from(*u).to_signed_bytes_be())
+        }
+        (AvroType::UnsignedLong, MySQLValue::SignedInteger(i)) => {
+            write_bytes(buf, &BigInt::from(*i).to_signed_bytes_be())
+        }
+        (AvroType::Int, MySQLValue::Year(year)) => write_long(buf, i64::from(*year)),
+        (AvroType::Float, MySQLValue::Float(f)) => buf.extend_from_slice(&f.to_le_bytes()),
+        (AvroType::Double, MySQLValue::Double(f)) => buf.extend_from_slice(&f.to_le_bytes()),
+        (AvroType::Decimal { scale, .. }, MySQLValue::Decimal(decimal)) => {
+            let (unscaled, _) = decimal
+                .with_scale(i64::from(scale))
+                .as_bigint_and_exponent();
+            write_bytes(buf, &unscaled.to_signed_bytes_be())
+        }
+        (AvroType::Text, MySQLValue::String(s)) => write_bytes(buf, s.as_bytes()),
+        (AvroType::Text, MySQLValue::Bytes(b)) | (AvroType::Text, MySQLValue::Blob(b)) => {
+            buf.truncate(branch);
+            write_long(buf, 2);
+            write_bytes(buf, &b.0)
+        }
+        (AvroType::Enum, MySQLValue::EnumString { name, .. }) => write_bytes(buf, name.as_bytes()),
+        (AvroType::Enum, MySQLValue::Enum(index)) => {
+            buf.truncate(branch);
+            write_long(buf, 2);
+            write_long(buf, i64::from(*index))
+        }
+        (AvroType::Date, MySQLValue::Date { year, month, day }) => {
+            write_long(buf, days_from_civil(i64::from(*year), *month, *day))
+        }
+        (
+            AvroType::Time,
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                subseconds,
+                ..
+            },
+        ) => {
+            let micros =
+                (i64::from(*hours) * 3_600 + i64::from(*minutes) * 60 + i64::from(*seconds))
+                    * 1_000_000
+                    + i64::from(*subseconds);
+            write_long(buf, if *negative { -micros } else { micros })
+        }
+        (AvroType::DateTime, MySQLValue::DateTime { .. })
+        | (AvroType::Timestamp, MySQLValue::Timestamp { .. }) => {
+            let (seconds, nanos) = value.as_datetime64().unwrap_or_default();
+            write_long(buf, seconds * 1_000_000 + i64::from(nanos / 1_000))
+        }
+        (AvroType::Json, MySQLValue::Json(document)) => {
+            write_bytes(buf, document.to_string().as_bytes())
+        }
+        (AvroType::Geometry, MySQLValue::Geometry { srid, wkb }) => {
+            write_long(buf, i64::from(*srid));
+            write_bytes(buf, &wkb.0)
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn write_row(
+    buf: &mut Vec<u8>,
+    table: &SingleTableMap,
+    cols: &[Option<MySQLValue>],
+) -> Result<(), AvroEncodeError> {
+    if cols.len() > table.columns.len() {
+        return Err(AvroEncodeError::ColumnCountMismatch {
+            table_columns: table.columns.len(),
+            row_columns: cols.len(),
+        });
+    }
+    for (i, avro_type) in column_types(table).enumerate() {
+        // columns left out of a minimal row image are null
+        let value = cols
+            .get(i)
+            .and_then(Option::as_ref)
+            .unwrap_or(&MySQLValue::Null);
+        if !write_value(buf, avro_type, value) {
+            return Err(AvroEncodeError::ValueMismatch {
+                column: i,
+                column_type: table.columns[i].clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn write_image(
+    buf: &mut Vec<u8>,
+    table: &SingleTableMap,
+    cols: Option<&[Option<MySQLValue>]>,
+) -> Result<(), AvroEncodeError> {
+    match cols {
+        Some(cols) => {
+            write_long(buf, 1);
+            write_row(buf, table, cols)
+        }
+        None => {
+            write_long(buf, 0);
+            Ok(())
+        }
+    }
+}
+
+/// Encode each row of a rows event against the [`envelope_schema`] of `table`, which must be the
+/// table the event modifies. Other events encode to no datums.
+pub fn encode_event(
+    event: &BinlogEvent,
+    table: &SingleTableMap,
+) -> Result<Vec<Vec<u8>>, AvroEncodeError> {
+    let mut datums = Vec::new();
+    if event.table_ref.is_none() {
+        return Ok(datums);
+    }
+    for row in event.rows_iter() {
+        let row = row?;
+        let (op, before, after) = match *row {
+            RowEvent::NewRow { ref cols } => (0, None, Some(cols.as_slice())),
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => (1, Some(before_cols.as_slice()), Some(after_cols.as_slice())),
+            RowEvent::DeletedRow { ref cols } => (2, Some(cols.as_slice()), None),
+        };
+        let mut buf = Vec::new();
+        write_long(&mut buf, op);
+        write_image(&mut buf, table, before)?;
+        write_image(&mut buf, table, after)?;
+        match event.gtid {
+            Some(gtid) => {
+                write_long(&mut buf, 1);
+                write_bytes(&mut buf, gtid.to_string().as_bytes());
+            }
+            None => write_long(&mut buf, 0),
+        }
+        write_long(&mut buf, i64::from(event.timestamp));
+        write_long(&mut buf, i64::from(event.server_id));
+        write_long(&mut buf, event.offset as i64);
+        datums.push(buf);
+    }
+    Ok(datums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        confluent_frame, encode_event, envelope_schema, row_schema, write_long, write_value,
+        AvroType,
+    };
+    use crate::parse_file;
+    use crate::value::MySQLValue;
+
+    #[test]
+    fn test_write_long() {
+        let mut buf = Vec::new();
+        for n in [0, -1, 1, -64, 64] {
+            write_long(&mut buf, n);
+        }
+        assert_eq!(buf, [0x00, 0x01, 0x02, 0x7f, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_write_value_out_of_range() {
+        let mut buf = Vec::new();
+        assert!(write_value(&mut buf, AvroType::Long, &MySQLValue::Set(5)));
+        assert_eq!(buf, [0x02, 0x0a]);
+        assert!(!write_value(
+            &mut buf,
+            AvroType::Long,
+            &MySQLValue::Set(u64::MAX)
+        ));
+        assert!(!write_value(
+            &mut buf,
+            AvroType::Long,
+            &MySQLValue::Bit {
+                value: 1 << 63,
+                length: 64
+            }
+        ));
+        assert!(write_value(
+            &mut buf,
+            AvroType::UnsignedLong,
+            &MySQLValue::UnsignedInteger(u64::MAX)
+        ));
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let mut events = parse_file("test_data/bin-log.000001").unwrap();
+        let event = events.nth(2).unwrap().unwrap();
+        let table = events.table_map().get(203).unwrap();
+
+        let schema = row_schema(table);
+        assert_eq!(schema["name"], "foo");
+        assert_eq!(schema["namespace"], "bltest");
+        assert_eq!(schema["fields"][0]["name"], "_0");
+        assert_eq!(schema["fields"][0]["type"][1], "long");
+        assert_eq!(schema["fields"][1]["type"][1]["logicalType"], "decimal");
+        assert_eq!(envelope_schema(table)["fields"][2]["type"][1], "bltest.foo");
+
+        let datums = encode_event(&event, table).unwrap();
+        assert_eq!(datums.len(), 1);
+        // an insert, with no before-image, and an after-image whose first column is 1
+        assert_eq!(datums[0][..5], [0x00, 0x00, 0x02, 0x02, 0x02]);
+        assert_eq!(confluent_frame(7, &datums[0])[..6], [0, 0, 0, 0, 7, 0]);
+    }
+}
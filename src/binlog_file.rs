@@ -1,9 +1,23 @@
+#[cfg(feature = "mmap")]
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{self, Read, Seek};
+use std::iter::FusedIterator;
 use std::path::{Path, PathBuf};
 
+use byteorder::{ByteOrder, LittleEndian};
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{DecryptingReader, KeyProvider};
 use crate::errors::{BinlogParseError, EventParseError};
-use crate::event::{Event, TypeCode};
+#[cfg(feature = "mmap")]
+use crate::event::BorrowedEvent;
+use crate::event::{
+    ChecksumAlgorithm, Event, EventFormat, EventHeader, FormatDescription, TypeCode, V4_HEADER_LEN,
+};
+
+// how much of a file to read at a time while looking for the next event after corrupt data
+const RESYNC_CHUNK: usize = 64 * 1024;
 
 /// Low level wrapper around a single Binlog file. Use this if you
 /// want to introspect all events (including internal events like the FDE
@@ -12,8 +26,19 @@ pub struct BinlogFile<I: Seek + Read> {
     file_name: Option<PathBuf>,
     file: I,
     first_event_offset: u64,
+    format: EventFormat,
+    description: FormatDescription,
+    max_event_size: Option<u32>,
+    // the end of a MariaDB StartEncryptionEvent, after which events can't be read
+    encrypted_from: Option<u64>,
 }
 
+/// Iterator over the raw events in a [`BinlogFile`].
+///
+/// A FormatDescriptionEvent after the start of the file (as in relay logs, which repeat the
+/// source's FDE after the replica's own) changes how the events after it are read, so files mixing
+/// events with and without checksums can be read. Iteration stops after a RotateEvent, at the end of the file, or after the first error; once
+/// `next` has returned `None` or an error it will always return `None`.
 pub struct BinlogEvents<I: Seek + Read> {
     file: BinlogFile<I>,
     // if the offset is None, it means that we can't read any more
@@ -22,37 +47,313 @@ pub struct BinlogEvents<I: Seek + Read> {
 }
 
 impl<I: Seek + Read> BinlogEvents<I> {
-    pub fn new(mut bf: BinlogFile<I>, start_offset: u64) -> Self {
-        bf.file.seek(io::SeekFrom::Start(start_offset)).unwrap();
+    pub fn new(bf: BinlogFile<I>, start_offset: u64) -> Self {
         BinlogEvents {
             offset: Some(start_offset),
             file: bf,
         }
     }
+
+    /// Stop reading; every subsequent call to `next` will return `None`
+    pub(crate) fn stop(&mut self) {
+        self.offset = None;
+    }
+
+    /// The offset of the next event to be read, or `None` if iteration has finished
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Read the next event from a file which may still be being written to. Returns `Ok(None)`
+    /// if the file ends before the next complete event (or after a RotateEvent); in the former
+    /// case the same position is read again on the next call, so partially-written events are
+    /// retried rather than reported as errors.
+    ///
+    /// Unlike `next`, this never finishes iteration at the end of the file, but other errors
+    /// still do.
+    pub fn poll(&mut self) -> Result<Option<Event>, EventParseError> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let event = match self.file.read_at(offset) {
+            Ok(e) => e,
+            Err(EventParseError::EofError) => return Ok(None),
+            Err(EventParseError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None)
+            }
+            Err(e) => {
+                self.offset = None;
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.file.observe(&event) {
+            self.offset = None;
+            return Err(e);
+        }
+        self.offset = if event.type_code() == TypeCode::RotateEvent {
+            None
+        } else {
+            Some(event.end_offset())
+        };
+        Ok(Some(event))
+    }
+
+    /// After failing to read the event at `failed_at`, continue from the next plausible event
+    /// after it. Returns the offset reading will resume from, which is the end of the file if
+    /// there is no such event.
+    pub(crate) fn resynchronize(&mut self, failed_at: u64) -> Result<u64, EventParseError> {
+        self.offset = None;
+        let resume_at = match self.file.find_next_event(failed_at + 1)? {
+            Some(offset) => {
+                self.offset = Some(offset);
+                offset
+            }
+            None => self.file.file.seek(io::SeekFrom::End(0))?,
+        };
+        Ok(resume_at)
+    }
+
+    /// Stop iterating and reclaim the underlying file
+    pub fn into_inner(self) -> BinlogFile<I> {
+        self.file
+    }
 }
 
 impl<I: Seek + Read> Iterator for BinlogEvents<I> {
     type Item = Result<Event, EventParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let event = match self.offset {
-            Some(offset) => match self.file.read_at(offset) {
-                Ok(e) => e,
-                Err(EventParseError::Io(_)) => return None,
-                Err(EventParseError::EofError) => return None,
-                Err(e) => return Some(Err(e)),
-            },
-            None => return None,
+        let offset = self.offset.take()?;
+        let event = match self.file.read_at(offset) {
+            Ok(e) => e,
+            // a clean end of file, between two events
+            Err(EventParseError::EofError) => return None,
+            Err(e) => return Some(Err(e)),
         };
-        if event.type_code() == TypeCode::RotateEvent {
-            self.offset = None;
-        } else {
-            self.offset = Some(event.next_position());
+        if let Err(e) = self.file.observe(&event) {
+            return Some(Err(e));
+        }
+        if event.type_code() != TypeCode::RotateEvent {
+            self.offset = Some(event.end_offset());
         }
         Some(Ok(event))
     }
 }
 
+impl<I: Seek + Read> FusedIterator for BinlogEvents<I> {}
+
+/// An event as it appears in the binlog, read by [`RawEvents`]
+#[derive(Debug, Clone, Copy)]
+pub struct RawEvent<'a> {
+    pub header: EventHeader,
+    /// The whole event: its header (including any extra headers), body, and checksum
+    pub bytes: &'a [u8],
+}
+
+/// Reads the events in a [`BinlogFile`] verbatim, without parsing their bodies, for tools which
+/// copy or filter events rather than decode them.
+///
+/// Each event is read into the same buffer, so this is not an `Iterator`: the event returned by
+/// [`next_event`](Self::next_event) borrows the reader until the next call. Like
+/// [`BinlogEvents`], reading stops after a RotateEvent, at the end of the file, or after the
+/// first error.
+pub struct RawEvents<I: Seek + Read> {
+    file: BinlogFile<I>,
+    // the offset of the next event, or None once reading has finished
+    offset: Option<u64>,
+    buf: Vec<u8>,
+}
+
+impl<I: Seek + Read> RawEvents<I> {
+    fn new(bf: BinlogFile<I>, start_offset: u64) -> io::Result<Self> {
+        let mut file = bf;
+        file.file.seek(io::SeekFrom::Start(start_offset))?;
+        Ok(RawEvents {
+            file,
+            offset: Some(start_offset),
+            buf: Vec::new(),
+        })
+    }
+
+    /// The offset of the next event to be read, or `None` if reading has finished
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Read the next event
+    pub fn next_event(&mut self) -> Option<Result<RawEvent<'_>, EventParseError>> {
+        let offset = self.offset.take()?;
+        let header = match self.read_event(offset) {
+            Ok(Some(header)) => header,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        if header.type_code != TypeCode::RotateEvent {
+            self.offset = Some(offset + u64::from(header.event_length));
+        }
+        Some(Ok(RawEvent {
+            header,
+            bytes: &self.buf,
+        }))
+    }
+
+    /// Read the event at `offset` (where the file is positioned) into `buf`. Returns `Ok(None)`
+    /// at a clean end of file.
+    fn read_event(&mut self, offset: u64) -> Result<Option<EventHeader>, EventParseError> {
+        let header_len = usize::from(V4_HEADER_LEN);
+        self.buf.resize(header_len, 0);
+        let mut read = 0;
+        while read < header_len {
+            match self.file.file.read(&mut self.buf[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let event_length = LittleEndian::read_u32(&self.buf[9..13]);
+        if (event_length as usize) < header_len {
+            return Err(io::Error::from(io::ErrorKind::InvalidData).into());
+        }
+        if let Some(limit) = self
+            .file
+            .max_event_size
+            .filter(|limit| event_length > *limit)
+        {
+            return Err(EventParseError::EventTooLarge {
+                offset,
+                length: event_length,
+                limit,
+            });
+        }
+        self.buf.resize(event_length as usize, 0);
+        self.file.file.read_exact(&mut self.buf[header_len..])?;
+        Ok(Some(EventHeader {
+            timestamp: LittleEndian::read_u32(&self.buf[0..4]),
+            type_code: TypeCode::from_byte(self.buf[4]),
+            server_id: LittleEndian::read_u32(&self.buf[5..9]),
+            event_length,
+            next_position: u64::from(LittleEndian::read_u32(&self.buf[13..17])),
+            flags: LittleEndian::read_u16(&self.buf[17..19]),
+            offset,
+        }))
+    }
+
+    /// Stop reading and reclaim the underlying file
+    pub fn into_inner(self) -> BinlogFile<I> {
+        self.file
+    }
+}
+
+/// The description of a binlog file, from its first event (which must be a
+/// FormatDescriptionEvent)
+pub(crate) fn first_event_description(fde: &Event) -> Result<FormatDescription, BinlogParseError> {
+    match FormatDescription::from_event(fde)? {
+        Some(description) if description.common_header_len < V4_HEADER_LEN => Err(
+            BinlogParseError::InvalidHeaderLength(description.common_header_len),
+        ),
+        Some(description) => Ok(description),
+        None => Err(BinlogParseError::BadFirstRecord),
+    }
+}
+
+/// Split a binlog file name into its base name and sequence number (e.g. `mysql-bin.000012`
+/// becomes `("mysql-bin", 12)`)
+fn split_sequence_number(path: &Path) -> Option<(String, u64)> {
+    let file_name = path.file_name()?.to_str()?;
+    let (base, suffix) = file_name.rsplit_once('.')?;
+    // the server always writes at least six digits, and more once it runs out
+    if suffix.len() < 6 || !suffix.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((base.to_owned(), suffix.parse().ok()?))
+}
+
+/// Find every binlog file (named `<base>.NNNNNN`) in a directory, ordered by sequence number.
+///
+/// Returns an error if the directory contains files with more than one base name or if there is
+/// a gap in the sequence numbers.
+pub fn discover_directory<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, BinlogParseError> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(BinlogParseError::OpenError)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if let Some((base, sequence)) = split_sequence_number(&path) {
+            files.push((base, sequence, path));
+        }
+    }
+    // sort numerically, since `.999999` is followed by `.1000000`
+    files.sort_by_key(|(_, sequence, _)| *sequence);
+    for pair in files.windows(2) {
+        let (ref base, sequence, ref path) = pair[0];
+        let (ref next_base, next_sequence, ref next_path) = pair[1];
+        if base != next_base {
+            return Err(BinlogParseError::MixedBaseNames(
+                base.to_owned(),
+                next_base.to_owned(),
+            ));
+        }
+        if next_sequence != sequence + 1 {
+            return Err(BinlogParseError::SequenceGap {
+                before: path.to_owned(),
+                after: next_path.to_owned(),
+            });
+        }
+    }
+    if files.is_empty() {
+        return Err(BinlogParseError::NoBinlogFiles(dir.to_owned()));
+    }
+    Ok(files.into_iter().map(|(_, _, path)| path).collect())
+}
+
+/// The binlog index file maintained by the server (e.g. `mysql-bin.index`), which lists its
+/// binlog files in the order they were written
+#[derive(Debug, Clone)]
+pub struct BinlogIndex {
+    files: Vec<PathBuf>,
+}
+
+impl BinlogIndex {
+    /// Read an index file. Relative paths in it (the server writes `./mysql-bin.000001` unless
+    /// `log_bin` was an absolute path) are resolved against the directory of the index file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BinlogParseError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(BinlogParseError::OpenError)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let files: Vec<PathBuf> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| dir.join(line))
+            .collect();
+        if files.is_empty() {
+            return Err(BinlogParseError::NoBinlogFiles(path.to_owned()));
+        }
+        Ok(BinlogIndex { files })
+    }
+
+    /// The binlog files, oldest first
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Open each binlog file in turn
+    pub fn binlog_files(
+        &self,
+    ) -> impl Iterator<Item = Result<BinlogFile<File>, BinlogParseError>> + '_ {
+        self.files.iter().map(BinlogFile::try_from_path)
+    }
+
+    pub(crate) fn into_files(self) -> Vec<PathBuf> {
+        self.files
+    }
+}
+
 impl BinlogFile<File> {
     /// Construct a new BinLogFile from the given path
     ///
@@ -64,6 +365,126 @@ impl BinlogFile<File> {
     }
 }
 
+/// A binlog file read from a memory mapping; see [`BinlogFile::try_from_mmap`]
+#[cfg(feature = "mmap")]
+pub type MmapBinlogFile = BinlogFile<io::Cursor<memmap2::Mmap>>;
+
+#[cfg(feature = "mmap")]
+impl BinlogFile<io::Cursor<memmap2::Mmap>> {
+    /// Construct a new BinlogFile by mapping the file at the given path into memory. Events are
+    /// then read from the mapping without a system call per event, which is much faster for
+    /// large offline scans. Use [`borrowed_events`](Self::borrowed_events) to avoid copying each
+    /// event out of the mapping as well.
+    ///
+    /// The file must not be truncated while it is mapped (so don't use this for a binlog the
+    /// server is still writing); reading a truncated mapping crashes the process.
+    pub fn try_from_mmap<P: AsRef<Path>>(path: P) -> Result<Self, BinlogParseError> {
+        let p = path.as_ref();
+        let fh = File::open(p).map_err(BinlogParseError::OpenError)?;
+        // safe as long as the file isn't truncated, as documented above
+        let map = unsafe { memmap2::Mmap::map(&fh) }.map_err(BinlogParseError::OpenError)?;
+        Self::try_new_from_reader_name(io::Cursor::new(map), Some(p.to_owned()))
+    }
+
+    /// Iterate through the events in this file, optionally from the given starting offset,
+    /// without copying them out of the mapping; see [`BorrowedEvents`]
+    pub fn borrowed_events(&self, offset: Option<u64>) -> BorrowedEvents<'_> {
+        BorrowedEvents {
+            map: self.file.get_ref(),
+            offset: Some(offset.unwrap_or(self.first_event_offset)),
+            format: self.format,
+            max_event_size: self.max_event_size,
+            encrypted_from: self.encrypted_from,
+        }
+    }
+}
+
+/// Iterator over the events in a [`MmapBinlogFile`], each of which borrows its header and body
+/// from the mapping, so no memory is allocated per event.
+///
+/// Like [`BinlogEvents`], a FormatDescriptionEvent changes how the events after it are read, and
+/// iteration stops after a RotateEvent, at the end of the file, or after the first error.
+#[cfg(feature = "mmap")]
+pub struct BorrowedEvents<'a> {
+    map: &'a [u8],
+    // None once iteration has finished
+    offset: Option<u64>,
+    format: EventFormat,
+    max_event_size: Option<u32>,
+    encrypted_from: Option<u64>,
+}
+
+#[cfg(feature = "mmap")]
+impl<'a> BorrowedEvents<'a> {
+    /// The offset of the next event to be read, or `None` if iteration has finished
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    fn read_at(&mut self, offset: u64) -> Result<BorrowedEvent<'a>, EventParseError> {
+        if let Some(encrypted_from) = self.encrypted_from {
+            if offset >= encrypted_from {
+                return Err(EventParseError::EncryptedEvents {
+                    offset: encrypted_from,
+                });
+            }
+        }
+        let buf = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.map.get(start..))
+            .unwrap_or(&[]);
+        let event = BorrowedEvent::parse(buf, offset, &self.format, self.max_event_size)?;
+        if event.type_code() == TypeCode::StartEncryptionEvent {
+            self.encrypted_from = Some(event.end_offset());
+        }
+        if let Some(description) = FormatDescription::from_borrowed(&event)? {
+            self.format = description.format();
+        }
+        Ok(event)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<'a> Iterator for BorrowedEvents<'a> {
+    type Item = Result<BorrowedEvent<'a>, EventParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset.take()?;
+        let event = match self.read_at(offset) {
+            Ok(e) => e,
+            // a clean end of file, between two events
+            Err(EventParseError::EofError) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        if event.type_code() != TypeCode::RotateEvent {
+            self.offset = Some(event.end_offset());
+        }
+        Some(Ok(event))
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl FusedIterator for BorrowedEvents<'_> {}
+
+/// An encrypted binlog file; see [`BinlogFile::try_from_encrypted_path`]
+#[cfg(feature = "encryption")]
+pub type EncryptedBinlogFile = BinlogFile<DecryptingReader<File>>;
+
+#[cfg(feature = "encryption")]
+impl BinlogFile<DecryptingReader<File>> {
+    /// Construct a new BinlogFile from an encrypted binlog at the given path, decrypting it with
+    /// the master key named by its header
+    pub fn try_from_encrypted_path<P, K>(path: P, keys: &K) -> Result<Self, BinlogParseError>
+    where
+        P: AsRef<Path>,
+        K: KeyProvider + ?Sized,
+    {
+        let p = path.as_ref();
+        let fh = File::open(p).map_err(BinlogParseError::OpenError)?;
+        Self::try_new_from_reader_name(DecryptingReader::new(fh, keys)?, Some(p.to_owned()))
+    }
+}
+
 impl<I: Seek + Read> BinlogFile<I> {
     pub fn try_from_reader(reader: I) -> Result<Self, BinlogParseError> {
         Self::try_new_from_reader_name(reader, None)
@@ -76,25 +497,114 @@ impl<I: Seek + Read> BinlogFile<I> {
         // read the magic bytes
         let mut magic = [0u8; 4];
         fh.read_exact(&mut magic)?;
+        if magic == [0xfdu8, 0x62, 0x69, 0x6e] {
+            return Err(BinlogParseError::Encrypted);
+        }
         if magic != [0xfeu8, 0x62, 0x69, 0x6e] {
             return Err(BinlogParseError::BadMagic(magic));
         }
         let fde = Event::read(&mut fh, 4)?;
-        if fde.inner(None)?.is_some() {
-            // XXX: todo: thread through common_header_len
-        } else {
-            return Err(BinlogParseError::BadFirstRecord);
-        }
+        let description = first_event_description(&fde)?;
         Ok(BinlogFile {
             file_name: name,
             file: fh,
-            first_event_offset: fde.next_position(),
+            first_event_offset: fde.end_offset(),
+            format: description.format(),
+            description,
+            max_event_size: None,
+            encrypted_from: None,
         })
     }
 
+    /// Fail with [`EventTooLarge`](EventParseError::EventTooLarge) rather than reading an event
+    /// longer than `limit` bytes, which guards against a corrupt length
+    pub fn set_max_event_size(&mut self, limit: Option<u32>) {
+        self.max_event_size = limit;
+    }
+
+    /// Switch to the format of a FormatDescriptionEvent read after the start of the file
+    fn observe(&mut self, event: &Event) -> Result<(), EventParseError> {
+        if event.type_code() == TypeCode::StartEncryptionEvent {
+            self.encrypted_from = Some(event.end_offset());
+        }
+        if let Some(description) = FormatDescription::from_event(event)? {
+            self.format = description.format();
+            self.description = description;
+        }
+        Ok(())
+    }
+
     fn read_at(&mut self, offset: u64) -> Result<Event, EventParseError> {
+        if let Some(encrypted_from) = self.encrypted_from {
+            if offset >= encrypted_from {
+                return Err(EventParseError::EncryptedEvents {
+                    offset: encrypted_from,
+                });
+            }
+        }
         self.file.seek(io::SeekFrom::Start(offset))?;
-        Event::read(&mut self.file, offset)
+        Event::read_with_limit(&mut self.file, offset, &self.format, self.max_event_size)
+    }
+
+    /// Find the first offset at or after `from` at which a plausible event starts: one with a
+    /// known type which fits in the file and whose checksum matches (or, if the file doesn't have
+    /// CRC32 checksums, whose next_position matches its length)
+    pub(crate) fn find_next_event(&mut self, from: u64) -> Result<Option<u64>, EventParseError> {
+        let file_length = self.file.seek(io::SeekFrom::End(0))?;
+        let header_len = usize::from(V4_HEADER_LEN);
+        let mut chunk_start = from;
+        let mut chunk = Vec::with_capacity(RESYNC_CHUNK + header_len);
+        while chunk_start < file_length {
+            self.file.seek(io::SeekFrom::Start(chunk_start))?;
+            chunk.clear();
+            (&mut self.file)
+                .take((RESYNC_CHUNK + header_len) as u64)
+                .read_to_end(&mut chunk)?;
+            if chunk.len() < header_len {
+                break;
+            }
+            // headers starting in the last few bytes are checked with the next chunk
+            let candidates = (chunk.len() - header_len + 1).min(RESYNC_CHUNK);
+            for i in 0..candidates {
+                let offset = chunk_start + i as u64;
+                if self.is_plausible_header(&chunk[i..i + header_len], offset, file_length)
+                    && self.is_valid_event_at(offset)
+                {
+                    return Ok(Some(offset));
+                }
+            }
+            chunk_start += RESYNC_CHUNK as u64;
+        }
+        Ok(None)
+    }
+
+    fn has_crc32(&self) -> bool {
+        self.format.has_checksum && self.format.checksum_algorithm == ChecksumAlgorithm::CRC32
+    }
+
+    fn is_plausible_header(&self, header: &[u8], offset: u64, file_length: u64) -> bool {
+        let type_code = TypeCode::from_byte(header[4]);
+        if matches!(type_code, TypeCode::Unknown | TypeCode::OtherUnknown(_)) {
+            return false;
+        }
+        let event_length = u64::from(LittleEndian::read_u32(&header[9..13]));
+        let min_length =
+            u64::from(self.format.common_header_len) + if self.format.has_checksum { 4 } else { 0 };
+        if event_length < min_length || offset + event_length > file_length {
+            return false;
+        }
+        // relay logs don't keep next_position in step with the file, so it's only relied on if
+        // there is no checksum to check instead
+        self.has_crc32()
+            || u64::from(LittleEndian::read_u32(&header[13..17])) == offset + event_length
+    }
+
+    fn is_valid_event_at(&mut self, offset: u64) -> bool {
+        match self.read_at(offset) {
+            Ok(event) if self.has_crc32() => event.checksum() == Some(event.compute_checksum()),
+            Ok(_) => true,
+            Err(_) => false,
+        }
     }
 
     /// Iterate throgh events in this BinLog file, optionally from the given
@@ -104,7 +614,334 @@ impl<I: Seek + Read> BinlogFile<I> {
         BinlogEvents::new(self, offset)
     }
 
+    /// Read the events in this file verbatim, optionally from the given starting offset; see
+    /// [`RawEvents`]. Pass an offset of 4 to include the FormatDescriptionEvent.
+    pub fn raw_events(self, offset: Option<u64>) -> Result<RawEvents<I>, EventParseError> {
+        let offset = offset.unwrap_or(self.first_event_offset);
+        Ok(RawEvents::new(self, offset)?)
+    }
+
+    /// How events in this file are framed, as detected from its FormatDescriptionEvent
+    pub fn format(&self) -> EventFormat {
+        self.format
+    }
+
+    /// The FormatDescriptionEvent at the start of this file (or the most recent one read from
+    /// it, if it has several)
+    pub fn format_description(&self) -> &FormatDescription {
+        &self.description
+    }
+
     pub fn file_name(&self) -> Option<&Path> {
         self.file_name.as_ref().map(|a| a.as_ref())
     }
+
+    /// Reclaim the underlying reader
+    pub fn into_inner(self) -> I {
+        self.file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use assert_matches::assert_matches;
+
+    use super::{BinlogFile, BinlogIndex};
+    use crate::errors::{BinlogParseError, EventParseError};
+    use crate::event::{compute_checksum, ChecksumAlgorithm, Event, EventFormat, TypeCode};
+
+    /// Rewrite a binlog as if it had been written with `binlog_checksum=NONE` and/or with
+    /// `extra_header` bytes of extra headers after each event's v4 header
+    fn rewrite(data: &[u8], strip_checksums: bool, extra_header: u8) -> Vec<u8> {
+        let fde_len = LittleEndian::read_u32(&data[13..17]) as usize;
+        let mut out = data[..4 + fde_len].to_vec();
+        // the FDE keeps its checksum, but the algorithm byte before it becomes NONE
+        if strip_checksums {
+            out[4 + fde_len - 5] = 0;
+        }
+        out[4 + 19 + 56] += extra_header;
+        let crc = compute_checksum(&out[4..4 + fde_len - 4], &[]);
+        LittleEndian::write_u32(&mut out[4 + fde_len - 4..], crc);
+        let mut offset = 4 + fde_len;
+        while offset < data.len() {
+            let len = LittleEndian::read_u32(&data[offset + 9..offset + 13]) as usize;
+            let mut event = data[offset..offset + 19].to_vec();
+            event.resize(event.len() + usize::from(extra_header), 0xaa);
+            let body_end = if strip_checksums { len - 4 } else { len };
+            event.extend_from_slice(&data[offset + 19..offset + body_end]);
+            let new_len = event.len() as u32;
+            LittleEndian::write_u32(&mut event[9..13], new_len);
+            LittleEndian::write_u32(&mut event[13..17], out.len() as u32 + new_len);
+            if !strip_checksums {
+                // the checksum covers the extra headers too
+                let crc = compute_checksum(&event[..event.len() - 4], &[]);
+                let end = event.len();
+                LittleEndian::write_u32(&mut event[end - 4..], crc);
+            }
+            out.extend_from_slice(&event);
+            offset += len;
+        }
+        out
+    }
+
+    #[test]
+    fn test_detect_checksum() {
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let bf = BinlogFile::try_from_reader(Cursor::new(data.clone())).unwrap();
+        assert_eq!(bf.format(), EventFormat::default());
+        let with_checksums = bf.events(None).collect::<Result<Vec<_>, _>>().unwrap();
+
+        let bf = BinlogFile::try_from_reader(Cursor::new(rewrite(&data, true, 0))).unwrap();
+        assert!(!bf.format().has_checksum);
+        let without_checksums = bf.events(None).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(with_checksums.len(), without_checksums.len());
+        for (a, b) in with_checksums.iter().zip(without_checksums.iter()) {
+            assert_eq!(a.type_code(), b.type_code());
+            assert_eq!(a.data(), b.data());
+            assert_eq!(b.checksum(), None);
+        }
+        assert_eq!(
+            without_checksums.last().unwrap().type_code(),
+            TypeCode::XidEvent
+        );
+    }
+
+    #[test]
+    fn test_fde_checksum_rules() {
+        let expected = BinlogFile::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .events(None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let read = |name: &str| {
+            let bf = BinlogFile::try_from_path(format!("test_data/fde/{}", name)).unwrap();
+            let format = bf.format();
+            let events = bf.events(None).collect::<Result<Vec<_>, _>>().unwrap();
+            (format, events)
+        };
+
+        // a pre-5.6.1 server writes no checksum algorithm and no checksums at all
+        let (format, events) = read("5.5-no-checksum.000001");
+        assert_eq!(format.checksum_algorithm, ChecksumAlgorithm::None);
+        assert!(!format.has_checksum);
+        assert!(events.iter().all(|e| e.checksum().is_none()));
+        assert_eq!(events.len(), expected.len());
+
+        let (format, events) = read("5.6-crc32.000001");
+        assert_eq!(format.checksum_algorithm, ChecksumAlgorithm::CRC32);
+        assert!(events
+            .iter()
+            .all(|e| e.checksum() == Some(e.compute_checksum())));
+
+        // with binlog_checksum=NONE, the FDE is still checksummed but nothing else is, and UNDEF
+        // (from a relay log) means the same for the events which follow
+        for &name in &["5.6-none.000001", "5.6-undef.000001"] {
+            let data = std::fs::read(format!("test_data/fde/{}", name)).unwrap();
+            let fde = Event::read(&mut &data[4..], 4).unwrap();
+            assert_eq!(fde.checksum(), Some(fde.compute_checksum()));
+            let (format, events) = read(name);
+            assert_eq!(
+                format.checksum_algorithm,
+                ChecksumAlgorithm::None,
+                "{}",
+                name
+            );
+            assert!(events.iter().all(|e| e.checksum().is_none()));
+            assert_eq!(events.len(), expected.len());
+        }
+
+        // a 5.6 replica's relay log of a 5.5 source switches formats at the source's FDE
+        let (format, events) = read("relay-mixed.000001");
+        assert_eq!(format.checksum_algorithm, ChecksumAlgorithm::CRC32);
+        assert_eq!(events.len(), expected.len() + 1);
+        assert_eq!(events[0].checksum(), Some(events[0].compute_checksum()));
+        assert_eq!(events[1].type_code(), TypeCode::FormatDescriptionEvent);
+        assert_eq!(events[1].checksum(), None);
+        for (a, b) in expected[1..].iter().zip(events[2..].iter()) {
+            assert_eq!(a.type_code(), b.type_code());
+            assert_eq!(a.data(), b.data());
+            assert_eq!(b.checksum(), None);
+        }
+    }
+
+    #[test]
+    fn test_common_header_len() {
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let expected = BinlogFile::try_from_reader(Cursor::new(data.clone()))
+            .unwrap()
+            .events(None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for &strip_checksums in &[false, true] {
+            let rewritten = rewrite(&data, strip_checksums, 4);
+            let bf = BinlogFile::try_from_reader(Cursor::new(rewritten)).unwrap();
+            assert_eq!(bf.format().common_header_len, 23);
+            let events = bf.events(None).collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(events.len(), expected.len());
+            for (a, b) in expected.iter().zip(events.iter()) {
+                assert_eq!(a.type_code(), b.type_code());
+                assert_eq!(a.data(), b.data());
+                if !strip_checksums {
+                    assert_eq!(b.checksum(), Some(b.compute_checksum()));
+                }
+            }
+        }
+
+        let mut short = data;
+        short[4 + 19 + 56] = 13;
+        assert_matches!(
+            BinlogFile::try_from_reader(Cursor::new(short)).err(),
+            Some(BinlogParseError::InvalidHeaderLength(13))
+        );
+    }
+
+    #[test]
+    fn test_events_fused() {
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        // cut the final event short
+        data.truncate(data.len() - 10);
+        let mut events = BinlogFile::try_from_reader(Cursor::new(data))
+            .unwrap()
+            .events(None);
+        for _ in 0..12 {
+            assert_matches!(events.next(), Some(Ok(_)));
+        }
+        assert_matches!(events.next(), Some(Err(EventParseError::Io(_))));
+        assert!(events.next().is_none());
+        assert!(events.next().is_none());
+        assert_eq!(events.offset(), None);
+        let reader = events.into_inner().into_inner();
+        assert_eq!(reader.get_ref().len(), 1029);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap() {
+        let mapped = BinlogFile::try_from_mmap("test_data/bin-log.000001").unwrap();
+        assert_eq!(
+            mapped.file_name(),
+            Some(std::path::Path::new("test_data/bin-log.000001"))
+        );
+        let offsets = |events: Vec<Event>| events.iter().map(Event::offset).collect::<Vec<_>>();
+        let mapped = mapped.events(None).collect::<Result<Vec<_>, _>>().unwrap();
+        let read = BinlogFile::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .events(None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(mapped.len(), 13);
+        assert_eq!(offsets(mapped), offsets(read));
+        assert!(matches!(
+            BinlogFile::try_from_mmap("test_data/missing"),
+            Err(BinlogParseError::OpenError(_))
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_borrowed_events() {
+        let read = BinlogFile::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .events(None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mapped = BinlogFile::try_from_mmap("test_data/bin-log.000001").unwrap();
+        let map = mapped.file.get_ref().as_ptr_range();
+        #[cfg(feature = "alloc-stats")]
+        let before = crate::alloc_stats::Counters::current();
+        let mut count = 0;
+        for (event, expected) in mapped.borrowed_events(None).zip(read.iter()) {
+            let event = event.unwrap();
+            assert_eq!(event.event_header(), expected.event_header());
+            assert_eq!(event.data(), expected.data().as_slice());
+            assert_eq!(event.checksum(), Some(event.compute_checksum()));
+            // the body is a slice of the mapping rather than a copy of it
+            assert!(map.contains(&event.data().as_ptr()));
+            count += 1;
+        }
+        #[cfg(feature = "alloc-stats")]
+        assert_eq!(
+            crate::alloc_stats::Counters::current().allocations,
+            before.allocations
+        );
+        assert_eq!(count, read.len());
+        let rows = mapped.borrowed_events(Some(652)).next().unwrap().unwrap();
+        assert_eq!(rows.rows_table_id(), Some(203));
+        assert_eq!(rows.to_event().to_bytes(), read[6].to_bytes());
+    }
+
+    #[test]
+    fn test_raw_events() {
+        let events = BinlogFile::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .events(None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut raw = BinlogFile::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .raw_events(None)
+            .unwrap();
+        for event in &events {
+            let raw_event = raw.next_event().unwrap().unwrap();
+            assert_eq!(raw_event.header, event.event_header());
+            assert_eq!(raw_event.bytes, event.to_bytes().as_slice());
+        }
+        assert!(raw.next_event().is_none());
+        assert_eq!(raw.offset(), None);
+
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        data.truncate(data.len() - 10);
+        let mut raw = BinlogFile::try_from_reader(Cursor::new(data))
+            .unwrap()
+            .raw_events(Some(4))
+            .unwrap();
+        assert_eq!(
+            raw.next_event().unwrap().unwrap().header.type_code,
+            TypeCode::FormatDescriptionEvent
+        );
+        for _ in 0..12 {
+            assert_matches!(raw.next_event(), Some(Ok(_)));
+        }
+        assert_matches!(raw.next_event(), Some(Err(EventParseError::Io(_))));
+        assert!(raw.next_event().is_none());
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::copy(
+            "test_data/bin-log.000001",
+            dir.path().join("bin-log.000001"),
+        )
+        .unwrap();
+        let absolute = dir.path().join("elsewhere.000002");
+        let index_path = dir.path().join("bin-log.index");
+        std::fs::write(
+            &index_path,
+            format!("./bin-log.000001\n\n{}\r\n", absolute.display()),
+        )
+        .unwrap();
+        let index = BinlogIndex::open(&index_path).unwrap();
+        assert_eq!(
+            index.files(),
+            &[dir.path().join("./bin-log.000001"), absolute][..]
+        );
+        let opened = index.binlog_files().collect::<Vec<_>>();
+        assert_eq!(opened[0].as_ref().unwrap().first_event_offset, 123);
+        assert!(matches!(opened[1], Err(BinlogParseError::OpenError(_))));
+
+        std::fs::write(&index_path, "\n").unwrap();
+        assert_matches!(
+            BinlogIndex::open(&index_path),
+            Err(BinlogParseError::NoBinlogFiles(_))
+        );
+        assert_matches!(
+            BinlogIndex::open(dir.path().join("missing.index")),
+            Err(BinlogParseError::OpenError(_))
+        );
+    }
 }
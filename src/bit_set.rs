@@ -16,15 +16,20 @@ impl fmt::Debug for BitSet {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BitSetError {
-    ItemOutOfRange,
+    ItemOutOfRange { item: usize, len: usize },
     SliceTooSmall,
 }
 
 impl fmt::Display for BitSetError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        <Self as fmt::Debug>::fmt(self, f)
+        match self {
+            BitSetError::ItemOutOfRange { item, len } => {
+                write!(f, "bit {} is out of range for a set of {}", item, len)
+            }
+            BitSetError::SliceTooSmall => f.write_str("slice is too small for the set"),
+        }
     }
 }
 
@@ -54,23 +59,44 @@ impl BitSet {
         })
     }
 
-    fn get_byte_offset(&self, item: usize) -> usize {
+    fn get_byte_offset(&self, item: usize) -> Result<usize, BitSetError> {
         if item >= self.num_elems {
-            panic!(
-                "attempted to index bit_set out of range: {} >= {}",
-                item, self.num_elems
-            );
+            return Err(BitSetError::ItemOutOfRange {
+                item,
+                len: self.num_elems,
+            });
         }
-        item >> 3
+        Ok(item >> 3)
     }
 
-    pub fn set_value(&mut self, item: usize, value: bool) {
-        let offset = self.get_byte_offset(item);
+    /// Like [`set_value`](Self::set_value), but returns an error instead of panicking if `item`
+    /// is out of range
+    pub fn try_set_value(&mut self, item: usize, value: bool) -> Result<(), BitSetError> {
+        let offset = self.get_byte_offset(item)?;
         if value {
             self.inner[offset] |= 1 << (item & 0x07);
         } else {
             self.inner[offset] &= !(1 << (item & 0x07));
         }
+        Ok(())
+    }
+
+    pub fn try_set(&mut self, item: usize) -> Result<(), BitSetError> {
+        self.try_set_value(item, true)
+    }
+
+    /// Like [`is_set`](Self::is_set), but returns an error instead of panicking if `item` is out
+    /// of range
+    pub fn try_is_set(&self, item: usize) -> Result<bool, BitSetError> {
+        let byte = self.inner[self.get_byte_offset(item)?];
+        Ok(byte & (1 << (item & 0x07)) != 0)
+    }
+
+    /// Panics if `item` is out of range
+    pub fn set_value(&mut self, item: usize, value: bool) {
+        if let Err(e) = self.try_set_value(item, value) {
+            panic!("attempted to index bit_set out of range: {}", e);
+        }
     }
 
     pub fn set(&mut self, item: usize) {
@@ -81,9 +107,12 @@ impl BitSet {
         self.set_value(item, false)
     }
 
+    /// Panics if `item` is out of range
     pub fn is_set(&self, item: usize) -> bool {
-        let byte = self.inner[self.get_byte_offset(item)];
-        byte & (1 << (item & 0x07)) != 0
+        match self.try_is_set(item) {
+            Ok(set) => set,
+            Err(e) => panic!("attempted to index bit_set out of range: {}", e),
+        }
     }
 
     pub fn as_vec(&self) -> Vec<bool> {
@@ -94,6 +123,15 @@ impl BitSet {
         out
     }
 
+    /// The number of elements in the set (whether or not they are set)
+    pub fn len(&self) -> usize {
+        self.num_elems
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_elems == 0
+    }
+
     pub fn bits_set(&self) -> usize {
         self.inner.iter().map(|c| c.count_ones() as usize).sum()
     }
@@ -101,7 +139,7 @@ impl BitSet {
 
 #[cfg(test)]
 mod tests {
-    use super::BitSet;
+    use super::{BitSet, BitSetError};
 
     #[test]
     fn test_basic() {
@@ -123,6 +161,19 @@ mod tests {
         assert_eq!(b.bits_set(), 2);
     }
 
+    #[test]
+    fn test_checked() {
+        let mut b = BitSet::new(10);
+        assert_eq!(b.try_set(9), Ok(()));
+        assert_eq!(b.try_is_set(9), Ok(true));
+        assert_eq!(
+            b.try_is_set(10),
+            Err(BitSetError::ItemOutOfRange { item: 10, len: 10 })
+        );
+        assert!(b.try_set_value(16, false).is_err());
+        assert!(BitSet::new(0).try_is_set(0).is_err());
+    }
+
     #[test]
     fn test_from_slice() {
         let b = BitSet::from_slice(9, &[255u8, 0u8]).expect("should construct");
@@ -0,0 +1,114 @@
+//! Validation of Table Map events against an expected schema catalog.
+//!
+//! Replication consumers usually assume a particular layout for the tables they read. If a
+//! column is added, dropped, or changes type on the source, rows will still decode but the values
+//! will no longer line up with what downstream code expects. Registering a [`SchemaCatalog`] on
+//! the [`BinlogFileParserBuilder`](crate::BinlogFileParserBuilder) causes every TableMapEvent to
+//! be checked against it.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::column_types::ColumnType;
+use crate::table_map::TableNameCase;
+
+/// What to do when a TableMapEvent does not match the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MismatchPolicy {
+    /// Return an [`EventParseError::SchemaMismatch`](crate::errors::EventParseError::SchemaMismatch)
+    /// from the iterator
+    Error,
+    /// Print a warning and continue using the columns from the binlog
+    Warn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Description of a table whose Table Map did not match the catalog
+pub struct SchemaMismatch {
+    pub schema_name: String,
+    pub table_name: String,
+    pub expected: Vec<ColumnType>,
+    pub actual: Vec<ColumnType>,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}: expected columns {:?}, got {:?}",
+            self.schema_name, self.table_name, self.expected, self.actual
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Expected column types for a set of tables. Tables which are not in the catalog are not
+/// checked.
+///
+/// Column types are compared including their metadata, so (for example) a `VARCHAR(255)` column
+/// in a `utf8mb4` table must be registered as `ColumnType::VarChar(1020)`.
+pub struct SchemaCatalog {
+    tables: HashMap<(String, String), Vec<ColumnType>>,
+}
+
+impl SchemaCatalog {
+    pub fn new() -> Self {
+        SchemaCatalog {
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Register the expected columns for a table, replacing any previous registration
+    pub fn with_table<S: Into<String>, T: Into<String>>(
+        mut self,
+        schema_name: S,
+        table_name: T,
+        columns: Vec<ColumnType>,
+    ) -> Self {
+        self.tables
+            .insert((schema_name.into(), table_name.into()), columns);
+        self
+    }
+
+    /// Re-key the catalog by the lookup form of each name
+    pub(crate) fn with_name_case(self, name_case: TableNameCase) -> Self {
+        SchemaCatalog {
+            tables: self
+                .tables
+                .into_iter()
+                .map(|((schema, table), columns)| {
+                    let key = (
+                        name_case.key(&schema).into_owned(),
+                        name_case.key(&table).into_owned(),
+                    );
+                    (key, columns)
+                })
+                .collect(),
+        }
+    }
+
+    /// The expected columns for a table, if it is in the catalog
+    pub fn get(&self, schema_name: &str, table_name: &str) -> Option<&[ColumnType]> {
+        self.tables
+            .get(&(schema_name.to_owned(), table_name.to_owned()))
+            .map(|c| c.as_slice())
+    }
+
+    /// Check the columns from a Table Map against the catalog
+    pub fn check(
+        &self,
+        schema_name: &str,
+        table_name: &str,
+        columns: &[ColumnType],
+    ) -> Result<(), SchemaMismatch> {
+        match self.get(schema_name, table_name) {
+            Some(expected) if expected != columns => Err(SchemaMismatch {
+                schema_name: schema_name.to_owned(),
+                table_name: table_name.to_owned(),
+                expected: expected.to_vec(),
+                actual: columns.to_vec(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
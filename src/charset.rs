@@ -0,0 +1,129 @@
+//! Decoding of `CHAR`, `VARCHAR`, `BINARY`, and `VARBINARY` values according to their column's
+//! collation, when it is known (from `binlog_row_metadata=FULL` or a
+//! [`SchemaProvider`](crate::table_map::SchemaProvider)).
+//!
+//! UTF-8 and ASCII text is always decoded. Other character sets are only decoded with the
+//! `encoding_rs` feature; without it (and for binary strings, and the few character sets
+//! `encoding_rs` doesn't support) the raw bytes are returned as a [`MySQLValue::Bytes`].
+
+use crate::value::{MySQLValue, MySQLValueRef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Charset {
+    Utf8,
+    Binary,
+    #[cfg_attr(not(feature = "encoding_rs"), allow(dead_code))]
+    Other(&'static str),
+    Unsupported,
+}
+
+/// The character set of a collation id, as listed in `information_schema.COLLATIONS`
+fn charset_of(collation: u64) -> Charset {
+    use Charset::*;
+    match collation {
+        11 | 65 | 33 | 76 | 83 | 192..=223 | 45 | 46 | 224..=247 | 255..=323 => Utf8,
+        63 => Binary,
+        1 | 84 => Other("Big5"),
+        5 | 8 | 15 | 31 | 47 | 48 | 49 | 94 => Other("windows-1252"),
+        7 | 74 => Other("KOI8-R"),
+        9 | 21 | 27 | 77 => Other("ISO-8859-2"),
+        12 | 91 | 97 | 98 => Other("EUC-JP"),
+        13 | 88 | 95 | 96 => Other("Shift_JIS"),
+        14 | 23 | 50 | 51 | 52 => Other("windows-1251"),
+        16 | 71 => Other("ISO-8859-8"),
+        18 | 89 => Other("windows-874"),
+        19 | 85 => Other("EUC-KR"),
+        20 | 41 | 42 | 79 => Other("ISO-8859-13"),
+        22 | 75 => Other("KOI8-U"),
+        // GBK is a superset of GB2312
+        24 | 86 | 28 | 87 => Other("GBK"),
+        25 | 70 => Other("ISO-8859-7"),
+        26 | 34 | 44 | 66 | 99 => Other("windows-1250"),
+        29 | 58 | 59 => Other("windows-1257"),
+        30 | 78 => Other("windows-1254"),
+        35 | 90 | 128..=159 | 54 | 55 | 101..=124 => Other("UTF-16BE"),
+        36 | 68 => Other("IBM866"),
+        39 | 53 => Other("macintosh"),
+        56 | 62 => Other("UTF-16LE"),
+        57 | 67 => Other("windows-1256"),
+        248..=250 => Other("gb18030"),
+        _ => Unsupported,
+    }
+}
+
+/// Decode the bytes of a string column with the given collation
+pub(crate) fn decode_text(bytes: Vec<u8>, collation: u64) -> MySQLValue {
+    match charset_of(collation) {
+        Charset::Utf8 => match String::from_utf8(bytes) {
+            Ok(s) => MySQLValue::String(s),
+            Err(e) => MySQLValue::Bytes(e.into_bytes().into()),
+        },
+        #[cfg(feature = "encoding_rs")]
+        Charset::Other(label) => {
+            let encoding = match encoding_rs::Encoding::for_label(label.as_bytes()) {
+                Some(encoding) => encoding,
+                None => return MySQLValue::Bytes(bytes.into()),
+            };
+            match encoding.decode_without_bom_handling_and_without_replacement(&bytes) {
+                Some(s) => MySQLValue::String(s.into_owned()),
+                None => MySQLValue::Bytes(bytes.into()),
+            }
+        }
+        _ => MySQLValue::Bytes(bytes.into()),
+    }
+}
+
+/// Decode the bytes of a string column with the given collation, borrowing them if the
+/// character set is UTF-8 or binary
+pub(crate) fn decode_text_ref(bytes: &[u8], collation: u64) -> MySQLValueRef<'_> {
+    match charset_of(collation) {
+        Charset::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(s) => MySQLValueRef::String(s),
+            Err(_) => MySQLValueRef::Bytes(bytes),
+        },
+        Charset::Other(_) => MySQLValueRef::Owned(decode_text(bytes.to_vec(), collation)),
+        Charset::Binary | Charset::Unsupported => MySQLValueRef::Bytes(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_text;
+    use crate::value::MySQLValue;
+
+    fn decoded(bytes: &[u8], collation: u64) -> Option<String> {
+        match decode_text(bytes.to_vec(), collation) {
+            MySQLValue::String(s) => Some(s),
+            MySQLValue::Bytes(b) => {
+                assert_eq!(b.0, bytes);
+                None
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decoded("héllo".as_bytes(), 255), Some("héllo".to_owned()));
+        assert_eq!(decoded("héllo".as_bytes(), 33), Some("héllo".to_owned()));
+        // invalid UTF-8 and binary strings are never mangled
+        assert_eq!(decoded(b"h\xe9llo", 45), None);
+        assert_eq!(decoded(b"abc", 63), None);
+        // swe7 isn't supported by encoding_rs
+        assert_eq!(decoded(b"abc", 10), None);
+
+        let latin1 = decoded(b"h\xe9llo", 8);
+        let cp1251 = decoded(b"\xcf\xf0\xe8\xe2\xe5\xf2", 51);
+        let gbk = decoded(b"\xc4\xe3\xba\xc3", 28);
+        if cfg!(feature = "encoding_rs") {
+            assert_eq!(latin1, Some("héllo".to_owned()));
+            assert_eq!(cp1251, Some("Привет".to_owned()));
+            assert_eq!(gbk, Some("你好".to_owned()));
+            assert_eq!(decoded(b"\x00h\x00i", 35), Some("hi".to_owned()));
+        } else {
+            assert_eq!(latin1, None);
+            assert_eq!(cp1251, None);
+            assert_eq!(gbk, None);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Event checksum verification, optionally offloaded to a worker thread so that it overlaps
+//! with decoding.
+//!
+//! MySQL only writes CRC32 checksums, but the FormatDescriptionEvent has room for other
+//! algorithms, which tooling that re-checksums archived binlogs may use. Implementations of
+//! [`Checksum`] for such algorithms can be registered with
+//! [`BinlogFileParserBuilder::checksum_algorithm`](crate::BinlogFileParserBuilder::checksum_algorithm).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, SendError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::errors::EventParseError;
+use crate::event::{compute_checksum, ChecksumAlgorithm, Event};
+use crate::stats::ParserStats;
+
+/// An algorithm for computing the 4-byte checksum at the end of each event
+pub trait Checksum: fmt::Debug + Send + Sync {
+    /// Compute the checksum of an event's header followed by its body
+    fn compute(&self, header: &[u8], body: &[u8]) -> u32;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// The CRC32 checksum written by MySQL
+pub struct Crc32;
+
+impl Checksum for Crc32 {
+    fn compute(&self, header: &[u8], body: &[u8]) -> u32 {
+        compute_checksum(header, body)
+    }
+}
+
+#[cfg(feature = "xxhash")]
+#[derive(Debug, Clone, Copy, Default)]
+/// The 32-bit xxHash of each event, which is considerably faster to compute than a CRC32
+pub struct XxHash32 {
+    pub seed: u32,
+}
+
+#[cfg(feature = "xxhash")]
+impl Checksum for XxHash32 {
+    fn compute(&self, header: &[u8], body: &[u8]) -> u32 {
+        let mut hasher = xxhash_rust::xxh32::Xxh32::new(self.seed);
+        hasher.update(header);
+        hasher.update(body);
+        hasher.digest()
+    }
+}
+
+/// Find the implementation of the checksum algorithm named by a FormatDescriptionEvent
+pub(crate) fn resolve(
+    algorithm: ChecksumAlgorithm,
+    registered: &HashMap<u8, Arc<dyn Checksum>>,
+) -> Result<Arc<dyn Checksum>, EventParseError> {
+    match algorithm {
+        ChecksumAlgorithm::None | ChecksumAlgorithm::CRC32 => Ok(Arc::new(Crc32)),
+        ChecksumAlgorithm::Other(id) => registered
+            .get(&id)
+            .cloned()
+            .ok_or(EventParseError::UnsupportedChecksumAlgorithm(id)),
+    }
+}
+
+struct Job {
+    // shared with the iterator, which decodes the event while it is verified
+    event: Arc<Event>,
+    expected: u32,
+    checksum: Arc<dyn Checksum>,
+}
+
+struct JobResult {
+    offset: u64,
+    expected: u32,
+    actual: u32,
+}
+
+impl Job {
+    fn run(self) -> JobResult {
+        JobResult {
+            offset: self.event.offset(),
+            expected: self.expected,
+            actual: self
+                .checksum
+                .compute(self.event.header(), self.event.data()),
+        }
+    }
+}
+
+/// The outcome of submitting an event for verification
+pub(crate) enum Verification {
+    /// The event carries no checksum (or one which can't be verified)
+    Unverified,
+    Verified,
+    Failed(EventParseError),
+    /// The event is being verified on the worker thread; see [`ChecksumVerifier::wait`]
+    Pending,
+}
+
+pub(crate) struct ChecksumVerifier {
+    // None if the binlog uses an algorithm with no registered implementation
+    checksum: Option<Arc<dyn Checksum>>,
+    jobs: Option<Sender<Job>>,
+    results: Option<Receiver<JobResult>>,
+    worker: Option<JoinHandle<()>>,
+    pending: bool,
+    // the checksum algorithm couldn't be resolved; reported once
+    failure: Option<EventParseError>,
+    verified: u64,
+    failed: u64,
+}
+
+impl ChecksumVerifier {
+    /// Construct a new verifier. If `threads` is zero, checksums are verified inline on the
+    /// calling thread; otherwise each event is verified on a worker thread while the caller
+    /// decodes it. If the checksum algorithm is not supported, the error is reported as a
+    /// failure and events are left unverified.
+    pub(crate) fn new(
+        threads: usize,
+        checksum: Result<Arc<dyn Checksum>, EventParseError>,
+    ) -> Self {
+        let mut verifier = ChecksumVerifier {
+            checksum: None,
+            jobs: None,
+            results: None,
+            worker: None,
+            pending: false,
+            failure: None,
+            verified: 0,
+            failed: 0,
+        };
+        if threads > 0 {
+            let (job_tx, job_rx) = mpsc::channel::<Job>();
+            let (result_tx, result_rx) = mpsc::channel();
+            verifier.worker = Some(thread::spawn(move || {
+                for job in job_rx {
+                    if result_tx.send(job.run()).is_err() {
+                        return;
+                    }
+                }
+            }));
+            verifier.jobs = Some(job_tx);
+            verifier.results = Some(result_rx);
+        }
+        verifier.set_checksum(checksum);
+        verifier
+    }
+
+    /// Switch to a different checksum algorithm (e.g. on moving to the next file in a
+    /// sequence)
+    pub(crate) fn set_checksum(&mut self, checksum: Result<Arc<dyn Checksum>, EventParseError>) {
+        match checksum {
+            Ok(checksum) => self.checksum = Some(checksum),
+            Err(e) => {
+                self.checksum = None;
+                self.failure = Some(e);
+            }
+        }
+    }
+
+    fn record(&mut self, result: JobResult) -> Verification {
+        if result.expected == result.actual {
+            self.verified += 1;
+            Verification::Verified
+        } else {
+            self.failed += 1;
+            Verification::Failed(EventParseError::ChecksumMismatch {
+                offset: result.offset,
+                expected: result.expected,
+                actual: result.actual,
+            })
+        }
+    }
+
+    /// Verify an event, or start verifying it on the worker thread. Only one event may be
+    /// pending at a time: [`wait`](Self::wait) for it before submitting the next.
+    pub(crate) fn submit(&mut self, event: &Arc<Event>) -> Verification {
+        let (expected, checksum) = match (event.checksum(), self.checksum.as_ref()) {
+            (Some(expected), Some(checksum)) => (expected, Arc::clone(checksum)),
+            _ => return Verification::Unverified,
+        };
+        let job = Job {
+            event: Arc::clone(event),
+            expected,
+            checksum,
+        };
+        let job = match self.jobs {
+            Some(ref jobs) => match jobs.send(job) {
+                Ok(()) => {
+                    self.pending = true;
+                    return Verification::Pending;
+                }
+                // the worker has gone away, so verify inline instead
+                Err(SendError(job)) => job,
+            },
+            None => job,
+        };
+        self.record(job.run())
+    }
+
+    /// Wait for the result of the pending event, if there is one
+    pub(crate) fn wait(&mut self) -> Verification {
+        if !std::mem::take(&mut self.pending) {
+            return Verification::Verified;
+        }
+        match self.results.as_ref().and_then(|r| r.recv().ok()) {
+            Some(result) => self.record(result),
+            None => Verification::Unverified,
+        }
+    }
+
+    /// Take the error from resolving the checksum algorithm, if it hasn't yet been reported
+    pub(crate) fn take_failure(&mut self) -> Option<EventParseError> {
+        self.failure.take()
+    }
+
+    pub(crate) fn update_stats(&self, stats: &mut ParserStats) {
+        stats.checksums_verified += self.verified;
+        stats.checksum_failures += self.failed;
+    }
+}
+
+impl Drop for ChecksumVerifier {
+    fn drop(&mut self) {
+        // closing the job channel causes the worker to exit
+        self.jobs = None;
+        self.results = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "xxhash"))]
+mod tests {
+    use super::{Checksum, XxHash32};
+
+    #[test]
+    fn test_xxhash32() {
+        assert_eq!(XxHash32::default().compute(&[], &[]), 0x02cc_5d05);
+        assert_eq!(
+            XxHash32::default().compute(b"ab", b"c"),
+            XxHash32::default().compute(b"abc", &[])
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! Per-column statistics gathered while rows events are decoded.
+//!
+//! Enabling [`BinlogFileParserBuilder::collect_column_stats`](crate::BinlogFileParserBuilder::collect_column_stats)
+//! makes the iterator fold every row image it decodes into a [`StatsCollector`], which can be
+//! read back (or serialized as a report) with
+//! [`EventIterator::column_stats`](crate::EventIterator::column_stats). This gives a rough
+//! profile of the data flowing through a binlog without keeping the values around.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use bigdecimal::BigDecimal;
+use serde::{Serialize, Serializer};
+
+use crate::event::{RowData, RowEvent};
+use crate::value::{days_from_civil, MySQLValue};
+
+// each sketch has 2^SKETCH_PRECISION one-byte registers
+const SKETCH_PRECISION: u32 = 10;
+const SKETCH_REGISTERS: usize = 1 << SKETCH_PRECISION;
+
+/// A HyperLogLog sketch for estimating the number of distinct values in a column. Estimates are
+/// typically within a few percent of the true count.
+#[derive(Debug, Clone)]
+struct DistinctSketch {
+    registers: Vec<u8>,
+}
+
+impl DistinctSketch {
+    fn new() -> Self {
+        DistinctSketch {
+            registers: vec![0; SKETCH_REGISTERS],
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let index = (hash >> (64 - SKETCH_PRECISION)) as usize;
+        let rank = (hash << SKETCH_PRECISION)
+            .leading_zeros()
+            .min(64 - SKETCH_PRECISION)
+            + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = SKETCH_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|r| 2f64.powi(-i32::from(*r)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let empty = self.registers.iter().filter(|r| **r == 0).count();
+        // linear counting is much more accurate while the sketch is sparse
+        if raw <= 2.5 * m && empty > 0 {
+            (m * (m / empty as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+fn hash_value(value: &MySQLValue) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match *value {
+        MySQLValue::SignedInteger(i) => (0u8, i).hash(&mut hasher),
+        MySQLValue::UnsignedInteger(u) => (15u8, u).hash(&mut hasher),
+        MySQLValue::Float(f) => (1u8, f.to_bits()).hash(&mut hasher),
+        MySQLValue::Double(f) => (2u8, f.to_bits()).hash(&mut hasher),
+        MySQLValue::String(ref s) => (3u8, s).hash(&mut hasher),
+        MySQLValue::Enum(e) | MySQLValue::EnumString { index: e, .. } => (4u8, e).hash(&mut hasher),
+        MySQLValue::Blob(ref b) | MySQLValue::Bytes(ref b) => (5u8, &b.0).hash(&mut hasher),
+        MySQLValue::Year(y) => (6u8, y).hash(&mut hasher),
+        MySQLValue::Json(ref j) => (7u8, j.to_string()).hash(&mut hasher),
+        MySQLValue::JsonDiff(ref d) => {
+            (16u8, serde_json::to_string(d).unwrap_or_default()).hash(&mut hasher)
+        }
+        MySQLValue::Decimal(ref d) => (8u8, d).hash(&mut hasher),
+        MySQLValue::Null => 9u8.hash(&mut hasher),
+        MySQLValue::Custom { ref value, .. } => (11u8, value.to_string()).hash(&mut hasher),
+        MySQLValue::Set(s) => (14u8, s).hash(&mut hasher),
+        MySQLValue::Bit { value, length } => (13u8, value, length).hash(&mut hasher),
+        MySQLValue::Geometry { srid, ref wkb } => (12u8, srid, &wkb.0).hash(&mut hasher),
+        // every temporal type is uniquely identified by its formatted value
+        ref temporal => (10u8, temporal.to_temporal_string()).hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// A minimum or maximum value of a column. Only numeric and temporal columns have these.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatValue {
+    Integer(i64),
+    UnsignedInteger(u64),
+    Float(f64),
+    Decimal(BigDecimal),
+    /// A date, time, datetime, or timestamp, formatted as MySQL would
+    Temporal {
+        // (seconds, nanoseconds) since the epoch (or since midnight, for a TIME)
+        at: (i64, u32),
+        formatted: String,
+    },
+}
+
+impl StatValue {
+    fn from_value(value: &MySQLValue) -> Option<Self> {
+        let at = match *value {
+            MySQLValue::SignedInteger(i) => return Some(StatValue::Integer(i)),
+            MySQLValue::UnsignedInteger(u) => return Some(StatValue::UnsignedInteger(u)),
+            MySQLValue::Year(y) => return Some(StatValue::Integer(i64::from(y))),
+            MySQLValue::Float(f) => return Some(StatValue::Float(f64::from(f))),
+            MySQLValue::Double(f) => return Some(StatValue::Float(f)),
+            MySQLValue::Decimal(ref d) => return Some(StatValue::Decimal(d.clone())),
+            MySQLValue::Date { year, month, day } => {
+                (days_from_civil(i64::from(year), month, day) * 86_400, 0)
+            }
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                ..
+            } => {
+                let seconds =
+                    i64::from(hours) * 3_600 + i64::from(minutes) * 60 + i64::from(seconds);
+                let nanos = value.subsecond_nanos().unwrap_or(0);
+                match (negative, nanos) {
+                    (false, _) => (seconds, nanos),
+                    (true, 0) => (-seconds, 0),
+                    // keep the nanoseconds positive, so that the pair orders correctly
+                    (true, _) => (-seconds - 1, 1_000_000_000 - nanos),
+                }
+            }
+            MySQLValue::DateTime { .. } | MySQLValue::Timestamp { .. } => value.as_datetime64()?,
+            _ => return None,
+        };
+        Some(StatValue::Temporal {
+            at,
+            formatted: value.to_temporal_string()?,
+        })
+    }
+
+    /// Compare two values of the same kind; values of different kinds are unordered
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (StatValue::Integer(a), StatValue::Integer(b)) => Some(a.cmp(b)),
+            (StatValue::UnsignedInteger(a), StatValue::UnsignedInteger(b)) => Some(a.cmp(b)),
+            (StatValue::Float(a), StatValue::Float(b)) => a.partial_cmp(b),
+            (StatValue::Decimal(a), StatValue::Decimal(b)) => Some(a.cmp(b)),
+            (StatValue::Temporal { at: a, .. }, StatValue::Temporal { at: b, .. }) => {
+                Some(a.cmp(b))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for StatValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            StatValue::Integer(i) => serializer.serialize_i64(i),
+            StatValue::UnsignedInteger(u) => serializer.serialize_u64(u),
+            StatValue::Float(f) => serializer.serialize_f64(f),
+            StatValue::Decimal(ref d) => d.serialize(serializer),
+            StatValue::Temporal { ref formatted, .. } => serializer.serialize_str(formatted),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Statistics for a single column of a table
+pub struct ColumnStats {
+    /// Number of row images in which this column was present, including nulls
+    pub values: u64,
+    /// Number of row images in which this column was null
+    pub nulls: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<StatValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<StatValue>,
+    #[serde(rename = "approx_distinct", serialize_with = "serialize_sketch")]
+    distinct: DistinctSketch,
+}
+
+fn serialize_sketch<S: Serializer>(
+    sketch: &DistinctSketch,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(sketch.estimate())
+}
+
+impl ColumnStats {
+    fn new() -> Self {
+        ColumnStats {
+            values: 0,
+            nulls: 0,
+            min: None,
+            max: None,
+            distinct: DistinctSketch::new(),
+        }
+    }
+
+    /// Fraction of values which were null, or `None` if no values have been seen
+    pub fn null_rate(&self) -> Option<f64> {
+        if self.values == 0 {
+            None
+        } else {
+            Some(self.nulls as f64 / self.values as f64)
+        }
+    }
+
+    /// Estimated number of distinct non-null values
+    pub fn approx_distinct(&self) -> u64 {
+        self.distinct.estimate()
+    }
+
+    fn record(&mut self, value: &MySQLValue) {
+        self.values += 1;
+        if let MySQLValue::Null = value {
+            self.nulls += 1;
+            return;
+        }
+        self.distinct.insert(hash_value(value));
+        if let Some(v) = StatValue::from_value(value) {
+            // if the column changed type, the old bounds are meaningless
+            if self.min.as_ref().and_then(|m| v.compare(m)) != Some(Ordering::Greater) {
+                self.min = Some(v.clone());
+            }
+            if self.max.as_ref().and_then(|m| v.compare(m)) != Some(Ordering::Less) {
+                self.max = Some(v);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Statistics for every column of a table, in table order
+pub struct TableStats {
+    pub schema_name: String,
+    pub table_name: String,
+    /// Number of row images seen (an UPDATE contributes two)
+    pub row_images: u64,
+    pub columns: Vec<ColumnStats>,
+}
+
+impl TableStats {
+    fn record(&mut self, row: &RowData) {
+        self.row_images += 1;
+        if self.columns.len() < row.len() {
+            self.columns.resize_with(row.len(), ColumnStats::new);
+        }
+        for (stats, value) in self.columns.iter_mut().zip(row) {
+            if let Some(value) = value {
+                stats.record(value);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Column statistics for every table seen in a binlog. Serializes as a list of [`TableStats`].
+pub struct StatsCollector {
+    tables: BTreeMap<(String, String), TableStats>,
+}
+
+impl StatsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold every row image in a rows event into the statistics for its table
+    pub fn record(&mut self, schema_name: &str, table_name: &str, rows: &[RowEvent]) {
+        let table = self
+            .tables
+            .entry((schema_name.to_owned(), table_name.to_owned()))
+            .or_insert_with(|| TableStats {
+                schema_name: schema_name.to_owned(),
+                table_name: table_name.to_owned(),
+                row_images: 0,
+                columns: Vec::new(),
+            });
+        for row in rows {
+            match row {
+                RowEvent::NewRow { cols } | RowEvent::DeletedRow { cols } => table.record(cols),
+                RowEvent::UpdatedRow {
+                    before_cols,
+                    after_cols,
+                } => {
+                    table.record(before_cols);
+                    table.record(after_cols);
+                }
+            }
+        }
+    }
+
+    /// Statistics for a single table, if any rows have been seen for it
+    pub fn table(&self, schema_name: &str, table_name: &str) -> Option<&TableStats> {
+        self.tables
+            .get(&(schema_name.to_owned(), table_name.to_owned()))
+    }
+
+    /// Statistics for every table, ordered by schema and table name
+    pub fn tables(&self) -> impl Iterator<Item = &TableStats> {
+        self.tables.values()
+    }
+}
+
+impl Serialize for StatsCollector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.tables.values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DistinctSketch, StatValue, StatsCollector};
+    use crate::event::RowEvent;
+    use crate::value::MySQLValue;
+
+    #[test]
+    fn test_record() {
+        let mut collector = StatsCollector::new();
+        let rows = (0..100)
+            .map(|i| RowEvent::NewRow {
+                cols: vec![
+                    Some(MySQLValue::SignedInteger(i % 10)),
+                    Some(if i % 4 == 0 {
+                        MySQLValue::Null
+                    } else {
+                        MySQLValue::Date {
+                            year: 2020,
+                            month: 1,
+                            day: 1 + (i as u32 % 28),
+                        }
+                    }),
+                    None,
+                ],
+            })
+            .collect::<Vec<_>>();
+        collector.record("db", "t", &rows);
+        let table = collector.table("db", "t").unwrap();
+        assert_eq!(table.row_images, 100);
+        let ints = &table.columns[0];
+        assert_eq!(ints.min, Some(StatValue::Integer(0)));
+        assert_eq!(ints.max, Some(StatValue::Integer(9)));
+        assert_eq!(ints.null_rate(), Some(0.0));
+        assert_eq!(ints.approx_distinct(), 10);
+        let dates = &table.columns[1];
+        assert_eq!(dates.null_rate(), Some(0.25));
+        match dates.max {
+            Some(StatValue::Temporal { ref formatted, .. }) => assert_eq!(formatted, "2020-01-28"),
+            ref other => panic!("unexpected max {:?}", other),
+        }
+        assert_eq!(table.columns[2].values, 0);
+        assert_eq!(
+            serde_json::to_value(&collector).unwrap()[0]["columns"][0]["max"],
+            serde_json::json!(9)
+        );
+    }
+
+    #[test]
+    fn test_distinct_sketch() {
+        let mut sketch = DistinctSketch::new();
+        for i in 0..100_000u64 {
+            sketch.insert(super::hash_value(&MySQLValue::SignedInteger(i as i64)));
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!(
+            (estimate - 100_000.0).abs() / 100_000.0 < 0.1,
+            "{}",
+            estimate
+        );
+    }
+}
@@ -1,13 +1,16 @@
 use std::io::{self, Read};
 
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 
+use serde::{Deserialize, Serialize};
+
+use crate::charset;
 use crate::errors::ColumnParseError;
 use crate::jsonb;
 use crate::packet_helpers::*;
 use crate::value::MySQLValue;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ColumnType {
     Decimal,
     Tiny,
@@ -28,6 +31,7 @@ pub enum ColumnType {
     DateTime2(u8),
     Time2(u8),
     VarChar(u16),
+    /// The length of the column is `8 * bytes + bits`, as `Bit(bits, bytes)`
     Bit(u8, u8),
     NewDecimal(u8, u8),
     Enum(u16),
@@ -42,9 +46,38 @@ pub enum ColumnType {
     Json(u8),
 }
 
+// TIME2 values are stored offset so that they sort as unsigned big-endian integers
+const TIMEF_INT_OFS: i64 = 0x80_0000;
+const TIMEF_OFS: i64 = 0x8000_0000_0000;
+
+/// Read a TIME2 value as MySQL's "packed" representation: a signed integer whose top bits are
+/// the hours, minutes, and seconds, and whose bottom 24 bits are microseconds. The fractional
+/// part of a negative time is stored as its (negative) difference from the next whole second.
+fn read_time2_packed<R: Read>(r: &mut R, pack_length: u8) -> io::Result<i64> {
+    if pack_length >= 5 {
+        return Ok(r.read_uint::<BigEndian>(6)? as i64 - TIMEF_OFS);
+    }
+    let mut int_part = r.read_uint::<BigEndian>(3)? as i64 - TIMEF_INT_OFS;
+    let (mut frac, frac_bytes, scale) = match pack_length {
+        1 | 2 => (i64::from(r.read_u8()?), 1, 10_000),
+        3 | 4 => (i64::from(r.read_u16::<BigEndian>()?), 2, 100),
+        _ => (0, 0, 0),
+    };
+    if int_part < 0 && frac != 0 {
+        int_part += 1;
+        frac -= 1 << (8 * frac_bytes);
+    }
+    Ok((int_part << 24) + frac * scale)
+}
+
+// the real types which can be packed into the metadata of a MYSQL_TYPE_STRING column
+const MYSQL_TYPE_ENUM: u8 = 247;
+const MYSQL_TYPE_SET: u8 = 248;
+const MYSQL_TYPE_STRING: u8 = 254;
+
 impl ColumnType {
-    pub(crate) fn from_byte(b: u8) -> Self {
-        match b {
+    pub(crate) fn from_byte(b: u8) -> Result<Self, ColumnParseError> {
+        Ok(match b {
             0 => ColumnType::Decimal,
             1 => ColumnType::Tiny,
             2 => ColumnType::Short,
@@ -61,7 +94,7 @@ impl ColumnType {
             13 => ColumnType::Year,
             14 => ColumnType::NewDate, // not implemented (or documented)
             15 => ColumnType::VarChar(0),
-            16 => ColumnType::Bit(0, 0), // not implemented
+            16 => ColumnType::Bit(0, 0),
             17 => ColumnType::Timestamp2(0),
             18 => ColumnType::DateTime2(0),
             19 => ColumnType::Time2(0),
@@ -75,13 +108,13 @@ impl ColumnType {
             252 => ColumnType::Blob(0),
             253 => ColumnType::VarString, // not implemented
             254 => ColumnType::MyString,
-            255 => ColumnType::Geometry(0), // not implemented
-            i => unimplemented!("unhandled column type {}", i),
-        }
+            255 => ColumnType::Geometry(0),
+            i => return Err(ColumnParseError::UnknownColumnType(i)),
+        })
     }
 
-    pub(crate) fn read_metadata<R: Read>(self, cursor: &mut R) -> Result<Self, io::Error> {
-        Ok(match self {
+    pub(crate) fn read_metadata<R: Read>(self, cursor: &mut R) -> Result<Self, ColumnParseError> {
+        let column_type = match self {
             ColumnType::Float(_) => {
                 let pack_length = cursor.read_u8()?;
                 ColumnType::Float(pack_length)
@@ -99,11 +132,13 @@ impl ColumnType {
                 ColumnType::Geometry(pack_length)
             }
             ColumnType::VarString | ColumnType::VarChar(_) => {
-                let max_length = cursor.read_u16::<LittleEndian>()?;
-                assert!(max_length != 0);
-                ColumnType::VarChar(max_length)
+                ColumnType::VarChar(cursor.read_u16::<LittleEndian>()?)
+            }
+            ColumnType::Bit(..) => {
+                let bits = cursor.read_u8()?;
+                let bytes = cursor.read_u8()?;
+                ColumnType::Bit(bits, bytes)
             }
-            ColumnType::Bit(..) => unimplemented!(),
             ColumnType::NewDecimal(_, _) => {
                 let precision = cursor.read_u8()?;
                 let num_decimals = cursor.read_u8()?;
@@ -120,7 +155,7 @@ impl ColumnType {
                 let (real_type, max_length) = if f1 == 0 {
                     // not sure which version of mysql emits this,
                     // but log_event.cc checks this case
-                    (ColumnType::MyString, f2 as u16)
+                    (MYSQL_TYPE_STRING, f2 as u16)
                 } else {
                     // The max length is in 0-1023,
                     // (since CHAR(255) CHARACTER SET utf8mb4 turns into max_length=1020)
@@ -130,13 +165,19 @@ impl ColumnType {
                     // in the 0x30 bits of the type, inverted
                     let real_type = f1 | 0x30;
                     let max_length = (!f1 as u16) << 4 & 0x300 | f2 as u16;
-                    (ColumnType::from_byte(real_type), max_length)
+                    (real_type, max_length)
                 };
                 match real_type {
-                    ColumnType::MyString => ColumnType::VarChar(max_length),
-                    ColumnType::Set(_) => ColumnType::Set(max_length),
-                    ColumnType::Enum(_) => ColumnType::Enum(max_length),
-                    i => unimplemented!("unimplemented stringy type {:?}", i),
+                    MYSQL_TYPE_STRING => ColumnType::VarChar(max_length),
+                    MYSQL_TYPE_SET => ColumnType::Set(max_length),
+                    MYSQL_TYPE_ENUM => ColumnType::Enum(max_length),
+                    other => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid real type {:#x} for a string column", other),
+                        )
+                        .into())
+                    }
                 }
             }
             ColumnType::Enum(_) => {
@@ -148,7 +189,28 @@ impl ColumnType {
             ColumnType::Timestamp2(..) => ColumnType::Timestamp2(cursor.read_u8()?),
             ColumnType::Json(..) => ColumnType::Json(cursor.read_u8()?),
             c => c,
-        })
+        };
+        if column_type.has_valid_metadata() {
+            Ok(column_type)
+        } else {
+            Err(ColumnParseError::InvalidColumnMetadata { column_type })
+        }
+    }
+
+    /// Whether the lengths in this type's metadata are ones we know how to read values with
+    fn has_valid_metadata(&self) -> bool {
+        match *self {
+            ColumnType::Float(length) => length == 4,
+            ColumnType::Double(length) => length == 8,
+            ColumnType::Blob(length_bytes)
+            | ColumnType::Json(length_bytes)
+            | ColumnType::Geometry(length_bytes) => matches!(length_bytes, 1..=4),
+            ColumnType::Enum(length_bytes) => matches!(length_bytes & 0xff, 1 | 2),
+            ColumnType::NewDecimal(precision, scale) => {
+                (1..=MAX_DECIMAL_PRECISION).contains(&precision) && scale <= precision
+            }
+            _ => true,
+        }
     }
 
     pub fn read_value<R: Read>(&self, r: &mut R) -> Result<MySQLValue, ColumnParseError> {
@@ -163,6 +225,7 @@ impl ColumnType {
             &ColumnType::Timestamp => Ok(MySQLValue::Timestamp {
                 unix_time: r.read_i32::<LittleEndian>()?,
                 subsecond: 0,
+                fsp: 0,
             }),
             &ColumnType::LongLong => Ok(MySQLValue::SignedInteger(r.read_i64::<LittleEndian>()?)),
             &ColumnType::Int24 => {
@@ -171,10 +234,8 @@ impl ColumnType {
             }
             &ColumnType::Null => Ok(MySQLValue::Null),
             &ColumnType::VarChar(max_len) => {
-                // TODO: don't decode to String,
-                // since type=real_type=MYSQL_TYPE_STRING is used for BINARY(n)
-                // and type=MYSQL_TYPE_VARCHAR is used for VARBINARY(n)
-                // and also the CHAR(n) and VARCHAR(n) encoding is not always utf-8
+                // without the column's collation (see read_text_value), CHAR(n), BINARY(n),
+                // VARCHAR(n), and VARBINARY(n) are all assumed to be utf-8
                 let value = if max_len > 255 {
                     read_two_byte_length_prefixed_string(r)?
                 } else {
@@ -199,15 +260,20 @@ impl ColumnType {
                 }
             }
             &ColumnType::Time => {
-                let val = read_uint24(r)?;
+                // HHMMSS, negated for negative times
+                let val = r.read_int::<LittleEndian>(3)?;
+                let negative = val < 0;
+                let val = val.unsigned_abs() as u32;
                 let hours = val / 10000;
                 let minutes = (val % 10000) / 100;
                 let seconds = val % 100;
                 Ok(MySQLValue::Time {
+                    negative,
                     hours,
                     minutes,
                     seconds,
                     subseconds: 0,
+                    fsp: 0,
                 })
             }
             &ColumnType::DateTime => {
@@ -234,6 +300,7 @@ impl ColumnType {
                             minute,
                             second,
                             subsecond: 0,
+                            fsp: 0,
                         })
                     }
                 }
@@ -268,6 +335,7 @@ impl ColumnType {
                     minute,
                     second,
                     subsecond,
+                    fsp: pack_length,
                 })
             }
             &ColumnType::Timestamp2(pack_length) => {
@@ -276,25 +344,22 @@ impl ColumnType {
                 Ok(MySQLValue::Timestamp {
                     unix_time: whole_part,
                     subsecond: frac_part,
+                    fsp: pack_length,
                 })
             }
             &ColumnType::Time2(pack_length) => {
-                // one bit sign
-                // one bit unused
-                // 10 bits hour
-                // 6 bits minute
-                // 6 bits second
-                let mut buf = [0u8; 3];
-                r.read_exact(&mut buf)?;
-                let hours = (((buf[0] & 0x3f) as u32) << 4) | (((buf[1] & 0xf0) as u32) >> 4);
-                let minutes = (((buf[1] & 0x0f) as u32) << 2) | (((buf[2] & 0xb0) as u32) >> 6);
-                let seconds = (buf[2] & 0x3f) as u32;
-                let frac_part = read_datetime_subsecond_part(r, pack_length)?;
+                let packed = read_time2_packed(r, pack_length)?;
+                let negative = packed < 0;
+                let packed = packed.unsigned_abs();
+                // 1 bit unused, 10 bits hour, 6 bits minute, 6 bits second, 24 bits microseconds
+                let hms = packed >> 24;
                 Ok(MySQLValue::Time {
-                    hours,
-                    minutes,
-                    seconds,
-                    subseconds: frac_part,
+                    negative,
+                    hours: ((hms >> 12) & 0x3ff) as u32,
+                    minutes: ((hms >> 6) & 0x3f) as u32,
+                    seconds: (hms & 0x3f) as u32,
+                    subseconds: (packed & 0xff_ffff) as u32,
+                    fsp: pack_length,
                 })
             }
             &ColumnType::Blob(length_bytes) => {
@@ -307,7 +372,9 @@ impl ColumnType {
                 } else if length == 8 {
                     Ok(MySQLValue::Double(r.read_f64::<LittleEndian>()?))
                 } else {
-                    unimplemented!("wtf is a {}-byte float?", length)
+                    Err(ColumnParseError::InvalidColumnMetadata {
+                        column_type: self.clone(),
+                    })
                 }
             }
             &ColumnType::NewDecimal(precision, decimal_places) => {
@@ -318,10 +385,33 @@ impl ColumnType {
                 let enum_value = match (length_bytes & 0xff) as u8 {
                     0x01 => i16::from(r.read_i8()?),
                     0x02 => r.read_i16::<LittleEndian>()?,
-                    i => unimplemented!("unhandled Enum pack_length {:?}", i),
+                    _ => {
+                        return Err(ColumnParseError::InvalidColumnMetadata {
+                            column_type: self.clone(),
+                        })
+                    }
                 };
                 Ok(MySQLValue::Enum(enum_value))
             }
+            &ColumnType::Bit(bits, bytes) => {
+                // stored big-endian, in as few bytes as will hold the column
+                let body = read_nbytes(r, bit_value_bytes(bits, bytes))?;
+                let value = body.iter().fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+                Ok(MySQLValue::Bit {
+                    value,
+                    length: u16::from(bytes) * 8 + u16::from(bits),
+                })
+            }
+            &ColumnType::Geometry(length_bytes) => {
+                let body = read_var_byte_length_prefixed_bytes(r, length_bytes)?;
+                if body.len() < 4 {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                Ok(MySQLValue::Geometry {
+                    srid: LittleEndian::read_u32(&body),
+                    wkb: body[4..].to_vec().into(),
+                })
+            }
             &ColumnType::Json(size) => {
                 let body = read_var_byte_length_prefixed_bytes(r, size)?;
                 Ok(MySQLValue::Json(jsonb::parse(body)?))
@@ -337,13 +427,342 @@ impl ColumnType {
                     column_type: self.clone(),
                 })
             }
-            &ColumnType::Decimal
-            | &ColumnType::NewDate
-            | &ColumnType::Bit(..)
-            | &ColumnType::Set(..)
-            | &ColumnType::Geometry(..) => {
-                unimplemented!("unhandled value type: {:?}", self);
+            &ColumnType::Set(length_bytes) => {
+                // a bitmask of the members which are set, in as many bytes as the set needs
+                let body = read_nbytes(r, set_value_bytes(length_bytes))?;
+                let value = body
+                    .iter()
+                    .rev()
+                    .fold(0u64, |acc, &b| acc << 8 | u64::from(b));
+                Ok(MySQLValue::Set(value))
+            }
+            &ColumnType::Decimal | &ColumnType::NewDate => {
+                Err(ColumnParseError::UnimplementedTypeError {
+                    column_type: self.clone(),
+                })
+            }
+        }
+    }
+
+    /// Read a value of a string column, decoding it according to the column's collation.
+    /// Values of other types are read as by [`read_value`](ColumnType::read_value).
+    pub fn read_text_value<R: Read>(
+        &self,
+        r: &mut R,
+        collation: u64,
+    ) -> Result<MySQLValue, ColumnParseError> {
+        match *self {
+            ColumnType::VarChar(max_len) => {
+                let bytes = if max_len > 255 {
+                    read_two_byte_length_prefixed_bytes(r)?
+                } else {
+                    read_one_byte_length_prefixed_bytes(r)?
+                };
+                Ok(charset::decode_text(bytes, collation))
             }
+            _ => self.read_value(r),
         }
     }
+
+    /// Read a value of an `UNSIGNED` integer column as a [`MySQLValue::UnsignedInteger`].
+    /// Values of other types are read as by [`read_value`](ColumnType::read_value).
+    pub fn read_unsigned_value<R: Read>(&self, r: &mut R) -> Result<MySQLValue, ColumnParseError> {
+        let value = match *self {
+            ColumnType::Tiny => u64::from(r.read_u8()?),
+            ColumnType::Short => u64::from(r.read_u16::<LittleEndian>()?),
+            ColumnType::Int24 => u64::from(read_uint24(r)?),
+            ColumnType::Long => u64::from(r.read_u32::<LittleEndian>()?),
+            ColumnType::LongLong => r.read_u64::<LittleEndian>()?,
+            _ => return self.read_value(r),
+        };
+        Ok(MySQLValue::UnsignedInteger(value))
+    }
+
+    /// Advance the reader past a single value of this type without materializing it
+    pub(crate) fn skip_value<R: Read>(&self, r: &mut R) -> Result<(), ColumnParseError> {
+        let length = match self {
+            &ColumnType::Null => 0,
+            &ColumnType::Tiny | &ColumnType::Year => 1,
+            &ColumnType::Short => 2,
+            &ColumnType::Int24 | &ColumnType::Date | &ColumnType::Time => 3,
+            &ColumnType::Long | &ColumnType::Timestamp => 4,
+            &ColumnType::LongLong | &ColumnType::DateTime => 8,
+            &ColumnType::Float(length) | &ColumnType::Double(length) => length as usize,
+            &ColumnType::DateTime2(pack_length) => 5 + datetime_subsecond_bytes(pack_length),
+            &ColumnType::Timestamp2(pack_length) => 4 + datetime_subsecond_bytes(pack_length),
+            &ColumnType::Time2(pack_length) => 3 + datetime_subsecond_bytes(pack_length),
+            &ColumnType::VarChar(max_len) => {
+                if max_len > 255 {
+                    r.read_u16::<LittleEndian>()? as usize
+                } else {
+                    r.read_u8()? as usize
+                }
+            }
+            &ColumnType::Blob(length_bytes)
+            | &ColumnType::Json(length_bytes)
+            | &ColumnType::Geometry(length_bytes) => read_var_byte_length(r, length_bytes)?,
+            &ColumnType::NewDecimal(precision, decimal_places) => {
+                decimal_binary_size(precision, decimal_places).ok_or_else(|| {
+                    ColumnParseError::InvalidColumnMetadata {
+                        column_type: self.clone(),
+                    }
+                })?
+            }
+            &ColumnType::Enum(length_bytes) => (length_bytes & 0xff) as usize,
+            &ColumnType::Bit(bits, bytes) => bit_value_bytes(bits, bytes),
+            &ColumnType::Set(length_bytes) => set_value_bytes(length_bytes),
+            _ => {
+                return Err(ColumnParseError::UnimplementedTypeError {
+                    column_type: self.clone(),
+                })
+            }
+        };
+        skip_nbytes(r, length)?;
+        Ok(())
+    }
+}
+
+fn bit_value_bytes(bits: u8, bytes: u8) -> usize {
+    bytes as usize + usize::from(bits > 0)
+}
+
+fn set_value_bytes(length_bytes: u16) -> usize {
+    (length_bytes & 0xff) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use assert_matches::assert_matches;
+
+    use super::ColumnType;
+    use crate::errors::ColumnParseError;
+    use crate::value::MySQLValue;
+
+    #[test]
+    fn test_string_metadata() {
+        let metadata =
+            |f1: u8, f2: u8| ColumnType::MyString.read_metadata(&mut Cursor::new(&[f1, f2]));
+        // CHAR(10)
+        assert_eq!(metadata(0xfe, 10).unwrap(), ColumnType::VarChar(10));
+        // CHAR(255) CHARACTER SET utf8mb4 is up to 1020 bytes; the high bits of the length are
+        // packed into the type
+        assert_eq!(metadata(0xce, 0xfc).unwrap(), ColumnType::VarChar(1020));
+        assert_eq!(metadata(0, 20).unwrap(), ColumnType::VarChar(20));
+        assert_eq!(metadata(0xf7, 1).unwrap(), ColumnType::Enum(1));
+        assert_eq!(metadata(0xf8, 8).unwrap(), ColumnType::Set(8));
+        assert!(metadata(0xc1, 1).is_err());
+
+        // values of wide CHAR columns have a two-byte length
+        let value = ColumnType::VarChar(1020)
+            .read_value(&mut Cursor::new(&[3, 0, b'a', b'b', b'c']))
+            .unwrap();
+        assert!(matches!(value, MySQLValue::String(ref s) if s == "abc"));
+    }
+
+    #[test]
+    fn test_fractional_seconds() {
+        // ".5" is stored as 50 hundredths with a precision of 1 or 2, and as 500000 millionths
+        // with a precision of 5 or 6
+        for (precision, fraction) in [(1u8, vec![50u8]), (6, vec![0x07, 0xa1, 0x20])].iter() {
+            let mut data = vec![0, 0, 0, 1];
+            data.extend_from_slice(fraction);
+            let value = ColumnType::Timestamp2(*precision)
+                .read_value(&mut data.as_slice())
+                .unwrap();
+            assert!(matches!(
+                value,
+                MySQLValue::Timestamp { unix_time: 1, subsecond: 500_000, fsp } if fsp == *precision
+            ));
+        }
+    }
+
+    #[test]
+    fn test_negative_time() {
+        let time = |column: ColumnType, data: &[u8]| {
+            let value = column.read_value(&mut &data[..]).unwrap();
+            value.to_temporal_string().unwrap()
+        };
+        // 838:59:59, and its negation
+        assert_eq!(time(ColumnType::Time2(0), &[0xb4, 0x6e, 0xfb]), "838:59:59");
+        assert_eq!(
+            time(ColumnType::Time2(0), &[0x4b, 0x91, 0x05]),
+            "-838:59:59"
+        );
+        // -00:00:01.5
+        assert_eq!(
+            time(ColumnType::Time2(1), &[0x7f, 0xff, 0xfe, 0xce]),
+            "-00:00:01.5"
+        );
+        // 12:34:56.000789
+        assert_eq!(
+            time(ColumnType::Time2(6), &[0x80, 0xc8, 0xb8, 0x00, 0x03, 0x15]),
+            "12:34:56.000789"
+        );
+        // the pre-5.6 TIME type stores HHMMSS, negated for negative times
+        let hhmmss = (-1_020_304i32).to_le_bytes();
+        assert_eq!(time(ColumnType::Time, &hhmmss[..3]), "-102:03:04");
+    }
+
+    #[test]
+    fn test_unsigned() {
+        let max = [0xffu8; 8];
+        assert!(matches!(
+            ColumnType::LongLong
+                .read_unsigned_value(&mut &max[..])
+                .unwrap(),
+            MySQLValue::UnsignedInteger(u64::MAX)
+        ));
+        assert!(matches!(
+            ColumnType::LongLong.read_value(&mut &max[..]).unwrap(),
+            MySQLValue::SignedInteger(-1)
+        ));
+        assert!(matches!(
+            ColumnType::Tiny
+                .read_unsigned_value(&mut &[200u8][..])
+                .unwrap(),
+            MySQLValue::UnsignedInteger(200)
+        ));
+        assert!(matches!(
+            ColumnType::Int24
+                .read_unsigned_value(&mut &[0xff, 0xff, 0xff][..])
+                .unwrap(),
+            MySQLValue::UnsignedInteger(0xff_ffff)
+        ));
+        // other types are unaffected
+        assert!(matches!(
+            ColumnType::Year
+                .read_unsigned_value(&mut &[120u8][..])
+                .unwrap(),
+            MySQLValue::Year(2020)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        assert_matches!(
+            ColumnType::from_byte(200),
+            Err(ColumnParseError::UnknownColumnType(200))
+        );
+        assert_matches!(
+            ColumnType::Float(0).read_metadata(&mut Cursor::new(&[3])),
+            Err(ColumnParseError::InvalidColumnMetadata { .. })
+        );
+        assert_matches!(
+            ColumnType::Blob(0).read_metadata(&mut Cursor::new(&[7])),
+            Err(ColumnParseError::InvalidColumnMetadata { .. })
+        );
+        for (precision, scale) in [(0, 0), (5, 10), (66, 0)] {
+            assert_matches!(
+                ColumnType::NewDecimal(0, 0).read_metadata(&mut Cursor::new(&[precision, scale])),
+                Err(ColumnParseError::InvalidColumnMetadata { .. })
+            );
+        }
+        // VARCHAR(0) is legal
+        assert_eq!(
+            ColumnType::VarChar(0)
+                .read_metadata(&mut Cursor::new(&[0, 0]))
+                .unwrap(),
+            ColumnType::VarChar(0)
+        );
+        // types constructed by hand aren't checked until a value is read
+        let mut cursor = Cursor::new(&[0u8; 8]);
+        assert_matches!(
+            ColumnType::Double(6).read_value(&mut cursor),
+            Err(ColumnParseError::InvalidColumnMetadata { .. })
+        );
+        assert_matches!(
+            ColumnType::Enum(3).read_value(&mut cursor),
+            Err(ColumnParseError::InvalidColumnMetadata { .. })
+        );
+        assert_matches!(
+            ColumnType::Blob(5).read_value(&mut cursor),
+            Err(ColumnParseError::Io(_))
+        );
+        assert_matches!(
+            ColumnType::Decimal.read_value(&mut cursor),
+            Err(ColumnParseError::UnimplementedTypeError { .. })
+        );
+    }
+
+    #[test]
+    fn test_bit() {
+        // BIT(10): two bits and one full byte of metadata
+        let column = ColumnType::Bit(0, 0)
+            .read_metadata(&mut Cursor::new(&[2, 1]))
+            .unwrap();
+        assert_eq!(column, ColumnType::Bit(2, 1));
+        let mut cursor = Cursor::new(&[0x02, 0x05, 0xff]);
+        match column.read_value(&mut cursor).unwrap() {
+            MySQLValue::Bit { value, length } => {
+                assert_eq!(value, 0x205);
+                assert_eq!(length, 10);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(cursor.position(), 2);
+
+        let mut cursor = Cursor::new(&[0xff; 9]);
+        ColumnType::Bit(0, 8).skip_value(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), 8);
+        assert!(matches!(
+            ColumnType::Bit(0, 8)
+                .read_value(&mut Cursor::new(&[0xff; 8]))
+                .unwrap(),
+            MySQLValue::Bit {
+                value: u64::MAX,
+                length: 64
+            }
+        ));
+    }
+
+    #[test]
+    fn test_set() {
+        // SET with 12 members; its ENUM/SET pack length is 2 bytes
+        let column = ColumnType::MyString
+            .read_metadata(&mut Cursor::new(&[0xf8, 2]))
+            .unwrap();
+        assert_eq!(column, ColumnType::Set(2));
+        let mut cursor = Cursor::new(&[0x05, 0x08, 0xff]);
+        assert!(matches!(
+            column.read_value(&mut cursor).unwrap(),
+            MySQLValue::Set(0x805)
+        ));
+        assert_eq!(cursor.position(), 2);
+        let mut cursor = Cursor::new(&[0x05, 0x08, 0xff]);
+        column.skip_value(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), 2);
+    }
+
+    #[test]
+    fn test_geometry() {
+        // POINT(1 2) with SRID 4326, behind a 4-byte length
+        let mut body = vec![25, 0, 0, 0, 0xe6, 0x10, 0, 0, 1, 1, 0, 0, 0];
+        body.extend_from_slice(&1.0f64.to_le_bytes());
+        body.extend_from_slice(&2.0f64.to_le_bytes());
+        body.push(0xff);
+        let column = ColumnType::Geometry(4);
+        let value = column.read_value(&mut Cursor::new(&body)).unwrap();
+        match value {
+            MySQLValue::Geometry { srid, ref wkb } => {
+                assert_eq!(srid, 4326);
+                assert_eq!(wkb.0, &body[8..29]);
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+        #[cfg(feature = "geo")]
+        assert_eq!(
+            value.geometry().unwrap().unwrap(),
+            crate::geometry::Geometry::Point(crate::geometry::Point { x: 1.0, y: 2.0 })
+        );
+
+        let mut cursor = Cursor::new(&body);
+        column.skip_value(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), 29);
+
+        assert!(column
+            .read_value(&mut Cursor::new(&[2, 0, 0, 0, 0, 0]))
+            .is_err());
+    }
 }
@@ -0,0 +1,603 @@
+//! Building a configured event pipeline from a declarative config file.
+//!
+//! Only available with the `config` feature. A [`PipelineConfig`] describes where to read from,
+//! which tables to include, which columns to keep or mask, and how to write the output, so tools
+//! built on this crate can share one config format:
+//!
+//! ```toml
+//! [source]
+//! path = "/var/lib/mysql/binlogs"   # a binlog file, or a directory of them
+//! verify_checksums = true
+//! executed_gtids = "87cee3a4-6b31-11e7-bdfe-0242ac110004:1-100"
+//! table_name_case = "lowercase"
+//!
+//! [filter]
+//! include = ["app.*"]
+//! exclude = ["app.audit_*"]
+//!
+//! [[projection]]
+//! table = "app.users"
+//! columns = [0, "email", "name"]
+//!
+//! [[mask]]
+//! table = "app.*"
+//! columns = ["email"]
+//! with = "redact"
+//!
+//! [output]
+//! format = "export_records"
+//! ```
+//!
+//! Tables are matched by `schema.table` patterns in which `*` matches any run of characters.
+//! Columns are selected by zero-based position, or by name if the binlog (or a
+//! [`SchemaProvider`](crate::table_map::SchemaProvider)) names them; masks are applied before
+//! projections, so both refer to the table's own columns. Filters, projections, and masks only
+//! affect rows events.
+//!
+//! The config types implement `Deserialize`, so formats other than TOML can be used with the
+//! appropriate serde crate.
+
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::errors::{ConfigError, ErrorPolicy};
+use crate::event::{ColumnCountPolicy, RowData, RowEvent};
+use crate::export::dedup_tables;
+use crate::table_map::{ColumnDescriptor, TableNameCase, TableRefFormat};
+use crate::value::MySQLValue;
+use crate::{BinlogEvent, BinlogFileParserBuilder, EventIterator, GtidSet};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// A complete pipeline; see the [module documentation](self)
+pub struct PipelineConfig {
+    #[serde(default)]
+    pub source: SourceConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default, rename = "projection")]
+    pub projections: Vec<ProjectionConfig>,
+    #[serde(default, rename = "mask")]
+    pub masks: Vec<MaskConfig>,
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Where to read events from, and the options of the [`BinlogFileParserBuilder`]
+pub struct SourceConfig {
+    /// A binlog file, or a directory of binlog files; not needed if the pipeline is built from
+    /// a [`BinlogFileParserBuilder`]
+    pub path: Option<PathBuf>,
+    pub start_position: Option<u64>,
+    pub stop_position: Option<u64>,
+    /// Seconds since the epoch
+    pub stop_timestamp: Option<u32>,
+    pub verify_checksums: bool,
+    pub lenient: bool,
+    /// Overrides `lenient` if set
+    pub error_policy: Option<ErrorPolicy>,
+    pub active_file: bool,
+    pub summaries_only: bool,
+    pub metadata_only: bool,
+    /// A GTID set, in the server's text format
+    pub executed_gtids: Option<String>,
+    pub max_rows_per_event: Option<usize>,
+    pub column_count_policy: ColumnCountPolicy,
+    pub table_name_case: TableNameCase,
+    pub table_ref_format: TableRefFormat,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// Which tables' rows events are emitted. If `include` is empty every table not excluded is.
+pub struct FilterConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+/// A column, by zero-based position or by name
+pub enum ColumnSelector {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Keep only some columns of the matching tables
+pub struct ProjectionConfig {
+    pub table: String,
+    pub columns: Vec<ColumnSelector>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What a masked value is replaced with
+pub enum MaskStyle {
+    /// SQL `NULL`
+    #[default]
+    Null,
+    /// The string `"[redacted]"`
+    Redact,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// Replace the values of some columns of the matching tables
+pub struct MaskConfig {
+    pub table: String,
+    pub columns: Vec<ColumnSelector>,
+    #[serde(default)]
+    pub with: MaskStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// One JSON-serialized [`BinlogEvent`] per line
+    #[default]
+    Json,
+    /// One JSON-serialized [`ExportRecord`](crate::export::ExportRecord) per line; see
+    /// [`dedup_tables`]
+    ExportRecords,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: OutputFormat,
+}
+
+/// A `schema.table` pattern
+#[derive(Debug, Clone)]
+struct TablePattern {
+    schema: String,
+    table: String,
+}
+
+impl TablePattern {
+    fn parse(pattern: &str, name_case: TableNameCase) -> Result<Self, ConfigError> {
+        match pattern.split_once('.') {
+            Some((schema, table)) if !schema.is_empty() && !table.is_empty() => Ok(TablePattern {
+                schema: name_case.key(schema).into_owned(),
+                table: name_case.key(table).into_owned(),
+            }),
+            _ => Err(ConfigError::InvalidTablePattern(pattern.to_owned())),
+        }
+    }
+
+    fn matches(&self, schema: &str, table: &str) -> bool {
+        glob_match(self.schema.as_bytes(), schema.as_bytes())
+            && glob_match(self.table.as_bytes(), table.as_bytes())
+    }
+}
+
+/// Match `text` against `pattern`, in which `*` matches any run of characters
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+impl PipelineConfig {
+    /// Parse a TOML config
+    pub fn from_toml(config: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(config)?)
+    }
+
+    /// Read and parse a TOML config file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Open the configured source and build the pipeline
+    pub fn build(&self) -> Result<Pipeline<File>, ConfigError> {
+        let path = self
+            .source
+            .path
+            .as_ref()
+            .ok_or(ConfigError::MissingSource)?;
+        let builder = if path.is_dir() {
+            BinlogFileParserBuilder::try_from_directory(path)?
+        } else {
+            BinlogFileParserBuilder::try_from_path(path)?
+        };
+        self.build_from(builder)
+    }
+
+    /// Build the pipeline on top of an existing builder (for example, one reading from memory),
+    /// ignoring the configured path
+    pub fn build_from<BR: Read + Seek>(
+        &self,
+        builder: BinlogFileParserBuilder<BR>,
+    ) -> Result<Pipeline<BR>, ConfigError> {
+        let source = &self.source;
+        let mut builder = builder
+            .column_count_policy(source.column_count_policy)
+            .table_name_case(source.table_name_case)
+            .table_ref_format(source.table_ref_format);
+        // don't turn off anything the builder was already configured with
+        if source.verify_checksums {
+            builder = builder.verify_checksums(true);
+        }
+        if source.lenient {
+            builder = builder.lenient(true);
+        }
+        if let Some(policy) = source.error_policy {
+            builder = builder.error_policy(policy);
+        }
+        if source.active_file {
+            builder = builder.active_file(true);
+        }
+        if source.summaries_only {
+            builder = builder.summaries_only(true);
+        }
+        if source.metadata_only {
+            builder = builder.metadata_only(true);
+        }
+        if let Some(position) = source.start_position {
+            builder = builder.start_position(position);
+        }
+        if let Some(position) = source.stop_position {
+            builder = builder.stop_position(position);
+        }
+        if let Some(timestamp) = source.stop_timestamp {
+            builder = builder.stop_timestamp(timestamp);
+        }
+        if let Some(ref executed) = source.executed_gtids {
+            builder = builder.executed_gtids(executed.parse::<GtidSet>()?);
+        }
+        if let Some(max_rows) = source.max_rows_per_event {
+            builder = builder.max_rows_per_event(max_rows);
+        }
+
+        let name_case = source.table_name_case;
+        let patterns = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| TablePattern::parse(p, name_case))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let rules = Rules {
+            name_case,
+            include: patterns(&self.filter.include)?,
+            exclude: patterns(&self.filter.exclude)?,
+            projections: self
+                .projections
+                .iter()
+                .map(|p| Ok((TablePattern::parse(&p.table, name_case)?, p.columns.clone())))
+                .collect::<Result<_, ConfigError>>()?,
+            masks: self
+                .masks
+                .iter()
+                .map(|m| {
+                    Ok((
+                        TablePattern::parse(&m.table, name_case)?,
+                        m.columns.clone(),
+                        m.with,
+                    ))
+                })
+                .collect::<Result<_, ConfigError>>()?,
+        };
+        Ok(Pipeline {
+            events: builder.build(),
+            rules,
+            format: self.output.format,
+        })
+    }
+}
+
+struct Rules {
+    name_case: TableNameCase,
+    include: Vec<TablePattern>,
+    exclude: Vec<TablePattern>,
+    projections: Vec<(TablePattern, Vec<ColumnSelector>)>,
+    masks: Vec<(TablePattern, Vec<ColumnSelector>, MaskStyle)>,
+}
+
+/// Find the position of each selected column
+fn resolve(
+    selectors: &[ColumnSelector],
+    columns: Option<&[ColumnDescriptor]>,
+    table: &str,
+) -> Result<Vec<usize>, ConfigError> {
+    selectors
+        .iter()
+        .map(|selector| match selector {
+            ColumnSelector::Index(i) => Ok(*i),
+            ColumnSelector::Name(name) => columns
+                .and_then(|columns| {
+                    columns
+                        .iter()
+                        .position(|c| c.name.as_deref() == Some(name.as_str()))
+                })
+                .ok_or_else(|| ConfigError::UnresolvedColumn {
+                    table: table.to_owned(),
+                    column: name.clone(),
+                }),
+        })
+        .collect()
+}
+
+fn images(row: &mut RowEvent) -> Vec<&mut RowData> {
+    match row {
+        RowEvent::NewRow { cols } | RowEvent::DeletedRow { cols } => vec![cols],
+        RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        } => vec![before_cols, after_cols],
+    }
+}
+
+impl Rules {
+    /// Apply the rules to an event, returning `None` if it is filtered out
+    fn apply(&self, mut event: BinlogEvent) -> Result<Option<BinlogEvent>, ConfigError> {
+        let (schema, table) = match event.table_ref {
+            Some(ref t) => (
+                self.name_case.key(&t.schema).into_owned(),
+                self.name_case.key(&t.table).into_owned(),
+            ),
+            None => return Ok(Some(event)),
+        };
+        let included =
+            self.include.is_empty() || self.include.iter().any(|p| p.matches(&schema, &table));
+        if !included || self.exclude.iter().any(|p| p.matches(&schema, &table)) {
+            return Ok(None);
+        }
+        let qualified = format!("{}.{}", schema, table);
+
+        for (pattern, selectors, style) in &self.masks {
+            if !pattern.matches(&schema, &table) {
+                continue;
+            }
+            let masked = resolve(selectors, event.columns.as_deref(), &qualified)?;
+            for row in event.rows.iter_mut() {
+                for image in images(row) {
+                    for &i in &masked {
+                        if let Some(value @ Some(_)) = image.get_mut(i) {
+                            *value = Some(match style {
+                                MaskStyle::Null => MySQLValue::Null,
+                                MaskStyle::Redact => MySQLValue::String("[redacted]".to_owned()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((_, selectors)) = self
+            .projections
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&schema, &table))
+        {
+            let mut kept = resolve(selectors, event.columns.as_deref(), &qualified)?;
+            kept.sort_unstable();
+            kept.dedup();
+            for row in event.rows.iter_mut() {
+                for image in images(row) {
+                    let mut i = 0;
+                    image.retain(|_| {
+                        i += 1;
+                        kept.binary_search(&(i - 1)).is_ok()
+                    });
+                }
+            }
+            event.columns = event.columns.map(|columns| {
+                kept.iter()
+                    .filter_map(|&i| columns.get(i).cloned())
+                    .collect::<Arc<[_]>>()
+            });
+            event.column_types = event.column_types.map(|types| {
+                kept.iter()
+                    .filter_map(|&i| types.get(i).cloned())
+                    .collect::<Arc<[_]>>()
+            });
+        }
+        Ok(Some(event))
+    }
+}
+
+/// An [`EventIterator`] with the filters, projections, and masks of a [`PipelineConfig`] applied
+pub struct Pipeline<BR: Read + Seek> {
+    events: EventIterator<BR>,
+    rules: Rules,
+    format: OutputFormat,
+}
+
+impl<BR: Read + Seek> Pipeline<BR> {
+    /// The underlying iterator, for its statistics and state
+    pub fn events(&self) -> &EventIterator<BR> {
+        &self.events
+    }
+
+    /// Write every event in the configured output format, one JSON document per line, returning
+    /// the number of lines written
+    pub fn write_to<W: Write>(self, mut w: W) -> Result<u64, ConfigError> {
+        let mut written = 0;
+        match self.format {
+            OutputFormat::Json => {
+                for event in self {
+                    serde_json::to_writer(&mut w, &event?)?;
+                    w.write_all(b"\n")?;
+                    written += 1;
+                }
+            }
+            OutputFormat::ExportRecords => {
+                for record in dedup_tables(self) {
+                    serde_json::to_writer(&mut w, &record?)?;
+                    w.write_all(b"\n")?;
+                    written += 1;
+                }
+            }
+        }
+        Ok(written)
+    }
+}
+
+impl<BR: Read + Seek> Iterator for Pipeline<BR> {
+    type Item = Result<BinlogEvent, ConfigError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = match self.events.next()? {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e.into())),
+            };
+            match self.rules.apply(event) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, PipelineConfig};
+    use crate::errors::ConfigError;
+    use crate::event::RowEvent;
+    use crate::table_map::ColumnDescriptor;
+    use crate::value::MySQLValue;
+    use crate::BinlogFileParserBuilder;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"audit_*", b"audit_log"));
+        assert!(glob_match(b"a*c*e", b"abcde"));
+        assert!(!glob_match(b"a*c", b"abcd"));
+        assert!(!glob_match(b"foo", b"fo"));
+    }
+
+    fn pipeline(config: &str) -> Vec<crate::BinlogEvent> {
+        let mut config = PipelineConfig::from_toml(config).unwrap();
+        config.source.path = Some("test_data/bin-log.000001".into());
+        config
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(pipeline("").len(), 7);
+        assert_eq!(pipeline("[filter]\ninclude = [\"bltest.*\"]").len(), 7);
+        assert_eq!(pipeline("[filter]\ninclude = [\"other.*\"]").len(), 5);
+        assert_eq!(
+            pipeline("[filter]\ninclude = [\"*.*\"]\nexclude = [\"*.f*\"]").len(),
+            5
+        );
+        // names are compared case-insensitively if so configured
+        let config = "[source]\ntable_name_case = \"case_insensitive\"\n\
+                      [filter]\nexclude = [\"BLTEST.Foo\"]";
+        assert_eq!(pipeline(config).len(), 5);
+
+        assert!(matches!(
+            PipelineConfig::from_toml("[filter]\ninclude = [\"nodot\"]")
+                .unwrap()
+                .build_from(
+                    BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap()
+                ),
+            Err(ConfigError::InvalidTablePattern(_))
+        ));
+        assert!(matches!(
+            PipelineConfig::from_toml("[surce]\npath = \"x\""),
+            Err(ConfigError::Toml(_))
+        ));
+        assert!(matches!(
+            PipelineConfig::from_toml("").unwrap().build(),
+            Err(ConfigError::MissingSource)
+        ));
+    }
+
+    #[test]
+    fn test_projection_and_masks() {
+        let config = "[[mask]]\ntable = \"bltest.foo\"\ncolumns = [2]\nwith = \"redact\"\n\
+                      [[projection]]\ntable = \"bltest.*\"\ncolumns = [2, 0]";
+        let events = pipeline(config);
+        let rows = events
+            .iter()
+            .flat_map(|e| e.rows.iter())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        for row in rows {
+            match row {
+                RowEvent::NewRow { cols } => {
+                    assert_eq!(cols.len(), 2);
+                    assert!(matches!(cols[0], Some(MySQLValue::SignedInteger(_))));
+                    assert!(
+                        matches!(cols[1], Some(MySQLValue::String(ref s)) if s == "[redacted]")
+                    );
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        // the test binlog doesn't name its columns, so names need a schema provider
+        let config = "[[mask]]\ntable = \"bltest.foo\"\ncolumns = [\"name\"]";
+        let mut events = PipelineConfig::from_toml(config)
+            .unwrap()
+            .build_from(BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap())
+            .unwrap();
+        assert!(matches!(
+            events.find_map(Result::err),
+            Some(ConfigError::UnresolvedColumn { ref column, .. }) if column == "name"
+        ));
+
+        let mut schema = std::collections::HashMap::new();
+        let named = |name: &str| ColumnDescriptor {
+            name: Some(name.to_owned()),
+            ..ColumnDescriptor::default()
+        };
+        schema.insert(
+            ("bltest".to_owned(), "foo".to_owned()),
+            vec![named("id"), named("price"), named("name")],
+        );
+        let events = PipelineConfig::from_toml(&format!(
+            "{}\n[[projection]]\ntable = \"bltest.foo\"\ncolumns = [\"name\"]",
+            config
+        ))
+        .unwrap()
+        .build_from(
+            BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+                .unwrap()
+                .schema_provider(schema),
+        )
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        let event = events.iter().find(|e| !e.rows.is_empty()).unwrap();
+        assert_eq!(event.columns.as_ref().unwrap().len(), 1);
+        assert!(matches!(
+            event.rows[0],
+            RowEvent::NewRow { ref cols } if cols.len() == 1 && matches!(cols[0], Some(MySQLValue::Null))
+        ));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let mut config =
+            PipelineConfig::from_toml("[output]\nformat = \"export_records\"").unwrap();
+        config.source.path = Some("test_data/bin-log.000001".into());
+        let mut out = Vec::new();
+        assert_eq!(config.build().unwrap().write_to(&mut out).unwrap(), 9);
+        let first_table = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .find(|r| r["record"] == "table")
+            .unwrap();
+        assert_eq!(first_table["table_name"], "foo");
+    }
+}
@@ -0,0 +1,265 @@
+//! Extracting a range of transactions into a standalone binlog.
+//!
+//! [`copy_range`] reads a sequence of binlog files and writes every transaction between two
+//! GTIDs (inclusive) into a new binlog, which can be read by this crate, `mysqlbinlog`, or
+//! replayed against another server. Row-based transactions carry their own Table Map events, so
+//! each copied transaction is self-contained; the output begins with the Format Description
+//! Event of the first source file and a Previous GTIDs event describing everything executed
+//! before the range.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::binlog_file::BinlogFile;
+use crate::errors::BinlogParseError;
+use crate::event::{Event, EventData, TypeCode};
+use crate::gtid::{Gtid, GtidSet};
+
+const BINLOG_MAGIC: [u8; 4] = [0xfe, 0x62, 0x69, 0x6e];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// What was written by [`copy_range`]
+pub struct CopySummary {
+    pub transactions: u64,
+    pub events: u64,
+    pub bytes: u64,
+}
+
+struct Writer<W: Write> {
+    dst: W,
+    offset: u64,
+    summary: CopySummary,
+}
+
+impl<W: Write> Writer<W> {
+    fn write(&mut self, event: &Event) -> Result<(), BinlogParseError> {
+        self.offset = event.write_at(&mut self.dst, self.offset)?;
+        self.summary.events += 1;
+        self.summary.bytes = self.offset;
+        Ok(())
+    }
+}
+
+fn is_transaction_start(type_code: TypeCode) -> bool {
+    matches!(
+        type_code,
+        TypeCode::GtidLogEvent | TypeCode::GtidTaggedLogEvent | TypeCode::AnonymousGtidLogEvent
+    )
+}
+
+/// Copy every transaction from `from` to `to` (inclusive) in `src_files` into a new binlog
+/// written to `dst`. Both GTIDs must belong to the same source (and tag); transactions from
+/// other sources which are interleaved with the range are not copied.
+///
+/// ## Errors
+///
+/// - [`BinlogParseError::InvalidGtidRange`] if `from` and `to` aren't from the same source, or
+///   `to` precedes `from`
+/// - [`BinlogParseError::GtidNotFound`] if the files end before `to` has been copied
+pub fn copy_range<P: AsRef<Path>, W: Write>(
+    src_files: &[P],
+    dst: W,
+    from: &Gtid,
+    to: &Gtid,
+) -> Result<CopySummary, BinlogParseError> {
+    if !from.same_sequence(to) || from.sequence() > to.sequence() {
+        return Err(BinlogParseError::InvalidGtidRange {
+            from: Box::new(*from),
+            to: Box::new(*to),
+        });
+    }
+    let in_range = |gtid: &Gtid| {
+        gtid.same_sequence(from)
+            && gtid.sequence() >= from.sequence()
+            && gtid.sequence() <= to.sequence()
+    };
+    let mut writer = Writer {
+        dst,
+        offset: BINLOG_MAGIC.len() as u64,
+        summary: CopySummary::default(),
+    };
+    writer.dst.write_all(&BINLOG_MAGIC)?;
+    // everything executed before the current transaction, if the source files track it
+    let mut executed: Option<GtidSet> = None;
+    let mut previous_gtids: Option<Event> = None;
+    let mut copying = false;
+    let mut found_end = false;
+    'files: for (i, path) in src_files.iter().enumerate() {
+        let mut events = BinlogFile::try_from_path(path)?.events(Some(4));
+        let fde = match events.next() {
+            Some(event) => event?,
+            None => return Err(BinlogParseError::BadFirstRecord),
+        };
+        if i == 0 {
+            writer.write(&fde)?;
+        }
+        for event in events {
+            let event = event?;
+            let type_code = event.type_code();
+            if type_code == TypeCode::PreviousGtidsLogEvent {
+                if previous_gtids.is_none() {
+                    executed = Some(GtidSet::read_from(&mut event.data().as_slice())?);
+                    previous_gtids = Some(event);
+                }
+                continue;
+            }
+            if is_transaction_start(type_code) {
+                if found_end {
+                    break 'files;
+                }
+                let gtid = match event.inner(None)? {
+                    Some(EventData::GtidLogEvent {
+                        uuid,
+                        tag,
+                        coordinate,
+                        ..
+                    }) => Some(Gtid::new(uuid, tag, coordinate)),
+                    _ => None,
+                };
+                copying = gtid.as_ref().map(in_range).unwrap_or(false);
+                if copying {
+                    if writer.summary.transactions == 0 {
+                        if let (Some(event), Some(executed)) =
+                            (previous_gtids.take(), executed.as_ref())
+                        {
+                            writer.write(&event.with_data(executed.encode()))?;
+                        }
+                    }
+                    writer.summary.transactions += 1;
+                    found_end = gtid.as_ref() == Some(to);
+                }
+                if let (Some(executed), Some(gtid)) = (executed.as_mut(), gtid) {
+                    executed.insert(&gtid);
+                }
+            } else if matches!(
+                type_code,
+                TypeCode::RotateEvent | TypeCode::StopEvent | TypeCode::FormatDescriptionEvent
+            ) {
+                // these describe the source files, not the transactions in them
+                continue;
+            }
+            if copying {
+                writer.write(&event)?;
+            }
+        }
+    }
+    writer.dst.flush()?;
+    if found_end {
+        Ok(writer.summary)
+    } else {
+        Err(BinlogParseError::GtidNotFound(Box::new(*to)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::copy_range;
+    use crate::binlog_file::BinlogFile;
+    use crate::errors::BinlogParseError;
+    use crate::gtid::{Gtid, GtidSet};
+    use crate::{parse_reader, BinlogFileParserBuilder};
+
+    fn gtid(sequence: u64) -> Gtid {
+        Gtid::new(
+            "87cee3a4-6b31-11e7-bdfd-0d98d6698870".parse().unwrap(),
+            None,
+            sequence,
+        )
+    }
+
+    #[test]
+    fn test_copy_range() {
+        let mut copied = Vec::new();
+        let summary = copy_range(
+            &["test_data/bin-log.000001"],
+            &mut copied,
+            &gtid(14919),
+            &gtid(14919),
+        )
+        .unwrap();
+        assert_eq!(summary.transactions, 1);
+        assert_eq!(summary.bytes, copied.len() as u64);
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(&copied))
+            .unwrap()
+            .verify_checksums(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].gtid, Some(gtid(14919)));
+        assert_eq!(results[1].rows.len(), 1);
+        // the previous GTIDs now include the transaction before the range
+        let previous = BinlogFile::try_from_reader(std::io::Cursor::new(&copied))
+            .unwrap()
+            .events(None)
+            .next()
+            .unwrap()
+            .unwrap();
+        let previous = GtidSet::read_from(&mut previous.data().as_slice()).unwrap();
+        assert_eq!(
+            previous.to_string(),
+            "87cee3a4-6b31-11e7-bdfd-0d98d6698870:1-14918"
+        );
+        assert_eq!(
+            parse_reader(std::io::Cursor::new(copied)).unwrap().count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_copy_range_flushes() {
+        #[derive(Default)]
+        struct Dst {
+            buffered: usize,
+            flushed: usize,
+        }
+
+        impl std::io::Write for Dst {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.buffered += buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushed = self.buffered;
+                Ok(())
+            }
+        }
+
+        // the range ends before the end of the file
+        let mut dst = Dst::default();
+        let summary = copy_range(
+            &["test_data/bin-log.000001"],
+            &mut dst,
+            &gtid(14918),
+            &gtid(14918),
+        )
+        .unwrap();
+        assert_eq!(summary.transactions, 1);
+        assert_eq!(dst.flushed, summary.bytes as usize);
+    }
+
+    #[test]
+    fn test_copy_range_errors() {
+        assert_matches!(
+            copy_range(
+                &["test_data/bin-log.000001"],
+                Vec::new(),
+                &gtid(14919),
+                &gtid(14918)
+            ),
+            Err(BinlogParseError::InvalidGtidRange { .. })
+        );
+        assert_matches!(
+            copy_range(
+                &["test_data/bin-log.000001"],
+                Vec::new(),
+                &gtid(14918),
+                &gtid(14920)
+            ),
+            Err(BinlogParseError::GtidNotFound(_))
+        );
+    }
+}
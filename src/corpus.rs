@@ -0,0 +1,185 @@
+//! Golden corpora of raw events and their decoded form, for checking that upgrading this crate
+//! doesn't change how archived binlogs decode.
+//!
+//! [`export_corpus`] writes every event in a binlog file as a [`GoldenRecord`]: the event's raw
+//! bytes alongside a canonical JSON serialization of what it decoded to (or of the error it
+//! failed with). Keep the corpus, and after upgrading run [`verify_corpus`], which decodes the
+//! raw bytes again with the current decoder and reports every record which decodes differently.
+//!
+//! The corpus is a stream of JSON lines in file order. It includes the FormatDescriptionEvent
+//! and every Table Map event, so it can be verified without the original binlog.
+
+use std::io::{BufRead, Read, Seek, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::binlog_file::BinlogFile;
+use crate::errors::{CorpusError, EventParseError};
+use crate::event::Event;
+use crate::session::ParseSession;
+
+// the FormatDescriptionEvent always follows the four magic bytes
+const FDE_OFFSET: u64 = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// One event in a golden corpus
+pub struct GoldenRecord {
+    pub offset: u64,
+    /// The bytes of the event, base64-encoded
+    pub raw: String,
+    /// The canonical serialization of the decoded event: its
+    /// [`EventData`](crate::event::EventData) (`null` for events which aren't decoded), or
+    /// `{"error": <code name>}` if it failed to decode
+    pub decoded: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// A record which no longer decodes as it did when the corpus was exported
+pub struct CorpusMismatch {
+    pub offset: u64,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+/// The result of [`verify_corpus`]
+pub struct CorpusReport {
+    /// Number of records checked
+    pub records: u64,
+    pub mismatches: Vec<CorpusMismatch>,
+}
+
+impl CorpusReport {
+    /// Whether every record decoded exactly as it did when exported
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Decode an event and serialize the result. `serde_json::Value` keeps object keys sorted, so
+/// the serialization doesn't depend on field order.
+fn canonical(session: &mut ParseSession, event: Result<Event, EventParseError>) -> Value {
+    let decoded = event.and_then(|event| session.decode(event));
+    let result = match decoded {
+        Ok(decoded) => serde_json::to_value(&decoded.data).map_err(|e| e.to_string()),
+        Err(e) => Err(e.code_name().to_owned()),
+    };
+    match result {
+        Ok(value) => value,
+        Err(error) => serde_json::json!({ "error": error }),
+    }
+}
+
+/// Write a golden record for every event in a binlog file, as JSON lines, returning the number
+/// of records written
+pub fn export_corpus<I: Read + Seek, W: Write>(
+    file: BinlogFile<I>,
+    mut w: W,
+) -> Result<u64, CorpusError> {
+    let mut session = ParseSession::new();
+    let mut written = 0;
+    for event in file.events(Some(FDE_OFFSET)) {
+        let event = event?;
+        let record = GoldenRecord {
+            offset: event.offset(),
+            raw: base64::encode(event.to_bytes()),
+            decoded: canonical(&mut session, Ok(event)),
+        };
+        serde_json::to_writer(&mut w, &record)?;
+        w.write_all(b"\n")?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Decode every record in a corpus written by [`export_corpus`] again, reporting those which
+/// decode differently now
+pub fn verify_corpus<R: BufRead>(r: R) -> Result<CorpusReport, CorpusError> {
+    let mut session = ParseSession::new();
+    let mut report = CorpusReport::default();
+    for line in r.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: GoldenRecord = serde_json::from_str(&line)?;
+        let raw = base64::decode(&record.raw).map_err(|source| CorpusError::InvalidRaw {
+            offset: record.offset,
+            source,
+        })?;
+        let event = session.read_event(&mut raw.as_slice(), record.offset);
+        let actual = canonical(&mut session, event);
+        report.records += 1;
+        if actual != record.decoded {
+            report.mismatches.push(CorpusMismatch {
+                offset: record.offset,
+                expected: record.decoded,
+                actual,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_corpus, verify_corpus, GoldenRecord};
+    use crate::binlog_file::BinlogFile;
+    use crate::errors::CorpusError;
+
+    fn corpus() -> Vec<u8> {
+        let file = BinlogFile::try_from_path("test_data/bin-log.000001").unwrap();
+        let mut corpus = Vec::new();
+        assert_eq!(export_corpus(file, &mut corpus).unwrap(), 14);
+        corpus
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let corpus = corpus();
+        let records = corpus
+            .split(|&c| c == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice::<GoldenRecord>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records[0].offset, 4);
+        assert_eq!(
+            records[0].decoded["FormatDescriptionEvent"]["checksum_algorithm"],
+            "CRC32"
+        );
+        let rows = &records[7];
+        assert_eq!(rows.offset, 652);
+        assert_eq!(base64::decode(&rows.raw).unwrap().len(), 66);
+        assert_eq!(
+            rows.decoded["WriteRowsEvent"]["rows"][0]["cols"][0]["SignedInteger"],
+            1
+        );
+
+        let report = verify_corpus(corpus.as_slice()).unwrap();
+        assert_eq!(report.records, 14);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        // pretend the first row used to decode differently
+        let corpus = String::from_utf8(corpus())
+            .unwrap()
+            .replace("\"SignedInteger\":1}", "\"SignedInteger\":7}");
+        let report = verify_corpus(corpus.as_bytes()).unwrap();
+        assert_eq!(report.records, 14);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].offset, 652);
+        assert_eq!(
+            report.mismatches[0].actual["WriteRowsEvent"]["rows"][0]["cols"][0]["SignedInteger"],
+            1
+        );
+
+        let corrupt = "{\"offset\": 4, \"raw\": \"!!\", \"decoded\": null}";
+        assert!(matches!(
+            verify_corpus(corrupt.as_bytes()),
+            Err(CorpusError::InvalidRaw { offset: 4, .. })
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+//! Reporting what an [`EventIterator`](crate::EventIterator) does with events it doesn't return.
+//!
+//! A handler registered with [`on_diagnostic`](crate::BinlogFileParserBuilder::on_diagnostic)
+//! receives every diagnostic. Without one, diagnostics are logged if the `log` feature is enabled
+//! (events which are dropped without being returned at debug level, everything else as a
+//! warning) and discarded otherwise.
+
+use std::fmt;
+
+use crate::catalog::SchemaMismatch;
+use crate::errors::EventParseError;
+use crate::event::TypeCode;
+
+/// Something the iterator did without returning an error
+#[derive(Debug)]
+pub enum Diagnostic<'a> {
+    /// An event was skipped because of a recoverable error, under an
+    /// [`ErrorPolicy`](crate::errors::ErrorPolicy) which skips them
+    SkippedEvent(&'a EventParseError),
+    /// A TableMapEvent didn't match the schema catalog, under
+    /// [`MismatchPolicy::Warn`](crate::catalog::MismatchPolicy::Warn)
+    SchemaMismatch(&'a SchemaMismatch),
+    /// An event this crate doesn't decode (such as a StopEvent, or one of an unknown type) was
+    /// dropped
+    UnhandledEvent { type_code: TypeCode, offset: u64 },
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::SkippedEvent(e) => write!(f, "skipping event: {}", e),
+            Diagnostic::SchemaMismatch(mismatch) => write!(f, "schema mismatch: {}", mismatch),
+            Diagnostic::UnhandledEvent { type_code, offset } => {
+                write!(f, "unhandled event: {:?} at offset {}", type_code, offset)
+            }
+        }
+    }
+}
+
+pub(crate) struct DiagnosticHandler(Box<dyn Fn(&Diagnostic<'_>) + Send + Sync>);
+
+impl DiagnosticHandler {
+    pub(crate) fn new<F: Fn(&Diagnostic<'_>) + Send + Sync + 'static>(handler: F) -> Self {
+        DiagnosticHandler(Box::new(handler))
+    }
+}
+
+impl fmt::Debug for DiagnosticHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DiagnosticHandler")
+    }
+}
+
+/// Pass a diagnostic to the handler, or log it if there isn't one
+pub(crate) fn report(handler: Option<&DiagnosticHandler>, diagnostic: Diagnostic<'_>) {
+    match handler {
+        Some(handler) => (handler.0)(&diagnostic),
+        #[cfg(feature = "log")]
+        None => match diagnostic {
+            Diagnostic::UnhandledEvent { .. } => log::debug!("{}", diagnostic),
+            _ => log::warn!("{}", diagnostic),
+        },
+        // a library shouldn't write to stderr, so there is nowhere else for it to go
+        #[cfg(not(feature = "log"))]
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Diagnostic;
+    use crate::catalog::{MismatchPolicy, SchemaCatalog};
+    use crate::BinlogFileParserBuilder;
+
+    #[test]
+    fn test_on_diagnostic() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let catalog = SchemaCatalog::new().with_table("bltest", "foo", Vec::new());
+        let events = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(catalog, MismatchPolicy::Warn)
+            .on_diagnostic(move |diagnostic| {
+                assert!(matches!(diagnostic, Diagnostic::SchemaMismatch(_)));
+                sink.lock().unwrap().push(diagnostic.to_string());
+            })
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 7);
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        assert!(reported[0].starts_with("schema mismatch: "));
+    }
+}
@@ -0,0 +1,424 @@
+//! Reading binlogs written with `binlog_encryption=ON` (MySQL 8.0.14+).
+//!
+//! An encrypted binlog starts with a 512-byte header instead of the usual magic bytes. The header
+//! names a replication master key, kept in the server's keyring, with which the file's own
+//! password is encrypted; the rest of the file is an ordinary binlog, encrypted with AES-256-CTR
+//! under a key derived from that password. A [`DecryptingReader`] undoes this, so the file can be
+//! read as if it weren't encrypted. Offsets in it are the ones the server reports, which don't
+//! count the header.
+//!
+//! The master key is looked up with a [`KeyProvider`], such as a [`Keyring`] read from the file
+//! of the `keyring_file` plugin.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, KeyInit, KeyIvInit, StreamCipher, StreamCipherSeek};
+use aes::Aes256;
+use byteorder::{ByteOrder, LittleEndian};
+use sha2::{Digest, Sha512};
+
+use crate::errors::BinlogParseError;
+
+/// The magic bytes at the start of an encrypted binlog
+pub const ENCRYPTED_MAGIC: [u8; 4] = [0xfd, 0x62, 0x69, 0x6e];
+
+// the header is padded to this size, and the encrypted binlog starts after it
+const HEADER_LEN: u64 = 512;
+const HEADER_VERSION: u8 = 1;
+
+const FIELD_END: u8 = 0;
+const FIELD_KEY_ID: u8 = 1;
+const FIELD_ENCRYPTED_PASSWORD: u8 = 2;
+const FIELD_IV: u8 = 3;
+
+const PASSWORD_LEN: usize = 32;
+const IV_LEN: usize = 16;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Looks up the keys which encrypt binlog passwords
+pub trait KeyProvider {
+    /// The key with the given ID (e.g. `MySQLReplicationKey_<server uuid>_1`), or None if it
+    /// isn't known
+    fn key(&self, key_id: &str) -> Option<Vec<u8>>;
+}
+
+impl<F> KeyProvider for F
+where
+    F: Fn(&str) -> Option<Vec<u8>>,
+{
+    fn key(&self, key_id: &str) -> Option<Vec<u8>> {
+        self(key_id)
+    }
+}
+
+/// Keys by ID, as stored by the `keyring_file` plugin
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+// the plugin stores keys XORed with this
+const KEYRING_OBFUSCATION: &[u8] = b"*305=Ljt0*!@$Hnm(*-9-w;:";
+const KEYRING_EOF: &[u8] = b"EOF";
+// key entries start with their size and the lengths of their four fields, each a 64-bit size_t
+const KEYRING_ENTRY_HEADER_LEN: usize = 5 * 8;
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, replacing any other key with the same ID
+    pub fn with_key(mut self, key_id: &str, key: &[u8]) -> Self {
+        self.keys.insert(key_id.to_owned(), key.to_vec());
+        self
+    }
+
+    /// Read the file of the `keyring_file` plugin (`keyring_file_data`)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, BinlogParseError> {
+        let data = fs::read(path).map_err(BinlogParseError::OpenError)?;
+        Self::from_bytes(&data)
+    }
+
+    /// Parse the contents of a `keyring_file` file: a version line, the keys, and then `EOF`
+    /// (followed by a digest, which isn't checked)
+    pub fn from_bytes(data: &[u8]) -> Result<Self, BinlogParseError> {
+        let invalid = |reason: &str| BinlogParseError::InvalidKeyring(reason.to_owned());
+        let mut rest = match data.strip_prefix(b"Keyring file version:".as_ref()) {
+            Some(rest) if rest.len() >= 3 => &rest[3..],
+            _ => return Err(invalid("missing version")),
+        };
+        let mut keyring = Keyring::new();
+        while !rest.starts_with(KEYRING_EOF) {
+            if rest.len() < KEYRING_ENTRY_HEADER_LEN {
+                return Err(invalid("truncated key entry"));
+            }
+            let field = |i: usize| LittleEndian::read_u64(&rest[i * 8..]) as usize;
+            let entry_len = field(0);
+            let lengths = [field(1), field(2), field(3), field(4)];
+            let fields_len = lengths
+                .iter()
+                .try_fold(0usize, |sum, l| sum.checked_add(*l));
+            if fields_len
+                .is_none_or(|l| entry_len < KEYRING_ENTRY_HEADER_LEN + l || entry_len > rest.len())
+            {
+                return Err(invalid("key entry has an invalid length"));
+            }
+            let mut fields = &rest[KEYRING_ENTRY_HEADER_LEN..entry_len];
+            let mut values = lengths.iter().map(|&l| {
+                let (value, after) = fields.split_at(l);
+                fields = after;
+                value
+            });
+            let key_id = String::from_utf8_lossy(values.next().unwrap_or_default()).into_owned();
+            // the key type and user ID don't matter here
+            let key = values.nth(2).unwrap_or_default();
+            let key = key
+                .iter()
+                .zip(KEYRING_OBFUSCATION.iter().cycle())
+                .map(|(b, o)| b ^ o)
+                .collect();
+            keyring.keys.entry(key_id).or_insert(key);
+            rest = &rest[entry_len..];
+        }
+        Ok(keyring)
+    }
+}
+
+impl KeyProvider for Keyring {
+    fn key(&self, key_id: &str) -> Option<Vec<u8>> {
+        self.keys.get(key_id).cloned()
+    }
+}
+
+/// The header of an encrypted binlog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionHeader {
+    /// The ID of the master key which encrypts the password
+    pub key_id: String,
+    encrypted_password: [u8; PASSWORD_LEN],
+    iv: [u8; IV_LEN],
+}
+
+impl EncryptionHeader {
+    /// Read the header from the start of an encrypted binlog
+    pub fn read<R: Read>(reader: R) -> Result<Self, BinlogParseError> {
+        let invalid = |reason: &str| BinlogParseError::InvalidEncryptionHeader(reason.to_owned());
+        let mut header = [0u8; HEADER_LEN as usize];
+        reader.take(HEADER_LEN).read_exact(&mut header)?;
+        if header[..4] != ENCRYPTED_MAGIC {
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&header[..4]);
+            return Err(BinlogParseError::BadMagic(magic));
+        }
+        if header[4] != HEADER_VERSION {
+            return Err(invalid(&format!("unsupported version {}", header[4])));
+        }
+        let mut key_id = None;
+        let mut encrypted_password = None;
+        let mut iv = None;
+        let mut fields = &header[5..];
+        loop {
+            let (&field, rest) = fields
+                .split_first()
+                .ok_or_else(|| invalid("unterminated"))?;
+            let length = match field {
+                FIELD_END => break,
+                FIELD_KEY_ID => {
+                    let (&length, rest) =
+                        rest.split_first().ok_or_else(|| invalid("unterminated"))?;
+                    fields = rest;
+                    usize::from(length)
+                }
+                FIELD_ENCRYPTED_PASSWORD => {
+                    fields = rest;
+                    PASSWORD_LEN
+                }
+                FIELD_IV => {
+                    fields = rest;
+                    IV_LEN
+                }
+                other => return Err(invalid(&format!("unknown field {}", other))),
+            };
+            if fields.len() < length {
+                return Err(invalid("unterminated"));
+            }
+            let (value, rest) = fields.split_at(length);
+            fields = rest;
+            match field {
+                FIELD_KEY_ID => key_id = Some(String::from_utf8_lossy(value).into_owned()),
+                FIELD_ENCRYPTED_PASSWORD => {
+                    let mut password = [0u8; PASSWORD_LEN];
+                    password.copy_from_slice(value);
+                    encrypted_password = Some(password);
+                }
+                _ => {
+                    let mut value_iv = [0u8; IV_LEN];
+                    value_iv.copy_from_slice(value);
+                    iv = Some(value_iv);
+                }
+            }
+        }
+        Ok(EncryptionHeader {
+            key_id: key_id.ok_or_else(|| invalid("missing key ID"))?,
+            encrypted_password: encrypted_password.ok_or_else(|| invalid("missing password"))?,
+            iv: iv.ok_or_else(|| invalid("missing IV"))?,
+        })
+    }
+
+    /// Decrypt the file's password with the master key (AES-256-CBC, without padding)
+    fn password<K: KeyProvider + ?Sized>(
+        &self,
+        keys: &K,
+    ) -> Result<[u8; PASSWORD_LEN], BinlogParseError> {
+        let key = keys
+            .key(&self.key_id)
+            .ok_or_else(|| BinlogParseError::MissingEncryptionKey(self.key_id.clone()))?;
+        let cipher = Aes256::new_from_slice(&key).map_err(|_| {
+            BinlogParseError::InvalidEncryptionHeader(format!(
+                "key {:?} is {} bytes long, not 32",
+                self.key_id,
+                key.len()
+            ))
+        })?;
+        let mut password = self.encrypted_password;
+        let mut previous = self.iv;
+        for block in password.chunks_exact_mut(IV_LEN) {
+            let mut ciphertext = [0u8; IV_LEN];
+            ciphertext.copy_from_slice(block);
+            cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            for (b, p) in block.iter_mut().zip(previous.iter()) {
+                *b ^= p;
+            }
+            previous = ciphertext;
+        }
+        Ok(password)
+    }
+}
+
+/// Derive the key and IV of the stream cipher from the file's password, as OpenSSL's
+/// `EVP_BytesToKey` does with SHA-512, no salt, and one iteration
+fn stream_cipher(password: &[u8]) -> Aes256Ctr {
+    let digest = Sha512::digest(password);
+    Aes256Ctr::new(
+        GenericArray::from_slice(&digest[..32]),
+        GenericArray::from_slice(&digest[32..48]),
+    )
+}
+
+/// Wraps an encrypted binlog, reading and seeking in it as if it weren't encrypted
+pub struct DecryptingReader<I> {
+    inner: I,
+    header: EncryptionHeader,
+    cipher: Aes256Ctr,
+}
+
+impl<I: Read + Seek> DecryptingReader<I> {
+    /// Read the encryption header from the start of `inner`, and decrypt the file's password
+    /// with the master key it names
+    pub fn new<K: KeyProvider + ?Sized>(mut inner: I, keys: &K) -> Result<Self, BinlogParseError> {
+        inner.seek(SeekFrom::Start(0))?;
+        let header = EncryptionHeader::read(&mut inner)?;
+        let cipher = stream_cipher(&header.password(keys)?);
+        inner.seek(SeekFrom::Start(HEADER_LEN))?;
+        Ok(DecryptingReader {
+            inner,
+            header,
+            cipher,
+        })
+    }
+
+    pub fn header(&self) -> &EncryptionHeader {
+        &self.header
+    }
+
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I: Read> Read for DecryptingReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.cipher.apply_keystream(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<I: Seek> Seek for DecryptingReader<I> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(offset.saturating_add(HEADER_LEN)),
+            other => other,
+        };
+        let current = self.inner.stream_position()?;
+        let position = self.inner.seek(pos)?;
+        if position < HEADER_LEN {
+            self.inner.seek(SeekFrom::Start(current))?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to before the start of the encrypted binlog",
+            ));
+        }
+        let position = position - HEADER_LEN;
+        self.cipher.seek(position);
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    use aes::cipher::generic_array::GenericArray;
+    use aes::cipher::{BlockEncrypt, KeyInit, StreamCipher};
+    use aes::Aes256;
+
+    use super::{stream_cipher, DecryptingReader, Keyring, KEYRING_OBFUSCATION};
+    use crate::errors::BinlogParseError;
+    use crate::{parse_file, BinlogEvent, BinlogFileParserBuilder};
+
+    const KEY_ID: &str = "MySQLReplicationKey_87cee3a4-6b31-11e7-bdfd-0d98d6698870_1";
+
+    /// Encrypt a binlog as the server does
+    fn encrypt(plaintext: &[u8], master_key: &[u8; 32], password: &[u8; 32]) -> Vec<u8> {
+        let iv = [7u8; 16];
+        let cipher = Aes256::new_from_slice(master_key).unwrap();
+        let mut encrypted_password = *password;
+        let mut previous = iv;
+        for block in encrypted_password.chunks_exact_mut(16) {
+            for (b, p) in block.iter_mut().zip(previous.iter()) {
+                *b ^= p;
+            }
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            previous.copy_from_slice(block);
+        }
+        let mut data = vec![0xfd, 0x62, 0x69, 0x6e, 1];
+        data.extend_from_slice(&[1, KEY_ID.len() as u8]);
+        data.extend_from_slice(KEY_ID.as_bytes());
+        data.push(2);
+        data.extend_from_slice(&encrypted_password);
+        data.push(3);
+        data.extend_from_slice(&iv);
+        data.resize(512, 0);
+        let mut body = plaintext.to_vec();
+        stream_cipher(password).apply_keystream(&mut body);
+        data.extend_from_slice(&body);
+        data
+    }
+
+    #[test]
+    fn test_decrypting_reader() {
+        let plaintext = fs::read("test_data/bin-log.000001").unwrap();
+        let master_key = [3u8; 32];
+        let encrypted = encrypt(&plaintext, &master_key, &[5u8; 32]);
+        let keyring = Keyring::new().with_key(KEY_ID, &master_key);
+
+        let mut reader = DecryptingReader::new(Cursor::new(&encrypted), &keyring).unwrap();
+        assert_eq!(reader.header().key_id, KEY_ID);
+        reader.seek(SeekFrom::Start(100)).unwrap();
+        let mut buf = [0u8; 50];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &plaintext[100..150]);
+
+        let reader = DecryptingReader::new(Cursor::new(&encrypted), &keyring).unwrap();
+        let events = BinlogFileParserBuilder::try_from_reader(reader)
+            .unwrap()
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let expected = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let offsets = |events: &[BinlogEvent]| events.iter().map(|e| e.offset).collect::<Vec<_>>();
+        assert_eq!(offsets(&events), offsets(&expected));
+        assert_eq!(
+            format!("{:?}", events[3].rows),
+            format!("{:?}", expected[3].rows)
+        );
+
+        assert!(matches!(
+            BinlogFileParserBuilder::try_from_reader(Cursor::new(&encrypted)),
+            Err(BinlogParseError::Encrypted)
+        ));
+        let no_keys = |_: &str| None;
+        assert!(matches!(
+            DecryptingReader::new(Cursor::new(&encrypted), &no_keys),
+            Err(BinlogParseError::MissingEncryptionKey(ref id)) if id == KEY_ID
+        ));
+    }
+
+    #[test]
+    fn test_keyring() {
+        let key = [9u8; 32];
+        let mut data = b"Keyring file version:2.0".to_vec();
+        let fields: [&[u8]; 4] = [KEY_ID.as_bytes(), b"AES", b"", &key];
+        let fields_len = fields.iter().map(|f| f.len()).sum::<usize>();
+        let entry_len = (40 + fields_len).div_ceil(8) * 8;
+        for length in [entry_len, KEY_ID.len(), 3, 0, key.len()] {
+            data.extend_from_slice(&(length as u64).to_le_bytes());
+        }
+        for field in &fields[..3] {
+            data.extend_from_slice(field);
+        }
+        data.extend(
+            key.iter()
+                .zip(KEYRING_OBFUSCATION.iter().cycle())
+                .map(|(b, o)| b ^ o),
+        );
+        data.resize(24 + entry_len, 0);
+        data.extend_from_slice(b"EOF");
+        data.extend_from_slice(&[0u8; 32]);
+
+        let keyring = Keyring::from_bytes(&data).unwrap();
+        assert_eq!(super::KeyProvider::key(&keyring, KEY_ID).unwrap(), key);
+        assert!(Keyring::from_bytes(&data[..50]).is_err());
+    }
+}
@@ -1,6 +1,67 @@
+use crate::bit_set::BitSetError;
+use crate::catalog::SchemaMismatch;
 use crate::column_types;
+use crate::gtid::{Gtid, GtidSet};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use thiserror::Error;
 
+/// Broad class of an error, for alerting and retry decisions.
+///
+/// The set of kinds is stable; new error variants are always assigned to an existing kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Reading from the underlying file or reader failed
+    Io,
+    /// The binlog contents are malformed, truncated, or fail their checksum
+    Corruption,
+    /// The binlog uses a feature or type this crate can't decode
+    Unsupported,
+    /// A table did not match the expected schema catalog
+    Schema,
+    /// A sequence of binlog files is missing files or transactions
+    Discontinuity,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io",
+            ErrorKind::Corruption => "corruption",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::Schema => "schema",
+            ErrorKind::Discontinuity => "discontinuity",
+        }
+    }
+}
+
+/// What an [`EventIterator`](crate::EventIterator) does after a
+/// [recoverable](EventParseError::is_recoverable) error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Return the error; the caller decides whether to keep iterating
+    #[default]
+    Abort,
+    /// Skip the event which caused the error and keep reading
+    SkipEvent,
+    /// Skip the rest of the transaction containing the event which caused the error, so that
+    /// no transaction is returned incomplete
+    SkipTransaction,
+}
+
+// I/O errors raised while decoding an in-memory event body mean that the body was shorter than
+// its contents claimed, not that the disk failed
+fn io_error_kind(e: &std::io::Error) -> ErrorKind {
+    match e.kind() {
+        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::InvalidData => {
+            ErrorKind::Corruption
+        }
+        _ => ErrorKind::Io,
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum EventParseError {
     #[error("unable to parse column: {0:?}")]
@@ -11,6 +72,72 @@ pub enum EventParseError {
     EofError,
     #[error("bad UUID in Gtid Event: {0:?}")]
     Uuid(#[from] uuid::Error),
+    #[error("invalid GTID in Gtid Event: {0}")]
+    Gtid(#[from] GtidParseError),
+    #[error("unknown flags in Gtid Event: {0:#04x}")]
+    InvalidGtidFlags(u8),
+    #[error("unknown logical clock type in Gtid Event: {0}")]
+    InvalidLogicalClockType(u8),
+    #[error("unknown value type in User Var Event: {0}")]
+    InvalidUserVarType(u8),
+    #[error("unknown non-ignorable field {0} in serialized event")]
+    UnknownSerializedField(u64),
+    #[error("unsupported compression type {0} in transaction payload")]
+    UnsupportedCompression(u64),
+    #[error("no implementation registered for checksum algorithm {0}")]
+    UnsupportedChecksumAlgorithm(u8),
+    #[error("{file:?} ended without rotating to the next binlog")]
+    MissingRotate { file: PathBuf },
+    #[error("{file:?} rotated to {expected:?}, but the next file is {found:?}")]
+    UnexpectedNextFile {
+        file: PathBuf,
+        expected: String,
+        found: PathBuf,
+    },
+    #[error(
+        "previous GTIDs of {file:?} are {actual}, but the preceding files executed {expected}"
+    )]
+    PreviousGtidsMismatch {
+        file: PathBuf,
+        expected: GtidSet,
+        actual: GtidSet,
+    },
+    #[error("error opening next binlog file {file:?}")]
+    NextFile {
+        file: PathBuf,
+        #[source]
+        inner: Box<BinlogParseError>,
+    },
+    #[error("table map does not match expected schema: {0}")]
+    SchemaMismatch(Box<SchemaMismatch>),
+    #[error("checksum mismatch in event at offset {offset}: expected {expected:#010x}, got {actual:#010x}")]
+    ChecksumMismatch {
+        offset: u64,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("skipped unreadable bytes from offset {start} to {end}")]
+    SkippedRange { start: u64, end: u64 },
+    #[error("unsupported binlog version {0}; only version 4 can be read")]
+    UnsupportedBinlogVersion(u16),
+    #[error("event at offset {offset} is {length} bytes long, more than the limit of {limit}")]
+    EventTooLarge {
+        offset: u64,
+        length: u32,
+        limit: u32,
+    },
+    #[error("rows event at offset {offset} is for table {table_id}, which has no table map")]
+    UnknownTableId { table_id: u64, offset: u64 },
+    #[error("events after offset {offset} are encrypted by the server, which isn't supported")]
+    EncryptedEvents { offset: u64 },
+    #[error("{0:?} events don't contain rows")]
+    NotRowsEvent(crate::event::TypeCode),
+    #[error("transaction payload is compressed with zstd, which requires the `zstd` feature")]
+    ZstdNotEnabled,
+    #[error("decompressed transaction payload is more than {limit} bytes long")]
+    PayloadTooLarge { limit: u64 },
+    #[error("server error {code}: {message}")]
+    ServerError { code: u16, message: String },
 }
 
 #[derive(Debug, Error)]
@@ -23,6 +150,12 @@ pub enum JsonbParseError {
     Io(#[from] ::std::io::Error),
     #[error("invalid JSON")]
     Json(#[from] serde_json::error::Error),
+    #[error("invalid JSON diff operation (got {0})")]
+    InvalidDiffOperation(u8),
+    #[error("JSON diff path {0:?} is malformed or doesn't apply to the before-image")]
+    InvalidDiffPath(String),
+    #[error("JSONB value is at offset {actual}, but its container says {expected}")]
+    InvalidOffset { expected: u64, actual: u64 },
     #[error("error parsing opaque column in json record: {inner:?}")]
     OpaqueColumnParseError {
         #[source]
@@ -48,6 +181,26 @@ pub enum ColumnParseError {
     Decimal(#[from] DecimalParseError),
     #[error("I/O error reading column")]
     Io(#[from] std::io::Error),
+    /// No longer returned, since partial JSON updates are now decoded
+    #[error("partial JSON updates are not supported")]
+    PartialJsonUnsupported,
+    #[error("custom decoder failed")]
+    CustomDecoder(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("rows event has {event_columns} columns, but the table map has {table_columns}")]
+    ColumnCountMismatch {
+        table_columns: usize,
+        event_columns: usize,
+    },
+    #[error("malformed column bitmap: {0}")]
+    Bitmap(#[from] BitSetError),
+    #[error("unknown column type {0}")]
+    UnknownColumnType(u8),
+    #[error("invalid metadata for column type: {column_type:?}")]
+    InvalidColumnMetadata {
+        column_type: column_types::ColumnType,
+    },
+    #[error("value is {length} bytes long, more than the limit of {limit}")]
+    ValueTooLarge { length: usize, limit: usize },
 }
 
 #[derive(Debug, Error)]
@@ -58,10 +211,32 @@ pub enum BinlogParseError {
     BadMagic([u8; 4]),
     #[error("bad first record in binlog")]
     BadFirstRecord,
+    #[error("common header length {0} is shorter than the 19-byte v4 event header")]
+    InvalidHeaderLength(u8),
     #[error("error opening binlog file")]
     OpenError(std::io::Error),
     #[error("other I/O error reading binlog file")]
     Io(#[from] std::io::Error),
+    #[error("no binlog files found in {0:?}")]
+    NoBinlogFiles(PathBuf),
+    #[error("directory contains binlogs with different base names ({0:?} and {1:?})")]
+    MixedBaseNames(String, String),
+    #[error("missing binlog file(s) between {before:?} and {after:?}")]
+    SequenceGap { before: PathBuf, after: PathBuf },
+    #[error("{from}..{to} is not a range of transactions from a single source")]
+    InvalidGtidRange { from: Box<Gtid>, to: Box<Gtid> },
+    #[error("transaction {0} was not found in the binlogs")]
+    GtidNotFound(Box<Gtid>),
+    #[error("binlog file {0:?} of the resume position was not found")]
+    PositionFileNotFound(String),
+    #[error("binlog is encrypted; it must be opened with a key provider")]
+    Encrypted,
+    #[error("invalid encryption header: {0}")]
+    InvalidEncryptionHeader(String),
+    #[error("encryption key {0:?} was not found")]
+    MissingEncryptionKey(String),
+    #[error("invalid keyring file: {0}")]
+    InvalidKeyring(String),
 }
 
 #[derive(Debug, Error)]
@@ -70,4 +245,747 @@ pub enum DecimalParseError {
     Io(#[from] std::io::Error),
     #[error("Decimal parse error")]
     BigDecimalParse(#[from] bigdecimal::ParseBigDecimalError),
+    #[error("invalid decimal precision {precision} and scale {scale}")]
+    InvalidPrecision { precision: u8, scale: u8 },
+}
+
+#[derive(Debug, Error)]
+pub enum GtidParseError {
+    #[error("invalid GTID: {0:?}")]
+    InvalidFormat(String),
+    #[error("invalid GTID interval {0}-{1}")]
+    InvalidInterval(u64, u64),
+    #[error("invalid GTID tag: {0:?}")]
+    InvalidTag(String),
+    #[error("bad UUID in GTID: {0:?}")]
+    Uuid(#[from] uuid::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum CorpusError {
+    #[error("I/O error reading or writing corpus")]
+    Io(#[from] std::io::Error),
+    #[error("error reading binlog")]
+    Event(#[from] EventParseError),
+    #[error("malformed corpus record")]
+    Json(#[from] serde_json::Error),
+    #[error("corpus record at offset {offset} has invalid raw bytes")]
+    InvalidRaw {
+        offset: u64,
+        #[source]
+        source: base64::DecodeError,
+    },
+}
+
+#[cfg(feature = "config")]
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("error reading config")]
+    Io(#[from] std::io::Error),
+    #[error("invalid config: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("the config has no source path")]
+    MissingSource,
+    #[error("invalid table pattern {0:?}; expected schema.table")]
+    InvalidTablePattern(String),
+    #[error("invalid executed GTID set")]
+    Gtid(#[from] GtidParseError),
+    #[error("error opening binlog")]
+    Binlog(#[from] BinlogParseError),
+    #[error("error reading binlog")]
+    Event(#[from] EventParseError),
+    #[error("column {column:?} of {table} can't be found; the binlog doesn't name its columns")]
+    UnresolvedColumn { table: String, column: String },
+    #[error("error writing output")]
+    Output(#[from] serde_json::Error),
+}
+
+#[cfg(feature = "geo")]
+#[derive(Debug, Error)]
+pub enum GeometryParseError {
+    #[error("I/O error reading geometry")]
+    Io(#[from] std::io::Error),
+    #[error("invalid WKB byte order {0}")]
+    InvalidByteOrder(u8),
+    #[error("unknown WKB geometry type {0}")]
+    UnknownType(u32),
+    #[error("WKB geometry of type {0} contains a member of the wrong type")]
+    UnexpectedMember(u32),
+    #[error("{0} unexpected bytes after WKB geometry")]
+    TrailingBytes(usize),
+}
+
+#[cfg(feature = "avro")]
+#[derive(Debug, Error)]
+pub enum AvroEncodeError {
+    #[error("error decoding rows")]
+    Event(#[from] EventParseError),
+    #[error("value of column {column} doesn't match its type {column_type:?}")]
+    ValueMismatch {
+        column: usize,
+        column_type: column_types::ColumnType,
+    },
+    #[error("row has {row_columns} columns, but the table map has {table_columns}")]
+    ColumnCountMismatch {
+        table_columns: usize,
+        row_columns: usize,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum SinkError {
+    #[error("error reading events")]
+    Event(#[from] EventParseError),
+    #[error("I/O error writing to sink: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unable to serialize event: {0}")]
+    Serialize(serde_json::Error),
+    #[cfg(feature = "kafka")]
+    #[error("Kafka producer error: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            SinkError::Io(e.into())
+        } else {
+            SinkError::Serialize(e)
+        }
+    }
+}
+
+// Error codes are stable and are never reused: 1xxx for events, 2xxx for columns, 3xxx for files,
+// 4xxx for JSONB values, 5xxx for decimals, 6xxx for GTIDs, 7xxx for geometries, 8xxx for
+// pipeline configs, 9xxx for golden corpora, 10xxx for Avro encoding, 11xxx for sinks. Errors which
+// only wrap another error report the code of the error they wrap.
+
+impl EventParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EventParseError::ColumnParseError(e) => e.kind(),
+            EventParseError::Io(e) => io_error_kind(e),
+            EventParseError::EofError | EventParseError::ServerError { .. } => ErrorKind::Io,
+            EventParseError::Uuid(_)
+            | EventParseError::InvalidGtidFlags(_)
+            | EventParseError::InvalidLogicalClockType(_)
+            | EventParseError::ChecksumMismatch { .. }
+            | EventParseError::SkippedRange { .. }
+            | EventParseError::EventTooLarge { .. }
+            | EventParseError::PayloadTooLarge { .. } => ErrorKind::Corruption,
+            EventParseError::Gtid(e) => e.kind(),
+            EventParseError::UnknownSerializedField(_)
+            | EventParseError::UnsupportedCompression(_)
+            | EventParseError::UnsupportedChecksumAlgorithm(_)
+            | EventParseError::InvalidUserVarType(_)
+            | EventParseError::UnsupportedBinlogVersion(_)
+            | EventParseError::EncryptedEvents { .. }
+            | EventParseError::NotRowsEvent(_)
+            | EventParseError::ZstdNotEnabled => ErrorKind::Unsupported,
+            EventParseError::MissingRotate { .. }
+            | EventParseError::UnexpectedNextFile { .. }
+            | EventParseError::PreviousGtidsMismatch { .. } => ErrorKind::Discontinuity,
+            EventParseError::NextFile { inner, .. } => inner.kind(),
+            EventParseError::SchemaMismatch(_) | EventParseError::UnknownTableId { .. } => {
+                ErrorKind::Schema
+            }
+        }
+    }
+
+    /// Whether it is safe to skip the event which caused this error and keep reading. Errors
+    /// which are not recoverable mean the stream can no longer be trusted to be in sync (or can't
+    /// be read at all).
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            // the event was framed correctly but its body could not be decoded
+            EventParseError::ColumnParseError(_)
+            | EventParseError::Uuid(_)
+            | EventParseError::Gtid(_)
+            | EventParseError::InvalidGtidFlags(_)
+            | EventParseError::InvalidLogicalClockType(_)
+            | EventParseError::UnknownSerializedField(_)
+            | EventParseError::UnsupportedCompression(_)
+            | EventParseError::InvalidUserVarType(_)
+            | EventParseError::SchemaMismatch(_)
+            | EventParseError::UnknownTableId { .. }
+            | EventParseError::NotRowsEvent(_)
+            | EventParseError::ZstdNotEnabled
+            | EventParseError::PayloadTooLarge { .. } => true,
+            // reading has already resumed at the next valid event
+            EventParseError::SkippedRange { .. } => true,
+            // event bodies are decoded from memory, so these mean the body was truncated
+            EventParseError::Io(e) => io_error_kind(e) == ErrorKind::Corruption,
+            // a bad checksum may mean a corrupt header, in which case the following event
+            // boundaries are wrong too
+            EventParseError::ChecksumMismatch { .. } => false,
+            // nothing is skipped; the events are just left unverified
+            EventParseError::UnsupportedChecksumAlgorithm(_) => false,
+            // the following events can't be framed without the FDE
+            EventParseError::UnsupportedBinlogVersion(_) => false,
+            // the length is more likely to be corrupt than the event to be genuinely that large,
+            // so the next event can't be found from it
+            EventParseError::EventTooLarge { .. } => false,
+            EventParseError::EofError
+            | EventParseError::ServerError { .. }
+            | EventParseError::EncryptedEvents { .. }
+            | EventParseError::MissingRotate { .. }
+            | EventParseError::UnexpectedNextFile { .. }
+            | EventParseError::PreviousGtidsMismatch { .. }
+            | EventParseError::NextFile { .. } => false,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            EventParseError::ColumnParseError(e) => e.code(),
+            EventParseError::Io(_) => 1001,
+            EventParseError::EofError => 1002,
+            EventParseError::Uuid(_) => 1003,
+            EventParseError::Gtid(e) => e.code(),
+            EventParseError::InvalidGtidFlags(_) => 1004,
+            EventParseError::InvalidLogicalClockType(_) => 1005,
+            EventParseError::UnknownSerializedField(_) => 1006,
+            EventParseError::MissingRotate { .. } => 1007,
+            EventParseError::UnexpectedNextFile { .. } => 1008,
+            EventParseError::PreviousGtidsMismatch { .. } => 1009,
+            EventParseError::NextFile { inner, .. } => inner.code(),
+            EventParseError::SchemaMismatch(_) => 1010,
+            EventParseError::ChecksumMismatch { .. } => 1011,
+            EventParseError::UnsupportedCompression(_) => 1012,
+            EventParseError::UnsupportedChecksumAlgorithm(_) => 1013,
+            EventParseError::InvalidUserVarType(_) => 1014,
+            EventParseError::SkippedRange { .. } => 1015,
+            EventParseError::UnsupportedBinlogVersion(_) => 1016,
+            EventParseError::EventTooLarge { .. } => 1017,
+            EventParseError::UnknownTableId { .. } => 1018,
+            EventParseError::EncryptedEvents { .. } => 1019,
+            EventParseError::NotRowsEvent(_) => 1020,
+            EventParseError::ZstdNotEnabled => 1021,
+            EventParseError::PayloadTooLarge { .. } => 1022,
+            EventParseError::ServerError { .. } => 1023,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            EventParseError::ColumnParseError(e) => e.code_name(),
+            EventParseError::Io(_) => "event_io",
+            EventParseError::EofError => "event_eof",
+            EventParseError::Uuid(_) => "event_bad_uuid",
+            EventParseError::Gtid(e) => e.code_name(),
+            EventParseError::InvalidGtidFlags(_) => "event_invalid_gtid_flags",
+            EventParseError::InvalidLogicalClockType(_) => "event_invalid_logical_clock_type",
+            EventParseError::UnknownSerializedField(_) => "event_unknown_serialized_field",
+            EventParseError::MissingRotate { .. } => "event_missing_rotate",
+            EventParseError::UnexpectedNextFile { .. } => "event_unexpected_next_file",
+            EventParseError::PreviousGtidsMismatch { .. } => "event_previous_gtids_mismatch",
+            EventParseError::NextFile { inner, .. } => inner.code_name(),
+            EventParseError::SchemaMismatch(_) => "event_schema_mismatch",
+            EventParseError::ChecksumMismatch { .. } => "event_checksum_mismatch",
+            EventParseError::UnsupportedCompression(_) => "event_unsupported_compression",
+            EventParseError::UnsupportedChecksumAlgorithm(_) => {
+                "event_unsupported_checksum_algorithm"
+            }
+            EventParseError::InvalidUserVarType(_) => "event_invalid_user_var_type",
+            EventParseError::SkippedRange { .. } => "event_skipped_range",
+            EventParseError::UnsupportedBinlogVersion(_) => "event_unsupported_binlog_version",
+            EventParseError::EventTooLarge { .. } => "event_too_large",
+            EventParseError::UnknownTableId { .. } => "event_unknown_table_id",
+            EventParseError::EncryptedEvents { .. } => "event_encrypted_events",
+            EventParseError::NotRowsEvent(_) => "event_not_rows_event",
+            EventParseError::ZstdNotEnabled => "event_zstd_not_enabled",
+            EventParseError::PayloadTooLarge { .. } => "event_payload_too_large",
+            EventParseError::ServerError { .. } => "event_server_error",
+        }
+    }
+}
+
+impl ColumnParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ColumnParseError::UnimplementedTypeError { .. } => ErrorKind::Unsupported,
+            ColumnParseError::Json(e) => e.kind(),
+            ColumnParseError::Decimal(e) => e.kind(),
+            ColumnParseError::Io(e) => io_error_kind(e),
+            ColumnParseError::ColumnCountMismatch { .. } => ErrorKind::Schema,
+            ColumnParseError::PartialJsonUnsupported => ErrorKind::Unsupported,
+            ColumnParseError::CustomDecoder(_) => ErrorKind::Schema,
+            ColumnParseError::Bitmap(_)
+            | ColumnParseError::InvalidColumnMetadata { .. }
+            | ColumnParseError::ValueTooLarge { .. } => ErrorKind::Corruption,
+            ColumnParseError::UnknownColumnType(_) => ErrorKind::Unsupported,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            ColumnParseError::UnimplementedTypeError { .. } => 2001,
+            ColumnParseError::Json(e) => e.code(),
+            ColumnParseError::Decimal(e) => e.code(),
+            ColumnParseError::Io(_) => 2002,
+            ColumnParseError::ColumnCountMismatch { .. } => 2003,
+            ColumnParseError::PartialJsonUnsupported => 2004,
+            ColumnParseError::CustomDecoder(_) => 2005,
+            ColumnParseError::Bitmap(_) => 2006,
+            ColumnParseError::UnknownColumnType(_) => 2007,
+            ColumnParseError::InvalidColumnMetadata { .. } => 2008,
+            ColumnParseError::ValueTooLarge { .. } => 2009,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            ColumnParseError::UnimplementedTypeError { .. } => "column_unimplemented_type",
+            ColumnParseError::Json(e) => e.code_name(),
+            ColumnParseError::Decimal(e) => e.code_name(),
+            ColumnParseError::Io(_) => "column_io",
+            ColumnParseError::ColumnCountMismatch { .. } => "column_count_mismatch",
+            ColumnParseError::PartialJsonUnsupported => "column_partial_json_unsupported",
+            ColumnParseError::CustomDecoder(_) => "column_custom_decoder",
+            ColumnParseError::Bitmap(_) => "column_bitmap",
+            ColumnParseError::UnknownColumnType(_) => "column_unknown_type",
+            ColumnParseError::InvalidColumnMetadata { .. } => "column_invalid_metadata",
+            ColumnParseError::ValueTooLarge { .. } => "column_value_too_large",
+        }
+    }
+}
+
+impl BinlogParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            BinlogParseError::EventParseError(e) => e.kind(),
+            BinlogParseError::BadMagic(_)
+            | BinlogParseError::BadFirstRecord
+            | BinlogParseError::InvalidHeaderLength(_)
+            | BinlogParseError::InvalidEncryptionHeader(_)
+            | BinlogParseError::InvalidKeyring(_) => ErrorKind::Corruption,
+            BinlogParseError::OpenError(_) | BinlogParseError::NoBinlogFiles(_) => ErrorKind::Io,
+            BinlogParseError::Io(e) => io_error_kind(e),
+            BinlogParseError::MixedBaseNames(..)
+            | BinlogParseError::SequenceGap { .. }
+            | BinlogParseError::GtidNotFound(_)
+            | BinlogParseError::PositionFileNotFound(_) => ErrorKind::Discontinuity,
+            BinlogParseError::InvalidGtidRange { .. } | BinlogParseError::Encrypted => {
+                ErrorKind::Unsupported
+            }
+            BinlogParseError::MissingEncryptionKey(_) => ErrorKind::Io,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            BinlogParseError::EventParseError(e) => e.code(),
+            BinlogParseError::BadMagic(_) => 3001,
+            BinlogParseError::BadFirstRecord => 3002,
+            BinlogParseError::OpenError(_) => 3003,
+            BinlogParseError::Io(_) => 3004,
+            BinlogParseError::NoBinlogFiles(_) => 3005,
+            BinlogParseError::MixedBaseNames(..) => 3006,
+            BinlogParseError::SequenceGap { .. } => 3007,
+            BinlogParseError::InvalidGtidRange { .. } => 3008,
+            BinlogParseError::GtidNotFound(_) => 3009,
+            BinlogParseError::InvalidHeaderLength(_) => 3010,
+            BinlogParseError::PositionFileNotFound(_) => 3011,
+            BinlogParseError::Encrypted => 3012,
+            BinlogParseError::InvalidEncryptionHeader(_) => 3013,
+            BinlogParseError::MissingEncryptionKey(_) => 3014,
+            BinlogParseError::InvalidKeyring(_) => 3015,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            BinlogParseError::EventParseError(e) => e.code_name(),
+            BinlogParseError::BadMagic(_) => "binlog_bad_magic",
+            BinlogParseError::BadFirstRecord => "binlog_bad_first_record",
+            BinlogParseError::OpenError(_) => "binlog_open",
+            BinlogParseError::Io(_) => "binlog_io",
+            BinlogParseError::NoBinlogFiles(_) => "binlog_no_files",
+            BinlogParseError::MixedBaseNames(..) => "binlog_mixed_base_names",
+            BinlogParseError::SequenceGap { .. } => "binlog_sequence_gap",
+            BinlogParseError::InvalidGtidRange { .. } => "binlog_invalid_gtid_range",
+            BinlogParseError::GtidNotFound(_) => "binlog_gtid_not_found",
+            BinlogParseError::InvalidHeaderLength(_) => "binlog_invalid_header_length",
+            BinlogParseError::PositionFileNotFound(_) => "binlog_position_file_not_found",
+            BinlogParseError::Encrypted => "binlog_encrypted",
+            BinlogParseError::InvalidEncryptionHeader(_) => "binlog_invalid_encryption_header",
+            BinlogParseError::MissingEncryptionKey(_) => "binlog_missing_encryption_key",
+            BinlogParseError::InvalidKeyring(_) => "binlog_invalid_keyring",
+        }
+    }
+}
+
+impl JsonbParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            JsonbParseError::InvalidTypeByte(_)
+            | JsonbParseError::InvalidLiteral(_)
+            | JsonbParseError::Json(_)
+            | JsonbParseError::InvalidDiffOperation(_)
+            | JsonbParseError::InvalidDiffPath(_)
+            | JsonbParseError::InvalidOffset { .. } => ErrorKind::Corruption,
+            JsonbParseError::Io(e) => io_error_kind(e),
+            JsonbParseError::OpaqueColumnParseError { inner } => inner.kind(),
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            JsonbParseError::InvalidTypeByte(_) => 4001,
+            JsonbParseError::InvalidLiteral(_) => 4002,
+            JsonbParseError::Io(_) => 4003,
+            JsonbParseError::Json(_) => 4004,
+            JsonbParseError::InvalidDiffOperation(_) => 4005,
+            JsonbParseError::InvalidDiffPath(_) => 4006,
+            JsonbParseError::InvalidOffset { .. } => 4007,
+            JsonbParseError::OpaqueColumnParseError { inner } => inner.code(),
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            JsonbParseError::InvalidTypeByte(_) => "jsonb_invalid_type_byte",
+            JsonbParseError::InvalidLiteral(_) => "jsonb_invalid_literal",
+            JsonbParseError::Io(_) => "jsonb_io",
+            JsonbParseError::Json(_) => "jsonb_invalid_json",
+            JsonbParseError::InvalidDiffOperation(_) => "jsonb_invalid_diff_operation",
+            JsonbParseError::InvalidDiffPath(_) => "jsonb_invalid_diff_path",
+            JsonbParseError::InvalidOffset { .. } => "jsonb_invalid_offset",
+            JsonbParseError::OpaqueColumnParseError { inner } => inner.code_name(),
+        }
+    }
+}
+
+impl DecimalParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DecimalParseError::Io(e) => io_error_kind(e),
+            DecimalParseError::BigDecimalParse(_) | DecimalParseError::InvalidPrecision { .. } => {
+                ErrorKind::Corruption
+            }
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            DecimalParseError::Io(_) => 5001,
+            DecimalParseError::BigDecimalParse(_) => 5002,
+            DecimalParseError::InvalidPrecision { .. } => 5003,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            DecimalParseError::Io(_) => "decimal_io",
+            DecimalParseError::BigDecimalParse(_) => "decimal_invalid",
+            DecimalParseError::InvalidPrecision { .. } => "decimal_invalid_precision",
+        }
+    }
+}
+
+impl GtidParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::Corruption
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            GtidParseError::InvalidFormat(_) => 6001,
+            GtidParseError::InvalidInterval(..) => 6002,
+            GtidParseError::InvalidTag(_) => 6003,
+            GtidParseError::Uuid(_) => 6004,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            GtidParseError::InvalidFormat(_) => "gtid_invalid_format",
+            GtidParseError::InvalidInterval(..) => "gtid_invalid_interval",
+            GtidParseError::InvalidTag(_) => "gtid_invalid_tag",
+            GtidParseError::Uuid(_) => "gtid_bad_uuid",
+        }
+    }
+}
+
+#[cfg(feature = "geo")]
+impl GeometryParseError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::Corruption
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            GeometryParseError::Io(_) => 7001,
+            GeometryParseError::InvalidByteOrder(_) => 7002,
+            GeometryParseError::UnknownType(_) => 7003,
+            GeometryParseError::UnexpectedMember(_) => 7004,
+            GeometryParseError::TrailingBytes(_) => 7005,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            GeometryParseError::Io(_) => "geometry_io",
+            GeometryParseError::InvalidByteOrder(_) => "geometry_invalid_byte_order",
+            GeometryParseError::UnknownType(_) => "geometry_unknown_type",
+            GeometryParseError::UnexpectedMember(_) => "geometry_unexpected_member",
+            GeometryParseError::TrailingBytes(_) => "geometry_trailing_bytes",
+        }
+    }
+}
+
+impl CorpusError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CorpusError::Io(e) => io_error_kind(e),
+            CorpusError::Event(e) => e.kind(),
+            CorpusError::Json(_) | CorpusError::InvalidRaw { .. } => ErrorKind::Corruption,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            CorpusError::Io(_) => 9001,
+            CorpusError::Event(e) => e.code(),
+            CorpusError::Json(_) => 9002,
+            CorpusError::InvalidRaw { .. } => 9003,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            CorpusError::Io(_) => "corpus_io",
+            CorpusError::Event(e) => e.code_name(),
+            CorpusError::Json(_) => "corpus_malformed_record",
+            CorpusError::InvalidRaw { .. } => "corpus_invalid_raw",
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl ConfigError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ConfigError::Io(e) => io_error_kind(e),
+            ConfigError::Gtid(e) => e.kind(),
+            ConfigError::Binlog(e) => e.kind(),
+            ConfigError::Event(e) => e.kind(),
+            ConfigError::UnresolvedColumn { .. } => ErrorKind::Schema,
+            ConfigError::Output(_) => ErrorKind::Io,
+            ConfigError::Toml(_)
+            | ConfigError::MissingSource
+            | ConfigError::InvalidTablePattern(_) => ErrorKind::Unsupported,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            ConfigError::Io(_) => 8001,
+            ConfigError::Toml(_) => 8002,
+            ConfigError::MissingSource => 8003,
+            ConfigError::InvalidTablePattern(_) => 8004,
+            ConfigError::Gtid(e) => e.code(),
+            ConfigError::Binlog(e) => e.code(),
+            ConfigError::Event(e) => e.code(),
+            ConfigError::UnresolvedColumn { .. } => 8005,
+            ConfigError::Output(_) => 8006,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            ConfigError::Io(_) => "config_io",
+            ConfigError::Toml(_) => "config_invalid",
+            ConfigError::MissingSource => "config_missing_source",
+            ConfigError::InvalidTablePattern(_) => "config_invalid_table_pattern",
+            ConfigError::Gtid(e) => e.code_name(),
+            ConfigError::Binlog(e) => e.code_name(),
+            ConfigError::Event(e) => e.code_name(),
+            ConfigError::UnresolvedColumn { .. } => "config_unresolved_column",
+            ConfigError::Output(_) => "config_output",
+        }
+    }
+}
+
+#[cfg(feature = "avro")]
+impl AvroEncodeError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AvroEncodeError::Event(e) => e.kind(),
+            AvroEncodeError::ValueMismatch { .. } | AvroEncodeError::ColumnCountMismatch { .. } => {
+                ErrorKind::Schema
+            }
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            AvroEncodeError::Event(e) => e.code(),
+            AvroEncodeError::ValueMismatch { .. } => 10001,
+            AvroEncodeError::ColumnCountMismatch { .. } => 10002,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            AvroEncodeError::Event(e) => e.code_name(),
+            AvroEncodeError::ValueMismatch { .. } => "avro_value_mismatch",
+            AvroEncodeError::ColumnCountMismatch { .. } => "avro_column_count_mismatch",
+        }
+    }
+}
+
+impl SinkError {
+    /// The class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            SinkError::Event(e) => e.kind(),
+            SinkError::Io(_) => ErrorKind::Io,
+            SinkError::Serialize(_) => ErrorKind::Unsupported,
+            #[cfg(feature = "kafka")]
+            SinkError::Kafka(_) => ErrorKind::Io,
+        }
+    }
+
+    /// A stable numeric code identifying this error
+    pub fn code(&self) -> u16 {
+        match self {
+            SinkError::Event(e) => e.code(),
+            SinkError::Io(_) => 11001,
+            SinkError::Serialize(_) => 11002,
+            #[cfg(feature = "kafka")]
+            SinkError::Kafka(_) => 11003,
+        }
+    }
+
+    /// A stable string code identifying this error
+    pub fn code_name(&self) -> &'static str {
+        match self {
+            SinkError::Event(e) => e.code_name(),
+            SinkError::Io(_) => "sink_io",
+            SinkError::Serialize(_) => "sink_serialize",
+            #[cfg(feature = "kafka")]
+            SinkError::Kafka(_) => "sink_kafka",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{
+        BinlogParseError, ColumnParseError, DecimalParseError, ErrorKind, EventParseError,
+        JsonbParseError,
+    };
+    use crate::column_types::ColumnType;
+    use crate::event::TypeCode;
+
+    #[test]
+    fn test_wrapped_codes() {
+        let column = ColumnParseError::UnimplementedTypeError {
+            column_type: ColumnType::TinyBlob,
+        };
+        let event = EventParseError::from(column);
+        assert_eq!(event.kind(), ErrorKind::Unsupported);
+        assert_eq!(event.code(), 2001);
+        assert_eq!(event.code_name(), "column_unimplemented_type");
+        let binlog = BinlogParseError::from(event);
+        assert_eq!(binlog.code(), 2001);
+
+        let truncated =
+            EventParseError::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(truncated.kind(), ErrorKind::Corruption);
+        let other =
+            EventParseError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert_eq!(other.kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn test_codes_unique() {
+        let errors = [
+            EventParseError::EofError,
+            EventParseError::InvalidGtidFlags(0),
+            EventParseError::InvalidLogicalClockType(0),
+            EventParseError::UnknownSerializedField(0),
+            EventParseError::ChecksumMismatch {
+                offset: 0,
+                expected: 0,
+                actual: 0,
+            },
+            EventParseError::MissingRotate { file: "a".into() },
+            EventParseError::SkippedRange { start: 0, end: 0 },
+            EventParseError::UnsupportedBinlogVersion(3),
+            EventParseError::EventTooLarge {
+                offset: 4,
+                length: 100,
+                limit: 10,
+            },
+            EventParseError::UnknownTableId {
+                table_id: 1,
+                offset: 4,
+            },
+            EventParseError::NotRowsEvent(TypeCode::XidEvent),
+            EventParseError::ZstdNotEnabled,
+            EventParseError::PayloadTooLarge { limit: 10 },
+            EventParseError::ServerError {
+                code: 1236,
+                message: "a".into(),
+            },
+            ColumnParseError::UnknownColumnType(200).into(),
+            ColumnParseError::ValueTooLarge {
+                length: 100,
+                limit: 10,
+            }
+            .into(),
+            ColumnParseError::InvalidColumnMetadata {
+                column_type: ColumnType::Float(3),
+            }
+            .into(),
+            ColumnParseError::Decimal(DecimalParseError::InvalidPrecision {
+                precision: 0,
+                scale: 0,
+            })
+            .into(),
+            ColumnParseError::Json(JsonbParseError::InvalidOffset {
+                expected: 0,
+                actual: 1,
+            })
+            .into(),
+        ];
+        let codes = errors.iter().map(|e| e.code()).collect::<HashSet<_>>();
+        let names = errors.iter().map(|e| e.code_name()).collect::<HashSet<_>>();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(names.len(), errors.len());
+    }
 }
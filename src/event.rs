@@ -1,19 +1,26 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt;
-use std::io::{self, Cursor, ErrorKind, Read, Seek};
+use std::io::{self, Cursor, ErrorKind, Read, Seek, Write};
+use std::sync::Arc;
 
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::bit_set::BitSet;
+use crate::charset;
 use crate::column_types::ColumnType;
 use crate::errors::{ColumnParseError, EventParseError};
+use crate::gtid::{GtidSet, GtidTag};
+use crate::json_diff::{apply_json_diffs, read_json_diffs};
 use crate::packet_helpers::*;
-use crate::table_map::{SingleTableMap, TableMap};
+use crate::server_version::ServerVersion;
+use crate::table_map::{ColumnDescriptor, SingleTableMap, TableMap, TableNameCase};
 use crate::tell::Tell;
-use crate::value::MySQLValue;
+use crate::value::{ColumnDecoders, MySQLValue, MySQLValueRef, ValueDecoders};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum TypeCode {
     Unknown,
@@ -52,11 +59,20 @@ pub enum TypeCode {
     GtidLogEvent,
     AnonymousGtidLogEvent,
     PreviousGtidsLogEvent,
+    TransactionContextEvent,
+    ViewChangeEvent,
+    XaPrepareLogEvent,
+    PartialUpdateRowsEvent,
+    TransactionPayloadEvent,
+    HeartbeatLogEventV2,
+    GtidTaggedLogEvent,
+    /// MariaDB's marker that the events after it are encrypted
+    StartEncryptionEvent,
     OtherUnknown(u8),
 }
 
 impl TypeCode {
-    fn from_byte(b: u8) -> Self {
+    pub(crate) fn from_byte(b: u8) -> Self {
         match b {
             0 => TypeCode::Unknown,
             1 => TypeCode::StartEventV3,
@@ -94,12 +110,34 @@ impl TypeCode {
             33 => TypeCode::GtidLogEvent,
             34 => TypeCode::AnonymousGtidLogEvent,
             35 => TypeCode::PreviousGtidsLogEvent,
+            36 => TypeCode::TransactionContextEvent,
+            37 => TypeCode::ViewChangeEvent,
+            38 => TypeCode::XaPrepareLogEvent,
+            39 => TypeCode::PartialUpdateRowsEvent,
+            40 => TypeCode::TransactionPayloadEvent,
+            41 => TypeCode::HeartbeatLogEventV2,
+            42 => TypeCode::GtidTaggedLogEvent,
+            164 => TypeCode::StartEncryptionEvent,
             i => TypeCode::OtherUnknown(i),
         }
     }
+
+    /// Whether this is one of the (v1 or v2) write, update, or delete rows events
+    pub fn is_rows_event(self) -> bool {
+        matches!(
+            self,
+            TypeCode::WriteRowsEventV1
+                | TypeCode::UpdateRowsEventV1
+                | TypeCode::DeleteRowsEventV1
+                | TypeCode::WriteRowsEventV2
+                | TypeCode::UpdateRowsEventV2
+                | TypeCode::DeleteRowsEventV2
+                | TypeCode::PartialUpdateRowsEvent
+        )
+    }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum ChecksumAlgorithm {
     None,
     CRC32,
@@ -116,16 +154,49 @@ impl From<u8> for ChecksumAlgorithm {
     }
 }
 
+/// The values of a row image, in column order. A column which wasn't logged (because of
+/// `binlog_row_image=MINIMAL` or `NOBLOB`) is `None`, while a NULL column is
+/// `Some(MySQLValue::Null)`; see [`ColumnValue`].
 pub type RowData = Vec<Option<MySQLValue>>;
 
-#[derive(Debug)]
+pub type RowDataRef<'a> = Vec<Option<MySQLValueRef<'a>>>;
+
+/// Commit metadata added to GTID events in MySQL 8.0. Every field is `None` for events written by
+/// older servers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GtidCommitInfo {
+    /// When the transaction committed on the server which wrote this binlog, in microseconds
+    /// since the epoch
+    pub immediate_commit_timestamp: Option<u64>,
+    /// When the transaction committed on the server where it originated, in microseconds since
+    /// the epoch
+    pub original_commit_timestamp: Option<u64>,
+    /// Size in bytes of the whole transaction, including this GTID event
+    pub transaction_length: Option<u64>,
+    /// Version of the server which wrote this binlog, as e.g. `80036`
+    pub immediate_server_version: Option<u32>,
+    /// Version of the server where the transaction originated
+    pub original_server_version: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
 pub enum EventData {
     GtidLogEvent {
         flags: u8,
         uuid: Uuid,
+        tag: Option<GtidTag>,
         coordinate: u64,
         last_committed: Option<u64>,
         sequence_number: Option<u64>,
+        commit_info: GtidCommitInfo,
+    },
+    /// Starts a transaction committed with `gtid_mode=OFF`; the same as a GtidLogEvent, but
+    /// without a GTID
+    AnonymousGtidLogEvent {
+        flags: u8,
+        last_committed: Option<u64>,
+        sequence_number: Option<u64>,
+        commit_info: GtidCommitInfo,
     },
     QueryEvent {
         thread_id: u32,
@@ -133,6 +204,7 @@ pub enum EventData {
         error_code: i16,
         schema: String,
         query: String,
+        status_vars: QueryStatusVars,
     },
     FormatDescriptionEvent {
         binlog_version: u16,
@@ -141,12 +213,19 @@ pub enum EventData {
         common_header_len: u8,
         checksum_algorithm: ChecksumAlgorithm,
     },
+    RotateEvent {
+        position: u64,
+        next_log_name: String,
+    },
     TableMapEvent {
         table_id: u64,
         schema_name: String,
         table_name: String,
         columns: Vec<ColumnType>,
+        // also recorded as `nullable` on each descriptor
+        #[serde(skip)]
         null_bitmap: BitSet,
+        descriptors: Vec<ColumnDescriptor>,
     },
     WriteRowsEvent {
         table_id: u64,
@@ -160,6 +239,614 @@ pub enum EventData {
         table_id: u64,
         rows: Vec<RowEvent>,
     },
+    /// The first phase of an XA transaction has been prepared (or, if `one_phase` is set, it was
+    /// committed in one phase)
+    XaPrepareEvent {
+        one_phase: bool,
+        format_id: i32,
+        gtrid: Vec<u8>,
+        bqual: Vec<u8>,
+    },
+    /// Sets `LAST_INSERT_ID()` or the next auto-increment value for the following QueryEvent
+    /// (only in statement-based binlogs)
+    IntvarEvent {
+        kind: IntvarKind,
+        value: u64,
+    },
+    /// The seeds of `RAND()` for the following QueryEvent (only in statement-based binlogs)
+    RandEvent {
+        seed1: u64,
+        seed2: u64,
+    },
+    /// The value of a user variable referenced by the following QueryEvent (only in
+    /// statement-based binlogs). `value` is `None` if the variable is NULL; `charset` is only set
+    /// for string values, which are decoded as strings if they are valid UTF-8 and as blobs
+    /// otherwise.
+    UserVarEvent {
+        name: String,
+        value: Option<MySQLValue>,
+        charset: Option<u32>,
+        unsigned: bool,
+    },
+    /// Every GTID executed before the start of this binlog file
+    PreviousGtidsEvent {
+        gtids: GtidSet,
+    },
+    /// A transaction on a transactional storage engine was committed
+    XidEvent {
+        xid: u64,
+    },
+    /// Something happened on the source which may make the binlog incomplete; in particular,
+    /// a `LostEvents` incident means that changes were not logged and replicas must resync
+    IncidentEvent {
+        incident_type: IncidentType,
+        message: String,
+    },
+    /// Sent periodically by a server streaming binlogs while there are no other events, with the
+    /// current position in its binlog (never written to binlog files)
+    HeartbeatEvent {
+        log_name: String,
+        position: u64,
+    },
+    /// Written by MariaDB with `encrypt_binlog=ON`; every event after it is encrypted with the
+    /// key of this version, which this crate can't decrypt
+    StartEncryptionEvent {
+        scheme: u8,
+        key_version: u32,
+        nonce: [u8; 12],
+    },
+    /// A group replication view change
+    ViewChangeEvent {
+        view_id: String,
+        seq_number: u64,
+    },
+    /// A (possibly compressed) block of events making up a whole transaction. The events are
+    /// decompressed when this is decoded; their offsets are relative to the start of the
+    /// uncompressed payload.
+    TransactionPayloadEvent {
+        compression_type: PayloadCompression,
+        uncompressed_size: Option<u64>,
+        #[serde(serialize_with = "serialize_event_headers")]
+        events: Vec<Event>,
+    },
+}
+
+/// Events are serialized by their headers; decoding their bodies may need a table map
+fn serialize_event_headers<S: serde::Serializer>(
+    events: &[Event],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(events.iter().map(Event::event_header))
+}
+
+/// Which value an INTVAR_EVENT sets for the statement which follows it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum IntvarKind {
+    /// The value returned by `LAST_INSERT_ID()`
+    LastInsertId,
+    /// The next value of an `AUTO_INCREMENT` column
+    InsertId,
+    Other(u8),
+}
+
+impl From<u8> for IntvarKind {
+    fn from(byte: u8) -> Self {
+        match byte {
+            1 => IntvarKind::LastInsertId,
+            2 => IntvarKind::InsertId,
+            other => IntvarKind::Other(other),
+        }
+    }
+}
+
+/// The kind of incident recorded by an INCIDENT_EVENT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IncidentType {
+    None,
+    /// Some changes were not written to the binlog
+    LostEvents,
+    Other(u16),
+}
+
+impl From<u16> for IncidentType {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => IncidentType::None,
+            1 => IncidentType::LostEvents,
+            other => IncidentType::Other(other),
+        }
+    }
+}
+
+/// An incident recorded in the binlog, as surfaced on a [`BinlogEvent`](crate::BinlogEvent)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Incident {
+    pub incident_type: IncidentType,
+    pub message: String,
+}
+
+/// A transaction boundary, as surfaced on a [`BinlogEvent`](crate::BinlogEvent): a `BEGIN`,
+/// `COMMIT` or `ROLLBACK` QueryEvent, or the XidEvent which commits a transaction on a
+/// transactional engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionMarker {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl TransactionMarker {
+    /// Classify a QueryEvent's statement
+    pub fn from_query(query: &str) -> Option<Self> {
+        let query = query.trim();
+        if query.eq_ignore_ascii_case("BEGIN") {
+            Some(TransactionMarker::Begin)
+        } else if query.eq_ignore_ascii_case("COMMIT") {
+            Some(TransactionMarker::Commit)
+        } else if query.eq_ignore_ascii_case("ROLLBACK") {
+            Some(TransactionMarker::Rollback)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where and how a QueryEvent's statement ran, as surfaced on a
+/// [`BinlogEvent`](crate::BinlogEvent)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryContext {
+    /// The default database of the session (empty if there was none)
+    pub schema: String,
+    pub thread_id: u32,
+    /// How long the statement took to run on the source, in seconds
+    pub exec_time: u32,
+    /// The error the statement failed with on the source; 0 if it succeeded
+    pub error_code: i16,
+}
+
+/// The `auto_increment_increment` and `auto_increment_offset` a statement ran with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AutoIncrement {
+    pub increment: u16,
+    pub offset: u16,
+}
+
+/// The character set variables a statement ran with, as collation ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QueryCharset {
+    pub character_set_client: u16,
+    pub collation_connection: u16,
+    pub collation_server: u16,
+}
+
+/// The definer of a stored routine or view whose statement was logged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Invoker {
+    pub user: String,
+    pub host: String,
+}
+
+/// The session state a QueryEvent's statement ran with, from the event's status variables.
+/// Each field is `None` if the server didn't log it (servers only log the variables they
+/// support, and some only when they differ from the default).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct QueryStatusVars {
+    /// `OPTION_*` flags such as `OPTION_AUTO_IS_NULL` and `OPTION_NOT_AUTOCOMMIT`
+    pub flags2: Option<u32>,
+    /// The `sql_mode` bitmask
+    pub sql_mode: Option<u64>,
+    pub catalog: Option<String>,
+    pub auto_increment: Option<AutoIncrement>,
+    pub charset: Option<QueryCharset>,
+    pub time_zone: Option<String>,
+    /// The `lc_time_names` locale id
+    pub lc_time_names: Option<u16>,
+    /// The collation id of the default database
+    pub charset_database: Option<u16>,
+    pub table_map_for_update: Option<u64>,
+    pub invoker: Option<Invoker>,
+    /// The databases the statement updated; empty if there were too many to log
+    pub updated_db_names: Option<Vec<String>>,
+    /// The microseconds part of the statement's start time
+    pub microseconds: Option<u32>,
+    pub explicit_defaults_for_timestamp: Option<bool>,
+    /// The XID of a DDL statement logged as part of its own transaction
+    pub ddl_xid: Option<u64>,
+    pub default_collation_for_utf8mb4: Option<u16>,
+    pub sql_require_primary_key: Option<bool>,
+    pub default_table_encryption: Option<bool>,
+}
+
+// Q_UPDATED_DB_NAMES count meaning that the databases weren't listed
+const OVER_MAX_DBS_IN_EVENT_MTS: u8 = 254;
+
+impl QueryStatusVars {
+    /// Decode the status variables block of a QueryEvent. Like the server, we stop at the
+    /// first variable we don't know, since its length can't be known either.
+    fn parse(block: &[u8]) -> io::Result<Self> {
+        fn read_string<R: Read>(cursor: &mut R, len: usize) -> io::Result<String> {
+            let mut buf = vec![0u8; len];
+            cursor.read_exact(&mut buf)?;
+            Ok(String::from_utf8_lossy(&buf).into_owned())
+        }
+        let mut vars = QueryStatusVars::default();
+        let mut cursor = Cursor::new(block);
+        while (cursor.position() as usize) < block.len() {
+            match cursor.read_u8()? {
+                0 => vars.flags2 = Some(cursor.read_u32::<LittleEndian>()?),
+                1 => vars.sql_mode = Some(cursor.read_u64::<LittleEndian>()?),
+                // the pre-5.0.4 catalog, which was followed by a NUL
+                2 => {
+                    let len = usize::from(cursor.read_u8()?);
+                    vars.catalog = Some(read_string(&mut cursor, len)?);
+                    cursor.read_u8()?;
+                }
+                3 => {
+                    vars.auto_increment = Some(AutoIncrement {
+                        increment: cursor.read_u16::<LittleEndian>()?,
+                        offset: cursor.read_u16::<LittleEndian>()?,
+                    })
+                }
+                4 => {
+                    vars.charset = Some(QueryCharset {
+                        character_set_client: cursor.read_u16::<LittleEndian>()?,
+                        collation_connection: cursor.read_u16::<LittleEndian>()?,
+                        collation_server: cursor.read_u16::<LittleEndian>()?,
+                    })
+                }
+                5 => {
+                    let len = usize::from(cursor.read_u8()?);
+                    vars.time_zone = Some(read_string(&mut cursor, len)?);
+                }
+                6 => {
+                    let len = usize::from(cursor.read_u8()?);
+                    vars.catalog = Some(read_string(&mut cursor, len)?);
+                }
+                7 => vars.lc_time_names = Some(cursor.read_u16::<LittleEndian>()?),
+                8 => vars.charset_database = Some(cursor.read_u16::<LittleEndian>()?),
+                9 => vars.table_map_for_update = Some(cursor.read_u64::<LittleEndian>()?),
+                // Q_MASTER_DATA_WRITTEN_CODE, only used in relay logs
+                10 => {
+                    cursor.read_u32::<LittleEndian>()?;
+                }
+                11 => {
+                    let len = usize::from(cursor.read_u8()?);
+                    let user = read_string(&mut cursor, len)?;
+                    let len = usize::from(cursor.read_u8()?);
+                    let host = read_string(&mut cursor, len)?;
+                    vars.invoker = Some(Invoker { user, host });
+                }
+                12 => {
+                    let count = cursor.read_u8()?;
+                    let mut names = Vec::new();
+                    if count != OVER_MAX_DBS_IN_EVENT_MTS {
+                        for _ in 0..count {
+                            let mut name = Vec::new();
+                            loop {
+                                match cursor.read_u8()? {
+                                    0 => break,
+                                    b => name.push(b),
+                                }
+                            }
+                            names.push(String::from_utf8_lossy(&name).into_owned());
+                        }
+                    }
+                    vars.updated_db_names = Some(names);
+                }
+                13 => vars.microseconds = Some(read_uint24(&mut cursor)?),
+                16 => vars.explicit_defaults_for_timestamp = Some(cursor.read_u8()? != 0),
+                17 => vars.ddl_xid = Some(cursor.read_u64::<LittleEndian>()?),
+                18 => vars.default_collation_for_utf8mb4 = Some(cursor.read_u16::<LittleEndian>()?),
+                19 => vars.sql_require_primary_key = Some(cursor.read_u8()? != 0),
+                20 => vars.default_table_encryption = Some(cursor.read_u8()? != 0),
+                _ => break,
+            }
+        }
+        Ok(vars)
+    }
+}
+
+/// The file (and the position in it) which the binlog continues in after a RotateEvent, as
+/// surfaced on a [`BinlogEvent`](crate::BinlogEvent)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rotation {
+    pub next_log_name: String,
+    pub position: u64,
+}
+
+/// The position reported by the most recent heartbeat from a server streaming binlogs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heartbeat {
+    pub log_name: String,
+    pub position: u64,
+}
+
+/// Compression applied to the events in a TRANSACTION_PAYLOAD_EVENT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PayloadCompression {
+    Zstd,
+    None,
+    Other(u64),
+}
+
+impl From<u64> for PayloadCompression {
+    fn from(value: u64) -> Self {
+        match value {
+            0 => PayloadCompression::Zstd,
+            255 => PayloadCompression::None,
+            other => PayloadCompression::Other(other),
+        }
+    }
+}
+
+/// What to do when a rows event has a different number of columns than the table map it refers
+/// to (for example, when replaying events written before a column was dropped against a newer
+/// table map)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnCountPolicy {
+    /// Fail with [`ColumnParseError::ColumnCountMismatch`]
+    #[default]
+    Error,
+    /// Decode only the columns known to both; each row has as many columns as the narrower of
+    /// the two
+    Truncate,
+    /// Decode only the columns known to both, and fill columns which are in the table map but not
+    /// in the event with `MySQLValue::Null`
+    PadNull,
+}
+
+/// Custom decoding for events of a particular type, registered with
+/// [`BinlogFileParserBuilder::event_decoder`](crate::BinlogFileParserBuilder::event_decoder).
+///
+/// The decoder receives the raw event and returns the value to emit as its
+/// [`BinlogEvent::custom`](crate::BinlogEvent::custom), or `None` to drop the event.
+pub trait EventDecoder: Send + Sync {
+    fn decode(&self, event: &Event) -> Result<Option<serde_json::Value>, EventParseError>;
+}
+
+impl<F> EventDecoder for F
+where
+    F: Fn(&Event) -> Result<Option<serde_json::Value>, EventParseError> + Send + Sync,
+{
+    fn decode(&self, event: &Event) -> Result<Option<serde_json::Value>, EventParseError> {
+        self(event)
+    }
+}
+
+/// The [`EventDecoder`]s registered for each event type
+#[derive(Clone, Default)]
+pub struct EventDecoders {
+    decoders: HashMap<TypeCode, Arc<dyn EventDecoder>>,
+}
+
+impl EventDecoders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a decoder for an event type, replacing any previous registration
+    pub fn register<D: EventDecoder + 'static>(&mut self, type_code: TypeCode, decoder: D) {
+        self.decoders.insert(type_code, Arc::new(decoder));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    pub(crate) fn get(&self, type_code: TypeCode) -> Option<&dyn EventDecoder> {
+        self.decoders.get(&type_code).map(|d| d.as_ref())
+    }
+}
+
+impl fmt::Debug for EventDecoders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.decoders.keys()).finish()
+    }
+}
+
+/// Options controlling how event bodies are decoded
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    pub column_count_policy: ColumnCountPolicy,
+    pub value_decoders: ValueDecoders,
+    pub raw_json: RawJsonColumns,
+    /// Fail with [`ValueTooLarge`](ColumnParseError::ValueTooLarge) rather than reading a
+    /// string, blob, JSON, or geometry value longer than this many bytes
+    pub max_value_size: Option<usize>,
+    /// Fail rather than decompressing a transaction payload to more than this many bytes, or
+    /// reading a longer event from one
+    pub max_event_size: Option<u32>,
+    pub projection: ColumnProjection,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ColumnSelection {
+    All,
+    Columns(HashSet<usize>),
+}
+
+impl ColumnSelection {
+    fn contains(&self, column: usize) -> bool {
+        match self {
+            ColumnSelection::All => true,
+            ColumnSelection::Columns(columns) => columns.contains(&column),
+        }
+    }
+}
+
+/// JSON columns whose values should be returned as their raw binary (JSONB) encoding in a
+/// [`MySQLValue::Blob`] rather than decoded, keyed by schema, table, and column index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawJsonColumns {
+    tables: HashMap<(String, String), ColumnSelection>,
+    name_case: TableNameCase,
+}
+
+impl RawJsonColumns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave a JSON column undecoded, or every JSON column in the table if `column` is `None`
+    pub fn insert(&mut self, schema_name: &str, table_name: &str, column: Option<usize>) {
+        let selection = self
+            .tables
+            .entry((
+                self.name_case.key(schema_name).into_owned(),
+                self.name_case.key(table_name).into_owned(),
+            ))
+            .or_insert_with(|| ColumnSelection::Columns(HashSet::new()));
+        match (column, selection) {
+            (None, selection) => *selection = ColumnSelection::All,
+            (Some(column), ColumnSelection::Columns(columns)) => {
+                columns.insert(column);
+            }
+            (Some(_), ColumnSelection::All) => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Change how table names are compared, including for tables already inserted
+    pub fn set_name_case(&mut self, name_case: TableNameCase) {
+        self.name_case = name_case;
+        self.tables = rekey_tables(std::mem::take(&mut self.tables), name_case);
+    }
+
+    fn for_table(&self, schema_name: &str, table_name: &str) -> Option<&ColumnSelection> {
+        get_table(&self.tables, self.name_case, schema_name, table_name)
+    }
+}
+
+/// A column of a [`ColumnProjection`] or of a [`ValueDecoders`] registration, by its zero-based
+/// position or its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectedColumn {
+    Index(usize),
+    /// Only matches if the table map has column names (`binlog_row_metadata=FULL`, or a
+    /// [`SchemaProvider`](crate::table_map::SchemaProvider)). Compared case-insensitively.
+    Name(String),
+}
+
+impl ProjectedColumn {
+    /// The position of this column in `table`, if it has one
+    pub(crate) fn position(&self, table: &SingleTableMap) -> Option<usize> {
+        match self {
+            ProjectedColumn::Index(index) => Some(*index),
+            ProjectedColumn::Name(wanted) => table.descriptors.iter().position(|d| {
+                d.name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(wanted))
+            }),
+        }
+    }
+}
+
+impl From<usize> for ProjectedColumn {
+    fn from(index: usize) -> Self {
+        ProjectedColumn::Index(index)
+    }
+}
+
+impl From<&str> for ProjectedColumn {
+    fn from(name: &str) -> Self {
+        ProjectedColumn::Name(name.to_owned())
+    }
+}
+
+impl From<String> for ProjectedColumn {
+    fn from(name: String) -> Self {
+        ProjectedColumn::Name(name)
+    }
+}
+
+/// The columns to decode from the rows of particular tables, keyed by schema and table. The
+/// other columns of those tables are skipped over without being decoded, and are left out of
+/// each row as if they hadn't been logged; the columns of other tables are all decoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnProjection {
+    tables: HashMap<(String, String), Vec<ProjectedColumn>>,
+    name_case: TableNameCase,
+}
+
+impl ColumnProjection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode `column` of a table, in addition to any columns of it already inserted
+    pub fn insert<C: Into<ProjectedColumn>>(
+        &mut self,
+        schema_name: &str,
+        table_name: &str,
+        column: C,
+    ) {
+        self.tables
+            .entry((
+                self.name_case.key(schema_name).into_owned(),
+                self.name_case.key(table_name).into_owned(),
+            ))
+            .or_default()
+            .push(column.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Change how table names are compared, including for tables already inserted
+    pub fn set_name_case(&mut self, name_case: TableNameCase) {
+        self.name_case = name_case;
+        self.tables = rekey_tables(std::mem::take(&mut self.tables), name_case);
+    }
+
+    fn for_table(&self, schema_name: &str, table_name: &str) -> Option<&[ProjectedColumn]> {
+        get_table(&self.tables, self.name_case, schema_name, table_name).map(Vec::as_slice)
+    }
+}
+
+/// Whether column `i` of `table` is one of `projection`'s columns
+fn is_projected(projection: &[ProjectedColumn], table: &SingleTableMap, i: usize) -> bool {
+    let name = table.descriptors.get(i).and_then(|d| d.name.as_deref());
+    projection.iter().any(|column| match column {
+        ProjectedColumn::Index(index) => *index == i,
+        ProjectedColumn::Name(wanted) => name.is_some_and(|name| name.eq_ignore_ascii_case(wanted)),
+    })
+}
+
+pub(crate) fn rekey_tables<T>(
+    tables: HashMap<(String, String), T>,
+    name_case: TableNameCase,
+) -> HashMap<(String, String), T> {
+    tables
+        .into_iter()
+        .map(|((schema, table), value)| {
+            let key = (
+                name_case.key(&schema).into_owned(),
+                name_case.key(&table).into_owned(),
+            );
+            (key, value)
+        })
+        .collect()
+}
+
+pub(crate) fn get_table<'a, T>(
+    tables: &'a HashMap<(String, String), T>,
+    name_case: TableNameCase,
+    schema_name: &str,
+    table_name: &str,
+) -> Option<&'a T> {
+    if tables.is_empty() {
+        return None;
+    }
+    tables.get(&(
+        name_case.key(schema_name).into_owned(),
+        name_case.key(table_name).into_owned(),
+    ))
 }
 
 struct RowsEvent {
@@ -167,138 +854,1223 @@ struct RowsEvent {
     rows: Vec<RowEvent>,
 }
 
+/// Check that the columns in a rows event line up with the table map, or that the policy allows
+/// them not to. Returns the number of columns which can be decoded.
+fn reconcile_column_count(
+    this_table_map: &SingleTableMap,
+    present_bitmask: &BitSet,
+    policy: ColumnCountPolicy,
+) -> Result<usize, ColumnParseError> {
+    let table_columns = this_table_map.columns.len();
+    let event_columns = present_bitmask.len();
+    if table_columns != event_columns && policy == ColumnCountPolicy::Error {
+        return Err(ColumnParseError::ColumnCountMismatch {
+            table_columns,
+            event_columns,
+        });
+    }
+    Ok(table_columns.min(event_columns))
+}
+
+/// Check that the columns the table map doesn't know about have no value in this row. We don't
+/// know their types, so we couldn't skip over them if they did.
+fn check_extra_columns(
+    this_table_map: &SingleTableMap,
+    present_bitmask: &BitSet,
+    null_bitmask: &BitSet,
+    mut null_index: usize,
+) -> Result<(), ColumnParseError> {
+    let table_columns = this_table_map.columns.len();
+    for i in table_columns..present_bitmask.len() {
+        if !present_bitmask.try_is_set(i)? {
+            continue;
+        }
+        if !null_bitmask.try_is_set(null_index)? {
+            return Err(ColumnParseError::ColumnCountMismatch {
+                table_columns,
+                event_columns: present_bitmask.len(),
+            });
+        }
+        null_index += 1;
+    }
+    Ok(())
+}
+
+// value_options bit indicating that JSON columns in an after-image may be encoded as diffs
+const PARTIAL_JSON_UPDATES: i64 = 0x01;
+
+/// The after-image of each row in a PARTIAL_UPDATE_ROWS_EVENT begins with a set of value options;
+/// if partial JSON updates are enabled, they are followed by a bitmap with one bit for each JSON
+/// column in the table, which is set if that column is encoded as a diff against the before-image
+fn read_partial_json_bitmap<R: Read>(
+    mut cursor: &mut R,
+    this_table_map: &SingleTableMap,
+) -> Result<Option<BitSet>, ColumnParseError> {
+    let value_options = read_variable_length_integer(&mut cursor)?;
+    if value_options & PARTIAL_JSON_UPDATES == 0 {
+        return Ok(None);
+    }
+    let json_columns = this_table_map
+        .columns
+        .iter()
+        .filter(|c| matches!(c, ColumnType::Json(_)))
+        .count();
+    let bitmap = read_nbytes(&mut cursor, (json_columns + 7) >> 3)?;
+    Ok(Some(BitSet::from_slice(json_columns, &bitmap)?))
+}
+
+/// Tracks which JSON column of a row we are on, to look it up in a partial JSON bitmap
+struct PartialJsonColumns<'a> {
+    bitmap: Option<&'a BitSet>,
+    json_index: usize,
+}
+
+impl<'a> PartialJsonColumns<'a> {
+    fn new(bitmap: Option<&'a BitSet>) -> Self {
+        PartialJsonColumns {
+            bitmap,
+            json_index: 0,
+        }
+    }
+
+    /// Whether the given column is encoded as a JSON diff; must be called for every column in
+    /// the table, in order
+    fn is_partial(&mut self, column: &ColumnType) -> Result<bool, ColumnParseError> {
+        if !matches!(column, ColumnType::Json(_)) {
+            return Ok(false);
+        }
+        self.json_index += 1;
+        match self.bitmap {
+            Some(b) => Ok(b.try_is_set(self.json_index - 1)?),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Parse one row image. `before` is the before-image of an update's after-image, which partial
+/// JSON updates are applied to.
 fn parse_one_row<R: Read + Seek>(
     mut cursor: &mut R,
     this_table_map: &SingleTableMap,
     present_bitmask: &BitSet,
+    partial_json: Option<&BitSet>,
+    before: Option<&RowData>,
+    decoders: Option<&ColumnDecoders>,
+    options: &DecodeOptions,
 ) -> Result<RowData, ColumnParseError> {
+    let columns =
+        reconcile_column_count(this_table_map, present_bitmask, options.column_count_policy)?;
     let num_set_columns = present_bitmask.bits_set();
     let null_bitmask_size = (num_set_columns + 7) >> 3;
     let mut row = Vec::with_capacity(this_table_map.columns.len());
     let null_bitmask = BitSet::from_slice(
         num_set_columns,
         &read_nbytes(&mut cursor, null_bitmask_size)?,
-    )
-    .unwrap();
+    )?;
     let mut null_index = 0;
-    for (i, column_definition) in this_table_map.columns.iter().enumerate() {
-        if !present_bitmask.is_set(i) {
+    let mut partial_json = PartialJsonColumns::new(partial_json);
+    let raw_json = options
+        .raw_json
+        .for_table(&this_table_map.schema_name, &this_table_map.table_name);
+    let projection = options
+        .projection
+        .for_table(&this_table_map.schema_name, &this_table_map.table_name);
+    for (i, column_definition) in this_table_map.columns[..columns].iter().enumerate() {
+        let is_partial = partial_json.is_partial(column_definition)?;
+        if !present_bitmask.try_is_set(i)? {
+            row.push(None);
+            continue;
+        }
+        let is_null = null_bitmask.try_is_set(null_index)?;
+        if projection.is_some_and(|p| !is_projected(p, this_table_map, i)) {
+            if !is_null {
+                skip_column_value(&mut cursor, column_definition, is_partial)?;
+            }
             row.push(None);
+            null_index += 1;
             continue;
         }
-        let is_null = null_bitmask.is_set(null_index);
         let val = if is_null {
             MySQLValue::Null
+        } else if is_partial {
+            let diffs = read_json_diffs(&mut cursor)?;
+            match before.and_then(|b| b.get(i)) {
+                Some(Some(MySQLValue::Json(document))) => {
+                    MySQLValue::Json(apply_json_diffs(document, &diffs)?)
+                }
+                _ => MySQLValue::JsonDiff(diffs),
+            }
         } else {
             //println!("parsing column {} ({:?})", i, column_definition);
-            column_definition.read_value(&mut cursor)?
+            check_value_size(&mut cursor, column_definition, options.max_value_size)?;
+            read_column_value(&mut cursor, this_table_map, i, raw_json, decoders)?
         };
         row.push(Some(val));
         null_index += 1;
     }
+    check_extra_columns(this_table_map, present_bitmask, &null_bitmask, null_index)?;
+    if options.column_count_policy == ColumnCountPolicy::PadNull {
+        row.resize_with(this_table_map.columns.len(), || Some(MySQLValue::Null));
+    }
     //println!("finished row: {:?}", row);
     Ok(row)
 }
 
-#[derive(Debug, Serialize)]
-#[serde(untagged)]
-pub enum RowEvent {
-    NewRow {
-        cols: RowData,
-    },
-    DeletedRow {
-        cols: RowData,
-    },
-    UpdatedRow {
-        before_cols: RowData,
-        after_cols: RowData,
-    },
-}
-
-impl RowEvent {
-    pub fn cols(&self) -> Option<&RowData> {
-        match self {
-            RowEvent::NewRow { cols } => Some(cols),
-            RowEvent::DeletedRow { cols } => Some(cols),
-            RowEvent::UpdatedRow { .. } => None,
+/// Check the length prefix of a variable-length value against `limit` before reading the value,
+/// leaving the cursor where it was
+fn check_value_size<R: Read + Seek>(
+    cursor: &mut R,
+    column: &ColumnType,
+    limit: Option<usize>,
+) -> Result<(), ColumnParseError> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let length_bytes = match column {
+        ColumnType::VarChar(max_len) => {
+            if *max_len > 255 {
+                2
+            } else {
+                1
+            }
         }
+        ColumnType::Blob(length_bytes)
+        | ColumnType::Json(length_bytes)
+        | ColumnType::Geometry(length_bytes) => *length_bytes,
+        _ => return Ok(()),
+    };
+    let start = cursor.stream_position()?;
+    let length = read_var_byte_length(cursor, length_bytes)?;
+    cursor.seek(io::SeekFrom::Start(start))?;
+    if length > limit {
+        return Err(ColumnParseError::ValueTooLarge { length, limit });
     }
+    Ok(())
 }
 
-fn parse_rows_event<R: Read + Seek>(
-    type_code: TypeCode,
-    data_len: usize,
+/// Read the (non-null, non-diff) value of column `i`
+fn read_column_value<R: Read>(
     mut cursor: &mut R,
-    table_map: Option<&TableMap>,
-) -> Result<RowsEvent, ColumnParseError> {
-    let mut table_id_buf = [0u8; 8];
-    cursor.read_exact(&mut table_id_buf[0..6])?;
-    let table_id = LittleEndian::read_u64(&table_id_buf);
-    // two-byte reserved value
-    cursor.seek(io::SeekFrom::Current(2))?;
-    match type_code {
-        TypeCode::WriteRowsEventV2 | TypeCode::UpdateRowsEventV2 | TypeCode::DeleteRowsEventV2 => {
-            let _ = cursor.read_i16::<LittleEndian>()?;
+    this_table_map: &SingleTableMap,
+    i: usize,
+    raw_json: Option<&ColumnSelection>,
+    decoders: Option<&ColumnDecoders>,
+) -> Result<MySQLValue, ColumnParseError> {
+    let column_definition = &this_table_map.columns[i];
+    let val = match column_definition {
+        ColumnType::Json(size) if raw_json.is_some_and(|r| r.contains(i)) => {
+            MySQLValue::Blob(read_var_byte_length_prefixed_bytes(&mut cursor, *size)?.into())
         }
-        _ => {}
-    }
-    let num_columns = read_variable_length_integer(&mut cursor)? as usize;
-    let bitmask_size = (num_columns + 7) >> 3;
-    let before_column_bitmask =
-        BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?).unwrap();
-    let after_column_bitmask = match type_code {
-        TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-            Some(BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?).unwrap())
+        ColumnType::VarChar(_) => match this_table_map.descriptors.get(i).and_then(|d| d.charset) {
+            Some(collation) => column_definition.read_text_value(&mut cursor, collation)?,
+            None => column_definition.read_value(&mut cursor)?,
+        },
+        ColumnType::Tiny
+        | ColumnType::Short
+        | ColumnType::Int24
+        | ColumnType::Long
+        | ColumnType::LongLong
+            if this_table_map
+                .descriptors
+                .get(i)
+                .is_some_and(|d| d.unsigned == Some(true)) =>
+        {
+            column_definition.read_unsigned_value(&mut cursor)?
         }
-        _ => None,
-    };
-    let mut rows = Vec::with_capacity(1);
-    if let Some(table_map) = table_map {
-        if let Some(this_table_map) = table_map.get(table_id) {
-            loop {
-                let pos = cursor.tell()? as usize;
-                if data_len - pos < 1 {
-                    break;
-                }
-                match type_code {
-                    TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
-                        rows.push(RowEvent::NewRow {
-                            cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                        });
-                    }
-                    TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-                        rows.push(RowEvent::UpdatedRow {
-                            before_cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                            after_cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                after_column_bitmask.as_ref().unwrap(),
-                            )?,
-                        })
-                    }
-                    TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
-                        rows.push(RowEvent::DeletedRow {
-                            cols: parse_one_row(
-                                &mut cursor,
-                                this_table_map,
-                                &before_column_bitmask,
-                            )?,
-                        });
-                    }
-                    _ => unimplemented!(),
-                }
+        ColumnType::Enum(_) => {
+            let val = column_definition.read_value(&mut cursor)?;
+            match this_table_map.descriptors.get(i) {
+                Some(descriptor) => descriptor.resolve_enum(val),
+                None => val,
             }
         }
+        _ => column_definition.read_value(&mut cursor)?,
+    };
+    match decoders.and_then(|d| d.get(i)).and_then(Option::as_ref) {
+        Some(decoder) => decoder.decode(column_definition, val),
+        None => Ok(val),
     }
-    Ok(RowsEvent { table_id, rows })
+}
+
+/// Take a length-prefixed value out of the buffer without copying it
+fn take_length_prefixed<'a>(
+    cursor: &mut Cursor<&'a [u8]>,
+    length_bytes: u8,
+) -> Result<&'a [u8], ColumnParseError> {
+    let len = read_var_byte_length(cursor, length_bytes)?;
+    let data: &'a [u8] = cursor.get_ref();
+    let start = cursor.position() as usize;
+    let value = data
+        .get(start..start + len)
+        .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+    cursor.set_position((start + len) as u64);
+    Ok(value)
+}
+
+/// Parse one row image, borrowing strings, blobs, and JSON documents from the buffer; the
+/// counterpart of `parse_one_row` for [`Event::rows_ref`]. Columns with a [`ValueDecoder`] are
+/// decoded as by `parse_one_row`.
+fn parse_one_row_ref<'a>(
+    cursor: &mut Cursor<&'a [u8]>,
+    this_table_map: &SingleTableMap,
+    present_bitmask: &BitSet,
+    partial_json: Option<&BitSet>,
+    before: Option<&RowDataRef<'a>>,
+    decoders: Option<&ColumnDecoders>,
+    options: &DecodeOptions,
+) -> Result<RowDataRef<'a>, ColumnParseError> {
+    let columns =
+        reconcile_column_count(this_table_map, present_bitmask, options.column_count_policy)?;
+    let num_set_columns = present_bitmask.bits_set();
+    let null_bitmask_size = (num_set_columns + 7) >> 3;
+    let mut row = Vec::with_capacity(this_table_map.columns.len());
+    let null_bitmask =
+        BitSet::from_slice(num_set_columns, &read_nbytes(cursor, null_bitmask_size)?)?;
+    let mut null_index = 0;
+    let mut partial_json = PartialJsonColumns::new(partial_json);
+    let projection = options
+        .projection
+        .for_table(&this_table_map.schema_name, &this_table_map.table_name);
+    for (i, column_definition) in this_table_map.columns[..columns].iter().enumerate() {
+        let is_partial = partial_json.is_partial(column_definition)?;
+        if !present_bitmask.try_is_set(i)? {
+            row.push(None);
+            continue;
+        }
+        let is_null = null_bitmask.try_is_set(null_index)?;
+        if projection.is_some_and(|p| !is_projected(p, this_table_map, i)) {
+            if !is_null {
+                skip_column_value(cursor, column_definition, is_partial)?;
+            }
+            row.push(None);
+            null_index += 1;
+            continue;
+        }
+        let val = if is_null {
+            MySQLValueRef::Owned(MySQLValue::Null)
+        } else if is_partial {
+            let diffs = read_json_diffs(cursor)?;
+            match before.and_then(|b| b.get(i)) {
+                Some(Some(MySQLValueRef::Json(document))) => {
+                    let document = crate::jsonb::parse(document.to_vec())?;
+                    MySQLValueRef::Owned(MySQLValue::Json(apply_json_diffs(&document, &diffs)?))
+                }
+                _ => MySQLValueRef::Owned(MySQLValue::JsonDiff(diffs)),
+            }
+        } else if decoders.is_some_and(|d| d.get(i).is_some_and(Option::is_some)) {
+            check_value_size(cursor, column_definition, options.max_value_size)?;
+            MySQLValueRef::Owned(read_column_value(
+                cursor,
+                this_table_map,
+                i,
+                None,
+                decoders,
+            )?)
+        } else {
+            check_value_size(cursor, column_definition, options.max_value_size)?;
+            let charset = this_table_map.descriptors.get(i).and_then(|d| d.charset);
+            match (column_definition, charset) {
+                (ColumnType::VarChar(max_len), Some(collation)) => {
+                    let length_bytes = if *max_len > 255 { 2 } else { 1 };
+                    charset::decode_text_ref(take_length_prefixed(cursor, length_bytes)?, collation)
+                }
+                (ColumnType::VarChar(max_len), None) => {
+                    let length_bytes = if *max_len > 255 { 2 } else { 1 };
+                    let bytes = take_length_prefixed(cursor, length_bytes)?;
+                    match std::str::from_utf8(bytes) {
+                        Ok(s) => MySQLValueRef::String(s),
+                        Err(_) => MySQLValueRef::Owned(MySQLValue::String(
+                            String::from_utf8_lossy(bytes).into_owned(),
+                        )),
+                    }
+                }
+                (ColumnType::Blob(length_bytes), _) => {
+                    MySQLValueRef::Blob(take_length_prefixed(cursor, *length_bytes)?)
+                }
+                (ColumnType::Json(size), _) => {
+                    MySQLValueRef::Json(take_length_prefixed(cursor, *size)?)
+                }
+                _ => {
+                    MySQLValueRef::Owned(read_column_value(cursor, this_table_map, i, None, None)?)
+                }
+            }
+        };
+        row.push(Some(val));
+        null_index += 1;
+    }
+    check_extra_columns(this_table_map, present_bitmask, &null_bitmask, null_index)?;
+    if options.column_count_policy == ColumnCountPolicy::PadNull {
+        row.resize_with(this_table_map.columns.len(), || {
+            Some(MySQLValueRef::Owned(MySQLValue::Null))
+        });
+    }
+    Ok(row)
+}
+
+/// A row changed by a rows event.
+///
+/// Serialized untagged, so an inserted and a deleted row look the same; a `{"cols": ..}` object
+/// deserializes as a `NewRow`, but a [`BinlogEvent`](crate::BinlogEvent) restores `DeletedRow`s
+/// from its `type_code`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RowEvent {
+    NewRow {
+        cols: RowData,
+    },
+    DeletedRow {
+        cols: RowData,
+    },
+    UpdatedRow {
+        before_cols: RowData,
+        after_cols: RowData,
+    },
+}
+
+impl RowEvent {
+    /// The row's values: the inserted or deleted row, or the row after an update
+    pub fn cols(&self) -> &RowData {
+        match self {
+            RowEvent::NewRow { cols } | RowEvent::DeletedRow { cols } => cols,
+            RowEvent::UpdatedRow { after_cols, .. } => after_cols,
+        }
+    }
+
+    /// The row before the change: the deleted row, or the row before an update
+    pub fn before_cols(&self) -> Option<&RowData> {
+        match self {
+            RowEvent::NewRow { .. } => None,
+            RowEvent::DeletedRow { cols } => Some(cols),
+            RowEvent::UpdatedRow { before_cols, .. } => Some(before_cols),
+        }
+    }
+
+    /// The row after the change: the inserted row, or the row after an update
+    pub fn after_cols(&self) -> Option<&RowData> {
+        match self {
+            RowEvent::NewRow { cols } => Some(cols),
+            RowEvent::DeletedRow { .. } => None,
+            RowEvent::UpdatedRow { after_cols, .. } => Some(after_cols),
+        }
+    }
+
+    /// Column `index` of the before-image; [`NotPresent`](ColumnValue::NotPresent) for an
+    /// inserted row
+    pub fn before_value(&self, index: usize) -> ColumnValue<'_> {
+        ColumnValue::from_image(self.before_cols(), index)
+    }
+
+    /// Column `index` of the after-image; [`NotPresent`](ColumnValue::NotPresent) for a deleted
+    /// row
+    pub fn after_value(&self, index: usize) -> ColumnValue<'_> {
+        ColumnValue::from_image(self.after_cols(), index)
+    }
+
+    /// The columns of an update whose value changed, in column order; empty for other rows.
+    ///
+    /// A column counts as changed if it is in the after-image and either differs from the
+    /// before-image or is missing from it (as with `binlog_row_image=MINIMAL`, which only logs
+    /// the primary key before the update and the changed columns after it).
+    pub fn changed_columns(&self) -> Vec<ChangedColumn<'_>> {
+        let (before_cols, after_cols) = match self {
+            RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            } => (before_cols, after_cols),
+            _ => return Vec::new(),
+        };
+        after_cols
+            .iter()
+            .enumerate()
+            .filter_map(|(index, after)| {
+                let after = after.as_ref()?;
+                let before = before_cols.get(index).and_then(Option::as_ref);
+                if before == Some(after) {
+                    return None;
+                }
+                Some(ChangedColumn {
+                    index,
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+
+    /// Drop every column of an update which didn't change from both images, except that the
+    /// before-image keeps the columns for which `is_key` is true
+    pub(crate) fn retain_changed_columns<F: Fn(usize) -> bool>(&mut self, is_key: F) {
+        if let RowEvent::UpdatedRow {
+            before_cols,
+            after_cols,
+        } = self
+        {
+            for (index, (before, after)) in before_cols.iter_mut().zip(after_cols).enumerate() {
+                if before.is_some() && before == after {
+                    *after = None;
+                    if !is_key(index) {
+                        *before = None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The change which undoes this one: an inserted row is deleted, a deleted row is inserted,
+    /// and an update's before and after images are swapped
+    pub fn invert(self) -> RowEvent {
+        match self {
+            RowEvent::NewRow { cols } => RowEvent::DeletedRow { cols },
+            RowEvent::DeletedRow { cols } => RowEvent::NewRow { cols },
+            RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            } => RowEvent::UpdatedRow {
+                before_cols: after_cols,
+                after_cols: before_cols,
+            },
+        }
+    }
+}
+
+/// A column whose value was changed by an update; see [`RowEvent::changed_columns`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChangedColumn<'a> {
+    pub index: usize,
+    /// None if the column wasn't in the before-image
+    pub before: Option<&'a MySQLValue>,
+    pub after: &'a MySQLValue,
+}
+
+/// A column of a row image, telling a column which wasn't logged apart from one which is NULL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnValue<'a> {
+    /// The column isn't in the image: with `binlog_row_image=MINIMAL` a before-image only has the
+    /// primary key and an after-image only the columns which were set, and with `NOBLOB`
+    /// unchanged BLOB and TEXT columns are left out
+    NotPresent,
+    Null,
+    Value(&'a MySQLValue),
+}
+
+impl<'a> ColumnValue<'a> {
+    fn from_image(image: Option<&'a RowData>, index: usize) -> Self {
+        match image.and_then(|image| image.get(index)) {
+            None | Some(None) => ColumnValue::NotPresent,
+            Some(Some(MySQLValue::Null)) => ColumnValue::Null,
+            Some(Some(value)) => ColumnValue::Value(value),
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        !matches!(self, ColumnValue::NotPresent)
+    }
+
+    /// The value, which is [`MySQLValue::Null`] for a NULL column; None if the column isn't
+    /// present
+    pub fn value(&self) -> Option<&'a MySQLValue> {
+        match *self {
+            ColumnValue::NotPresent => None,
+            ColumnValue::Null => Some(&MySQLValue::Null),
+            ColumnValue::Value(value) => Some(value),
+        }
+    }
+}
+
+/// A row of a rows event decoded by [`Event::rows_ref`]; the borrowed counterpart of
+/// [`RowEvent`]
+#[derive(Debug, Clone)]
+pub enum RowEventRef<'a> {
+    NewRow {
+        cols: RowDataRef<'a>,
+    },
+    DeletedRow {
+        cols: RowDataRef<'a>,
+    },
+    UpdatedRow {
+        before_cols: RowDataRef<'a>,
+        after_cols: RowDataRef<'a>,
+    },
+}
+
+impl RowEventRef<'_> {
+    /// Copy this row into a [`RowEvent`]
+    pub fn to_owned_row(&self) -> Result<RowEvent, ColumnParseError> {
+        fn to_owned(cols: &RowDataRef<'_>) -> Result<RowData, ColumnParseError> {
+            cols.iter()
+                .map(|c| c.as_ref().map(MySQLValueRef::to_owned_value).transpose())
+                .collect()
+        }
+        Ok(match self {
+            RowEventRef::NewRow { cols } => RowEvent::NewRow {
+                cols: to_owned(cols)?,
+            },
+            RowEventRef::DeletedRow { cols } => RowEvent::DeletedRow {
+                cols: to_owned(cols)?,
+            },
+            RowEventRef::UpdatedRow {
+                before_cols,
+                after_cols,
+            } => RowEvent::UpdatedRow {
+                before_cols: to_owned(before_cols)?,
+                after_cols: to_owned(after_cols)?,
+            },
+        })
+    }
+}
+
+/// A rows event whose values borrow from the event's buffer; see [`Event::rows_ref`]
+#[derive(Debug, Clone)]
+pub struct EventRef<'a> {
+    pub type_code: TypeCode,
+    pub table_id: u64,
+    /// Empty if the table was not present in the table map
+    pub rows: Vec<RowEventRef<'a>>,
+}
+
+struct RowsEventHeader {
+    table_id: u64,
+    before_column_bitmask: BitSet,
+    after_column_bitmask: Option<BitSet>,
+}
+
+fn parse_rows_event_header<R: Read + Seek>(
+    type_code: TypeCode,
+    mut cursor: &mut R,
+) -> Result<RowsEventHeader, ColumnParseError> {
+    let mut table_id_buf = [0u8; 8];
+    cursor.read_exact(&mut table_id_buf[0..6])?;
+    let table_id = LittleEndian::read_u64(&table_id_buf);
+    // two-byte reserved value
+    cursor.seek(io::SeekFrom::Current(2))?;
+    match type_code {
+        TypeCode::WriteRowsEventV2
+        | TypeCode::UpdateRowsEventV2
+        | TypeCode::DeleteRowsEventV2
+        | TypeCode::PartialUpdateRowsEvent => {
+            // extra data (e.g. NDB or partition info); its length includes the length itself
+            let extra_data_len = cursor.read_u16::<LittleEndian>()?;
+            skip_nbytes(&mut cursor, usize::from(extra_data_len.saturating_sub(2)))?;
+        }
+        _ => {}
+    }
+    let num_columns = read_variable_length_integer(&mut cursor)? as usize;
+    let bitmask_size = (num_columns + 7) >> 3;
+    let before_column_bitmask =
+        BitSet::from_slice(num_columns, &read_nbytes(&mut cursor, bitmask_size)?)?;
+    let after_column_bitmask = match type_code {
+        TypeCode::UpdateRowsEventV1
+        | TypeCode::UpdateRowsEventV2
+        | TypeCode::PartialUpdateRowsEvent => Some(BitSet::from_slice(
+            num_columns,
+            &read_nbytes(&mut cursor, bitmask_size)?,
+        )?),
+        _ => None,
+    };
+    Ok(RowsEventHeader {
+        table_id,
+        before_column_bitmask,
+        after_column_bitmask,
+    })
+}
+
+/// Read the next row of a rows event
+fn read_row<R: Read + Seek>(
+    type_code: TypeCode,
+    mut cursor: &mut R,
+    this_table_map: &SingleTableMap,
+    header: &RowsEventHeader,
+    decoders: Option<&ColumnDecoders>,
+    options: &DecodeOptions,
+) -> Result<RowEvent, EventParseError> {
+    match type_code {
+        TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => Ok(RowEvent::NewRow {
+            cols: parse_one_row(
+                &mut cursor,
+                this_table_map,
+                &header.before_column_bitmask,
+                None,
+                None,
+                decoders,
+                options,
+            )?,
+        }),
+        TypeCode::UpdateRowsEventV1
+        | TypeCode::UpdateRowsEventV2
+        | TypeCode::PartialUpdateRowsEvent => {
+            let before_cols = parse_one_row(
+                &mut cursor,
+                this_table_map,
+                &header.before_column_bitmask,
+                None,
+                None,
+                decoders,
+                options,
+            )?;
+            let partial_json = if type_code == TypeCode::PartialUpdateRowsEvent {
+                read_partial_json_bitmap(&mut cursor, this_table_map)?
+            } else {
+                None
+            };
+            let after_cols = parse_one_row(
+                &mut cursor,
+                this_table_map,
+                header.after_column_bitmask.as_ref().unwrap(),
+                partial_json.as_ref(),
+                Some(&before_cols),
+                decoders,
+                options,
+            )?;
+            Ok(RowEvent::UpdatedRow {
+                before_cols,
+                after_cols,
+            })
+        }
+        TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => Ok(RowEvent::DeletedRow {
+            cols: parse_one_row(
+                &mut cursor,
+                this_table_map,
+                &header.before_column_bitmask,
+                None,
+                None,
+                decoders,
+                options,
+            )?,
+        }),
+        other => Err(EventParseError::NotRowsEvent(other)),
+    }
+}
+
+/// Read the next row of a rows event, borrowing from the buffer
+fn read_row_ref<'a>(
+    type_code: TypeCode,
+    cursor: &mut Cursor<&'a [u8]>,
+    this_table_map: &SingleTableMap,
+    header: &RowsEventHeader,
+    decoders: Option<&ColumnDecoders>,
+    options: &DecodeOptions,
+) -> Result<RowEventRef<'a>, EventParseError> {
+    let before_cols = parse_one_row_ref(
+        cursor,
+        this_table_map,
+        &header.before_column_bitmask,
+        None,
+        None,
+        decoders,
+        options,
+    )?;
+    match type_code {
+        TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
+            Ok(RowEventRef::NewRow { cols: before_cols })
+        }
+        TypeCode::UpdateRowsEventV1
+        | TypeCode::UpdateRowsEventV2
+        | TypeCode::PartialUpdateRowsEvent => {
+            let partial_json = if type_code == TypeCode::PartialUpdateRowsEvent {
+                read_partial_json_bitmap(cursor, this_table_map)?
+            } else {
+                None
+            };
+            let after_cols = parse_one_row_ref(
+                cursor,
+                this_table_map,
+                header.after_column_bitmask.as_ref().unwrap(),
+                partial_json.as_ref(),
+                Some(&before_cols),
+                decoders,
+                options,
+            )?;
+            Ok(RowEventRef::UpdatedRow {
+                before_cols,
+                after_cols,
+            })
+        }
+        TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
+            Ok(RowEventRef::DeletedRow { cols: before_cols })
+        }
+        other => Err(EventParseError::NotRowsEvent(other)),
+    }
+}
+
+fn parse_rows_event_ref<'a>(
+    type_code: TypeCode,
+    data: &'a [u8],
+    this_table_map: Option<&SingleTableMap>,
+    options: &DecodeOptions,
+) -> Result<EventRef<'a>, EventParseError> {
+    let mut cursor = Cursor::new(data);
+    let header = parse_rows_event_header(type_code, &mut cursor)?;
+    let mut rows = Vec::with_capacity(1);
+    if let Some(this_table_map) = this_table_map {
+        let decoders = options
+            .value_decoders
+            .for_table(header.table_id, this_table_map);
+        while (cursor.position() as usize) < data.len() {
+            let row_start = cursor.position();
+            rows.push(read_row_ref(
+                type_code,
+                &mut cursor,
+                this_table_map,
+                &header,
+                decoders.as_deref(),
+                options,
+            )?);
+            check_row_progress(row_start, cursor.position())?;
+        }
+    }
+    Ok(EventRef {
+        type_code,
+        table_id: header.table_id,
+        rows,
+    })
+}
+
+fn parse_rows_event<R: Read + Seek>(
+    type_code: TypeCode,
+    data_len: usize,
+    mut cursor: &mut R,
+    table_map: Option<&TableMap>,
+    options: &DecodeOptions,
+) -> Result<RowsEvent, EventParseError> {
+    let header = parse_rows_event_header(type_code, &mut cursor)?;
+    let mut rows = Vec::with_capacity(1);
+    if let Some(this_table_map) = table_map.and_then(|t| t.get(header.table_id)) {
+        let decoders = options
+            .value_decoders
+            .for_table(header.table_id, this_table_map);
+        while (cursor.tell()? as usize) < data_len {
+            let row_start = cursor.tell()?;
+            rows.push(read_row(
+                type_code,
+                &mut cursor,
+                this_table_map,
+                &header,
+                decoders.as_deref(),
+                options,
+            )?);
+            check_row_progress(row_start, cursor.tell()?)?;
+        }
+    }
+    Ok(RowsEvent {
+        table_id: header.table_id,
+        rows,
+    })
+}
+
+/// The undecoded rows of a rows event, with a snapshot of its table map, for decoding one row at
+/// a time (see [`BinlogFileParserBuilder::lazy_rows`](crate::BinlogFileParserBuilder::lazy_rows))
+#[derive(Debug, Clone)]
+pub struct LazyRows {
+    type_code: TypeCode,
+    // shared rather than copied out of the event
+    event: Arc<Event>,
+    table: SingleTableMap,
+    options: Arc<DecodeOptions>,
+}
+
+impl LazyRows {
+    /// Keep the rows of `event` for later, if it is a rows event for a table in `table_map`
+    pub(crate) fn new(
+        event: Arc<Event>,
+        table_map: &TableMap,
+        options: Arc<DecodeOptions>,
+    ) -> Result<Self, Arc<Event>> {
+        let table = match event.rows_table_id().and_then(|id| table_map.get(id)) {
+            Some(table) => table.clone(),
+            None => return Err(event),
+        };
+        Ok(LazyRows {
+            type_code: event.type_code,
+            event,
+            table,
+            options,
+        })
+    }
+
+    pub fn table_id(&self) -> u64 {
+        LittleEndian::read_u48(&self.event.data[..6])
+    }
+
+    /// Size of the undecoded rows, in bytes
+    pub fn len(&self) -> usize {
+        self.event.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.event.data.is_empty()
+    }
+
+    /// Decode all of the rows, borrowing from this buffer; see [`Event::rows_ref`]
+    pub fn rows_ref(&self) -> Result<EventRef<'_>, EventParseError> {
+        parse_rows_event_ref(
+            self.type_code,
+            &self.event.data,
+            Some(&self.table),
+            &self.options,
+        )
+    }
+
+    /// Decode the rows, in order. The iterator ends after the first error.
+    pub fn iter(&self) -> LazyRowsIter<'_> {
+        let mut cursor = Cursor::new(self.event.data.as_slice());
+        let header = parse_rows_event_header(self.type_code, &mut cursor);
+        let decoders = self
+            .options
+            .value_decoders
+            .for_table(self.table_id(), &self.table);
+        LazyRowsIter {
+            rows: self,
+            cursor,
+            header: Some(header),
+            decoders,
+        }
+    }
+}
+
+/// Iterator over the rows of a [`LazyRows`]
+pub struct LazyRowsIter<'a> {
+    rows: &'a LazyRows,
+    cursor: Cursor<&'a [u8]>,
+    // None once iteration has finished
+    header: Option<Result<RowsEventHeader, ColumnParseError>>,
+    decoders: Option<Arc<ColumnDecoders>>,
+}
+
+impl Iterator for LazyRowsIter<'_> {
+    type Item = Result<RowEvent, EventParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = match self.header.as_ref()? {
+            Ok(header) => header,
+            Err(_) => {
+                return self
+                    .header
+                    .take()
+                    .and_then(Result::err)
+                    .map(|e| Err(e.into()))
+            }
+        };
+        if self.cursor.position() as usize >= self.rows.event.data.len() {
+            self.header = None;
+            return None;
+        }
+        let row_start = self.cursor.position();
+        let row = read_row(
+            self.rows.type_code,
+            &mut self.cursor,
+            &self.rows.table,
+            header,
+            self.decoders.as_deref(),
+            &self.rows.options,
+        )
+        .and_then(|row| {
+            check_row_progress(row_start, self.cursor.position())?;
+            Ok(row)
+        });
+        if row.is_err() {
+            self.header = None;
+        }
+        Some(row)
+    }
+}
+
+/// Fail on a row which took up no bytes, which only a corrupt event (one whose present-columns
+/// bitmap is empty) can contain; reading further rows from the same position would never finish
+fn check_row_progress(row_start: u64, row_end: u64) -> Result<(), ColumnParseError> {
+    if row_end == row_start {
+        return Err(
+            io::Error::new(ErrorKind::InvalidData, "rows event contains an empty row").into(),
+        );
+    }
+    Ok(())
+}
+
+fn skip_one_row<R: Read + Seek>(
+    mut cursor: &mut R,
+    this_table_map: &SingleTableMap,
+    present_bitmask: &BitSet,
+    partial_json: Option<&BitSet>,
+    options: &DecodeOptions,
+) -> Result<(), ColumnParseError> {
+    let columns =
+        reconcile_column_count(this_table_map, present_bitmask, options.column_count_policy)?;
+    let num_set_columns = present_bitmask.bits_set();
+    let null_bitmask_size = (num_set_columns + 7) >> 3;
+    let null_bitmask = BitSet::from_slice(
+        num_set_columns,
+        &read_nbytes(&mut cursor, null_bitmask_size)?,
+    )?;
+    let mut null_index = 0;
+    let mut partial_json = PartialJsonColumns::new(partial_json);
+    for (i, column_definition) in this_table_map.columns[..columns].iter().enumerate() {
+        let is_partial = partial_json.is_partial(column_definition)?;
+        if !present_bitmask.try_is_set(i)? {
+            continue;
+        }
+        // a NULL has no value to skip
+        if !null_bitmask.try_is_set(null_index)? {
+            skip_column_value(&mut cursor, column_definition, is_partial)?;
+        }
+        null_index += 1;
+    }
+    check_extra_columns(this_table_map, present_bitmask, &null_bitmask, null_index)
+}
+
+/// Move past a non-NULL value without decoding it
+fn skip_column_value<R: Read + Seek>(
+    mut cursor: &mut R,
+    column_definition: &ColumnType,
+    is_partial: bool,
+) -> Result<(), ColumnParseError> {
+    if is_partial {
+        let diff_length = cursor.read_u32::<LittleEndian>()? as usize;
+        skip_nbytes(&mut cursor, diff_length)?;
+    } else {
+        column_definition.skip_value(&mut cursor)?;
+    }
+    Ok(())
+}
+
+/// Summary of a rows event, produced without decoding any column values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowsSummary {
+    pub table_id: u64,
+    /// Number of rows in the event, or `None` if the table was not present in the table map (in
+    /// which case the row boundaries cannot be determined)
+    pub row_count: Option<usize>,
+    /// Whether `row_count` was [estimated](Event::estimate_rows) rather than counted
+    pub estimated: bool,
+}
+
+fn summarize_rows_event<R: Read + Seek>(
+    type_code: TypeCode,
+    data_len: usize,
+    mut cursor: &mut R,
+    table_map: Option<&TableMap>,
+    options: &DecodeOptions,
+    estimate: bool,
+) -> Result<RowsSummary, ColumnParseError> {
+    let header = parse_rows_event_header(type_code, &mut cursor)?;
+    let this_table_map = match table_map.and_then(|t| t.get(header.table_id)) {
+        Some(m) => m,
+        None => {
+            return Ok(RowsSummary {
+                table_id: header.table_id,
+                row_count: None,
+                estimated: false,
+            })
+        }
+    };
+    let rows_start = cursor.tell()? as usize;
+    let mut row_count = 0;
+    while (cursor.tell()? as usize) < data_len {
+        if estimate && row_count == 1 {
+            // assume the rest of the rows are the same size as the first
+            let position = cursor.tell()? as usize;
+            let row_size = position - rows_start;
+            let remaining = data_len - position;
+            return Ok(RowsSummary {
+                table_id: header.table_id,
+                row_count: Some(1 + (remaining + row_size / 2) / row_size),
+                estimated: true,
+            });
+        }
+        let row_start = cursor.tell()?;
+        skip_one_row(
+            &mut cursor,
+            this_table_map,
+            &header.before_column_bitmask,
+            None,
+            options,
+        )?;
+        if let Some(ref after_column_bitmask) = header.after_column_bitmask {
+            let partial_json = if type_code == TypeCode::PartialUpdateRowsEvent {
+                read_partial_json_bitmap(&mut cursor, this_table_map)?
+            } else {
+                None
+            };
+            skip_one_row(
+                &mut cursor,
+                this_table_map,
+                after_column_bitmask,
+                partial_json.as_ref(),
+                options,
+            )?;
+        }
+        check_row_progress(row_start, cursor.tell()?)?;
+        row_count += 1;
+    }
+    Ok(RowsSummary {
+        table_id: header.table_id,
+        row_count: Some(row_count),
+        estimated: false,
+    })
+}
+
+const LOGICAL_CLOCK_TYPECODE: u8 = 0x02;
+// the only flag defined for GTID events: the transaction may contain statement-based events
+const GTID_FLAG_MAY_HAVE_SBR: u8 = 0x01;
+
+fn check_gtid_flags(flags: u8) -> Result<u8, EventParseError> {
+    if flags & !GTID_FLAG_MAY_HAVE_SBR != 0 {
+        return Err(EventParseError::InvalidGtidFlags(flags));
+    }
+    Ok(flags)
+}
+
+// set in the immediate commit timestamp / server version if a distinct original value follows
+const ORIGINAL_COMMIT_TIMESTAMP_FOLLOWS: u64 = 1 << 55;
+const ORIGINAL_SERVER_VERSION_FOLLOWS: u32 = 1 << 31;
+
+fn read_commit_timestamp<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf[..7])?;
+    Ok(LittleEndian::read_u64(&buf))
+}
+
+/// Read the fields MySQL 8.0 appends to a GTID event after the logical clock. Each group was
+/// added in a different release, so stop at whichever one the event ends before.
+fn read_gtid_commit_info(cursor: &mut Cursor<&[u8]>) -> Result<GtidCommitInfo, EventParseError> {
+    let mut info = GtidCommitInfo::default();
+    let at_end = |c: &Cursor<&[u8]>| c.position() >= c.get_ref().len() as u64;
+    if at_end(cursor) {
+        return Ok(info);
+    }
+    let immediate = read_commit_timestamp(cursor)?;
+    let original = if immediate & ORIGINAL_COMMIT_TIMESTAMP_FOLLOWS != 0 {
+        read_commit_timestamp(cursor)?
+    } else {
+        immediate
+    };
+    info.immediate_commit_timestamp = Some(immediate & !ORIGINAL_COMMIT_TIMESTAMP_FOLLOWS);
+    info.original_commit_timestamp = Some(original);
+    if at_end(cursor) {
+        return Ok(info);
+    }
+    info.transaction_length = Some(read_packed_unsigned(cursor)?);
+    if at_end(cursor) {
+        return Ok(info);
+    }
+    let immediate = cursor.read_u32::<LittleEndian>()?;
+    let original = if immediate & ORIGINAL_SERVER_VERSION_FOLLOWS != 0 {
+        cursor.read_u32::<LittleEndian>()?
+    } else {
+        immediate
+    };
+    info.immediate_server_version = Some(immediate & !ORIGINAL_SERVER_VERSION_FOLLOWS);
+    info.original_server_version = Some(original);
+    Ok(info)
+}
+
+/// Parse a tagged GTID event (MySQL 8.3+). Unlike every other event, these are encoded with the
+/// `mysql::serialization` library: a varlen message size and the id of the last field readers
+/// must understand, followed by (varlen field id, value) pairs for each field which is present.
+fn parse_tagged_gtid_event(cursor: &mut Cursor<&[u8]>) -> Result<EventData, EventParseError> {
+    let start = cursor.position();
+    let message_size = read_serialized_unsigned(cursor)?;
+    let last_non_ignorable_field = read_serialized_unsigned(cursor)?;
+    // the size includes the header we just read, so bound the fields by what is left
+    let header_size = cursor.position() - start;
+    let mut r = cursor.take(message_size.saturating_sub(header_size));
+    let mut flags = 0;
+    let mut uuid = Uuid::nil();
+    let mut tag = None;
+    let mut coordinate = 0;
+    let mut last_committed = None;
+    let mut sequence_number = None;
+    let mut commit_info = GtidCommitInfo::default();
+    loop {
+        let field_id = match read_serialized_unsigned(&mut r) {
+            Ok(id) => id,
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        match field_id {
+            0 => {
+                let value = read_serialized_unsigned(&mut r)?;
+                flags = check_gtid_flags(u8::try_from(value).unwrap_or(u8::MAX))?;
+            }
+            1 => {
+                let mut uuid_buf = [0u8; 16];
+                r.read_exact(&mut uuid_buf)?;
+                uuid = Uuid::from_slice(&uuid_buf)?;
+            }
+            2 => coordinate = read_serialized_signed(&mut r)? as u64,
+            3 => {
+                let len = read_serialized_unsigned(&mut r)? as usize;
+                let tag_bytes = read_nbytes(&mut r, len)?;
+                let tag_str = String::from_utf8_lossy(&tag_bytes);
+                // an empty tag is how untagged transactions are written
+                if !tag_str.is_empty() {
+                    tag = Some(tag_str.parse()?);
+                }
+            }
+            4 => last_committed = Some(read_serialized_signed(&mut r)? as u64),
+            5 => sequence_number = Some(read_serialized_signed(&mut r)? as u64),
+            6 => commit_info.immediate_commit_timestamp = Some(read_serialized_unsigned(&mut r)?),
+            7 => commit_info.original_commit_timestamp = Some(read_serialized_unsigned(&mut r)?),
+            8 => commit_info.transaction_length = Some(read_serialized_unsigned(&mut r)?),
+            9 => {
+                let version = read_serialized_unsigned(&mut r)?;
+                commit_info.immediate_server_version = Some(version as u32);
+            }
+            10 => {
+                let version = read_serialized_unsigned(&mut r)?;
+                commit_info.original_server_version = Some(version as u32);
+            }
+            // group replication commit ticket
+            11 => {
+                read_serialized_unsigned(&mut r)?;
+            }
+            // newer fields which we don't know how to decode; we can only skip them if the
+            // server has said it's safe to do so
+            other if other > last_non_ignorable_field => break,
+            other => return Err(EventParseError::UnknownSerializedField(other)),
+        }
+    }
+    // the original values are only written when they differ from the immediate ones
+    if commit_info.original_commit_timestamp.is_none() {
+        commit_info.original_commit_timestamp = commit_info.immediate_commit_timestamp;
+    }
+    if commit_info.original_server_version.is_none() {
+        commit_info.original_server_version = commit_info.immediate_server_version;
+    }
+    Ok(EventData::GtidLogEvent {
+        flags,
+        uuid,
+        tag,
+        coordinate,
+        last_committed,
+        sequence_number,
+        commit_info,
+    })
+}
+
+/// Parse the fields of a HEARTBEAT_LOG_EVENT_V2, each of which is a type byte followed by a
+/// length-prefixed value
+fn parse_heartbeat_v2(cursor: &mut Cursor<&[u8]>) -> Result<EventData, EventParseError> {
+    const END_MARK: u8 = 0;
+    const LOG_FILENAME: u8 = 1;
+    const LOG_POSITION: u8 = 2;
+
+    let mut log_name = String::new();
+    let mut position = 0;
+    while (cursor.position() as usize) < cursor.get_ref().len() {
+        let field = cursor.read_u8()?;
+        if field == END_MARK {
+            break;
+        }
+        let length = read_packed_unsigned(cursor)? as usize;
+        let value = read_nbytes(cursor, length)?;
+        match field {
+            LOG_FILENAME => log_name = String::from_utf8_lossy(&value).into_owned(),
+            LOG_POSITION => position = read_packed_unsigned(&mut value.as_slice())?,
+            // fields added by later versions
+            _ => {}
+        }
+    }
+    Ok(EventData::HeartbeatEvent { log_name, position })
 }
 
 impl EventData {
@@ -306,32 +2078,37 @@ impl EventData {
         type_code: TypeCode,
         data: &[u8],
         table_map: Option<&TableMap>,
+        options: &DecodeOptions,
     ) -> Result<Option<Self>, EventParseError> {
         let mut cursor = Cursor::new(data);
         match type_code {
             TypeCode::FormatDescriptionEvent => {
                 let binlog_version = cursor.read_u16::<LittleEndian>()?;
                 if binlog_version != 4 {
-                    unimplemented!("can only parse a version 4 binary log");
+                    return Err(EventParseError::UnsupportedBinlogVersion(binlog_version));
                 }
                 let mut server_version_buf = [0u8; 50];
                 cursor.read_exact(&mut server_version_buf)?;
-                let server_version = ::std::str::from_utf8(
+                let server_version = String::from_utf8_lossy(
                     server_version_buf
                         .split(|c| *c == 0x00)
                         .next()
                         .unwrap_or(&[]),
                 )
-                .unwrap()
-                .to_owned();
+                .into_owned();
                 let create_timestamp = cursor.read_u32::<LittleEndian>()?;
                 let common_header_len = cursor.read_u8()?;
-                let event_types = data.len() - 2 - 50 - 4 - 1 - 5;
-                let mut event_sizes_tables = vec![0u8; event_types];
-                cursor.read_exact(&mut event_sizes_tables)?;
-                let checksum_algo = ChecksumAlgorithm::from(cursor.read_u8()?);
-                let mut checksum_buf = [0u8; 4];
-                cursor.read_exact(&mut checksum_buf)?;
+                // the rest of the event is a table of post-header lengths, followed by the
+                // checksum algorithm if the server supports checksums
+                let checksum_algo = if is_checksum_aware(&server_version) {
+                    match data.last() {
+                        Some(&CHECKSUM_ALG_UNDEF) => ChecksumAlgorithm::None,
+                        Some(byte) => ChecksumAlgorithm::from(*byte),
+                        None => return Err(EventParseError::EofError),
+                    }
+                } else {
+                    ChecksumAlgorithm::None
+                };
                 Ok(Some(EventData::FormatDescriptionEvent {
                     binlog_version,
                     server_version,
@@ -340,34 +2117,51 @@ impl EventData {
                     checksum_algorithm: checksum_algo,
                 }))
             }
-            TypeCode::GtidLogEvent => {
-                let flags = cursor.read_u8()?;
+            TypeCode::GtidLogEvent | TypeCode::AnonymousGtidLogEvent => {
+                let flags = check_gtid_flags(cursor.read_u8()?)?;
                 let mut uuid_buf = [0u8; 16];
                 cursor.read_exact(&mut uuid_buf)?;
                 let uuid = Uuid::from_slice(&uuid_buf)?;
                 let offset = cursor.read_u64::<LittleEndian>()?;
+                // 5.6 servers stop here; 5.7+ servers always write a logical clock
                 let (last_committed, sequence_number) = match cursor.read_u8() {
-                    Ok(0x02) => {
+                    Ok(LOGICAL_CLOCK_TYPECODE) => {
                         let last_committed = cursor.read_u64::<LittleEndian>()?;
                         let sequence_number = cursor.read_u64::<LittleEndian>()?;
                         (Some(last_committed), Some(sequence_number))
                     }
-                    _ => (None, None),
+                    Ok(other) => return Err(EventParseError::InvalidLogicalClockType(other)),
+                    Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => (None, None),
+                    Err(e) => return Err(e.into()),
                 };
+                let commit_info = read_gtid_commit_info(&mut cursor)?;
+                if type_code == TypeCode::AnonymousGtidLogEvent {
+                    // the UUID and sequence number are zeroes
+                    return Ok(Some(EventData::AnonymousGtidLogEvent {
+                        flags,
+                        last_committed,
+                        sequence_number,
+                        commit_info,
+                    }));
+                }
                 Ok(Some(EventData::GtidLogEvent {
                     flags,
                     uuid,
+                    tag: None,
                     coordinate: offset,
                     last_committed,
                     sequence_number,
+                    commit_info,
                 }))
             }
+            TypeCode::GtidTaggedLogEvent => parse_tagged_gtid_event(&mut cursor).map(Some),
             TypeCode::QueryEvent => {
                 let thread_id = cursor.read_u32::<LittleEndian>()?;
                 let execution_time = cursor.read_u32::<LittleEndian>()?;
                 let schema_len = cursor.read_u8()?;
                 let error_code = cursor.read_i16::<LittleEndian>()?;
-                let _status_vars = read_two_byte_length_prefixed_bytes(&mut cursor)?;
+                let status_vars =
+                    QueryStatusVars::parse(&read_two_byte_length_prefixed_bytes(&mut cursor)?)?;
                 let schema =
                     String::from_utf8_lossy(&read_nbytes(&mut cursor, schema_len)?).into_owned();
                 cursor.seek(io::SeekFrom::Current(1))?;
@@ -379,8 +2173,58 @@ impl EventData {
                     error_code,
                     schema,
                     query: statement,
+                    status_vars,
+                }))
+            }
+            TypeCode::RotateEvent => {
+                let position = cursor.read_u64::<LittleEndian>()?;
+                let mut next_log_name = String::new();
+                cursor.read_to_string(&mut next_log_name)?;
+                Ok(Some(EventData::RotateEvent {
+                    position,
+                    next_log_name,
+                }))
+            }
+            TypeCode::IntvarEvent => Ok(Some(EventData::IntvarEvent {
+                kind: IntvarKind::from(cursor.read_u8()?),
+                value: cursor.read_u64::<LittleEndian>()?,
+            })),
+            TypeCode::RandEvent => Ok(Some(EventData::RandEvent {
+                seed1: cursor.read_u64::<LittleEndian>()?,
+                seed2: cursor.read_u64::<LittleEndian>()?,
+            })),
+            TypeCode::UserVarEvent => parse_user_var(&mut cursor).map(Some),
+            TypeCode::IncidentEvent => {
+                let incident_type = IncidentType::from(cursor.read_u16::<LittleEndian>()?);
+                // the message is optional
+                let message = if (cursor.position() as usize) < data.len() {
+                    read_one_byte_length_prefixed_string(&mut cursor)?
+                } else {
+                    String::new()
+                };
+                Ok(Some(EventData::IncidentEvent {
+                    incident_type,
+                    message,
                 }))
             }
+            TypeCode::HeartbeatLogEventV2 => parse_heartbeat_v2(&mut cursor).map(Some),
+            TypeCode::StartEncryptionEvent => {
+                let scheme = cursor.read_u8()?;
+                let key_version = cursor.read_u32::<LittleEndian>()?;
+                let mut nonce = [0u8; 12];
+                cursor.read_exact(&mut nonce)?;
+                Ok(Some(EventData::StartEncryptionEvent {
+                    scheme,
+                    key_version,
+                    nonce,
+                }))
+            }
+            TypeCode::PreviousGtidsLogEvent => Ok(Some(EventData::PreviousGtidsEvent {
+                gtids: GtidSet::read_from(&mut cursor)?,
+            })),
+            TypeCode::XidEvent => Ok(Some(EventData::XidEvent {
+                xid: cursor.read_u64::<LittleEndian>()?,
+            })),
             TypeCode::TableMapEvent => {
                 let mut table_id_buf = [0u8; 8];
                 cursor.read_exact(&mut table_id_buf[0..6])?;
@@ -395,9 +2239,9 @@ impl EventData {
                 cursor.seek(io::SeekFrom::Current(1))?;
                 //println!("parsing table map for {}.{}", schema_name, table_name);
                 let column_count = read_variable_length_integer(&mut cursor)? as usize;
-                let mut columns = Vec::with_capacity(column_count);
+                let mut columns = Vec::with_capacity(column_count.min(data.len()));
                 for _ in 0..column_count {
-                    let column_type = ColumnType::from_byte(cursor.read_u8()?);
+                    let column_type = ColumnType::from_byte(cursor.read_u8()?)?;
                     columns.push(column_type);
                 }
                 //let pos = cursor.tell()? as usize;
@@ -413,85 +2257,687 @@ impl EventData {
                 let num_columns = final_columns.len();
                 let null_bitmask_size = (num_columns + 7) >> 3;
                 let null_bitmap_source = read_nbytes(&mut cursor, null_bitmask_size)?;
-                let nullable_bitmap = BitSet::from_slice(num_columns, &null_bitmap_source).unwrap();
+                let nullable_bitmap = BitSet::from_slice(num_columns, &null_bitmap_source)
+                    .map_err(ColumnParseError::from)?;
+                let mut descriptors = (0..num_columns)
+                    .map(|i| ColumnDescriptor {
+                        nullable: nullable_bitmap.is_set(i),
+                        ..ColumnDescriptor::default()
+                    })
+                    .collect::<Vec<_>>();
+                read_optional_metadata(&mut cursor, &final_columns, &mut descriptors)?;
                 Ok(Some(EventData::TableMapEvent {
                     table_id,
                     schema_name,
                     table_name,
                     columns: final_columns,
                     null_bitmap: nullable_bitmap,
+                    descriptors,
                 }))
             }
             TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map, options)?;
                 Ok(Some(EventData::WriteRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
-            TypeCode::UpdateRowsEventV1 | TypeCode::UpdateRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+            TypeCode::UpdateRowsEventV1
+            | TypeCode::UpdateRowsEventV2
+            | TypeCode::PartialUpdateRowsEvent => {
+                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map, options)?;
                 Ok(Some(EventData::UpdateRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
             TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
-                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map)?;
+                let ev = parse_rows_event(type_code, data.len(), &mut cursor, table_map, options)?;
                 Ok(Some(EventData::DeleteRowsEvent {
                     table_id: ev.table_id,
                     rows: ev.rows,
                 }))
             }
+            TypeCode::XaPrepareLogEvent => {
+                let one_phase = cursor.read_u8()? != 0;
+                let format_id = cursor.read_i32::<LittleEndian>()?;
+                let gtrid_length = cursor.read_u32::<LittleEndian>()? as usize;
+                let bqual_length = cursor.read_u32::<LittleEndian>()? as usize;
+                let gtrid = read_nbytes(&mut cursor, gtrid_length)?;
+                let bqual = read_nbytes(&mut cursor, bqual_length)?;
+                Ok(Some(EventData::XaPrepareEvent {
+                    one_phase,
+                    format_id,
+                    gtrid,
+                    bqual,
+                }))
+            }
+            TypeCode::ViewChangeEvent => {
+                let view_id = read_nbytes(&mut cursor, VIEW_ID_LENGTH)?;
+                let view_id =
+                    String::from_utf8_lossy(view_id.split(|c| *c == 0x00).next().unwrap_or(&[]))
+                        .into_owned();
+                let seq_number = cursor.read_u64::<LittleEndian>()?;
+                Ok(Some(EventData::ViewChangeEvent {
+                    view_id,
+                    seq_number,
+                }))
+            }
+            TypeCode::TransactionPayloadEvent => {
+                parse_transaction_payload(&mut cursor, options).map(Some)
+            }
             _ => Ok(None),
         }
     }
 }
 
-pub struct Event {
-    timestamp: u32,
-    type_code: TypeCode,
-    server_id: u32,
-    event_length: u32,
-    next_position: u32,
-    flags: u16,
-    data: Vec<u8>,
-    offset: u64,
+// field types in the optional metadata of a TABLE_MAP_EVENT
+const METADATA_SIGNEDNESS: u8 = 1;
+const METADATA_DEFAULT_CHARSET: u8 = 2;
+const METADATA_COLUMN_CHARSET: u8 = 3;
+const METADATA_COLUMN_NAME: u8 = 4;
+const METADATA_SET_STR_VALUE: u8 = 5;
+const METADATA_ENUM_STR_VALUE: u8 = 6;
+const METADATA_GEOMETRY_TYPE: u8 = 7;
+const METADATA_SIMPLE_PRIMARY_KEY: u8 = 8;
+const METADATA_PRIMARY_KEY_WITH_PREFIX: u8 = 9;
+const METADATA_ENUM_AND_SET_DEFAULT_CHARSET: u8 = 10;
+const METADATA_ENUM_AND_SET_COLUMN_CHARSET: u8 = 11;
+const METADATA_COLUMN_VISIBILITY: u8 = 12;
+
+fn is_numeric_column(column: &ColumnType) -> bool {
+    matches!(
+        column,
+        ColumnType::Decimal
+            | ColumnType::Tiny
+            | ColumnType::Short
+            | ColumnType::Int24
+            | ColumnType::Long
+            | ColumnType::LongLong
+            | ColumnType::Float(_)
+            | ColumnType::Double(_)
+            | ColumnType::NewDecimal(..)
+    )
+}
+
+fn is_character_column(column: &ColumnType) -> bool {
+    matches!(
+        column,
+        ColumnType::VarChar(_)
+            | ColumnType::VarString
+            | ColumnType::MyString
+            | ColumnType::TinyBlob
+            | ColumnType::MediumBlob
+            | ColumnType::LongBlob
+            | ColumnType::Blob(_)
+    )
+}
+
+fn is_enum_or_set_column(column: &ColumnType) -> bool {
+    matches!(column, ColumnType::Enum(_) | ColumnType::Set(_))
+}
+
+/// Several optional metadata fields only describe some of the columns (e.g. only the numeric
+/// ones), in table order; this gives the indices of the columns such a field describes
+fn column_indices(columns: &[ColumnType], filter: fn(&ColumnType) -> bool) -> Vec<usize> {
+    (0..columns.len())
+        .filter(|i| filter(&columns[*i]))
+        .collect()
+}
+
+/// Bitmaps in the optional metadata are stored most significant bit first
+fn read_metadata_bitmap(value: &[u8], count: usize) -> Vec<bool> {
+    (0..count)
+        .map(|i| {
+            value
+                .get(i / 8)
+                .map(|b| b & (0x80 >> (i % 8)) != 0)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+fn read_metadata_string(value: &mut &[u8]) -> io::Result<String> {
+    let len = read_packed_unsigned(value)? as usize;
+    Ok(String::from_utf8_lossy(&read_nbytes(value, len)?).into_owned())
+}
+
+/// Read the optional metadata which MySQL 8.0 appends to a TABLE_MAP_EVENT: a sequence of
+/// (type, packed length, value) fields. Unknown fields are skipped.
+fn read_optional_metadata(
+    cursor: &mut Cursor<&[u8]>,
+    columns: &[ColumnType],
+    descriptors: &mut [ColumnDescriptor],
+) -> Result<(), EventParseError> {
+    while cursor.position() < cursor.get_ref().len() as u64 {
+        let field_type = cursor.read_u8()?;
+        let length = read_packed_unsigned(cursor)? as usize;
+        let value = read_nbytes(&mut *cursor, length)?;
+        let mut value = value.as_slice();
+        match field_type {
+            METADATA_SIGNEDNESS => {
+                let numeric = column_indices(columns, is_numeric_column);
+                let unsigned = read_metadata_bitmap(value, numeric.len());
+                for (i, unsigned) in numeric.into_iter().zip(unsigned) {
+                    descriptors[i].unsigned = Some(unsigned);
+                }
+            }
+            METADATA_DEFAULT_CHARSET | METADATA_ENUM_AND_SET_DEFAULT_CHARSET => {
+                let described = if field_type == METADATA_DEFAULT_CHARSET {
+                    column_indices(columns, is_character_column)
+                } else {
+                    column_indices(columns, is_enum_or_set_column)
+                };
+                let default = read_packed_unsigned(&mut value)?;
+                for i in described.iter() {
+                    descriptors[*i].charset = Some(default);
+                }
+                // followed by (index among the described columns, charset) for the exceptions
+                while !value.is_empty() {
+                    let index = read_packed_unsigned(&mut value)? as usize;
+                    let charset = read_packed_unsigned(&mut value)?;
+                    if let Some(i) = described.get(index) {
+                        descriptors[*i].charset = Some(charset);
+                    }
+                }
+            }
+            METADATA_COLUMN_CHARSET | METADATA_ENUM_AND_SET_COLUMN_CHARSET => {
+                let described = if field_type == METADATA_COLUMN_CHARSET {
+                    column_indices(columns, is_character_column)
+                } else {
+                    column_indices(columns, is_enum_or_set_column)
+                };
+                for i in described {
+                    descriptors[i].charset = Some(read_packed_unsigned(&mut value)?);
+                }
+            }
+            METADATA_COLUMN_NAME => {
+                for descriptor in descriptors.iter_mut() {
+                    descriptor.name = Some(read_metadata_string(&mut value)?);
+                }
+            }
+            METADATA_SET_STR_VALUE | METADATA_ENUM_STR_VALUE => {
+                let wanted = if field_type == METADATA_SET_STR_VALUE {
+                    |c: &ColumnType| matches!(c, ColumnType::Set(_))
+                } else {
+                    |c: &ColumnType| matches!(c, ColumnType::Enum(_))
+                };
+                for i in column_indices(columns, wanted) {
+                    let count = read_packed_unsigned(&mut value)?;
+                    let values = (0..count)
+                        .map(|_| read_metadata_string(&mut value))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    descriptors[i].values = Some(values);
+                }
+            }
+            METADATA_GEOMETRY_TYPE => {
+                for i in column_indices(columns, |c| matches!(c, ColumnType::Geometry(_))) {
+                    descriptors[i].geometry_type = Some(read_packed_unsigned(&mut value)?);
+                }
+            }
+            METADATA_SIMPLE_PRIMARY_KEY | METADATA_PRIMARY_KEY_WITH_PREFIX => {
+                while !value.is_empty() {
+                    let index = read_packed_unsigned(&mut value)? as usize;
+                    let prefix = if field_type == METADATA_PRIMARY_KEY_WITH_PREFIX {
+                        read_packed_unsigned(&mut value)?
+                    } else {
+                        0
+                    };
+                    if let Some(descriptor) = descriptors.get_mut(index) {
+                        descriptor.primary_key = true;
+                        // a prefix length of zero means the whole column
+                        descriptor.primary_key_prefix = Some(prefix).filter(|p| *p > 0);
+                    }
+                }
+            }
+            METADATA_COLUMN_VISIBILITY => {
+                let visible = read_metadata_bitmap(value, descriptors.len());
+                for (descriptor, visible) in descriptors.iter_mut().zip(visible) {
+                    descriptor.visible = Some(visible);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// the type of a user variable's value, from `Item_result`
+const USER_VAR_STRING: u8 = 0;
+const USER_VAR_REAL: u8 = 1;
+const USER_VAR_INT: u8 = 2;
+const USER_VAR_DECIMAL: u8 = 4;
+
+const USER_VAR_UNSIGNED_FLAG: u8 = 0x01;
+
+// the binary character set, for strings which are really byte strings
+const BINARY_CHARSET: u32 = 63;
+
+fn parse_user_var(cursor: &mut Cursor<&[u8]>) -> Result<EventData, EventParseError> {
+    let name_length = cursor.read_u32::<LittleEndian>()?;
+    let name = String::from_utf8_lossy(&read_nbytes(cursor, name_length as usize)?).into_owned();
+    if cursor.read_u8()? != 0 {
+        return Ok(EventData::UserVarEvent {
+            name,
+            value: None,
+            charset: None,
+            unsigned: false,
+        });
+    }
+    let value_type = cursor.read_u8()?;
+    let charset = cursor.read_u32::<LittleEndian>()?;
+    let value_length = cursor.read_u32::<LittleEndian>()?;
+    let value = read_nbytes(cursor, value_length as usize)?;
+    // servers since 5.1 follow the value with flags
+    let unsigned = match cursor.read_u8() {
+        Ok(flags) => flags & USER_VAR_UNSIGNED_FLAG != 0,
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e.into()),
+    };
+    let mut value_cursor = Cursor::new(value.as_slice());
+    let (value, charset) = match value_type {
+        USER_VAR_STRING => {
+            let value = match String::from_utf8(value) {
+                Ok(s) if charset != BINARY_CHARSET => MySQLValue::String(s),
+                Ok(s) => MySQLValue::Blob(s.into_bytes().into()),
+                Err(e) => MySQLValue::Blob(e.into_bytes().into()),
+            };
+            (value, Some(charset))
+        }
+        USER_VAR_REAL => (
+            MySQLValue::Double(value_cursor.read_f64::<LittleEndian>()?),
+            None,
+        ),
+        USER_VAR_INT => (
+            MySQLValue::SignedInteger(value_cursor.read_i64::<LittleEndian>()?),
+            None,
+        ),
+        USER_VAR_DECIMAL => {
+            let precision = value_cursor.read_u8()?;
+            let scale = value_cursor.read_u8()?;
+            if scale > precision {
+                return Err(io::Error::from(ErrorKind::InvalidData).into());
+            }
+            let value = read_new_decimal(&mut value_cursor, precision, scale)
+                .map_err(ColumnParseError::from)?;
+            (MySQLValue::Decimal(value), None)
+        }
+        other => return Err(EventParseError::InvalidUserVarType(other)),
+    };
+    Ok(EventData::UserVarEvent {
+        name,
+        value: Some(value),
+        charset,
+        unsigned,
+    })
+}
+
+// view ids in a VIEW_CHANGE_EVENT are written into a fixed-size, nul-padded field
+const VIEW_ID_LENGTH: usize = 40;
+
+const PAYLOAD_FIELD_END: u64 = 0;
+const PAYLOAD_FIELD_SIZE: u64 = 1;
+const PAYLOAD_FIELD_COMPRESSION_TYPE: u64 = 2;
+const PAYLOAD_FIELD_UNCOMPRESSED_SIZE: u64 = 3;
+
+/// Parse a TRANSACTION_PAYLOAD_EVENT: a header of (type, length, value) fields, all packed
+/// integers, terminated by an end marker and followed by the payload itself. The payload is a
+/// sequence of events which, unlike those in the binlog itself, never carry checksums.
+fn parse_transaction_payload(
+    cursor: &mut Cursor<&[u8]>,
+    options: &DecodeOptions,
+) -> Result<EventData, EventParseError> {
+    let mut payload_size = None;
+    let mut compression_type = PayloadCompression::None;
+    let mut uncompressed_size = None;
+    loop {
+        let field_type = read_packed_unsigned(cursor)?;
+        if field_type == PAYLOAD_FIELD_END {
+            break;
+        }
+        let field_length = read_packed_unsigned(cursor)? as usize;
+        match field_type {
+            PAYLOAD_FIELD_SIZE => payload_size = Some(read_packed_unsigned(cursor)?),
+            PAYLOAD_FIELD_COMPRESSION_TYPE => {
+                compression_type = read_packed_unsigned(cursor)?.into();
+            }
+            PAYLOAD_FIELD_UNCOMPRESSED_SIZE => {
+                uncompressed_size = Some(read_packed_unsigned(cursor)?);
+            }
+            _ => skip_nbytes(cursor, field_length)?,
+        }
+    }
+    let payload = match payload_size {
+        Some(size) => read_nbytes(cursor, size as usize)?,
+        None => {
+            let mut payload = Vec::new();
+            cursor.read_to_end(&mut payload)?;
+            payload
+        }
+    };
+    let payload = match compression_type {
+        PayloadCompression::None => payload,
+        #[cfg(feature = "zstd")]
+        PayloadCompression::Zstd => {
+            let limit = options.max_event_size.map_or(u64::MAX, u64::from);
+            decompress_zstd(&payload, uncompressed_size.unwrap_or(u64::MAX).min(limit))?
+        }
+        #[cfg(not(feature = "zstd"))]
+        PayloadCompression::Zstd => return Err(EventParseError::ZstdNotEnabled),
+        PayloadCompression::Other(other) => {
+            return Err(EventParseError::UnsupportedCompression(other))
+        }
+    };
+    let mut events = Vec::new();
+    let mut r = Cursor::new(payload.as_slice());
+    while r.position() < payload.len() as u64 {
+        let offset = r.position();
+        let format = EventFormat {
+            has_checksum: false,
+            checksum_algorithm: ChecksumAlgorithm::None,
+            ..EventFormat::default()
+        };
+        events.push(Event::read_with_limit(
+            &mut r,
+            offset,
+            &format,
+            options.max_event_size,
+        )?);
+    }
+    Ok(EventData::TransactionPayloadEvent {
+        compression_type,
+        uncompressed_size,
+        events,
+    })
+}
+
+/// Decompress a zstd transaction payload, which may be at most `limit` bytes long
+#[cfg(feature = "zstd")]
+fn decompress_zstd(payload: &[u8], limit: u64) -> Result<Vec<u8>, EventParseError> {
+    let mut decompressed = Vec::with_capacity(limit.min(MAX_PREALLOCATION as u64) as usize);
+    // read one byte past the limit to tell whether there's more
+    zstd::stream::read::Decoder::new(payload)?
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > limit {
+        return Err(EventParseError::PayloadTooLarge { limit });
+    }
+    Ok(decompressed)
+}
+
+/// The fields of an event's common header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventHeader {
+    pub timestamp: u32,
+    pub type_code: TypeCode,
+    pub server_id: u32,
+    /// Size of the event in bytes, including its header
+    pub event_length: u32,
+    pub next_position: u64,
+    pub flags: u16,
+    /// The absolute offset the event was read at
+    pub offset: u64,
+}
+
+/// An event decoded by [`Event::decode`], along with the raw event it was decoded from
+#[derive(Debug)]
+pub struct DecodedEvent {
+    pub header: EventHeader,
+    /// `None` if this type of event isn't decoded
+    pub data: Option<EventData>,
+    pub raw: Event,
+}
+
+pub struct Event {
+    timestamp: u32,
+    type_code: TypeCode,
+    server_id: u32,
+    event_length: u32,
+    next_position: u32,
+    flags: u16,
+    header: Vec<u8>,
+    data: Vec<u8>,
+    checksum: Option<u32>,
+    offset: u64,
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Event {{ timestamp: {:?}, type_code: {:?}, server_id: {:?}, data_len: {:?}, offset: {:?} }}", self.timestamp, self.type_code, self.server_id, self.data.len(), self.offset)
+    }
+}
+
+/// How the events in a binlog are framed, as described by its FormatDescriptionEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventFormat {
+    /// Whether every event (other than the FDE, which has its own rules) ends with a checksum
+    pub has_checksum: bool,
+    /// The algorithm used for those checksums
+    pub checksum_algorithm: ChecksumAlgorithm,
+    /// Length of the header at the start of every event (other than the FDE, whose header is
+    /// always 19 bytes). Any bytes after the first 19 are extra headers which this crate skips.
+    pub common_header_len: u8,
+}
+
+/// Length of the event header in v4 binlogs, and of the FDE header in every binlog
+pub const V4_HEADER_LEN: u8 = 19;
+
+impl Default for EventFormat {
+    fn default() -> Self {
+        EventFormat {
+            has_checksum: true,
+            checksum_algorithm: ChecksumAlgorithm::CRC32,
+            common_header_len: V4_HEADER_LEN,
+        }
+    }
+}
+
+impl EventFormat {
+    /// The format described by a FormatDescriptionEvent's checksum algorithm and header length.
+    /// Unknown checksum algorithms are assumed to occupy the same four bytes as a CRC32.
+    pub fn new(checksum_algorithm: ChecksumAlgorithm, common_header_len: u8) -> Self {
+        EventFormat {
+            has_checksum: checksum_algorithm != ChecksumAlgorithm::None,
+            checksum_algorithm,
+            common_header_len,
+        }
+    }
+}
+
+/// The contents of a FormatDescriptionEvent, which starts every binlog file and describes the
+/// server which wrote it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormatDescription {
+    pub binlog_version: u16,
+    /// The version of the server, as reported by `SELECT VERSION()` (e.g. `8.0.36-log`)
+    pub server_version: String,
+    /// When the file was created (seconds since the epoch); zero in files other than the first
+    /// written after the server started
+    pub create_timestamp: u32,
+    pub common_header_len: u8,
+    pub checksum_algorithm: ChecksumAlgorithm,
+}
+
+impl FormatDescription {
+    /// Decode a FormatDescriptionEvent; `None` for other events
+    pub fn from_event(event: &Event) -> Result<Option<Self>, EventParseError> {
+        Self::from_borrowed(&event.as_borrowed())
+    }
+
+    /// Like [`from_event`](Self::from_event), for a [`BorrowedEvent`]
+    pub fn from_borrowed(event: &BorrowedEvent<'_>) -> Result<Option<Self>, EventParseError> {
+        if event.type_code() != TypeCode::FormatDescriptionEvent {
+            return Ok(None);
+        }
+        Ok(event
+            .inner(None)?
+            .and_then(|data| FormatDescription::from_data(&data)))
+    }
+
+    pub(crate) fn from_data(data: &EventData) -> Option<Self> {
+        match *data {
+            EventData::FormatDescriptionEvent {
+                binlog_version,
+                ref server_version,
+                create_timestamp,
+                common_header_len,
+                checksum_algorithm,
+            } => Some(FormatDescription {
+                binlog_version,
+                server_version: server_version.clone(),
+                create_timestamp,
+                common_header_len,
+                checksum_algorithm,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The parsed [`server_version`](Self::server_version)
+    pub fn version(&self) -> ServerVersion {
+        ServerVersion::parse(&self.server_version)
+    }
+
+    /// How the events after this one are framed
+    pub fn format(&self) -> EventFormat {
+        EventFormat::new(self.checksum_algorithm, self.common_header_len)
+    }
+}
+
+// offset and length of the nul-padded server version in a FormatDescriptionEvent body
+const FDE_SERVER_VERSION_OFFSET: usize = 2;
+const FDE_SERVER_VERSION_LEN: usize = 50;
+
+// the checksum algorithm written into relay logs for FDEs from sources which predate checksums
+const CHECKSUM_ALG_UNDEF: u8 = 0xff;
+
+/// Whether a server writes the checksum algorithm into its FormatDescriptionEvent (5.6.1 and up).
+///
+/// The FDE follows its own checksum rules, since it is what tells a reader whether the events
+/// after it have checksums:
+/// - servers older than 5.6.1 write neither a checksum algorithm nor a checksum;
+/// - newer servers write the algorithm as the last byte of the body, followed by a CRC32 of the
+///   FDE, even if the algorithm is `NONE` (so the FDE carries a checksum which nothing else does);
+/// - an algorithm of `UNDEF` appears in relay logs, for FDEs copied from a source which
+///   predates checksums, and means that the following events have no checksums.
+fn is_checksum_aware(server_version: &str) -> bool {
+    ServerVersion::parse(server_version).supports_checksums()
 }
 
-impl fmt::Debug for Event {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Event {{ timestamp: {:?}, type_code: {:?}, server_id: {:?}, data_len: {:?}, offset: {:?} }}", self.timestamp, self.type_code, self.server_id, self.data.len(), self.offset)
+fn fde_server_version(data: &[u8]) -> &str {
+    let end = data
+        .len()
+        .min(FDE_SERVER_VERSION_OFFSET + FDE_SERVER_VERSION_LEN);
+    let field = data.get(FDE_SERVER_VERSION_OFFSET..end).unwrap_or(&[]);
+    let field = field.split(|c| *c == 0x00).next().unwrap_or(&[]);
+    ::std::str::from_utf8(field).unwrap_or("")
+}
+
+/// The length of an event's header, including any extra headers (which the FDE never has)
+fn event_header_len(type_code: TypeCode, format: &EventFormat) -> u8 {
+    if type_code == TypeCode::FormatDescriptionEvent {
+        V4_HEADER_LEN
+    } else {
+        format.common_header_len.max(V4_HEADER_LEN)
+    }
+}
+
+/// Split the checksum, if the event has one, off the end of its body
+fn split_checksum<'a>(
+    type_code: TypeCode,
+    body: &'a [u8],
+    format: &EventFormat,
+) -> Result<(&'a [u8], Option<u32>), EventParseError> {
+    let has_checksum = if type_code == TypeCode::FormatDescriptionEvent {
+        is_checksum_aware(fde_server_version(body))
+    } else {
+        format.has_checksum
+    };
+    if !has_checksum {
+        return Ok((body, None));
+    }
+    if body.len() < 4 {
+        return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
     }
+    let (data, checksum) = body.split_at(body.len() - 4);
+    Ok((data, Some(LittleEndian::read_u32(checksum))))
 }
 
-// TODO: determine this by examining the server version
-const HAS_CHECKSUM: bool = true;
+pub(crate) fn compute_checksum(header: &[u8], data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(header);
+    hasher.update(data);
+    hasher.finalize()
+}
 
 impl Event {
+    /// Read an event from a binlog whose events carry checksums. Use
+    /// [`read_with_format`](Self::read_with_format) if the binlog may have been written with
+    /// `binlog_checksum=NONE`.
     pub fn read<R: Read>(reader: &mut R, offset: u64) -> Result<Self, EventParseError> {
-        let mut header = [0u8; 19];
-        match reader.read_exact(&mut header) {
-            Ok(_) => {}
-            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => {
-                return Err(EventParseError::EofError)
+        Self::read_with_format(reader, offset, &EventFormat::default())
+    }
+
+    /// Read an event framed as described by the binlog's FormatDescriptionEvent
+    pub fn read_with_format<R: Read>(
+        reader: &mut R,
+        offset: u64,
+        format: &EventFormat,
+    ) -> Result<Self, EventParseError> {
+        Self::read_with_limit(reader, offset, format, None)
+    }
+
+    /// Like [`read_with_format`](Self::read_with_format), but fail with
+    /// [`EventTooLarge`](EventParseError::EventTooLarge) rather than reading an event longer than
+    /// `max_event_size` bytes
+    pub fn read_with_limit<R: Read>(
+        reader: &mut R,
+        offset: u64,
+        format: &EventFormat,
+        max_event_size: Option<u32>,
+    ) -> Result<Self, EventParseError> {
+        let mut header = vec![0u8; usize::from(V4_HEADER_LEN)];
+        let mut read = 0;
+        while read < header.len() {
+            match reader.read(&mut header[read..]) {
+                // only a file which ends between two events ends cleanly; a partial header means
+                // the event was truncated
+                Ok(0) if read == 0 => return Err(EventParseError::EofError),
+                Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(n) => read += n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
             }
-            Err(e) => return Err(e.into()),
         }
-        let mut c = Cursor::new(header);
+        let mut c = Cursor::new(&header);
         let timestamp = c.read_u32::<LittleEndian>()?;
         let type_code = TypeCode::from_byte(c.read_u8()?);
         let server_id = c.read_u32::<LittleEndian>()?;
         let event_length = c.read_u32::<LittleEndian>()?;
         let next_position = c.read_u32::<LittleEndian>()?;
         let flags = c.read_u16::<LittleEndian>()?;
-        let mut data_length: usize = (event_length - 19) as usize;
-        if HAS_CHECKSUM {
-            data_length -= 4;
+        if let Some(limit) = max_event_size.filter(|limit| event_length > *limit) {
+            return Err(EventParseError::EventTooLarge {
+                offset,
+                length: event_length,
+                limit,
+            });
         }
-        //println!("finished reading event header with type_code {:?} event_length {} and next_position {}", type_code, event_length, next_position);
-        let mut data = vec![0u8; data_length];
-        reader.read_exact(&mut data)?;
+        let header_len = event_header_len(type_code, format);
+        if header_len > V4_HEADER_LEN {
+            header.resize(usize::from(header_len), 0);
+            reader.read_exact(&mut header[usize::from(V4_HEADER_LEN)..])?;
+        }
+        let data_length = match event_length.checked_sub(u32::from(header_len)) {
+            Some(length) => length as usize,
+            None => return Err(io::Error::from(ErrorKind::InvalidData).into()),
+        };
+        let mut data = read_nbytes(reader, data_length)?;
+        let (body_length, checksum) = {
+            let (body, checksum) = split_checksum(type_code, &data, format)?;
+            (body.len(), checksum)
+        };
+        data.truncate(body_length);
         //println!("finished reading body");
         Ok(Event {
             timestamp,
@@ -500,11 +2946,56 @@ impl Event {
             event_length,
             next_position,
             flags,
+            header,
             data,
+            checksum,
             offset,
         })
     }
 
+    /// The checksum stored at the end of this event, if the binlog has checksums
+    pub fn checksum(&self) -> Option<u32> {
+        self.checksum
+    }
+
+    /// Compute the CRC32 of this event's header and body
+    pub fn compute_checksum(&self) -> u32 {
+        self.as_borrowed().compute_checksum()
+    }
+
+    /// A view of this event which borrows its header and body
+    pub fn as_borrowed(&self) -> BorrowedEvent<'_> {
+        BorrowedEvent {
+            header: self.event_header(),
+            raw_header: &self.header,
+            data: &self.data,
+            checksum: self.checksum,
+        }
+    }
+
+    /// The raw header of this event, including any extra headers
+    pub(crate) fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// The bytes of this event as they appear in the binlog: its header (including any extra
+    /// headers), its body, and its checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.checksummed_bytes();
+        if let Some(checksum) = self.checksum {
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// The raw bytes covered by this event's checksum (its header followed by its body)
+    pub(crate) fn checksummed_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.header.len() + self.data.len());
+        bytes.extend_from_slice(&self.header);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
     pub fn type_code(&self) -> TypeCode {
         self.type_code
     }
@@ -513,15 +3004,138 @@ impl Event {
         self.timestamp
     }
 
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+
+    /// The `next_position` field from the event header. This is only 32 bits wide, and in relay
+    /// logs it refers to the position in the source server's binlog rather than this file, so use
+    /// [`end_offset`](Self::end_offset) to find the next event.
     pub fn next_position(&self) -> u64 {
         u64::from(self.next_position)
     }
 
+    /// Replace the body of this event, updating its length and checksum to match
+    pub(crate) fn with_data(mut self, data: Vec<u8>) -> Self {
+        let checksum_len = if self.checksum.is_some() { 4 } else { 0 };
+        self.event_length = (self.header.len() + data.len() + checksum_len) as u32;
+        LittleEndian::write_u32(&mut self.header[9..13], self.event_length);
+        self.data = data;
+        if self.checksum.is_some() {
+            self.checksum = Some(self.compute_checksum());
+        }
+        self
+    }
+
+    /// Write this event into another binlog at the given offset, updating its `next_position`
+    /// (and so its checksum) to match. Returns the offset of the byte after the event.
+    pub(crate) fn write_at<W: Write>(&self, w: &mut W, offset: u64) -> io::Result<u64> {
+        let end_offset = offset + u64::from(self.event_length);
+        let mut header = self.header.clone();
+        LittleEndian::write_u32(&mut header[13..17], end_offset as u32);
+        w.write_all(&header)?;
+        w.write_all(&self.data)?;
+        if self.checksum.is_some() {
+            w.write_all(&compute_checksum(&header, &self.data).to_le_bytes())?;
+        }
+        Ok(end_offset)
+    }
+
+    /// Move this event to a different offset; used for events unpacked from a transaction
+    /// payload, which have no position of their own in the binlog
+    pub(crate) fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The absolute offset of the byte after this event, computed from the offset it was read at
+    /// and its length
+    pub fn end_offset(&self) -> u64 {
+        self.offset + u64::from(self.event_length)
+    }
+
     pub fn inner(
         &self,
         table_map: Option<&TableMap>,
     ) -> Result<Option<EventData>, EventParseError> {
-        EventData::from_data(self.type_code, &self.data, table_map).map_err(Into::into)
+        self.inner_with_options(table_map, &DecodeOptions::default())
+    }
+
+    /// Like [`inner`](Self::inner), but with control over how the event is decoded
+    pub fn inner_with_options(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<EventData>, EventParseError> {
+        self.as_borrowed().inner_with_options(table_map, options)
+    }
+
+    /// Decode this event, keeping its header and raw contents alongside the decoded data
+    pub fn decode(self, table_map: &TableMap) -> Result<DecodedEvent, EventParseError> {
+        self.decode_with_options(table_map, &DecodeOptions::default())
+    }
+
+    /// Like [`decode`](Self::decode), but with control over how the event is decoded
+    pub fn decode_with_options(
+        self,
+        table_map: &TableMap,
+        options: &DecodeOptions,
+    ) -> Result<DecodedEvent, EventParseError> {
+        let data = self.inner_with_options(Some(table_map), options)?;
+        Ok(DecodedEvent {
+            header: self.event_header(),
+            data,
+            raw: self,
+        })
+    }
+
+    /// The fields of this event's common header
+    pub fn event_header(&self) -> EventHeader {
+        EventHeader {
+            timestamp: self.timestamp,
+            type_code: self.type_code,
+            server_id: self.server_id,
+            event_length: self.event_length,
+            next_position: self.next_position(),
+            flags: self.flags,
+            offset: self.offset,
+        }
+    }
+
+    /// Count the rows in this event (if it is a rows event) without decoding any values
+    pub fn rows_summary(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<RowsSummary>, EventParseError> {
+        self.as_borrowed().rows_summary(table_map, options)
+    }
+
+    /// Like [`rows_summary`](Self::rows_summary), but only measures the first row and estimates
+    /// the row count from it, so the cost doesn't grow with the size of the event. The estimate
+    /// is exact for tables whose columns are all fixed-width.
+    pub fn estimate_rows(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<RowsSummary>, EventParseError> {
+        self.as_borrowed().estimate_rows(table_map, options)
+    }
+
+    /// Decode the rows of this event (if it is a rows event) without copying strings, blobs,
+    /// or JSON documents out of its buffer. [`RawJsonColumns`] don't apply: JSON values are
+    /// always left in their binary encoding.
+    pub fn rows_ref(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<EventRef<'_>>, EventParseError> {
+        self.as_borrowed().rows_ref(table_map, options)
+    }
+
+    /// The id of the table a rows event modifies, read without decoding the rest of the event
+    pub fn rows_table_id(&self) -> Option<u64> {
+        self.as_borrowed().rows_table_id()
     }
 
     pub fn data(&self) -> &Vec<u8> {
@@ -540,3 +3154,1051 @@ impl Event {
         self.offset
     }
 }
+
+/// An event whose header and body are borrowed from a buffer rather than copied out of it, such
+/// as the events read from a memory-mapped binlog by `BinlogFile::borrowed_events` (with the
+/// `mmap` feature)
+#[derive(Clone, Copy)]
+pub struct BorrowedEvent<'a> {
+    header: EventHeader,
+    raw_header: &'a [u8],
+    data: &'a [u8],
+    checksum: Option<u32>,
+}
+
+impl fmt::Debug for BorrowedEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BorrowedEvent {{ timestamp: {:?}, type_code: {:?}, server_id: {:?}, data_len: {:?}, offset: {:?} }}", self.header.timestamp, self.header.type_code, self.header.server_id, self.data.len(), self.header.offset)
+    }
+}
+
+impl<'a> BorrowedEvent<'a> {
+    /// Parse the event at the start of `buf`, which may continue past the end of the event. Fails
+    /// like [`Event::read_with_limit`]: with [`EofError`](EventParseError::EofError) if `buf` is
+    /// empty, and with an `UnexpectedEof` I/O error if it ends partway through the event.
+    pub fn parse(
+        buf: &'a [u8],
+        offset: u64,
+        format: &EventFormat,
+        max_event_size: Option<u32>,
+    ) -> Result<Self, EventParseError> {
+        if buf.is_empty() {
+            return Err(EventParseError::EofError);
+        }
+        if buf.len() < usize::from(V4_HEADER_LEN) {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        let header = EventHeader {
+            timestamp: LittleEndian::read_u32(&buf[0..4]),
+            type_code: TypeCode::from_byte(buf[4]),
+            server_id: LittleEndian::read_u32(&buf[5..9]),
+            event_length: LittleEndian::read_u32(&buf[9..13]),
+            next_position: u64::from(LittleEndian::read_u32(&buf[13..17])),
+            flags: LittleEndian::read_u16(&buf[17..19]),
+            offset,
+        };
+        if let Some(limit) = max_event_size.filter(|limit| header.event_length > *limit) {
+            return Err(EventParseError::EventTooLarge {
+                offset,
+                length: header.event_length,
+                limit,
+            });
+        }
+        let header_len = usize::from(event_header_len(header.type_code, format));
+        if buf.len() < header_len {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        let event_length = header.event_length as usize;
+        if event_length < header_len {
+            return Err(io::Error::from(ErrorKind::InvalidData).into());
+        }
+        let body = buf
+            .get(header_len..event_length)
+            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+        let (data, checksum) = split_checksum(header.type_code, body, format)?;
+        Ok(BorrowedEvent {
+            header,
+            raw_header: &buf[..header_len],
+            data,
+            checksum,
+        })
+    }
+
+    /// Copy this event out of its buffer
+    pub fn to_event(&self) -> Event {
+        Event {
+            timestamp: self.header.timestamp,
+            type_code: self.header.type_code,
+            server_id: self.header.server_id,
+            event_length: self.header.event_length,
+            next_position: self.header.next_position as u32,
+            flags: self.header.flags,
+            header: self.raw_header.to_vec(),
+            data: self.data.to_vec(),
+            checksum: self.checksum,
+            offset: self.header.offset,
+        }
+    }
+
+    /// The fields of this event's common header
+    pub fn event_header(&self) -> EventHeader {
+        self.header
+    }
+
+    pub fn type_code(&self) -> TypeCode {
+        self.header.type_code
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.header.offset
+    }
+
+    /// The absolute offset of the byte after this event
+    pub fn end_offset(&self) -> u64 {
+        self.header.offset + u64::from(self.header.event_length)
+    }
+
+    /// The body of this event, without its header or checksum
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The checksum stored at the end of this event, if the binlog has checksums
+    pub fn checksum(&self) -> Option<u32> {
+        self.checksum
+    }
+
+    /// Compute the CRC32 of this event's header and body
+    pub fn compute_checksum(&self) -> u32 {
+        compute_checksum(self.raw_header, self.data)
+    }
+
+    pub fn inner(
+        &self,
+        table_map: Option<&TableMap>,
+    ) -> Result<Option<EventData>, EventParseError> {
+        self.inner_with_options(table_map, &DecodeOptions::default())
+    }
+
+    /// See [`Event::inner_with_options`]
+    pub fn inner_with_options(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<EventData>, EventParseError> {
+        if self.header.type_code == TypeCode::HeartbeatLogEvent {
+            // the original heartbeat carries its position in the header
+            return Ok(Some(EventData::HeartbeatEvent {
+                log_name: String::from_utf8_lossy(self.data).into_owned(),
+                position: self.header.next_position,
+            }));
+        }
+        EventData::from_data(self.header.type_code, self.data, table_map, options)
+    }
+
+    /// See [`Event::rows_summary`]
+    pub fn rows_summary(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<RowsSummary>, EventParseError> {
+        self.summarize_rows(table_map, options, false)
+    }
+
+    /// See [`Event::estimate_rows`]
+    pub fn estimate_rows(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<RowsSummary>, EventParseError> {
+        self.summarize_rows(table_map, options, true)
+    }
+
+    fn summarize_rows(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+        estimate: bool,
+    ) -> Result<Option<RowsSummary>, EventParseError> {
+        if !self.header.type_code.is_rows_event() {
+            return Ok(None);
+        }
+        let mut cursor = Cursor::new(self.data);
+        Ok(Some(summarize_rows_event(
+            self.header.type_code,
+            self.data.len(),
+            &mut cursor,
+            table_map,
+            options,
+            estimate,
+        )?))
+    }
+
+    /// See [`Event::rows_ref`]; the rows borrow from the same buffer as this event
+    pub fn rows_ref(
+        &self,
+        table_map: Option<&TableMap>,
+        options: &DecodeOptions,
+    ) -> Result<Option<EventRef<'a>>, EventParseError> {
+        if !self.header.type_code.is_rows_event() {
+            return Ok(None);
+        }
+        let this_table_map = table_map.and_then(|t| t.get(self.rows_table_id()?));
+        Ok(Some(parse_rows_event_ref(
+            self.header.type_code,
+            self.data,
+            this_table_map,
+            options,
+        )?))
+    }
+
+    /// The id of the table a rows event modifies, read without decoding the rest of the event
+    pub fn rows_table_id(&self) -> Option<u64> {
+        if !self.header.type_code.is_rows_event() || self.data.len() < 6 {
+            return None;
+        }
+        Some(LittleEndian::read_u48(&self.data[..6]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::{
+        AutoIncrement, ColumnCountPolicy, ColumnValue, DecodeOptions, Event, EventData,
+        GtidCommitInfo, IncidentType, IntvarKind, Invoker, LazyRows, QueryCharset, QueryStatusVars,
+        RowEvent, RowEventRef, TypeCode,
+    };
+    use crate::column_types::ColumnType;
+    use crate::errors::{ColumnParseError, EventParseError};
+    use crate::table_map::TableMap;
+    use crate::value::{MySQLValue, MySQLValueRef};
+    use std::sync::Arc;
+
+    const UUID: [u8; 16] = [
+        0x87, 0xce, 0xe3, 0xa4, 0x6b, 0x31, 0x11, 0xe7, 0xbd, 0xfd, 0x0d, 0x98, 0xd6, 0x69, 0x88,
+        0x70,
+    ];
+
+    #[test]
+    fn test_tagged_gtid_event() {
+        // message size 38, last non-ignorable field 5
+        let mut data = vec![0x4c, 0x0a];
+        // flags
+        data.extend_from_slice(&[0x00, 0x02]);
+        // uuid
+        data.push(0x02);
+        data.extend_from_slice(&UUID);
+        // gno 14918
+        data.extend_from_slice(&[0x04, 0x63, 0xa4, 0x03]);
+        // tag
+        data.extend_from_slice(&[0x06, 0x0a]);
+        data.extend_from_slice(b"batch");
+        // last_committed 3, sequence_number 4
+        data.extend_from_slice(&[0x08, 0x0c, 0x0a, 0x10]);
+        // a field from the future, which may be ignored
+        data.extend_from_slice(&[0x28, 0x00]);
+        let event = EventData::from_data(
+            TypeCode::GtidTaggedLogEvent,
+            &data,
+            None,
+            &DecodeOptions::default(),
+        )
+        .unwrap()
+        .unwrap();
+        match event {
+            EventData::GtidLogEvent {
+                flags,
+                uuid,
+                tag,
+                coordinate,
+                last_committed,
+                sequence_number,
+                commit_info,
+            } => {
+                assert_eq!(flags, 1);
+                assert_eq!(uuid.to_string(), "87cee3a4-6b31-11e7-bdfd-0d98d6698870");
+                assert_eq!(tag.unwrap().as_str(), "batch");
+                assert_eq!(coordinate, 14918);
+                assert_eq!(last_committed, Some(3));
+                assert_eq!(sequence_number, Some(4));
+                assert_eq!(commit_info, GtidCommitInfo::default());
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        // the same field is an error if the server says it cannot be ignored
+        data[1] = 0x28;
+        assert_matches!(
+            EventData::from_data(
+                TypeCode::GtidTaggedLogEvent,
+                &data,
+                None,
+                &DecodeOptions::default()
+            ),
+            Err(EventParseError::UnknownSerializedField(20))
+        );
+    }
+
+    #[test]
+    fn test_gtid_event_strict() {
+        let mut data = vec![0x00];
+        data.extend_from_slice(&UUID);
+        data.extend_from_slice(&14918u64.to_le_bytes());
+        data.push(0x02);
+        data.extend_from_slice(&[0u8; 16]);
+        assert_matches!(
+            EventData::from_data(
+                TypeCode::GtidLogEvent,
+                &data,
+                None,
+                &DecodeOptions::default()
+            ),
+            Ok(Some(EventData::GtidLogEvent { tag: None, .. }))
+        );
+        data[25] = 0x03;
+        assert_matches!(
+            EventData::from_data(
+                TypeCode::GtidLogEvent,
+                &data,
+                None,
+                &DecodeOptions::default()
+            ),
+            Err(EventParseError::InvalidLogicalClockType(3))
+        );
+        data[25] = 0x02;
+        data[0] = 0x80;
+        assert_matches!(
+            EventData::from_data(
+                TypeCode::GtidLogEvent,
+                &data,
+                None,
+                &DecodeOptions::default()
+            ),
+            Err(EventParseError::InvalidGtidFlags(0x80))
+        );
+    }
+
+    #[test]
+    fn test_gtid_commit_info() {
+        let mut data = vec![0x00];
+        data.extend_from_slice(&UUID);
+        data.extend_from_slice(&14918u64.to_le_bytes());
+        data.push(0x02);
+        data.extend_from_slice(&[0u8; 16]);
+        // immediate commit timestamp, with the original following
+        data.extend_from_slice(&(1_600_000_000_000_001u64 | (1 << 55)).to_le_bytes()[..7]);
+        data.extend_from_slice(&1_600_000_000_000_000u64.to_le_bytes()[..7]);
+        // transaction length 300
+        data.extend_from_slice(&[0xfc, 0x2c, 0x01]);
+        // same immediate and original server version
+        data.extend_from_slice(&80036u32.to_le_bytes());
+        let decode = |data: &[u8]| match EventData::from_data(
+            TypeCode::GtidLogEvent,
+            data,
+            None,
+            &DecodeOptions::default(),
+        ) {
+            Ok(Some(EventData::GtidLogEvent { commit_info, .. })) => commit_info,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            decode(&data),
+            GtidCommitInfo {
+                immediate_commit_timestamp: Some(1_600_000_000_000_001),
+                original_commit_timestamp: Some(1_600_000_000_000_000),
+                transaction_length: Some(300),
+                immediate_server_version: Some(80036),
+                original_server_version: Some(80036),
+            }
+        );
+        // servers before 8.0.14 don't write server versions
+        let partial = decode(&data[..data.len() - 4]);
+        assert_eq!(partial.transaction_length, Some(300));
+        assert_eq!(partial.immediate_server_version, None);
+    }
+
+    #[test]
+    fn test_xa_prepare_event() {
+        let mut data = vec![0x00];
+        data.extend_from_slice(&1i32.to_le_bytes());
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"abcde");
+        assert_matches!(
+            EventData::from_data(
+                TypeCode::XaPrepareLogEvent,
+                &data,
+                None,
+                &DecodeOptions::default()
+            ),
+            Ok(Some(EventData::XaPrepareEvent { one_phase: false, format_id: 1, ref gtrid, ref bqual }))
+                if gtrid == b"abc" && bqual == b"de"
+        );
+    }
+
+    #[test]
+    fn test_query_status_vars() {
+        let bf = crate::binlog_file::BinlogFile::try_from_path("test_data/bin-log.000001").unwrap();
+        let event = bf.events(Some(259)).next().unwrap().unwrap();
+        let status_vars = match event.inner(None) {
+            Ok(Some(EventData::QueryEvent { status_vars, .. })) => status_vars,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_eq!(
+            status_vars,
+            QueryStatusVars {
+                flags2: Some(0),
+                sql_mode: Some(0x40_0000),
+                catalog: Some("std".to_owned()),
+                charset: Some(QueryCharset {
+                    character_set_client: 33,
+                    collation_connection: 33,
+                    collation_server: 33,
+                }),
+                updated_db_names: Some(vec!["bltest".to_owned()]),
+                ..QueryStatusVars::default()
+            }
+        );
+
+        let mut block = vec![3, 2, 0, 1, 0];
+        block.extend_from_slice(&[5, 3, b'U', b'T', b'C']);
+        block.extend_from_slice(&[11, 1, b'u', 1, b'h']);
+        block.extend_from_slice(&[12, 254, 13, 7, 0, 0, 16, 1, 17]);
+        block.extend_from_slice(&9u64.to_le_bytes());
+        // an unknown variable ends the block
+        block.extend_from_slice(&[99, 1, 2, 3]);
+        let status_vars = QueryStatusVars::parse(&block).unwrap();
+        assert_eq!(
+            status_vars.auto_increment,
+            Some(AutoIncrement {
+                increment: 2,
+                offset: 1
+            })
+        );
+        assert_eq!(status_vars.time_zone.as_deref(), Some("UTC"));
+        assert_eq!(
+            status_vars.invoker,
+            Some(Invoker {
+                user: "u".to_owned(),
+                host: "h".to_owned()
+            })
+        );
+        assert_eq!(status_vars.updated_db_names, Some(vec![]));
+        assert_eq!(status_vars.microseconds, Some(7));
+        assert_eq!(status_vars.explicit_defaults_for_timestamp, Some(true));
+        assert_eq!(status_vars.ddl_xid, Some(9));
+        assert!(QueryStatusVars::parse(&[1, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_statement_context_events() {
+        let decode = |type_code, data: &[u8]| {
+            EventData::from_data(type_code, data, None, &DecodeOptions::default())
+        };
+        let mut intvar = vec![0x02];
+        intvar.extend_from_slice(&42u64.to_le_bytes());
+        assert_matches!(
+            decode(TypeCode::IntvarEvent, &intvar),
+            Ok(Some(EventData::IntvarEvent {
+                kind: IntvarKind::InsertId,
+                value: 42
+            }))
+        );
+
+        let mut rand = 1u64.to_le_bytes().to_vec();
+        rand.extend_from_slice(&2u64.to_le_bytes());
+        assert_matches!(
+            decode(TypeCode::RandEvent, &rand),
+            Ok(Some(EventData::RandEvent { seed1: 1, seed2: 2 }))
+        );
+
+        assert_matches!(
+            decode(TypeCode::IncidentEvent, &[0x01, 0x00, 0x03, b'f', b'o', b'o']),
+            Ok(Some(EventData::IncidentEvent { incident_type: IncidentType::LostEvents, ref message }))
+                if message == "foo"
+        );
+        assert_matches!(
+            decode(TypeCode::IncidentEvent, &[0x07, 0x00]),
+            Ok(Some(EventData::IncidentEvent { incident_type: IncidentType::Other(7), ref message }))
+                if message.is_empty()
+        );
+
+        let mut heartbeat = vec![0x01, 0x0e];
+        heartbeat.extend_from_slice(b"bin-log.000001");
+        heartbeat.extend_from_slice(&[0x02, 0x03, 0xfc, 0x17, 0x04, 0x00]);
+        assert_matches!(
+            decode(TypeCode::HeartbeatLogEventV2, &heartbeat),
+            Ok(Some(EventData::HeartbeatEvent { ref log_name, position: 1047 }))
+                if log_name == "bin-log.000001"
+        );
+        let mut start_encryption = vec![0x01, 0x02, 0x00, 0x00, 0x00];
+        start_encryption.extend_from_slice(&[0xab; 12]);
+        assert_matches!(
+            decode(TypeCode::StartEncryptionEvent, &start_encryption),
+            Ok(Some(EventData::StartEncryptionEvent { scheme: 1, key_version: 2, nonce })) if nonce == [0xab; 12]
+        );
+
+        let user_var = |value_type: u8, charset: u32, value: &[u8], flags: u8| {
+            let mut data = 3u32.to_le_bytes().to_vec();
+            data.extend_from_slice(b"foo");
+            data.push(0);
+            data.push(value_type);
+            data.extend_from_slice(&charset.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(value);
+            data.push(flags);
+            data
+        };
+        assert_matches!(
+            decode(TypeCode::UserVarEvent, &user_var(0, 33, b"bar", 0)),
+            Ok(Some(EventData::UserVarEvent { ref name, value: Some(MySQLValue::String(ref v)), charset: Some(33), unsigned: false }))
+                if name == "foo" && v == "bar"
+        );
+        assert_matches!(
+            decode(
+                TypeCode::UserVarEvent,
+                &user_var(2, 0, &(-5i64).to_le_bytes(), 1)
+            ),
+            Ok(Some(EventData::UserVarEvent {
+                value: Some(MySQLValue::SignedInteger(-5)),
+                charset: None,
+                unsigned: true,
+                ..
+            }))
+        );
+        assert_matches!(
+            decode(TypeCode::UserVarEvent, &user_var(1, 0, &1.5f64.to_le_bytes(), 0)),
+            Ok(Some(EventData::UserVarEvent { value: Some(MySQLValue::Double(v)), .. })) if v == 1.5
+        );
+        // DECIMAL(4, 2) 12.34
+        assert_matches!(
+            decode(TypeCode::UserVarEvent, &user_var(4, 0, &[4, 2, 0x8c, 0x22], 0)),
+            Ok(Some(EventData::UserVarEvent { value: Some(MySQLValue::Decimal(ref d)), .. }))
+                if d.to_string() == "12.34"
+        );
+        assert_matches!(
+            decode(TypeCode::UserVarEvent, &user_var(9, 0, &[], 0)),
+            Err(EventParseError::InvalidUserVarType(9))
+        );
+        let mut null = 3u32.to_le_bytes().to_vec();
+        null.extend_from_slice(b"foo\x01");
+        assert_matches!(
+            decode(TypeCode::UserVarEvent, &null),
+            Ok(Some(EventData::UserVarEvent { value: None, .. }))
+        );
+    }
+
+    #[test]
+    fn test_raw_json() {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            203,
+            "bltest".to_owned(),
+            "docs".to_owned(),
+            vec![ColumnType::Json(4)],
+        );
+        // one row, whose only column is the JSON literal `true`
+        let body = [
+            203, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0x01, 0x00, 2, 0, 0, 0, 0x04, 0x01,
+        ];
+        let decode = |options: &DecodeOptions| match EventData::from_data(
+            TypeCode::WriteRowsEventV2,
+            &body,
+            Some(&table_map),
+            options,
+        ) {
+            Ok(Some(EventData::WriteRowsEvent { mut rows, .. })) => match rows.remove(0) {
+                RowEvent::NewRow { mut cols } => cols.remove(0).unwrap(),
+                other => panic!("unexpected row {:?}", other),
+            },
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_matches!(
+            decode(&DecodeOptions::default()),
+            MySQLValue::Json(serde_json::Value::Bool(true))
+        );
+
+        let mut options = DecodeOptions::default();
+        options.raw_json.insert("bltest", "other", None);
+        options.raw_json.insert("bltest", "docs", Some(1));
+        assert_matches!(decode(&options), MySQLValue::Json(_));
+        options.raw_json.insert("bltest", "docs", Some(0));
+        assert_matches!(decode(&options), MySQLValue::Blob(ref b) if b.0 == [0x04, 0x01]);
+
+        let mut options = DecodeOptions::default();
+        options.raw_json.insert("bltest", "docs", None);
+        assert_matches!(decode(&options), MySQLValue::Blob(_));
+    }
+
+    #[test]
+    fn test_partial_json() {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            203,
+            "bltest".to_owned(),
+            "docs".to_owned(),
+            vec![ColumnType::Long, ColumnType::Json(4)],
+        );
+        let event = |with_before_json: bool| {
+            let before_bitmap = if with_before_json { 0x03 } else { 0x01 };
+            let mut body = vec![203, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, before_bitmap, 0x03];
+            // before-image: 1, [1]
+            body.extend_from_slice(&[0x00, 1, 0, 0, 0]);
+            if with_before_json {
+                body.extend_from_slice(&[8, 0, 0, 0, 0x02, 1, 0, 7, 0, 0x05, 1, 0]);
+            }
+            // partial JSON enabled, for the only JSON column
+            body.extend_from_slice(&[0x01, 0x01]);
+            // after-image: 2, and INSERT 9 at $[1]
+            body.extend_from_slice(&[0x00, 2, 0, 0, 0, 10, 0, 0, 0]);
+            body.extend_from_slice(&[1, 4, b'$', b'[', b'1', b']', 3, 0x05, 9, 0]);
+            match EventData::from_data(
+                TypeCode::PartialUpdateRowsEvent,
+                &body,
+                Some(&table_map),
+                &DecodeOptions::default(),
+            ) {
+                Ok(Some(EventData::UpdateRowsEvent { mut rows, .. })) => match rows.remove(0) {
+                    RowEvent::UpdatedRow { mut after_cols, .. } => after_cols.remove(1).unwrap(),
+                    other => panic!("unexpected row {:?}", other),
+                },
+                other => panic!("unexpected event {:?}", other),
+            }
+        };
+        assert_matches!(
+            event(true),
+            MySQLValue::Json(ref j) if *j == serde_json::json!([1, 9])
+        );
+        assert_matches!(
+            event(false),
+            MySQLValue::JsonDiff(ref diffs) if diffs.len() == 1 && diffs[0].path == "$[1]"
+        );
+    }
+
+    #[test]
+    fn test_transaction_payload_event() {
+        // the Xid event from the test binlog, without its checksum
+        let mut inner = std::fs::read("test_data/bin-log.000001").unwrap()[1008..1035].to_vec();
+        inner[9] = 27;
+        let payload_event_with = |compression_type: &[u8],
+                                  uncompressed_size: u8,
+                                  payload: &[u8],
+                                  options: &DecodeOptions| {
+            let mut data = vec![0x01, 0x01, payload.len() as u8, 0x02];
+            data.push(compression_type.len() as u8);
+            data.extend_from_slice(compression_type);
+            data.extend_from_slice(&[0x03, 0x01, uncompressed_size]);
+            data.push(0x00);
+            data.extend_from_slice(payload);
+            EventData::from_data(TypeCode::TransactionPayloadEvent, &data, None, options)
+        };
+        let payload_event = |compression_type: &[u8], uncompressed_size: u8, payload: &[u8]| {
+            payload_event_with(
+                compression_type,
+                uncompressed_size,
+                payload,
+                &DecodeOptions::default(),
+            )
+        };
+        #[cfg(feature = "zstd")]
+        let compressed = zstd::encode_all(inner.as_slice(), 0).unwrap();
+        #[cfg(feature = "zstd")]
+        let payloads = [(&[0x00][..], &compressed), (&[0xfc, 0xff, 0x00], &inner)];
+        #[cfg(not(feature = "zstd"))]
+        let payloads = [(&[0xfc, 0xff, 0x00][..], &inner)];
+        for (compression_type, payload) in payloads {
+            match payload_event(compression_type, 27, payload) {
+                Ok(Some(EventData::TransactionPayloadEvent { events, .. })) => {
+                    assert_eq!(events.len(), 1);
+                    assert_eq!(events[0].type_code(), TypeCode::XidEvent);
+                    assert_eq!(events[0].checksum(), None);
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+        assert_matches!(
+            payload_event(&[0x05], 27, &inner),
+            Err(EventParseError::UnsupportedCompression(5))
+        );
+        let limited = DecodeOptions {
+            max_event_size: Some(20),
+            ..DecodeOptions::default()
+        };
+        assert_matches!(
+            payload_event_with(&[0xfc, 0xff, 0x00], 27, &inner, &limited),
+            Err(EventParseError::EventTooLarge { limit: 20, .. })
+        );
+        #[cfg(feature = "zstd")]
+        assert_matches!(
+            payload_event(&[0x00], 26, &compressed),
+            Err(EventParseError::PayloadTooLarge { limit: 26 })
+        );
+        #[cfg(feature = "zstd")]
+        assert_matches!(
+            payload_event_with(&[0x00], 27, &compressed, &limited),
+            Err(EventParseError::PayloadTooLarge { limit: 20 })
+        );
+        #[cfg(not(feature = "zstd"))]
+        assert_matches!(
+            payload_event(&[0x00], 27, &inner),
+            Err(EventParseError::ZstdNotEnabled)
+        );
+    }
+
+    #[test]
+    fn test_table_map_metadata() {
+        let binlog = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut data = binlog[598 + 19..598 + 50].to_vec();
+        // the id column is unsigned
+        data.extend_from_slice(&[1, 1, 0x80]);
+        // every character column uses charset 45
+        data.extend_from_slice(&[2, 1, 45]);
+        data.extend_from_slice(&[4, 23, 2]);
+        data.extend_from_slice(b"id");
+        data.push(11);
+        data.extend_from_slice(b"val_decimal");
+        data.push(7);
+        data.extend_from_slice(b"comment");
+        data.extend_from_slice(&[8, 1, 0]);
+        // a field from the future
+        data.extend_from_slice(&[99, 2, 0xaa, 0xbb]);
+        let descriptors = match EventData::from_data(
+            TypeCode::TableMapEvent,
+            &data,
+            None,
+            &DecodeOptions::default(),
+        ) {
+            Ok(Some(EventData::TableMapEvent { descriptors, .. })) => descriptors,
+            other => panic!("unexpected result {:?}", other),
+        };
+        let names = descriptors
+            .iter()
+            .map(|d| d.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "val_decimal", "comment"]);
+        assert_eq!(descriptors[0].unsigned, Some(true));
+        assert!(descriptors[0].primary_key);
+        assert_eq!(descriptors[1].unsigned, Some(false));
+        assert!(!descriptors[1].primary_key);
+        assert_eq!(descriptors[1].charset, None);
+        assert_eq!(descriptors[2].unsigned, None);
+        assert_eq!(descriptors[2].charset, Some(45));
+        assert!(!descriptors[2].nullable);
+    }
+
+    fn write_rows_event() -> super::Event {
+        let bf = crate::binlog_file::BinlogFile::try_from_path("test_data/bin-log.000001").unwrap();
+        bf.events(Some(652)).next().unwrap().unwrap()
+    }
+
+    fn decode_rows(
+        columns: Vec<ColumnType>,
+        policy: ColumnCountPolicy,
+    ) -> Result<Vec<Option<MySQLValue>>, EventParseError> {
+        let mut table_map = TableMap::new();
+        table_map.handle(203, "bltest".to_owned(), "foo".to_owned(), columns);
+        let options = DecodeOptions {
+            column_count_policy: policy,
+            ..DecodeOptions::default()
+        };
+        match write_rows_event().inner_with_options(Some(&table_map), &options)? {
+            Some(EventData::WriteRowsEvent { mut rows, .. }) => match rows.remove(0) {
+                RowEvent::NewRow { cols } => Ok(cols),
+                other => panic!("unexpected row {:?}", other),
+            },
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut table_map = TableMap::new();
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::NewDecimal(10, 5),
+            ColumnType::VarChar(765),
+        ];
+        table_map.handle(203, "bltest".to_owned(), "foo".to_owned(), columns);
+        let event = write_rows_event();
+        let data_len = event.data().len();
+        let decoded = event.decode(&table_map).unwrap();
+        assert_eq!(decoded.header.offset, 652);
+        assert_eq!(decoded.header.type_code, TypeCode::WriteRowsEventV2);
+        assert_eq!(decoded.header.event_length, 66);
+        assert_eq!(decoded.header.next_position, 718);
+        assert_eq!(decoded.raw.data().len(), data_len);
+        assert_matches!(
+            decoded.data,
+            Some(EventData::WriteRowsEvent { table_id: 203, ref rows }) if rows.len() == 1
+        );
+    }
+
+    #[test]
+    fn test_rows_ref() {
+        let mut table_map = TableMap::new();
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::NewDecimal(10, 5),
+            ColumnType::VarChar(765),
+        ];
+        table_map.handle(203, "bltest".to_owned(), "foo".to_owned(), columns);
+        let event = write_rows_event();
+        let options = DecodeOptions::default();
+        let borrowed = event.rows_ref(Some(&table_map), &options).unwrap().unwrap();
+        assert_eq!(borrowed.table_id, 203);
+        let cols = match &borrowed.rows[0] {
+            RowEventRef::NewRow { cols } => cols,
+            other => panic!("unexpected row {:?}", other),
+        };
+        assert_matches!(
+            cols[0],
+            Some(MySQLValueRef::Owned(MySQLValue::SignedInteger(_)))
+        );
+        // the string points into the event's buffer
+        let s = cols[2].as_ref().and_then(MySQLValueRef::as_str).unwrap();
+        assert!(event.data().as_ptr_range().contains(&s.as_ptr()));
+
+        let owned = match event.inner_with_options(Some(&table_map), &options) {
+            Ok(Some(EventData::WriteRowsEvent { rows, .. })) => rows,
+            other => panic!("unexpected event {:?}", other),
+        };
+        let copied = borrowed
+            .rows
+            .iter()
+            .map(|r| r.to_owned_row().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            serde_json::to_value(&copied).unwrap(),
+            serde_json::to_value(&owned).unwrap()
+        );
+
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            203,
+            "bltest".to_owned(),
+            "docs".to_owned(),
+            vec![ColumnType::Json(4)],
+        );
+        let event = Event {
+            data: vec![
+                203, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0x01, 0x00, 2, 0, 0, 0, 0x04, 0x01,
+            ],
+            ..write_rows_event()
+        };
+        let borrowed = event.rows_ref(Some(&table_map), &options).unwrap().unwrap();
+        match &borrowed.rows[0] {
+            RowEventRef::NewRow { cols } => {
+                assert_matches!(cols[0], Some(MySQLValueRef::Json(&[0x04, 0x01])));
+                assert_matches!(
+                    cols[0].as_ref().unwrap().to_owned_value(),
+                    Ok(MySQLValue::Json(serde_json::Value::Bool(true)))
+                );
+            }
+            other => panic!("unexpected row {:?}", other),
+        }
+        assert!(Event {
+            type_code: TypeCode::XidEvent,
+            ..write_rows_event()
+        }
+        .rows_ref(Some(&table_map), &options)
+        .unwrap()
+        .is_none());
+    }
+
+    #[test]
+    fn test_estimate_rows() {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            203,
+            "bltest".to_owned(),
+            "ids".to_owned(),
+            vec![ColumnType::LongLong],
+        );
+        let mut data = vec![203, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0x01];
+        for id in 1..=3u64 {
+            data.push(0x00);
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        let event = Event {
+            data,
+            ..write_rows_event()
+        };
+        let options = DecodeOptions::default();
+        let counted = event.rows_summary(Some(&table_map), &options).unwrap();
+        assert_eq!(
+            counted.map(|s| (s.row_count, s.estimated)),
+            Some((Some(3), false))
+        );
+        let estimated = event.estimate_rows(Some(&table_map), &options).unwrap();
+        assert_eq!(
+            estimated.map(|s| (s.row_count, s.estimated)),
+            Some((Some(3), true))
+        );
+        let unknown = event.estimate_rows(None, &options).unwrap();
+        assert_eq!(unknown.and_then(|s| s.row_count), None);
+    }
+
+    /// A rows event with no columns present, so each row would take up no bytes at all
+    fn empty_rows_event() -> (TableMap, Event) {
+        let mut table_map = TableMap::new();
+        table_map.handle(
+            203,
+            "bltest".to_owned(),
+            "ids".to_owned(),
+            vec![ColumnType::LongLong],
+        );
+        let event = Event {
+            data: vec![203, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0x00, 0x00, 0x00],
+            ..write_rows_event()
+        };
+        (table_map, event)
+    }
+
+    #[test]
+    fn test_summarize_empty_rows() {
+        let (table_map, event) = empty_rows_event();
+        let options = DecodeOptions::default();
+        assert!(event.rows_summary(Some(&table_map), &options).is_err());
+        assert!(event.estimate_rows(Some(&table_map), &options).is_err());
+    }
+
+    #[test]
+    fn test_decode_empty_rows() {
+        let (table_map, event) = empty_rows_event();
+        let options = DecodeOptions::default();
+        assert!(event
+            .inner_with_options(Some(&table_map), &options)
+            .is_err());
+        assert!(event.rows_ref(Some(&table_map), &options).is_err());
+        let lazy = LazyRows::new(Arc::new(event), &table_map, Arc::new(options)).unwrap();
+        let rows = lazy.iter().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].is_err());
+        assert!(lazy.rows_ref().is_err());
+    }
+
+    #[test]
+    fn test_column_count_policy() {
+        let columns = vec![
+            ColumnType::LongLong,
+            ColumnType::NewDecimal(10, 5),
+            ColumnType::VarChar(765),
+        ];
+        assert_eq!(
+            decode_rows(columns.clone(), ColumnCountPolicy::Error)
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // the table map has a column the event doesn't
+        let mut wider = columns.clone();
+        wider.push(ColumnType::LongLong);
+        assert_matches!(
+            decode_rows(wider.clone(), ColumnCountPolicy::Error),
+            Err(EventParseError::ColumnParseError(
+                ColumnParseError::ColumnCountMismatch {
+                    table_columns: 4,
+                    event_columns: 3
+                }
+            ))
+        );
+        let row = decode_rows(wider.clone(), ColumnCountPolicy::Truncate).unwrap();
+        assert_eq!(row.len(), 3);
+        assert_matches!(row[2], Some(MySQLValue::String(_)));
+        let row = decode_rows(wider, ColumnCountPolicy::PadNull).unwrap();
+        assert_eq!(row.len(), 4);
+        assert_matches!(row[3], Some(MySQLValue::Null));
+
+        // the event has a column the table map doesn't; since it has a value we can't skip it
+        let narrower = columns[..2].to_vec();
+        assert_matches!(
+            decode_rows(narrower, ColumnCountPolicy::Truncate),
+            Err(EventParseError::ColumnParseError(
+                ColumnParseError::ColumnCountMismatch { .. }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_row_images() {
+        let one = vec![Some(MySQLValue::SignedInteger(1))];
+        let two = vec![Some(MySQLValue::SignedInteger(2))];
+        let insert = RowEvent::NewRow { cols: one.clone() };
+        assert_eq!(insert.cols(), &one);
+        assert_eq!(insert.before_cols(), None);
+        assert_eq!(insert.after_cols(), Some(&one));
+        let delete = RowEvent::DeletedRow { cols: one.clone() };
+        assert_eq!(delete.cols(), &one);
+        assert_eq!(delete.before_cols(), Some(&one));
+        assert_eq!(delete.after_cols(), None);
+        let update = RowEvent::UpdatedRow {
+            before_cols: one.clone(),
+            after_cols: two.clone(),
+        };
+        assert_eq!(update.cols(), &two);
+        assert_eq!(update.before_cols(), Some(&one));
+        assert_eq!(update.after_cols(), Some(&two));
+    }
+
+    #[test]
+    fn test_changed_columns() {
+        let mut row = RowEvent::UpdatedRow {
+            before_cols: vec![
+                Some(MySQLValue::SignedInteger(1)),
+                Some(MySQLValue::String("a".to_owned())),
+                Some(MySQLValue::Null),
+                None,
+            ],
+            after_cols: vec![
+                Some(MySQLValue::SignedInteger(1)),
+                Some(MySQLValue::String("b".to_owned())),
+                Some(MySQLValue::Null),
+                Some(MySQLValue::SignedInteger(4)),
+            ],
+        };
+        let changed = row.changed_columns();
+        assert_eq!(changed.iter().map(|c| c.index).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!(changed[0].before, Some(&MySQLValue::String("a".to_owned())));
+        assert_eq!(changed[1].before, None);
+        assert!(RowEvent::NewRow { cols: vec![] }
+            .changed_columns()
+            .is_empty());
+        assert_eq!(row.before_value(2), ColumnValue::Null);
+        assert_eq!(row.before_value(3), ColumnValue::NotPresent);
+        assert_eq!(
+            row.after_value(3),
+            ColumnValue::Value(&MySQLValue::SignedInteger(4))
+        );
+        assert_eq!(row.after_value(4), ColumnValue::NotPresent);
+        assert!(!RowEvent::NewRow { cols: vec![] }
+            .before_value(0)
+            .is_present());
+
+        row.retain_changed_columns(|i| i == 0);
+        assert_matches!(
+            row,
+            RowEvent::UpdatedRow { before_cols, after_cols } => {
+                assert_eq!(
+                    before_cols,
+                    [
+                        Some(MySQLValue::SignedInteger(1)),
+                        Some(MySQLValue::String("a".to_owned())),
+                        None,
+                        None
+                    ]
+                );
+                assert_eq!(
+                    after_cols,
+                    [
+                        None,
+                        Some(MySQLValue::String("b".to_owned())),
+                        None,
+                        Some(MySQLValue::SignedInteger(4))
+                    ]
+                );
+            }
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Data-quality expectations evaluated against rows as they stream by.
+//!
+//! An [`Expectations`] set declares, per table, what every row is expected to look like (a
+//! column is never null, a numeric column stays within a range, the primary key is always
+//! present). [`Expectations::check`] evaluates them against a [`BinlogEvent`] and returns a
+//! [`Violation`] record for every row which doesn't meet them, which makes it easy to turn a
+//! replication stream into a lightweight data-quality monitor.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use bigdecimal::ToPrimitive;
+use serde::Serialize;
+
+use crate::table_map::ColumnDescriptor;
+use crate::value::MySQLValue;
+use crate::{BinlogEvent, Gtid};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+/// A column, by its zero-based position or by name. Names can only be resolved for events
+/// whose [`columns`](BinlogEvent::columns) are known.
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for ColumnRef {
+    fn from(index: usize) -> Self {
+        ColumnRef::Index(index)
+    }
+}
+
+impl From<&str> for ColumnRef {
+    fn from(name: &str) -> Self {
+        ColumnRef::Name(name.to_owned())
+    }
+}
+
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnRef::Index(i) => write!(f, "#{}", i),
+            ColumnRef::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl ColumnRef {
+    fn resolve(&self, columns: Option<&[ColumnDescriptor]>) -> Option<usize> {
+        match self {
+            ColumnRef::Index(i) => Some(*i),
+            ColumnRef::Name(name) => columns?
+                .iter()
+                .position(|c| c.name.as_deref() == Some(name.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something every row of a table is expected to satisfy
+pub enum Expectation {
+    /// The column is never null in an inserted or updated row
+    NotNull(ColumnRef),
+    /// The numeric value of the column in an inserted or updated row is within the bounds
+    /// (inclusive). Null and non-numeric values are not checked.
+    Range {
+        column: ColumnRef,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// Every primary key column is present and non-null in every row image, including deleted
+    /// rows and the before image of updates
+    PrimaryKeyPresent(Vec<ColumnRef>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// How a row failed an expectation
+pub enum ViolationKind {
+    Null,
+    OutOfRange {
+        value: f64,
+    },
+    MissingPrimaryKey,
+    /// The expectation names a column which the table doesn't have (or whose name isn't known)
+    UnknownColumn,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// A row which did not meet an expectation
+pub struct Violation {
+    pub offset: u64,
+    pub gtid: Option<Gtid>,
+    pub schema_name: String,
+    pub table_name: String,
+    /// Index of the row within the event
+    pub row: usize,
+    pub column: ColumnRef,
+    #[serde(flatten)]
+    pub kind: ViolationKind,
+}
+
+#[derive(Debug, Default, Clone)]
+/// A set of expectations for any number of tables
+pub struct Expectations {
+    tables: HashMap<(String, String), Vec<Expectation>>,
+}
+
+fn numeric_value(value: &MySQLValue) -> Option<f64> {
+    match *value {
+        MySQLValue::SignedInteger(i) => Some(i as f64),
+        MySQLValue::UnsignedInteger(u) => Some(u as f64),
+        MySQLValue::Float(f) => Some(f64::from(f)),
+        MySQLValue::Double(f) => Some(f),
+        MySQLValue::Decimal(ref d) => d.to_f64(),
+        MySQLValue::Year(y) => Some(f64::from(y)),
+        _ => None,
+    }
+}
+
+impl Expectations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an expectation for a table
+    pub fn expect(mut self, schema_name: &str, table_name: &str, expectation: Expectation) -> Self {
+        self.tables
+            .entry((schema_name.to_owned(), table_name.to_owned()))
+            .or_default()
+            .push(expectation);
+        self
+    }
+
+    /// Evaluate the expectations for the table of a rows event against each of its rows
+    pub fn check(&self, event: &BinlogEvent) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let table_ref = match event.table_ref {
+            Some(ref table_ref) => table_ref,
+            None => return violations,
+        };
+        let expectations = match self
+            .tables
+            .get(&(table_ref.schema.clone(), table_ref.table.clone()))
+        {
+            Some(expectations) => expectations,
+            None => return violations,
+        };
+        let columns = event.columns.as_deref();
+        let mut violation = |row: usize, column: &ColumnRef, kind: ViolationKind| {
+            violations.push(Violation {
+                offset: event.offset,
+                gtid: event.gtid,
+                schema_name: table_ref.schema.clone(),
+                table_name: table_ref.table.clone(),
+                row,
+                column: column.clone(),
+                kind,
+            })
+        };
+        for (row_index, row) in event.rows.iter().enumerate() {
+            // the image of the row as it is after this event, and every image of the row
+            let current = row.after_cols();
+            let images = row
+                .before_cols()
+                .into_iter()
+                .chain(current)
+                .collect::<Vec<_>>();
+            for expectation in expectations {
+                match expectation {
+                    Expectation::NotNull(column) | Expectation::Range { column, .. } => {
+                        let current = match current {
+                            Some(current) => current,
+                            None => continue,
+                        };
+                        let value = match column.resolve(columns) {
+                            Some(i) if i < current.len() => current[i].as_ref(),
+                            _ => {
+                                violation(row_index, column, ViolationKind::UnknownColumn);
+                                continue;
+                            }
+                        };
+                        match (expectation, value) {
+                            (Expectation::NotNull(_), Some(MySQLValue::Null)) => {
+                                violation(row_index, column, ViolationKind::Null)
+                            }
+                            (Expectation::Range { min, max, .. }, Some(value)) => {
+                                if let Some(value) = numeric_value(value) {
+                                    if min.map(|min| value < min).unwrap_or(false)
+                                        || max.map(|max| value > max).unwrap_or(false)
+                                    {
+                                        violation(
+                                            row_index,
+                                            column,
+                                            ViolationKind::OutOfRange { value },
+                                        );
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Expectation::PrimaryKeyPresent(key) => {
+                        for column in key {
+                            let index = match column.resolve(columns) {
+                                Some(i) => i,
+                                None => {
+                                    violation(row_index, column, ViolationKind::UnknownColumn);
+                                    continue;
+                                }
+                            };
+                            let missing = images.iter().any(|image| {
+                                matches!(
+                                    image.get(index),
+                                    None | Some(None) | Some(Some(MySQLValue::Null))
+                                )
+                            });
+                            if missing {
+                                violation(row_index, column, ViolationKind::MissingPrimaryKey);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnRef, Expectation, Expectations, ViolationKind};
+    use crate::parse_file;
+
+    #[test]
+    fn test_check() {
+        let expectations = Expectations::new()
+            .expect("bltest", "foo", Expectation::NotNull(0.into()))
+            .expect(
+                "bltest",
+                "foo",
+                Expectation::Range {
+                    column: 0.into(),
+                    min: Some(2.0),
+                    max: None,
+                },
+            )
+            .expect(
+                "bltest",
+                "foo",
+                Expectation::PrimaryKeyPresent(vec![0.into(), "id".into()]),
+            );
+        let violations = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .flat_map(|event| expectations.check(&event.unwrap()))
+            .collect::<Vec<_>>();
+        // the first row has id 1, and column names aren't known
+        assert_eq!(violations.len(), 3);
+        assert_eq!(violations[0].kind, ViolationKind::OutOfRange { value: 1.0 });
+        assert_eq!(violations[1].column, ColumnRef::Name("id".to_owned()));
+        assert_eq!(violations[1].kind, ViolationKind::UnknownColumn);
+        assert_eq!(violations[2].kind, ViolationKind::UnknownColumn);
+        let serialized = serde_json::to_value(&violations[0]).unwrap();
+        assert_eq!(serialized["kind"], "out_of_range");
+        assert_eq!(serialized["column"], 0);
+    }
+}
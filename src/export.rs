@@ -0,0 +1,172 @@
+//! Helpers for exporting [`BinlogEvent`]s into compact, serializable record streams.
+//!
+//! Row events for wide tables spend a surprising amount of their serialized size repeating the
+//! same schema and table names. [`dedup_tables`] rewrites a stream of events so that each table
+//! is declared once per transaction (and again if its columns change) by a
+//! [`ExportRecord::Table`] record and subsequent events refer to it by a small numeric key.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::column_types::ColumnType;
+use crate::event::TransactionMarker;
+use crate::table_map::ColumnDescriptor;
+use crate::{BinlogEvent, Gtid};
+
+/// A single record in a table-deduplicated export stream
+#[derive(Debug, Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum ExportRecord {
+    /// Declares a table; emitted the first time a table is referenced within each transaction.
+    /// `table_key` is stable for the lifetime of the export, but is unrelated to the MySQL
+    /// `table_id` (which is reassigned by the server and is not unique across files).
+    Table {
+        table_key: u32,
+        schema_name: String,
+        table_name: String,
+        /// The types of the table's columns, with their metadata, if its Table Map event was seen
+        #[serde(skip_serializing_if = "Option::is_none")]
+        column_types: Option<Arc<[ColumnType]>>,
+        /// The names and other attributes of the table's columns, if known; see
+        /// [`BinlogEvent::columns`]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        columns: Option<Arc<[ColumnDescriptor]>>,
+    },
+    /// An event whose table reference and columns have been replaced by a reference to a
+    /// previously emitted `Table` record
+    Event {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        table_key: Option<u32>,
+        event: Box<BinlogEvent>,
+    },
+}
+
+/// Iterator adapter returned by [`dedup_tables`]
+pub struct TableDedup<I> {
+    inner: I,
+    keys: HashMap<(String, String), u32>,
+    emitted: HashSet<u32>,
+    // the columns most recently declared for each table
+    declared: HashMap<u32, TableColumns>,
+    current_gtid: Option<Gtid>,
+    pending: Option<ExportRecord>,
+}
+
+type TableColumns = (Option<Arc<[ColumnType]>>, Option<Arc<[ColumnDescriptor]>>);
+
+impl<I> TableDedup<I> {
+    fn new(inner: I) -> Self {
+        TableDedup {
+            inner,
+            keys: HashMap::new(),
+            emitted: HashSet::new(),
+            declared: HashMap::new(),
+            current_gtid: None,
+            pending: None,
+        }
+    }
+
+    fn is_transaction_start(&self, event: &BinlogEvent) -> bool {
+        event.gtid != self.current_gtid
+            || event.transaction_marker == Some(TransactionMarker::Begin)
+    }
+
+    fn key_for(&mut self, schema_name: &str, table_name: &str) -> u32 {
+        let next_key = self.keys.len() as u32;
+        *self
+            .keys
+            .entry((schema_name.to_owned(), table_name.to_owned()))
+            .or_insert(next_key)
+    }
+}
+
+impl<I, E> Iterator for TableDedup<I>
+where
+    I: Iterator<Item = Result<BinlogEvent, E>>,
+{
+    type Item = Result<ExportRecord, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.pending.take() {
+            return Some(Ok(record));
+        }
+        let mut event = match self.inner.next()? {
+            Ok(event) => event,
+            Err(e) => return Some(Err(e)),
+        };
+        if self.is_transaction_start(&event) {
+            self.emitted.clear();
+            self.current_gtid = event.gtid;
+        }
+        let (schema_name, table_name) = match event.table_ref.take() {
+            Some(table_ref) => (table_ref.schema, table_ref.table),
+            None => {
+                return Some(Ok(ExportRecord::Event {
+                    table_key: None,
+                    event: Box::new(event),
+                }));
+            }
+        };
+        let table_key = self.key_for(&schema_name, &table_name);
+        let columns = (event.column_types.take(), event.columns.take());
+        let record = ExportRecord::Event {
+            table_key: Some(table_key),
+            event: Box::new(event),
+        };
+        let changed = self.declared.get(&table_key) != Some(&columns);
+        if self.emitted.insert(table_key) || changed {
+            self.pending = Some(record);
+            self.declared.insert(table_key, columns.clone());
+            let (column_types, columns) = columns;
+            Some(Ok(ExportRecord::Table {
+                table_key,
+                schema_name,
+                table_name,
+                column_types,
+                columns,
+            }))
+        } else {
+            Some(Ok(record))
+        }
+    }
+}
+
+/// Rewrite a stream of [`BinlogEvent`]s so that schema and table names, and the table's columns,
+/// are emitted once per transaction rather than once per event
+pub fn dedup_tables<I: IntoIterator>(events: I) -> TableDedup<I::IntoIter> {
+    TableDedup::new(events.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::{dedup_tables, ExportRecord};
+    use crate::parse_file;
+
+    #[test]
+    fn test_dedup_tables() {
+        let records = dedup_tables(parse_file("test_data/bin-log.000001").unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // two transactions, each of which writes to bltest.foo once
+        let tables = records
+            .iter()
+            .filter(|r| matches!(r, ExportRecord::Table { .. }))
+            .count();
+        assert_eq!(tables, 2);
+        assert_eq!(records.len(), 9);
+        assert_matches!(records[2], ExportRecord::Table { table_key: 0, ref table_name, .. } if table_name == "foo");
+        assert_matches!(records[3], ExportRecord::Event { table_key: Some(0), ref event } if event.table_ref.is_none());
+        let serialized = serde_json::to_value(&records[3]).unwrap();
+        assert_eq!(serialized["record"], "event");
+        assert!(serialized["event"].get("table_ref").is_none());
+        let serialized = serde_json::to_value(&records[2]).unwrap();
+        assert_eq!(
+            serialized["column_types"],
+            serde_json::json!(["LongLong", {"NewDecimal": [10, 5]}, {"VarChar": 765}])
+        );
+    }
+}
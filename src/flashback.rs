@@ -0,0 +1,251 @@
+//! Undoing changes, in the manner of MariaDB's `mysqlbinlog --flashback`.
+//!
+//! [`flashback`] turns a stream of events into the [inverse](BinlogEvent::invert) of each of its
+//! rows events, last change first, so that applying them in order rolls the tables back to where
+//! they were at the start of the stream. [`to_sql`] renders rows events as SQL statements, for
+//! applying them with a MySQL client.
+
+use crate::errors::EventParseError;
+use crate::event::RowEvent;
+use crate::table_map::ColumnDescriptor;
+use crate::value::MySQLValue;
+use crate::view::RowChangeKind;
+use crate::BinlogEvent;
+
+/// Iterator adapter returned by [`flashback`]
+pub struct Flashback<I> {
+    // None once every event has been read
+    inner: Option<I>,
+    inverted: Vec<BinlogEvent>,
+}
+
+impl<I> Iterator for Flashback<I>
+where
+    I: Iterator<Item = Result<BinlogEvent, EventParseError>>,
+{
+    type Item = Result<BinlogEvent, EventParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ref mut inner) = self.inner {
+            for event in inner.by_ref() {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => return Some(Err(e)),
+                };
+                if RowChangeKind::from_type_code(event.type_code).is_some() {
+                    match event.invert() {
+                        Ok(event) => self.inverted.push(event),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            }
+            self.inner = None;
+        }
+        self.inverted.pop().map(Ok)
+    }
+}
+
+/// Invert every rows event in `events`, returning them in reverse order. Other events are
+/// dropped; the inverted events keep the GTIDs of the transactions they undo.
+///
+/// Nothing can be returned until the last event has been read, so every rows event in the stream
+/// is held in memory.
+pub fn flashback<I: IntoIterator>(events: I) -> Flashback<I::IntoIter> {
+    Flashback {
+        inner: Some(events.into_iter()),
+        inverted: Vec::new(),
+    }
+}
+
+/// The SQL statements which apply the rows of a rows event, one per row; empty for other events.
+///
+/// Column names come from the event's [`ColumnDescriptor`]s; columns whose names aren't known
+/// are written as `@1`, `@2`, and so on (as `mysqlbinlog --verbose` does), and those statements
+/// can't be executed until they're filled in. Rows are found by their primary key if the event
+/// says which columns that is, and otherwise by every column in their before-image.
+pub fn to_sql(event: &BinlogEvent) -> Result<Vec<String>, EventParseError> {
+    let table = match event.table_ref {
+        Some(ref table) => table,
+        None => return Ok(Vec::new()),
+    };
+    let table = format!(
+        "{}.{}",
+        quote_identifier(&table.schema),
+        quote_identifier(&table.table)
+    );
+    let columns = event.columns.as_deref().unwrap_or(&[]);
+    let mut statements = Vec::new();
+    for row in event.rows_iter() {
+        statements.push(match *row? {
+            RowEvent::NewRow { ref cols } => {
+                let (names, values): (Vec<_>, Vec<_>) = assignments(cols, columns).unzip();
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    names.join(", "),
+                    values.join(", ")
+                )
+            }
+            RowEvent::DeletedRow { ref cols } => format!(
+                "DELETE FROM {} WHERE {} LIMIT 1",
+                table,
+                condition(cols, columns)
+            ),
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => format!(
+                "UPDATE {} SET {} WHERE {} LIMIT 1",
+                table,
+                assignments(after_cols, columns)
+                    .map(|(name, value)| format!("{} = {}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                condition(before_cols, columns)
+            ),
+        });
+    }
+    Ok(statements)
+}
+
+/// The name and literal value of each column present in an image
+fn assignments<'a>(
+    cols: &'a [Option<MySQLValue>],
+    columns: &'a [ColumnDescriptor],
+) -> impl Iterator<Item = (String, String)> + 'a {
+    cols.iter().enumerate().filter_map(move |(i, value)| {
+        Some((column_name(columns, i), sql_literal(value.as_ref()?)?))
+    })
+}
+
+fn column_name(columns: &[ColumnDescriptor], i: usize) -> String {
+    match columns.get(i).and_then(|c| c.name.as_deref()) {
+        Some(name) => quote_identifier(name),
+        None => format!("@{}", i + 1),
+    }
+}
+
+fn condition(cols: &[Option<MySQLValue>], columns: &[ColumnDescriptor]) -> String {
+    let is_key = |i: usize| columns.get(i).is_some_and(|c| c.primary_key);
+    let has_key = (0..cols.len()).any(|i| is_key(i) && cols[i].is_some());
+    cols.iter()
+        .enumerate()
+        .filter(|&(i, _)| !has_key || is_key(i))
+        .filter_map(|(i, value)| {
+            let value = sql_literal(value.as_ref()?)?;
+            Some(format!("{} <=> {}", column_name(columns, i), value))
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+fn quote_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\0' => quoted.push_str("\\0"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\x1a' => quoted.push_str("\\Z"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let digits = bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<String>();
+    format!("X'{}'", digits)
+}
+
+/// `value` as a MySQL literal, or None if it can't be written as one (a partial JSON update)
+fn sql_literal(value: &MySQLValue) -> Option<String> {
+    Some(match *value {
+        MySQLValue::Null => "NULL".to_owned(),
+        MySQLValue::SignedInteger(i) => i.to_string(),
+        MySQLValue::UnsignedInteger(u) => u.to_string(),
+        MySQLValue::Float(f) => f.to_string(),
+        MySQLValue::Double(f) => f.to_string(),
+        MySQLValue::Decimal(ref d) => d.to_string(),
+        MySQLValue::String(ref s) => quote_string(s),
+        MySQLValue::EnumString { ref name, .. } => quote_string(name),
+        MySQLValue::Enum(index) => index.to_string(),
+        MySQLValue::Set(bits) => bits.to_string(),
+        MySQLValue::Bit { value, .. } => format!("b'{:b}'", value),
+        MySQLValue::Year(year) => year.to_string(),
+        MySQLValue::Blob(ref b) | MySQLValue::Bytes(ref b) => hex(&b.0),
+        MySQLValue::Date { .. } | MySQLValue::Time { .. } | MySQLValue::DateTime { .. } => {
+            quote_string(&value.to_temporal_string()?)
+        }
+        // zero is how the server stores '0000-00-00 00:00:00'
+        MySQLValue::Timestamp { unix_time: 0, .. } => "'0000-00-00 00:00:00'".to_owned(),
+        MySQLValue::Timestamp {
+            unix_time,
+            subsecond,
+            fsp,
+        } => {
+            if fsp > 0 {
+                format!("FROM_UNIXTIME({}.{:06})", unix_time, subsecond)
+            } else {
+                format!("FROM_UNIXTIME({})", unix_time)
+            }
+        }
+        MySQLValue::Json(ref document) => {
+            format!("CAST({} AS JSON)", quote_string(&document.to_string()))
+        }
+        MySQLValue::JsonDiff(_) => return None,
+        MySQLValue::Custom { ref value, .. } => match *value {
+            serde_json::Value::String(ref s) => quote_string(s),
+            ref value => quote_string(&value.to_string()),
+        },
+        MySQLValue::Geometry { srid, ref wkb } => {
+            format!("ST_GeomFromWKB({}, {})", hex(&wkb.0), srid)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flashback, quote_string, to_sql};
+    use crate::event::{RowEvent, TypeCode};
+    use crate::parse_file;
+
+    #[test]
+    fn test_flashback() {
+        let mut inverted = flashback(parse_file("test_data/bin-log.000001").unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // the last change is undone first
+        assert_eq!(inverted.len(), 2);
+        assert_eq!(inverted[0].offset, 942);
+        let event = &inverted[1];
+        assert_eq!(event.type_code, TypeCode::DeleteRowsEventV2);
+        assert!(matches!(event.rows[0], RowEvent::DeletedRow { .. }));
+        assert_eq!(
+            to_sql(event).unwrap(),
+            ["DELETE FROM `bltest`.`foo` WHERE @1 <=> 1 AND @2 <=> 0.10000 AND @3 <=> 'zero point one' LIMIT 1"]
+        );
+
+        let reinverted = inverted.pop().unwrap().invert().unwrap();
+        assert_eq!(
+            to_sql(&reinverted).unwrap(),
+            ["INSERT INTO `bltest`.`foo` (@1, @2, @3) VALUES (1, 0.10000, 'zero point one')"]
+        );
+    }
+
+    #[test]
+    fn test_quote_string() {
+        assert_eq!(quote_string("it's a \\ test\n"), "'it\\'s a \\\\ test\\n'");
+    }
+}
@@ -0,0 +1,245 @@
+//! Typed decoding of the WKB (Well-Known Binary) payload of
+//! [`MySQLValue::Geometry`](crate::value::MySQLValue::Geometry) values.
+//!
+//! Only available with the `geo` feature.
+
+use std::io::{Cursor, Read};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use serde::Serialize;
+
+use crate::errors::GeometryParseError;
+
+const WKB_POINT: u32 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTIPOINT: u32 = 4;
+const WKB_MULTILINESTRING: u32 = 5;
+const WKB_MULTIPOLYGON: u32 = 6;
+const WKB_GEOMETRYCOLLECTION: u32 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "coordinates")]
+/// A geometry decoded from WKB. Polygons are a list of rings, the first of which is the exterior.
+pub enum Geometry {
+    Point(Point),
+    LineString(Vec<Point>),
+    Polygon(Vec<Vec<Point>>),
+    MultiPoint(Vec<Point>),
+    MultiLineString(Vec<Vec<Point>>),
+    MultiPolygon(Vec<Vec<Vec<Point>>>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+impl Geometry {
+    /// Decode a WKB-encoded geometry, as found in
+    /// [`MySQLValue::Geometry`](crate::value::MySQLValue::Geometry) (after the SRID)
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, GeometryParseError> {
+        let mut cursor = Cursor::new(wkb);
+        let geometry = read_geometry(&mut cursor)?;
+        if cursor.position() != wkb.len() as u64 {
+            return Err(GeometryParseError::TrailingBytes(
+                wkb.len() - cursor.position() as usize,
+            ));
+        }
+        Ok(geometry)
+    }
+}
+
+/// Each geometry (including those nested in a collection) starts with its own byte order
+struct Reader<'a, R> {
+    r: &'a mut R,
+    little_endian: bool,
+}
+
+impl<'a, R: Read> Reader<'a, R> {
+    fn u32(&mut self) -> Result<u32, GeometryParseError> {
+        Ok(if self.little_endian {
+            self.r.read_u32::<LittleEndian>()?
+        } else {
+            self.r.read_u32::<BigEndian>()?
+        })
+    }
+
+    fn point(&mut self) -> Result<Point, GeometryParseError> {
+        let mut buf = [0u8; 16];
+        self.r.read_exact(&mut buf)?;
+        let (x, y) = if self.little_endian {
+            (
+                LittleEndian::read_f64(&buf),
+                LittleEndian::read_f64(&buf[8..]),
+            )
+        } else {
+            (BigEndian::read_f64(&buf), BigEndian::read_f64(&buf[8..]))
+        };
+        Ok(Point { x, y })
+    }
+
+    fn points(&mut self) -> Result<Vec<Point>, GeometryParseError> {
+        let count = self.u32()?;
+        // don't trust the count for the allocation; a corrupt value would be enormous
+        let mut points = Vec::new();
+        for _ in 0..count {
+            points.push(self.point()?);
+        }
+        Ok(points)
+    }
+
+    fn rings(&mut self) -> Result<Vec<Vec<Point>>, GeometryParseError> {
+        let count = self.u32()?;
+        let mut rings = Vec::new();
+        for _ in 0..count {
+            rings.push(self.points()?);
+        }
+        Ok(rings)
+    }
+
+    fn members(&mut self) -> Result<Vec<Geometry>, GeometryParseError> {
+        let count = self.u32()?;
+        let mut members = Vec::new();
+        for _ in 0..count {
+            members.push(read_geometry(self.r)?);
+        }
+        Ok(members)
+    }
+}
+
+fn read_geometry<R: Read>(r: &mut R) -> Result<Geometry, GeometryParseError> {
+    let little_endian = match r.read_u8()? {
+        0 => false,
+        1 => true,
+        other => return Err(GeometryParseError::InvalidByteOrder(other)),
+    };
+    let mut reader = Reader { r, little_endian };
+    let geometry_type = reader.u32()?;
+    Ok(match geometry_type {
+        WKB_POINT => Geometry::Point(reader.point()?),
+        WKB_LINESTRING => Geometry::LineString(reader.points()?),
+        WKB_POLYGON => Geometry::Polygon(reader.rings()?),
+        WKB_MULTIPOINT => Geometry::MultiPoint(
+            reader
+                .members()?
+                .into_iter()
+                .map(|member| match member {
+                    Geometry::Point(p) => Ok(p),
+                    _ => Err(GeometryParseError::UnexpectedMember(geometry_type)),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        WKB_MULTILINESTRING => Geometry::MultiLineString(
+            reader
+                .members()?
+                .into_iter()
+                .map(|member| match member {
+                    Geometry::LineString(l) => Ok(l),
+                    _ => Err(GeometryParseError::UnexpectedMember(geometry_type)),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        WKB_MULTIPOLYGON => Geometry::MultiPolygon(
+            reader
+                .members()?
+                .into_iter()
+                .map(|member| match member {
+                    Geometry::Polygon(p) => Ok(p),
+                    _ => Err(GeometryParseError::UnexpectedMember(geometry_type)),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        WKB_GEOMETRYCOLLECTION => Geometry::GeometryCollection(reader.members()?),
+        other => return Err(GeometryParseError::UnknownType(other)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Geometry, Point};
+    use crate::errors::GeometryParseError;
+
+    fn point_wkb(x: f64, y: f64) -> Vec<u8> {
+        let mut wkb = vec![1, 1, 0, 0, 0];
+        wkb.extend_from_slice(&x.to_le_bytes());
+        wkb.extend_from_slice(&y.to_le_bytes());
+        wkb
+    }
+
+    #[test]
+    fn test_point() {
+        assert_eq!(
+            Geometry::from_wkb(&point_wkb(1.5, -2.0)).unwrap(),
+            Geometry::Point(Point { x: 1.5, y: -2.0 })
+        );
+        // big-endian
+        let mut wkb = vec![0, 0, 0, 0, 1];
+        wkb.extend_from_slice(&3.0f64.to_be_bytes());
+        wkb.extend_from_slice(&4.0f64.to_be_bytes());
+        assert_eq!(
+            Geometry::from_wkb(&wkb).unwrap(),
+            Geometry::Point(Point { x: 3.0, y: 4.0 })
+        );
+    }
+
+    #[test]
+    fn test_polygon_and_collection() {
+        // POLYGON((0 0, 1 0, 0 1, 0 0))
+        let mut wkb = vec![1, 3, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0];
+        for (x, y) in &[(0.0f64, 0.0f64), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)] {
+            wkb.extend_from_slice(&x.to_le_bytes());
+            wkb.extend_from_slice(&y.to_le_bytes());
+        }
+        let polygon = Geometry::from_wkb(&wkb).unwrap();
+        match polygon {
+            Geometry::Polygon(ref rings) => {
+                assert_eq!(rings.len(), 1);
+                assert_eq!(rings[0].len(), 4);
+                assert_eq!(rings[0][1], Point { x: 1.0, y: 0.0 });
+            }
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        // GEOMETRYCOLLECTION(POINT(1 2), POLYGON(...))
+        let mut collection = vec![1, 7, 0, 0, 0, 2, 0, 0, 0];
+        collection.extend(point_wkb(1.0, 2.0));
+        collection.extend(wkb);
+        assert_eq!(
+            Geometry::from_wkb(&collection).unwrap(),
+            Geometry::GeometryCollection(vec![Geometry::Point(Point { x: 1.0, y: 2.0 }), polygon])
+        );
+
+        // a MULTIPOINT may only contain points
+        let mut multipoint = vec![1, 4, 0, 0, 0, 1, 0, 0, 0];
+        multipoint.extend(&collection);
+        assert!(matches!(
+            Geometry::from_wkb(&multipoint),
+            Err(GeometryParseError::UnexpectedMember(4))
+        ));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(matches!(
+            Geometry::from_wkb(&[2, 1, 0, 0, 0]),
+            Err(GeometryParseError::InvalidByteOrder(2))
+        ));
+        assert!(matches!(
+            Geometry::from_wkb(&[1, 99, 0, 0, 0]),
+            Err(GeometryParseError::UnknownType(99))
+        ));
+        assert!(matches!(
+            Geometry::from_wkb(&point_wkb(0.0, 0.0)[..10]),
+            Err(GeometryParseError::Io(_))
+        ));
+        let mut trailing = point_wkb(0.0, 0.0);
+        trailing.push(0);
+        assert!(matches!(
+            Geometry::from_wkb(&trailing),
+            Err(GeometryParseError::TrailingBytes(1))
+        ));
+    }
+}
@@ -0,0 +1,706 @@
+//! Global Transaction IDs and sets of them.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use uuid::Uuid;
+
+use crate::errors::{EventParseError, GtidParseError};
+use crate::packet_helpers::{read_nbytes, read_serialized_unsigned, write_serialized_unsigned};
+
+// maximum length of a GTID tag, in bytes
+const MAX_TAG_LEN: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The tag of a tagged GTID (MySQL 8.3+), e.g. the `batch` in `uuid:batch:12`.
+///
+/// Tags are 1-32 characters long, start with a letter or underscore, contain only letters,
+/// digits and underscores, and are case-insensitive (they are stored in lowercase).
+pub struct GtidTag {
+    len: u8,
+    bytes: [u8; MAX_TAG_LEN],
+}
+
+impl GtidTag {
+    pub fn as_str(&self) -> &str {
+        // only ASCII is ever stored, so this cannot fail
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+}
+
+impl FromStr for GtidTag {
+    type Err = GtidParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = !s.is_empty()
+            && s.len() <= MAX_TAG_LEN
+            && s.bytes().enumerate().all(|(i, c)| {
+                c == b'_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+            });
+        if !valid {
+            return Err(GtidParseError::InvalidTag(s.to_owned()));
+        }
+        let mut bytes = [0u8; MAX_TAG_LEN];
+        for (dest, c) in bytes.iter_mut().zip(s.bytes()) {
+            *dest = c.to_ascii_lowercase();
+        }
+        Ok(GtidTag {
+            len: s.len() as u8,
+            bytes,
+        })
+    }
+}
+
+impl serde::Serialize for GtidTag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for GtidTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GtidTag({:?})", self.as_str())
+    }
+}
+
+impl fmt::Display for GtidTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Global Transaction ID.
+///
+/// GTIDs are ordered by source UUID, then tag, then sequence number, so transactions from the
+/// same source (with the same tag) sort in the order they were committed.
+pub struct Gtid {
+    uuid: Uuid,
+    tag: Option<GtidTag>,
+    sequence: u64,
+}
+
+impl Gtid {
+    pub(crate) fn new(uuid: Uuid, tag: Option<GtidTag>, sequence: u64) -> Self {
+        Gtid {
+            uuid,
+            tag,
+            sequence,
+        }
+    }
+
+    /// The UUID of the server where this transaction originated
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// The tag of this transaction, if it was committed with a tagged GTID
+    pub fn tag(&self) -> Option<&GtidTag> {
+        self.tag.as_ref()
+    }
+
+    /// Whether two transactions were committed by the same source with the same tag, and so
+    /// are numbered from the same sequence
+    pub(crate) fn same_sequence(&self, other: &Gtid) -> bool {
+        self.uuid == other.uuid && self.tag == other.tag
+    }
+
+    /// The sequence number of this transaction among those from the same source (and tag)
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl FromStr for Gtid {
+    type Err = GtidParseError;
+
+    /// Parse the textual representation used by MySQL, `uuid:sequence` or
+    /// `uuid:tag:sequence`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || GtidParseError::InvalidFormat(s.to_owned());
+        let mut pieces = s.trim().split(':');
+        let uuid = Uuid::parse_str(pieces.next().unwrap_or(""))?;
+        let (tag, sequence) = match (pieces.next(), pieces.next(), pieces.next()) {
+            (Some(sequence), None, None) => (None, sequence),
+            (Some(tag), Some(sequence), None) => (Some(tag.parse()?), sequence),
+            _ => return Err(invalid()),
+        };
+        match sequence.parse() {
+            Ok(0) | Ok(u64::MAX) | Err(_) => Err(invalid()),
+            Ok(sequence) => Ok(Gtid::new(uuid, tag, sequence)),
+        }
+    }
+}
+
+impl TryFrom<&str> for Gtid {
+    type Error = GtidParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl serde::Serialize for Gtid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Gtid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Gtid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tag {
+            Some(ref tag) => write!(f, "{}:{}:{}", self.uuid.hyphenated(), tag, self.sequence),
+            None => write!(f, "{}:{}", self.uuid.hyphenated(), self.sequence),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A contiguous, non-empty range of transaction sequence numbers from a single server
+pub struct GtidInterval {
+    start: u64,
+    // exclusive, to match the on-disk representation
+    end: u64,
+}
+
+impl GtidInterval {
+    /// Construct an interval containing `first` through `last` (inclusive)
+    pub fn new(first: u64, last: u64) -> Result<Self, GtidParseError> {
+        if first == 0 || last < first || last == u64::MAX {
+            return Err(GtidParseError::InvalidInterval(first, last));
+        }
+        Ok(GtidInterval {
+            start: first,
+            end: last + 1,
+        })
+    }
+
+    /// The first sequence number in this interval
+    pub fn first(&self) -> u64 {
+        self.start
+    }
+
+    /// The last sequence number in this interval (inclusive)
+    pub fn last(&self) -> u64 {
+        self.end - 1
+    }
+
+    /// The number of transactions in this interval
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, sequence: u64) -> bool {
+        sequence >= self.start && sequence < self.end
+    }
+}
+
+impl fmt::Display for GtidInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.len() == 1 {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.last())
+        }
+    }
+}
+
+/// Sort and merge a list of intervals so that they are ordered, non-overlapping, and
+/// non-adjacent
+fn normalize(intervals: &mut Vec<GtidInterval>) {
+    intervals.sort();
+    let mut merged: Vec<GtidInterval> = Vec::with_capacity(intervals.len());
+    for interval in intervals.drain(..) {
+        match merged.last_mut() {
+            Some(last) if interval.start <= last.end => {
+                last.end = last.end.max(interval.end);
+            }
+            _ => merged.push(interval),
+        }
+    }
+    *intervals = merged;
+}
+
+/// Subtract one normalized interval list from another
+fn subtract(from: &[GtidInterval], remove: &[GtidInterval]) -> Vec<GtidInterval> {
+    let mut out = Vec::new();
+    let mut remove = remove.iter().peekable();
+    for interval in from {
+        let mut start = interval.start;
+        while let Some(r) = remove.peek() {
+            if r.end <= start {
+                remove.next();
+                continue;
+            }
+            if r.start >= interval.end {
+                break;
+            }
+            if r.start > start {
+                out.push(GtidInterval {
+                    start,
+                    end: r.start,
+                });
+            }
+            start = r.end;
+            if r.end > interval.end {
+                break;
+            }
+            remove.next();
+        }
+        if start < interval.end {
+            out.push(GtidInterval {
+                start,
+                end: interval.end,
+            });
+        }
+    }
+    out
+}
+
+// tagged and untagged transactions from the same server are tracked separately; `None` sorts
+// first, which matches the order MySQL prints them in
+type GtidSetKey = (Uuid, Option<GtidTag>);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A set of GTIDs, represented as a list of intervals of sequence numbers per server UUID (the
+/// same representation MySQL uses for `gtid_executed`).
+///
+/// Intervals are kept merged and sorted, so two sets containing the same transactions always
+/// compare equal and serialize identically.
+pub struct GtidSet {
+    intervals: BTreeMap<GtidSetKey, Vec<GtidInterval>>,
+}
+
+impl GtidSet {
+    pub fn new() -> Self {
+        GtidSet {
+            intervals: BTreeMap::new(),
+        }
+    }
+
+    /// Add a single transaction to this set. A transaction numbered `u64::MAX` can't be held in
+    /// an interval (whose end is exclusive) and is ignored; MySQL never numbers a transaction
+    /// past 2^63 - 1.
+    pub fn insert(&mut self, gtid: &Gtid) {
+        let end = match gtid.sequence.checked_add(1) {
+            Some(end) => end,
+            None => return,
+        };
+        let intervals = self.intervals.entry((gtid.uuid, gtid.tag)).or_default();
+        // fast path: transactions are usually added in order
+        if let Some(last) = intervals.last_mut() {
+            if last.end == gtid.sequence {
+                last.end = end;
+                return;
+            }
+            if last.contains(gtid.sequence) {
+                return;
+            }
+        }
+        intervals.push(GtidInterval {
+            start: gtid.sequence,
+            end,
+        });
+        normalize(intervals);
+    }
+
+    /// Add an interval of transactions from the given server to this set
+    pub fn add_interval(&mut self, uuid: Uuid, tag: Option<GtidTag>, interval: GtidInterval) {
+        let intervals = self.intervals.entry((uuid, tag)).or_default();
+        intervals.push(interval);
+        normalize(intervals);
+    }
+
+    pub fn contains(&self, gtid: &Gtid) -> bool {
+        self.intervals
+            .get(&(gtid.uuid, gtid.tag))
+            .map(|intervals| intervals.iter().any(|i| i.contains(gtid.sequence)))
+            .unwrap_or(false)
+    }
+
+    /// Whether every transaction in this set is also in `other`
+    pub fn is_subset(&self, other: &GtidSet) -> bool {
+        self.diff(other).is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The number of transactions in this set
+    pub fn len(&self) -> u64 {
+        self.intervals
+            .values()
+            .flat_map(|intervals| intervals.iter())
+            .map(|i| i.len())
+            .sum()
+    }
+
+    /// The server UUIDs with at least one transaction in this set
+    pub fn uuids(&self) -> impl Iterator<Item = &Uuid> {
+        let mut last = None;
+        self.intervals.keys().filter_map(move |(uuid, _)| {
+            if last == Some(uuid) {
+                None
+            } else {
+                last = Some(uuid);
+                Some(uuid)
+            }
+        })
+    }
+
+    /// The (normalized) intervals for the given server UUID and tag
+    pub fn intervals(&self, uuid: &Uuid, tag: Option<&GtidTag>) -> &[GtidInterval] {
+        self.intervals
+            .get(&(*uuid, tag.copied()))
+            .map(|i| i.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Add every transaction in `other` to this set
+    pub fn union(&mut self, other: &GtidSet) {
+        for (key, other_intervals) in &other.intervals {
+            let intervals = self.intervals.entry(*key).or_default();
+            intervals.extend_from_slice(other_intervals);
+            normalize(intervals);
+        }
+    }
+
+    /// The transactions in this set which are not in `other`
+    pub fn diff(&self, other: &GtidSet) -> GtidSet {
+        let mut out = GtidSet::new();
+        for (key, intervals) in &self.intervals {
+            let remaining = match other.intervals.get(key) {
+                Some(remove) => subtract(intervals, remove),
+                None => intervals.clone(),
+            };
+            if !remaining.is_empty() {
+                out.intervals.insert(*key, remaining);
+            }
+        }
+        out
+    }
+
+    /// The transactions which have been added to this set since it was equal to `earlier`;
+    /// useful for exchanging progress as compact deltas
+    pub fn added_since(&self, earlier: &GtidSet) -> GtidSet {
+        self.diff(earlier)
+    }
+
+    /// The binary encoding of this set, as used by PreviousGtidsLogEvent and
+    /// `COM_BINLOG_DUMP_GTID`. Sets containing tagged GTIDs use the MySQL 8.3+ format.
+    pub fn encode(&self) -> Vec<u8> {
+        let tagged = self.intervals.keys().any(|(_, tag)| tag.is_some());
+        let mut out = Vec::new();
+        let mut header = self.intervals.len() as u64;
+        if tagged {
+            header |= 1 << 56;
+        }
+        out.extend_from_slice(&header.to_le_bytes());
+        for ((uuid, tag), intervals) in &self.intervals {
+            out.extend_from_slice(uuid.as_bytes());
+            if tagged {
+                let tag = tag.as_ref().map(|t| t.as_str()).unwrap_or("");
+                write_serialized_unsigned(&mut out, tag.len() as u64);
+                out.extend_from_slice(tag.as_bytes());
+            }
+            out.extend_from_slice(&(intervals.len() as u64).to_le_bytes());
+            for interval in intervals {
+                out.extend_from_slice(&interval.start.to_le_bytes());
+                out.extend_from_slice(&interval.end.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    /// Read the binary encoding of a GTID set used by PreviousGtidsLogEvent
+    pub(crate) fn read_from<R: Read>(r: &mut R) -> Result<Self, EventParseError> {
+        let header = r.read_u64::<LittleEndian>()?;
+        // 8.3+ servers put a format marker in the top byte; 1 means that each UUID is followed
+        // by a tag
+        let tagged = match header >> 56 {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(
+                    io::Error::new(io::ErrorKind::InvalidData, "unknown GTID set encoding").into(),
+                )
+            }
+        };
+        let num_sids = header & 0x00ff_ffff_ffff_ffff;
+        let mut set = GtidSet::new();
+        for _ in 0..num_sids {
+            let mut uuid_buf = [0u8; 16];
+            r.read_exact(&mut uuid_buf)?;
+            let uuid = Uuid::from_slice(&uuid_buf)?;
+            let tag = if tagged {
+                let len = read_serialized_unsigned(r)? as usize;
+                let tag = String::from_utf8_lossy(&read_nbytes(r, len)?).into_owned();
+                if tag.is_empty() {
+                    None
+                } else {
+                    Some(tag.parse()?)
+                }
+            } else {
+                None
+            };
+            let num_intervals = r.read_u64::<LittleEndian>()?;
+            for _ in 0..num_intervals {
+                let start = r.read_u64::<LittleEndian>()?;
+                let end = r.read_u64::<LittleEndian>()?;
+                if start == 0 || end <= start {
+                    return Err(
+                        GtidParseError::InvalidInterval(start, end.saturating_sub(1)).into(),
+                    );
+                }
+                set.add_interval(uuid, tag, GtidInterval { start, end });
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut last_uuid = None;
+        for ((uuid, tag), intervals) in self.intervals.iter() {
+            if last_uuid != Some(uuid) {
+                if last_uuid.is_some() {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", uuid.hyphenated())?;
+                last_uuid = Some(uuid);
+            }
+            if let Some(tag) = tag {
+                write!(f, ":{}", tag)?;
+            }
+            for interval in intervals {
+                write!(f, ":{}", interval)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GtidSet {
+    type Err = GtidParseError;
+
+    /// Parse the textual representation used by MySQL (e.g. the value of `gtid_executed`),
+    /// including tagged intervals such as `uuid:1-5:batch:1-3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = GtidSet::new();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let mut pieces = part.split(':');
+            let uuid = Uuid::parse_str(pieces.next().unwrap_or(""))?;
+            let mut tag = None;
+            let mut any = false;
+            for interval in pieces.map(str::trim) {
+                if interval.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                    tag = Some(interval.parse()?);
+                    any = false;
+                    continue;
+                }
+                let (first, last) = match interval.split_once('-') {
+                    Some((first, last)) => (first, last),
+                    None => (interval, interval),
+                };
+                let first = first
+                    .trim()
+                    .parse()
+                    .map_err(|_| GtidParseError::InvalidFormat(part.to_owned()))?;
+                let last = last
+                    .trim()
+                    .parse()
+                    .map_err(|_| GtidParseError::InvalidFormat(part.to_owned()))?;
+                set.add_interval(uuid, tag, GtidInterval::new(first, last)?);
+                any = true;
+            }
+            if !any {
+                return Err(GtidParseError::InvalidFormat(part.to_owned()));
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl serde::Serialize for GtidSet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for GtidSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{Gtid, GtidSet, GtidTag};
+
+    const UUID_A: &str = "87cee3a4-6b31-11e7-bdfd-0d98d6698870";
+    const UUID_B: &str = "9b5ce1c4-0b5e-11e9-a0d3-0242ac110002";
+
+    #[test]
+    fn test_normalize() {
+        let set = format!("{}:1-5:3-8:10:9", UUID_A)
+            .parse::<GtidSet>()
+            .unwrap();
+        assert_eq!(set.to_string(), format!("{}:1-10", UUID_A));
+        assert_eq!(set.len(), 10);
+    }
+
+    #[test]
+    fn test_parse_gtid() {
+        let gtid = format!("{}:23", UUID_A).parse::<Gtid>().unwrap();
+        assert_eq!(gtid.uuid().hyphenated().to_string(), UUID_A);
+        assert_eq!(gtid.sequence(), 23);
+        assert!(gtid.tag().is_none());
+        assert_eq!(gtid.to_string(), format!("{}:23", UUID_A));
+
+        let tagged = Gtid::try_from(format!("{}:Batch:7", UUID_A).as_str()).unwrap();
+        assert_eq!(tagged.tag().map(|t| t.as_str()), Some("batch"));
+        assert_eq!(tagged.sequence(), 7);
+
+        for bad in &["", "23", "nonsense:1", ":1"] {
+            assert!(bad.parse::<Gtid>().is_err(), "{}", bad);
+        }
+        for bad in &[
+            ":0",
+            ":18446744073709551615",
+            ":1-5",
+            ":x",
+            ":batch",
+            ":batch:1:2",
+        ] {
+            assert!(
+                format!("{}{}", UUID_A, bad).parse::<Gtid>().is_err(),
+                "{}",
+                bad
+            );
+        }
+
+        // ordered by source and then sequence, and usable as a map key
+        let later = format!("{}:100", UUID_A).parse::<Gtid>().unwrap();
+        let other = format!("{}:1", UUID_B).parse::<Gtid>().unwrap();
+        assert!(gtid < later);
+        assert!(later < other);
+        let mut seen = std::collections::HashSet::new();
+        assert!(seen.insert(gtid));
+        assert!(!seen.insert(format!("{}:23", UUID_A).parse().unwrap()));
+    }
+
+    #[test]
+    fn test_insert() {
+        let uuid = UUID_A.parse().unwrap();
+        let mut set = GtidSet::new();
+        for seq in &[1, 2, 3, 7, 5, 6] {
+            set.insert(&Gtid::new(uuid, None, *seq));
+        }
+        assert_eq!(set.to_string(), format!("{}:1-3:5-7", UUID_A));
+        assert!(set.contains(&Gtid::new(uuid, None, 6)));
+        assert!(!set.contains(&Gtid::new(uuid, None, 4)));
+        set.insert(&Gtid::new(uuid, None, 4));
+        assert_eq!(set.to_string(), format!("{}:1-7", UUID_A));
+
+        // the last sequence number can't be represented
+        set.insert(&Gtid::new(uuid, None, u64::MAX));
+        assert_eq!(set.to_string(), format!("{}:1-7", UUID_A));
+        let mut set = GtidSet::new();
+        set.insert(&Gtid::new(uuid, None, u64::MAX - 1));
+        set.insert(&Gtid::new(uuid, None, u64::MAX));
+        assert_eq!(set.len(), 1);
+        let mut set = GtidSet::new();
+        set.insert(&Gtid::new(uuid, None, u64::MAX));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let later = format!("{}:1-100,{}:1-10", UUID_A, UUID_B)
+            .parse::<GtidSet>()
+            .unwrap();
+        let earlier = format!("{}:1-10:20-30:95-200", UUID_A)
+            .parse::<GtidSet>()
+            .unwrap();
+        let delta = later.added_since(&earlier);
+        assert_eq!(
+            delta.to_string(),
+            format!("{}:11-19:31-94,{}:1-10", UUID_A, UUID_B)
+        );
+        assert!(!later.is_subset(&earlier));
+        let mut merged = earlier.clone();
+        merged.union(&delta);
+        assert!(later.is_subset(&merged));
+        assert!(later.diff(&later).is_empty());
+    }
+
+    #[test]
+    fn test_tagged() {
+        let set = format!("{}:1-5:Batch:3:1-2,{}:7", UUID_A, UUID_B)
+            .parse::<GtidSet>()
+            .unwrap();
+        assert_eq!(
+            set.to_string(),
+            format!("{}:1-5:batch:1-3,{}:7", UUID_A, UUID_B)
+        );
+        assert_eq!(set.len(), 9);
+        assert_eq!(set.uuids().count(), 2);
+        let uuid = UUID_A.parse().unwrap();
+        let tag = "batch".parse::<GtidTag>().unwrap();
+        let gtid = Gtid::new(uuid, Some(tag), 3);
+        assert_eq!(gtid.to_string(), format!("{}:batch:3", UUID_A));
+        assert!(set.contains(&gtid));
+        assert!(!set.contains(&Gtid::new(uuid, Some(tag), 4)));
+        assert!(set.contains(&Gtid::new(uuid, None, 4)));
+        assert!("9batch".parse::<GtidTag>().is_err());
+        assert!("a".repeat(33).parse::<GtidTag>().is_err());
+        assert!(format!("{}:batch", UUID_A).parse::<GtidSet>().is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        for text in &[
+            format!("{}:1-5:7,{}:3", UUID_A, UUID_B),
+            format!("{}:1-5:batch:3", UUID_A),
+        ] {
+            let set = text.parse::<GtidSet>().unwrap();
+            let encoded = set.encode();
+            assert_eq!(GtidSet::read_from(&mut encoded.as_slice()).unwrap(), set);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Partial updates of JSON columns.
+//!
+//! With `binlog_row_value_options=PARTIAL_JSON`, MySQL 8.0 logs updates which only modify part of
+//! a JSON document (with `JSON_SET`, `JSON_REPLACE`, or `JSON_REMOVE`) in a
+//! PARTIAL_UPDATE_ROWS_EVENT, as a list of [`JsonDiff`]s against the before-image. If the
+//! before-image of the column is in the event, the diffs are applied to it and the after-image
+//! is an ordinary [`MySQLValue::Json`](crate::value::MySQLValue::Json); otherwise it is a
+//! [`MySQLValue::JsonDiff`](crate::value::MySQLValue::JsonDiff).
+
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::errors::JsonbParseError;
+use crate::jsonb;
+use crate::packet_helpers::{read_nbytes, read_packed_unsigned};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonDiffOperation {
+    /// Replace the value at the path, which must exist
+    Replace,
+    /// Add a member to an object, or insert an element into an array before the given index
+    Insert,
+    /// Remove the value at the path
+    Remove,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// One modification of a JSON document
+pub struct JsonDiff {
+    pub operation: JsonDiffOperation,
+    /// The location of the modification, as a MySQL JSON path (e.g. `$.a[1]`)
+    pub path: String,
+    /// The new value (absent for [`Remove`](JsonDiffOperation::Remove))
+    pub value: Option<JsonValue>,
+}
+
+/// Read the diffs for one column: a 4-byte length, then that many bytes of
+/// (operation, packed-length path, packed-length JSONB value) entries
+pub(crate) fn read_json_diffs<R: Read>(r: &mut R) -> Result<Vec<JsonDiff>, JsonbParseError> {
+    let length = r.read_u32::<LittleEndian>()? as usize;
+    let data = read_nbytes(r, length)?;
+    let mut data = data.as_slice();
+    let mut diffs = Vec::new();
+    while !data.is_empty() {
+        let operation = match data.read_u8()? {
+            0 => JsonDiffOperation::Replace,
+            1 => JsonDiffOperation::Insert,
+            2 => JsonDiffOperation::Remove,
+            other => return Err(JsonbParseError::InvalidDiffOperation(other)),
+        };
+        let path_length = read_packed_unsigned(&mut data)? as usize;
+        let path = String::from_utf8_lossy(&read_nbytes(&mut data, path_length)?).into_owned();
+        let value = if operation == JsonDiffOperation::Remove {
+            None
+        } else {
+            let value_length = read_packed_unsigned(&mut data)? as usize;
+            Some(jsonb::parse(read_nbytes(&mut data, value_length)?)?)
+        };
+        diffs.push(JsonDiff {
+            operation,
+            path,
+            value,
+        });
+    }
+    Ok(diffs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathLeg {
+    Member(String),
+    Index(usize),
+}
+
+fn parse_path(path: &str) -> Option<Vec<PathLeg>> {
+    let mut rest = path.trim().strip_prefix('$')?;
+    let mut legs = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some(legs);
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            legs.push(PathLeg::Index(after[..end].trim().parse().ok()?));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let after = after.trim_start();
+            if after.starts_with('"') {
+                // a quoted key, with JSON escapes; find the closing quote
+                let mut escaped = false;
+                let end = after
+                    .char_indices()
+                    .skip(1)
+                    .find(|&(_, c)| {
+                        let done = c == '"' && !escaped;
+                        escaped = c == '\\' && !escaped;
+                        done
+                    })?
+                    .0;
+                legs.push(PathLeg::Member(serde_json::from_str(&after[..=end]).ok()?));
+                rest = &after[end + 1..];
+            } else {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                legs.push(PathLeg::Member(after[..end].trim_end().to_owned()));
+                rest = &after[end..];
+            }
+        } else {
+            return None;
+        }
+    }
+}
+
+fn apply_one(document: &mut JsonValue, diff: &JsonDiff) -> Option<()> {
+    let legs = parse_path(&diff.path)?;
+    let (last, parents) = match legs.split_last() {
+        Some(split) => split,
+        None => {
+            // the whole document
+            return match (diff.operation, diff.value.as_ref()) {
+                (JsonDiffOperation::Replace, Some(value)) => {
+                    *document = value.clone();
+                    Some(())
+                }
+                _ => None,
+            };
+        }
+    };
+    let mut parent = document;
+    for leg in parents {
+        parent = match (leg, parent) {
+            (PathLeg::Member(key), JsonValue::Object(map)) => map.get_mut(key)?,
+            (PathLeg::Index(i), JsonValue::Array(array)) => array.get_mut(*i)?,
+            _ => return None,
+        };
+    }
+    match (diff.operation, last, parent) {
+        (JsonDiffOperation::Remove, PathLeg::Member(key), JsonValue::Object(map)) => {
+            map.remove(key).map(drop)
+        }
+        (JsonDiffOperation::Remove, PathLeg::Index(i), JsonValue::Array(array)) => {
+            if *i < array.len() {
+                array.remove(*i);
+                Some(())
+            } else {
+                None
+            }
+        }
+        (JsonDiffOperation::Replace, PathLeg::Member(key), JsonValue::Object(map)) => {
+            *map.get_mut(key)? = diff.value.clone()?;
+            Some(())
+        }
+        (JsonDiffOperation::Replace, PathLeg::Index(i), JsonValue::Array(array)) => {
+            *array.get_mut(*i)? = diff.value.clone()?;
+            Some(())
+        }
+        (JsonDiffOperation::Insert, PathLeg::Member(key), JsonValue::Object(map)) => {
+            map.insert(key.clone(), diff.value.clone()?);
+            Some(())
+        }
+        (JsonDiffOperation::Insert, PathLeg::Index(i), JsonValue::Array(array)) => {
+            let i = (*i).min(array.len());
+            array.insert(i, diff.value.clone()?);
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+/// Apply diffs, in order, to a copy of a JSON document
+pub fn apply_json_diffs(
+    document: &JsonValue,
+    diffs: &[JsonDiff],
+) -> Result<JsonValue, JsonbParseError> {
+    let mut document = document.clone();
+    for diff in diffs {
+        apply_one(&mut document, diff)
+            .ok_or_else(|| JsonbParseError::InvalidDiffPath(diff.path.clone()))?;
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        apply_json_diffs, parse_path, read_json_diffs, JsonDiff, JsonDiffOperation, PathLeg,
+    };
+    use crate::errors::JsonbParseError;
+
+    fn diff(
+        operation: JsonDiffOperation,
+        path: &str,
+        value: Option<serde_json::Value>,
+    ) -> JsonDiff {
+        JsonDiff {
+            operation,
+            path: path.to_owned(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("$"), Some(vec![]));
+        assert_eq!(
+            parse_path("$.a[2].\"b.c\\\"d\""),
+            Some(vec![
+                PathLeg::Member("a".to_owned()),
+                PathLeg::Index(2),
+                PathLeg::Member("b.c\"d".to_owned()),
+            ])
+        );
+        assert_eq!(parse_path("a.b"), None);
+        assert_eq!(parse_path("$[x]"), None);
+    }
+
+    #[test]
+    fn test_read_json_diffs() {
+        let mut data = vec![];
+        // REPLACE $.a with the int16 7
+        data.extend_from_slice(&[0, 3, b'$', b'.', b'a', 3, 5, 7, 0]);
+        // REMOVE $[1]
+        data.extend_from_slice(&[2, 4, b'$', b'[', b'1', b']']);
+        let mut column = (data.len() as u32).to_le_bytes().to_vec();
+        column.extend(data);
+        let diffs = read_json_diffs(&mut column.as_slice()).unwrap();
+        assert_eq!(
+            diffs,
+            vec![
+                diff(JsonDiffOperation::Replace, "$.a", Some(json!(7))),
+                diff(JsonDiffOperation::Remove, "$[1]", None),
+            ]
+        );
+
+        let bad = [1, 0, 0, 0, 9];
+        assert!(matches!(
+            read_json_diffs(&mut &bad[..]),
+            Err(JsonbParseError::InvalidDiffOperation(9))
+        ));
+    }
+
+    #[test]
+    fn test_apply() {
+        let before = json!({"a": 1, "b": [1, 2, 3], "c": {"d": true}});
+        let after = apply_json_diffs(
+            &before,
+            &[
+                diff(JsonDiffOperation::Replace, "$.a", Some(json!("x"))),
+                diff(JsonDiffOperation::Insert, "$.b[1]", Some(json!(9))),
+                diff(JsonDiffOperation::Insert, "$.b[10]", Some(json!(10))),
+                diff(JsonDiffOperation::Remove, "$.b[0]", None),
+                diff(JsonDiffOperation::Remove, "$.c.d", None),
+                diff(JsonDiffOperation::Insert, "$.e", Some(json!(null))),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            after,
+            json!({"a": "x", "b": [9, 2, 3, 10], "c": {}, "e": null})
+        );
+        assert!(matches!(
+            apply_json_diffs(
+                &before,
+                &[diff(JsonDiffOperation::Replace, "$.z.y", Some(json!(1)))]
+            ),
+            Err(JsonbParseError::InvalidDiffPath(ref p)) if p == "$.z.y"
+        ));
+    }
+}
@@ -119,11 +119,14 @@ fn parse_compound(
             cursor.read_u32::<LittleEndian>()?,
         ),
     };
+    // every element takes at least a byte, so a corrupt count can't preallocate more than the
+    // document's length
     let elems = elems as usize;
+    let capacity = elems.min(cursor.get_ref().len());
     let key_offsets = match compound_type {
         CompoundType::Array => None,
         CompoundType::Object => {
-            let mut offsets = Vec::with_capacity(elems);
+            let mut offsets = Vec::with_capacity(capacity);
             for _ in 0..elems {
                 let offset = match compound_size {
                     CompoundSize::Small => u32::from(cursor.read_u16::<LittleEndian>()?),
@@ -136,14 +139,14 @@ fn parse_compound(
         }
     };
     let value_offsets = {
-        let mut offsets = Vec::with_capacity(elems);
+        let mut offsets = Vec::with_capacity(capacity);
         for _ in 0..elems {
             offsets.push(parse_maybe_inlined_value(cursor, compound_size)?);
         }
         offsets
     };
     let keys = if let Some(key_offsets) = key_offsets {
-        let mut keys = Vec::with_capacity(elems);
+        let mut keys = Vec::with_capacity(capacity);
         for (_, size) in key_offsets.into_iter() {
             let key = packet_helpers::read_nbytes(cursor, size)?;
             let key = String::from_utf8_lossy(&key).into_owned();
@@ -154,12 +157,18 @@ fn parse_compound(
         None
     };
     let values = {
-        let mut values = Vec::with_capacity(elems);
+        let mut values = Vec::with_capacity(capacity);
         for (field_type, offset_or_inlined) in value_offsets.into_iter() {
             let val = match offset_or_inlined {
                 OffsetOrInline::Inline(v) => v,
                 OffsetOrInline::Offset(o) => {
-                    assert!(u64::from(o) + start_offset == cursor.position());
+                    let expected = u64::from(o) + start_offset;
+                    if expected != cursor.position() {
+                        return Err(JsonbParseError::InvalidOffset {
+                            expected,
+                            actual: cursor.position(),
+                        });
+                    }
                     let type_indicator = FieldType::from_byte(field_type)?;
                     parse_any_with_type_indicator(cursor, type_indicator)?
                 }
@@ -169,7 +178,7 @@ fn parse_compound(
         values
     };
     Ok(if let Some(keys) = keys {
-        let map = JsonMap::from_iter(keys.into_iter().zip(values.into_iter()));
+        let map = JsonMap::from_iter(keys.into_iter().zip(values));
         JsonValue::Object(map)
     } else {
         JsonValue::Array(values)
@@ -238,7 +247,7 @@ fn parse_any_with_type_indicator(
              * MySQL 8.0
              */
             let raw_mysql_column_type = cursor.read_u8()?;
-            let column_type = ColumnType::from_byte(raw_mysql_column_type);
+            let column_type = ColumnType::from_byte(raw_mysql_column_type)?;
             let payload = packet_helpers::read_variable_length_bytes(&mut cursor)?;
             match column_type {
                 ColumnType::NewDecimal(..)
@@ -344,7 +353,7 @@ mod tests {
         let parsed = parse(blob).expect("should parse");
         assert_eq!(
             parsed,
-            json!({"date": null,"datetime":{"DateTime":{"day":7,"hour":82,"minute":69,"month":78,"second":44,"subsecond":0,"year":184640201}},"time":{"Time":{"hours":0,"minutes":0,"seconds":0,"subseconds":0}},"timestamp":{"Timestamp":{"subsecond":0,"unix_time":1291845632}}})
+            json!({"date": null,"datetime":{"DateTime":{"day":7,"hour":82,"minute":69,"month":78,"second":44,"subsecond":0,"fsp":0,"year":184640201}},"time":{"Time":{"negative":false,"hours":0,"minutes":0,"seconds":0,"subseconds":0,"fsp":0}},"timestamp":{"Timestamp":{"subsecond":0,"fsp":0,"unix_time":1291845632}}})
         );
     }
 }
@@ -2,8 +2,14 @@
 //!
 //! # Limitations
 //!
-//! - Targets Percona and Oracle MySQL 5.6 and 5.7. Has not been tested with MariaDB, MySQL 8.0, or older versions of MySQL
-//! - Like all 5.6/5.7 MySQL implementations, UNSIGNED BIGINT cannot safely represent numbers between `2^63` and `2^64` because `i64` is used internally for all integral data types
+//! - Targets Percona and Oracle MySQL 5.6, 5.7 and 8.0, including the 8.0 optional table map
+//!   metadata, partial JSON updates and compressed transaction payloads. MariaDB's own event types
+//!   (other than its encryption marker) are not decoded.
+//! - Integer columns are only decoded as [`MySQLValue::UnsignedInteger`](value::MySQLValue::UnsignedInteger)
+//!   if their signedness is known, either from the optional table map metadata MySQL 8.0 writes
+//!   or from a [`SchemaProvider`](table_map::SchemaProvider);
+//!   otherwise UNSIGNED columns are decoded as signed integers, so values with the top bit set
+//!   (e.g. BIGINT UNSIGNED values from `2^63` up) come out negative
 //!
 //! # Example
 //!
@@ -15,171 +21,1340 @@
 //! }
 //! ```
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::{Read, Seek};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+#[cfg(feature = "alloc-stats")]
+pub mod alloc_stats;
+#[cfg(feature = "async")]
+pub mod async_binlog;
+#[cfg(feature = "avro")]
+pub mod avro;
 pub mod binlog_file;
 mod bit_set;
+pub mod catalog;
+pub mod charset;
+pub mod checksum;
+pub mod column_stats;
 pub mod column_types;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod copy;
+pub mod corpus;
+pub mod debezium;
+pub mod diagnostics;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod errors;
 pub mod event;
+pub mod expectations;
+pub mod export;
+pub mod flashback;
+#[cfg(feature = "geo")]
+pub mod geometry;
+pub mod gtid;
+pub mod json_diff;
 mod jsonb;
+pub mod maxwell;
+mod pacing;
 mod packet_helpers;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod protocol;
+pub mod reconcile;
+mod sequence;
+pub mod server_version;
+pub mod session;
+pub mod sink;
+pub mod stats;
 pub mod table_map;
 mod tell;
 pub mod value;
+pub mod view;
 
-use event::EventData;
-use serde::Serialize;
+use event::{EventData, TypeCode};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use errors::{BinlogParseError, EventParseError};
+use errors::{BinlogParseError, ErrorKind, ErrorPolicy, EventParseError};
+pub use gtid::{Gtid, GtidSet};
 
-#[derive(Debug, Clone, Copy)]
-/// Global Transaction ID
-pub struct Gtid(uuid::Uuid, u64);
-
-impl serde::Serialize for Gtid {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let serialized = format!("{}:{}", self.0.hyphenated(), self.1);
-        serializer.serialize_str(&serialized)
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+/// Identity of the server which originated a transaction: its `server_id` and, when GTIDs are
+/// enabled, its server UUID
+pub struct SourceIdentity {
+    pub server_id: u32,
+    pub server_uuid: Option<uuid::Uuid>,
 }
 
-impl ToString for Gtid {
-    fn to_string(&self) -> String {
-        format!("{}:{}", self.0.hyphenated(), self.1)
-    }
-}
-
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct LogicalTimestamp {
     last_committed: u64,
     sequence_number: u64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
+// the derived implementations are wrapped by the ones below, to fill in what isn't serialized
+#[serde(remote = "Self")]
 /// A binlog event as returned by [`EventIterator`]. Filters out internal events
 /// like the TableMapEvent and simplifies mapping GTIDs to individual events.
+///
+/// Events can be serialized and deserialized again. Deserialized events have no
+/// [`lazy_rows`](Self::lazy_rows), and their `row_count` is the number of `rows`.
 pub struct BinlogEvent {
     pub type_code: event::TypeCode,
     // warning: Y2038 Problem ahead
     pub timestamp: u32,
     pub gtid: Option<Gtid>,
+    /// Set for events in transactions which have no GTID because they were committed with
+    /// `gtid_mode=OFF` (they still have a `logical_timestamp`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub anonymous: bool,
     pub logical_timestamp: Option<LogicalTimestamp>,
+    /// The commit timestamps, length, and server versions of the transaction, from its GTID
+    /// event; only written by MySQL 8.0 and later
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub schema_name: Option<String>,
+    pub commit_info: Option<event::GtidCommitInfo>,
+    /// The table this event modifies; only set for rows events
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub table_name: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub table_ref: Option<table_map::TableRef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub rows: Vec<event::RowEvent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<String>,
+    /// The default database, thread, and outcome of a QueryEvent's statement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_context: Option<event::QueryContext>,
+    /// Set if this event starts or ends a transaction, so that consumers don't need to match
+    /// `query` against `BEGIN` and `COMMIT`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_marker: Option<event::TransactionMarker>,
+    /// The transaction id from an XidEvent, which marks the commit of the transaction
+    /// identified by `gtid`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xid: Option<u64>,
+    /// Set for IncidentEvents; a [`LostEvents`](event::IncidentType::LostEvents) incident means
+    /// the binlog is missing changes and consumers should resync
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incident: Option<event::Incident>,
+    /// Set for RotateEvents, which end each binlog file (except the last) and name the next one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<event::Rotation>,
+    pub offset: u64,
+    /// Size of the source event in bytes, including its header
+    pub event_length: u32,
+    /// The `server_id` of the server which originated the event, for attributing changes when
+    /// several sources replicate into one binlog
+    pub server_id: u32,
+    /// The flags from the event's header (e.g. `LOG_EVENT_ARTIFICIAL_F`)
+    pub flags: u16,
+    /// The offset of the event after this one, as recorded in its header (in a relay log, the
+    /// position in the source's binlog)
+    pub next_position: u64,
+    /// Number of rows in the source event (or in this part of it, if it was split). Unlike
+    /// `rows.len()`, this is populated even when row values were not decoded (see
+    /// [`BinlogFileParserBuilder::summaries_only`]); it is `None` if the rows couldn't be
+    /// counted, because the event's table layout isn't known or its rows are read lazily
+    #[serde(skip)]
+    pub row_count: Option<usize>,
+    /// Descriptions of the columns of the table, for rows events, if the Table Map event or a
+    /// [`SchemaProvider`](table_map::SchemaProvider) named them; `rows` are in the same order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Arc<[table_map::ColumnDescriptor]>>,
+    /// The types of the columns of the table, for rows events whose Table Map event was seen;
+    /// not serialized (see [`export::dedup_tables`])
+    #[serde(skip)]
+    pub column_types: Option<Arc<[column_types::ColumnType]>>,
+    /// Set if the source rows event was split into several `BinlogEvent`s (see
+    /// [`BinlogFileParserBuilder::max_rows_per_event`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split: Option<EventSplit>,
+    /// Where to resume reading to continue after this event; see [`BinlogPosition`]. Set by the
+    /// iterators which read events, but not by deserialization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<BinlogPosition>,
+    /// The value returned for this event by an [`EventDecoder`](event::EventDecoder) registered
+    /// with [`BinlogFileParserBuilder::event_decoder`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom: Option<serde_json::Value>,
+    /// The undecoded rows of a rows event, when reading with
+    /// [`BinlogFileParserBuilder::lazy_rows`]; `rows` is then empty. Use
+    /// [`rows_iter`](Self::rows_iter) to read rows either way.
+    #[serde(skip)]
+    pub lazy_rows: Option<event::LazyRows>,
+}
+
+impl Serialize for BinlogEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BinlogEvent::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BinlogEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut event = BinlogEvent::deserialize(deserializer)?;
+        event.row_count = Some(event.rows.len());
+        // deleted rows serialize the same way as inserted ones
+        if view::RowChangeKind::from_type_code(event.type_code) == Some(view::RowChangeKind::Delete)
+        {
+            for row in event.rows.iter_mut() {
+                if let event::RowEvent::NewRow { cols } = row {
+                    *row = event::RowEvent::DeletedRow {
+                        cols: std::mem::take(cols),
+                    };
+                }
+            }
+        }
+        Ok(event)
+    }
+}
+
+/// Iterator over the rows of a [`BinlogEvent`]; see [`BinlogEvent::rows_iter`]
+pub enum RowsIter<'a> {
+    Decoded(std::slice::Iter<'a, event::RowEvent>),
+    Lazy(event::LazyRowsIter<'a>),
+}
+
+impl<'a> Iterator for RowsIter<'a> {
+    type Item = Result<Cow<'a, event::RowEvent>, EventParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            RowsIter::Decoded(rows) => rows.next().map(|row| Ok(Cow::Borrowed(row))),
+            RowsIter::Lazy(rows) => rows.next().map(|row| row.map(Cow::Owned)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Which part of a split rows event a [`BinlogEvent`] holds. Every part has the same offset,
+/// GTID, and table as the source event.
+pub struct EventSplit {
+    /// Zero-based index of this part
+    pub index: u32,
+    /// Total number of parts the source event was split into
+    pub count: u32,
+}
+
+impl BinlogEvent {
+    /// The rows of this event, decoding them one at a time if they were read lazily
+    pub fn rows_iter(&self) -> RowsIter<'_> {
+        match self.lazy_rows {
+            Some(ref lazy) => RowsIter::Lazy(lazy.iter()),
+            None => RowsIter::Decoded(self.rows.iter()),
+        }
+    }
+
+    /// The event which undoes this one, for flashback-style rollbacks: inserts become deletes,
+    /// deletes become inserts, and updates have their before and after images swapped. The rows
+    /// are reversed too, so that applying them in order undoes the last change first. Events
+    /// which aren't rows events are returned unchanged.
+    ///
+    /// Lazily-read rows are decoded; summaries (which have no rows) stay empty. Inverting a
+    /// [`PartialUpdateRowsEvent`](TypeCode::PartialUpdateRowsEvent) gives an
+    /// `UpdateRowsEventV2` whose before-image holds the original's
+    /// [`JsonDiff`](value::MySQLValue::JsonDiff)s.
+    pub fn invert(mut self) -> Result<BinlogEvent, EventParseError> {
+        self.type_code = match self.type_code {
+            TypeCode::WriteRowsEventV1 => TypeCode::DeleteRowsEventV1,
+            TypeCode::DeleteRowsEventV1 => TypeCode::WriteRowsEventV1,
+            TypeCode::WriteRowsEventV2 => TypeCode::DeleteRowsEventV2,
+            TypeCode::DeleteRowsEventV2 => TypeCode::WriteRowsEventV2,
+            TypeCode::UpdateRowsEventV1 => TypeCode::UpdateRowsEventV1,
+            TypeCode::UpdateRowsEventV2 | TypeCode::PartialUpdateRowsEvent => {
+                TypeCode::UpdateRowsEventV2
+            }
+            _ => return Ok(self),
+        };
+        if self.lazy_rows.is_some() {
+            self.rows = self
+                .rows_iter()
+                .map(|row| row.map(Cow::into_owned))
+                .collect::<Result<_, _>>()?;
+            self.lazy_rows = None;
+        }
+        self.rows = std::mem::take(&mut self.rows)
+            .into_iter()
+            .rev()
+            .map(event::RowEvent::invert)
+            .collect();
+        if let Some(ref mut split) = self.split {
+            split.index = split.count - 1 - split.index;
+        }
+        Ok(self)
+    }
+
+    /// Summarize this event without any of its row values
+    pub fn summary(&self) -> Summary {
+        Summary {
+            type_code: self.type_code,
+            timestamp: self.timestamp,
+            gtid: self.gtid,
+            table_ref: self.table_ref.clone(),
+            row_count: self.row_count,
+            byte_size: self.event_length,
+            offset: self.offset,
+        }
+    }
+
+    /// View this event as a rows event, if it is one
+    pub fn as_row_change(&self) -> Option<view::RowChange<'_>> {
+        Some(view::RowChange {
+            kind: view::RowChangeKind::from_type_code(self.type_code)?,
+            table: self.table_ref.as_ref()?,
+            rows: &self.rows,
+            gtid: self.gtid,
+            timestamp: self.timestamp,
+            offset: self.offset,
+        })
+    }
+
+    /// View this event as a QueryEvent, if it is one
+    pub fn as_query(&self) -> Option<view::Query<'_>> {
+        if self.type_code != TypeCode::QueryEvent {
+            return None;
+        }
+        Some(view::Query {
+            query: self.query.as_deref()?,
+            schema: self
+                .query_context
+                .as_ref()
+                .map_or("", |context| context.schema.as_str()),
+            gtid: self.gtid,
+            timestamp: self.timestamp,
+            offset: self.offset,
+        })
+    }
+
+    /// View this event as a schema change, if it is a QueryEvent containing a DDL statement
+    pub fn as_ddl(&self) -> Option<view::Ddl<'_>> {
+        let query = self.as_query()?;
+        Some(view::Ddl {
+            kind: view::DdlKind::from_query(query.query)?,
+            query: query.query,
+            schema: query.schema,
+            gtid: query.gtid,
+            timestamp: query.timestamp,
+            offset: query.offset,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Lightweight description of a [`BinlogEvent`] for monitoring purposes; contains no values
+pub struct Summary {
+    pub type_code: event::TypeCode,
+    pub timestamp: u32,
+    pub gtid: Option<Gtid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_ref: Option<table_map::TableRef>,
+    /// `None` (serialized as `null`) if the number of rows isn't known
+    pub row_count: Option<usize>,
+    pub byte_size: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A checkpoint from which reading can resume with
+/// [`BinlogFileParserBuilder::resume_from`], as attached to every [`BinlogEvent`] read from a
+/// binlog. Resuming from an event's position continues with the event after it.
+pub struct BinlogPosition {
+    /// The name of the binlog file (without its directory), if the events were read from a file
+    pub file_name: Option<String>,
+    /// The offset in that file of the event to resume from
     pub offset: u64,
+    /// Every transaction which is complete at this point, including those executed before the
+    /// file began
+    pub gtid_set: Arc<GtidSet>,
+}
+
+/// Computes the [`BinlogPosition`] of each event returned by an [`EventIterator`]
+#[derive(Default)]
+struct PositionTracker {
+    file_name: Option<String>,
+    // a transaction was started with BEGIN and hasn't ended yet
+    in_transaction: bool,
+    gtid_set: Arc<GtidSet>,
+    // what `gtid_set` was computed from: the number of GTIDs seen, the size of the current
+    // file's PreviousGtids, and the incomplete transaction left out
+    computed_from: (u64, Option<u64>, Option<Gtid>),
+}
+
+impl PositionTracker {
+    fn start_file(&mut self, file_name: Option<&Path>) {
+        self.file_name = file_name
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+
+    /// The position after `event`, which ends at `end`
+    fn position(
+        &mut self,
+        event: &BinlogEvent,
+        end: u64,
+        session: &session::ParseSession,
+    ) -> BinlogPosition {
+        let complete = match event.transaction_marker {
+            Some(event::TransactionMarker::Begin) => {
+                self.in_transaction = true;
+                false
+            }
+            Some(_) => {
+                self.in_transaction = false;
+                end != event.offset
+            }
+            // a DDL statement is a transaction of its own
+            None => event.type_code == TypeCode::QueryEvent && !self.in_transaction,
+        };
+        let incomplete = if complete { None } else { event.gtid };
+        let computed_from = (
+            session.gtids_seen().len(),
+            session.previous_gtids().map(GtidSet::len),
+            incomplete,
+        );
+        if computed_from != self.computed_from {
+            let mut gtid_set = session.gtids_seen().clone();
+            if let Some(previous) = session.previous_gtids() {
+                gtid_set.union(previous);
+            }
+            if let Some(ref gtid) = incomplete {
+                let mut current = GtidSet::new();
+                current.insert(gtid);
+                gtid_set = gtid_set.diff(&current);
+            }
+            self.gtid_set = Arc::new(gtid_set);
+            self.computed_from = computed_from;
+        }
+        BinlogPosition {
+            file_name: self.file_name.clone(),
+            offset: end,
+            gtid_set: Arc::clone(&self.gtid_set),
+        }
+    }
+}
+
+/// Where to begin emitting transactions, by GTID
+#[derive(Debug)]
+enum StartAfter {
+    /// After this transaction
+    Gtid(Gtid),
+    /// At the first transaction not in this set
+    GtidSet(GtidSet),
+}
+
+#[derive(Debug, Default)]
+struct IteratorOptions {
+    start_position: Option<u64>,
+    stop_position: Option<u64>,
+    stop_timestamp: Option<u32>,
+    summaries_only: bool,
+    metadata_only: bool,
+    lazy_rows: bool,
+    catalog: Option<(catalog::SchemaCatalog, catalog::MismatchPolicy)>,
+    // number of checksum worker threads; None if checksums are not being verified
+    checksum_threads: Option<usize>,
+    error_policy: errors::ErrorPolicy,
+    recover_corruption: bool,
+    table_ref_format: table_map::TableRefFormat,
+    table_name_case: table_map::TableNameCase,
+    executed_gtids: Option<GtidSet>,
+    start_after: Option<StartAfter>,
+    // tables by schema; None to include every table
+    include_tables: Option<HashMap<String, HashSet<String>>>,
+    exclude_schemas: HashSet<String>,
+    decode: event::DecodeOptions,
+    pace_to_realtime: bool,
+    collect_column_stats: bool,
+    max_rows_per_event: Option<usize>,
+    changed_columns_only: bool,
+    strict_table_map: bool,
+    io_errors_end_stream: bool,
+    diagnostics: Option<diagnostics::DiagnosticHandler>,
+    event_decoders: event::EventDecoders,
+    max_event_size: Option<u32>,
+    // the offset to resume from, after priming the table map from the start of the file
+    resume_offset: Option<u64>,
+    schema_provider: Option<Box<dyn table_map::SchemaProvider>>,
+    active_file: bool,
+    // how long to wait before checking for more events when following; None to stop instead
+    follow: Option<Duration>,
+    // implementations of checksum algorithms other than CRC32, by their FDE id
+    checksums: HashMap<u8, Arc<dyn checksum::Checksum>>,
 }
 
 /// Iterator over [`BinlogEvent`]s
 pub struct EventIterator<BR: Read + Seek> {
     events: binlog_file::BinlogEvents<BR>,
-    table_map: table_map::TableMap,
-    current_gtid: Option<Gtid>,
-    logical_timestamp: Option<LogicalTimestamp>,
+    files: Option<sequence::FileSequence<BR>>,
+    verifier: Option<checksum::ChecksumVerifier>,
+    processor: EventProcessor,
+    // events before this offset are only read to prime the table map
+    priming_until: Option<u64>,
+}
+
+/// Iterator over the [`BinlogEvent`]s of a sequence of binlog files on disk, as returned by
+/// [`parse_directory`]
+pub type BinlogDirectoryIterator = EventIterator<File>;
+
+/// Turns raw events into [`BinlogEvent`]s, wherever the raw events come from
+struct EventProcessor {
+    session: session::ParseSession,
+    // the current transaction is in `executed_gtids`, comes before `start_after`, or failed
+    skipping_transaction: bool,
+    // transactions are being skipped until `start_after` is reached
+    waiting_for_start: bool,
+    pacer: Option<pacing::Pacer>,
+    column_stats: Option<column_stats::StatsCollector>,
+    // events unpacked from the most recent TRANSACTION_PAYLOAD_EVENT which are yet to be read
+    payload_events: VecDeque<event::Event>,
+    // the remaining parts of a rows event which was split by `max_rows_per_event`
+    split_events: VecDeque<BinlogEvent>,
+    // a copy of the decode options for each lazily-read rows event to share
+    lazy_decode: Option<Arc<event::DecodeOptions>>,
+    positions: PositionTracker,
+    options: IteratorOptions,
 }
 
 impl<BR: Read + Seek> EventIterator<BR> {
-    fn new(bf: binlog_file::BinlogFile<BR>, start_offset: Option<u64>) -> Self {
+    fn new(
+        mut bf: binlog_file::BinlogFile<BR>,
+        mut files: Option<sequence::FileSequence<BR>>,
+        session: session::ParseSession,
+        options: IteratorOptions,
+    ) -> Self {
+        if let Some(ref mut files) = files {
+            if options.follow.is_some() {
+                files.follow_rotations();
+            }
+        }
+        let algorithm = bf.format().checksum_algorithm;
+        let verifier = options.checksum_threads.map(|threads| {
+            checksum::ChecksumVerifier::new(
+                threads,
+                checksum::resolve(algorithm, &options.checksums),
+            )
+        });
+        bf.set_max_event_size(options.max_event_size);
+        let mut processor = EventProcessor::new(session, options);
+        processor.session.start_file(bf.format_description());
+        processor.positions.start_file(bf.file_name());
+        let priming_until = processor.options.resume_offset;
+        let start = match priming_until {
+            Some(_) => None,
+            None => processor.options.start_position,
+        };
         EventIterator {
-            events: bf.events(start_offset),
-            table_map: table_map::TableMap::new(),
-            current_gtid: None,
-            logical_timestamp: None,
+            events: bf.events(start),
+            files,
+            verifier,
+            processor,
+            priming_until,
         }
     }
+
+    /// Counters describing the work done by this iterator so far
+    pub fn stats(&self) -> stats::ParserStats {
+        let mut stats = self.processor.session.stats;
+        if let Some(ref verifier) = self.verifier {
+            verifier.update_stats(&mut stats);
+        }
+        stats
+    }
+
+    /// Statistics for every column decoded so far, if enabled with
+    /// [`BinlogFileParserBuilder::collect_column_stats`]
+    pub fn column_stats(&self) -> Option<&column_stats::StatsCollector> {
+        self.processor.column_stats.as_ref()
+    }
+
+    /// The tables described by every Table Map event read so far
+    pub fn table_map(&self) -> &table_map::TableMap {
+        self.processor.session.table_map()
+    }
+
+    /// The state of the parser; see [`ParseSession`](session::ParseSession)
+    pub fn session(&self) -> &session::ParseSession {
+        &self.processor.session
+    }
+
+    /// Stop reading, returning the state of the parser so that it can be carried over to the
+    /// next file or stream with [`BinlogFileParserBuilder::session`]
+    pub fn into_session(mut self) -> session::ParseSession {
+        self.processor.session.stats = self.stats();
+        self.processor.session
+    }
+
+    /// Identity of the server which originated the most recently read transaction
+    pub fn source_identity(&self) -> Option<&SourceIdentity> {
+        self.processor.session.source_identity()
+    }
+
+    /// Every distinct source identity seen so far, in the order they were first encountered.
+    /// More than one entry usually means the source was switched (for example, after a
+    /// failover).
+    pub fn sources_seen(&self) -> &[SourceIdentity] {
+        self.processor.session.sources_seen()
+    }
+
+    /// The GTIDs executed before the start of the current binlog file, from its
+    /// PreviousGtidsLogEvent (or `None` if the file has none, or it hasn't been read yet)
+    pub fn previous_gtids(&self) -> Option<&GtidSet> {
+        self.processor.session.previous_gtids()
+    }
+
+    /// The FormatDescriptionEvent of the current binlog file, which gives the version of the
+    /// server which wrote it and its checksum algorithm
+    pub fn format_description(&self) -> Option<&event::FormatDescription> {
+        self.processor.session.format_description()
+    }
+
+    /// The position reported by the most recent heartbeat, if any. Heartbeats only arrive when
+    /// streaming from a server, while it has no other events to send; the position is a safe
+    /// checkpoint if every event before it has been handled.
+    pub fn last_heartbeat(&self) -> Option<&event::Heartbeat> {
+        self.processor.session.last_heartbeat()
+    }
 }
 
 impl<BR: Read + Seek> Iterator for EventIterator<BR> {
     type Item = Result<BinlogEvent, EventParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for event in self.events.by_ref() {
-            let event = match event {
-                Ok(event) => event,
+        #[cfg(feature = "alloc-stats")]
+        let before = alloc_stats::Counters::current();
+        let result = loop {
+            match self.next_event() {
+                // skipped ranges are always reported, since they may have contained anything
+                Some(Err(e))
+                    if self.processor.options.error_policy != ErrorPolicy::Abort
+                        && e.is_recoverable()
+                        && !matches!(e, EventParseError::SkippedRange { .. }) =>
+                {
+                    diagnostics::report(
+                        self.processor.options.diagnostics.as_ref(),
+                        diagnostics::Diagnostic::SkippedEvent(&e),
+                    );
+                    self.processor.session.stats.events_skipped += 1;
+                    if self.processor.options.error_policy == ErrorPolicy::SkipTransaction {
+                        self.processor.skipping_transaction = true;
+                    }
+                }
+                Some(Ok(mut event)) => {
+                    let next_offset = self.events.offset();
+                    event.position = Some(self.processor.position_after(&event, next_offset));
+                    break Some(Ok(event));
+                }
+                other => break other,
+            }
+        };
+        #[cfg(feature = "alloc-stats")]
+        {
+            let after = alloc_stats::Counters::current();
+            self.processor.session.stats.allocations += after.allocations - before.allocations;
+            self.processor.session.stats.bytes_allocated +=
+                after.bytes_allocated - before.bytes_allocated;
+        }
+        result
+    }
+}
+
+impl<BR: Read + Seek> EventIterator<BR> {
+    /// Whether `event` is past the configured stop position or timestamp
+    fn reached_stop(&self, event: &event::Event) -> bool {
+        let options = &self.processor.options;
+        // like mysqlbinlog, the stop position applies to the last file of a sequence
+        let last_file = self.files.as_ref().is_none_or(|files| files.is_last());
+        let past_position = options
+            .stop_position
+            .is_some_and(|position| last_file && event.offset() >= position);
+        let past_timestamp = options
+            .stop_timestamp
+            .is_some_and(|timestamp| event.timestamp() >= timestamp);
+        past_position || past_timestamp
+    }
+
+    fn next_event(&mut self) -> Option<Result<BinlogEvent, EventParseError>> {
+        if let Some(part) = self.processor.split_events.pop_front() {
+            return Some(Ok(part));
+        }
+        loop {
+            if let Some(e) = self.verifier.as_mut().and_then(|v| v.take_failure()) {
+                return Some(Err(e));
+            }
+            let (event, pending) = if let Some(event) = self.processor.payload_events.pop_front() {
+                // events unpacked from a transaction payload were counted and checked as part of
+                // the payload event
+                (Arc::new(event), false)
+            } else {
+                let start = self.events.offset();
+                let next = if self.processor.options.active_file {
+                    match self.events.poll() {
+                        Ok(Some(event)) => Some(Ok(event)),
+                        // caught up with the writer; try again from the same place next time
+                        Ok(None) if self.events.offset().is_some() => {
+                            match self.processor.options.follow {
+                                Some(interval) => {
+                                    std::thread::sleep(interval);
+                                    continue;
+                                }
+                                None => return None,
+                            }
+                        }
+                        Ok(None) => None,
+                        Err(e) => Some(Err(e)),
+                    }
+                } else {
+                    self.events.next()
+                };
+                let next = match next {
+                    Some(Err(EventParseError::Io(_)))
+                        if self.processor.options.io_errors_end_stream =>
+                    {
+                        self.events.stop();
+                        None
+                    }
+                    next => next,
+                };
+                let event = match next {
+                    Some(Ok(event)) => event,
+                    Some(Err(e)) => match start {
+                        Some(start)
+                            if self.processor.options.recover_corruption
+                                && e.kind() == ErrorKind::Corruption =>
+                        {
+                            let error = match self.events.resynchronize(start) {
+                                Ok(end) => EventParseError::SkippedRange { start, end },
+                                Err(e) => e,
+                            };
+                            return Some(Err(error));
+                        }
+                        _ => return Some(Err(e)),
+                    },
+                    None => {
+                        match self.files.as_mut().and_then(|f| f.advance()) {
+                            Some(Ok(mut bf)) => {
+                                bf.set_max_event_size(self.processor.options.max_event_size);
+                                if let Some(ref mut verifier) = self.verifier {
+                                    let algorithm = bf.format().checksum_algorithm;
+                                    verifier.set_checksum(checksum::resolve(
+                                        algorithm,
+                                        &self.processor.options.checksums,
+                                    ));
+                                }
+                                self.processor.session.start_file(bf.format_description());
+                                self.processor.positions.start_file(bf.file_name());
+                                self.priming_until = None;
+                                self.events = bf.events(None);
+                                continue;
+                            }
+                            Some(Err(e)) => return Some(Err(e)),
+                            None => {}
+                        }
+                        if let Some(interval) = self.processor.options.follow {
+                            if self.files.as_ref().is_some_and(|f| f.awaiting_rotation()) {
+                                std::thread::sleep(interval);
+                                continue;
+                            }
+                        }
+                        return self.verifier.as_mut()?.take_failure().map(Err);
+                    }
+                };
+                if self.reached_stop(&event) {
+                    self.events.stop();
+                    self.files = None;
+                    continue;
+                }
+                self.processor.session.stats.events_read += 1;
+                self.processor.session.stats.bytes_read += u64::from(event.event_length());
+                let event = Arc::new(event);
+                let verification = match self.verifier {
+                    Some(ref mut verifier) => verifier.submit(&event),
+                    None => checksum::Verification::Unverified,
+                };
+                let pending = match verification {
+                    // verified inline, so the corrupt event is never decoded
+                    checksum::Verification::Failed(e) => return Some(Err(e)),
+                    checksum::Verification::Unverified => {
+                        self.processor.session.stats.checksums_unverified += 1;
+                        false
+                    }
+                    checksum::Verification::Verified => false,
+                    checksum::Verification::Pending => true,
+                };
+                if let Some(ref mut files) = self.files {
+                    if let Err(e) = files.observe(&event) {
+                        self.events.stop();
+                        self.files = None;
+                        return Some(Err(e));
+                    }
+                }
+                (event, pending)
+            };
+            let priming = match self.priming_until {
+                Some(resume) if event.offset() < resume => true,
+                Some(_) => {
+                    self.priming_until = None;
+                    false
+                }
+                None => false,
+            };
+            let result = if priming {
+                self.prime(event)
+            } else {
+                self.processor.process(event)
+            };
+            // the event was decoded while its checksum was verified on the worker thread; it
+            // is only returned once it is known to be intact
+            if pending {
+                if let Some(verifier) = self.verifier.as_mut() {
+                    match verifier.wait() {
+                        checksum::Verification::Failed(e) => {
+                            self.processor.split_events.clear();
+                            self.processor.payload_events.clear();
+                            return Some(Err(e));
+                        }
+                        checksum::Verification::Unverified => {
+                            self.processor.session.stats.checksums_unverified += 1;
+                        }
+                        checksum::Verification::Verified | checksum::Verification::Pending => {}
+                    }
+                }
+            }
+            match result {
+                Some(Err(e)) if priming => return Some(Err(e)),
+                Some(result) if !priming => return Some(result),
+                _ => {}
+            }
+        }
+    }
+
+    /// Process an event from before the resume position only for what it tells us about the
+    /// events after it: the tables they refer to and the transaction they're in
+    fn prime(&mut self, event: Arc<event::Event>) -> Option<Result<BinlogEvent, EventParseError>> {
+        match event.type_code() {
+            TypeCode::TableMapEvent
+            | TypeCode::GtidLogEvent
+            | TypeCode::GtidTaggedLogEvent
+            | TypeCode::AnonymousGtidLogEvent
+            | TypeCode::PreviousGtidsLogEvent
+            | TypeCode::FormatDescriptionEvent => self.processor.process(event),
+            _ => None,
+        }
+    }
+}
+
+/// Whether events of this type are written between transactions rather than inside them
+fn between_transactions(type_code: TypeCode) -> bool {
+    matches!(
+        type_code,
+        TypeCode::GtidLogEvent
+            | TypeCode::GtidTaggedLogEvent
+            | TypeCode::AnonymousGtidLogEvent
+            | TypeCode::RotateEvent
+            | TypeCode::FormatDescriptionEvent
+            | TypeCode::PreviousGtidsLogEvent
+            | TypeCode::HeartbeatLogEvent
+            | TypeCode::HeartbeatLogEventV2
+            | TypeCode::IncidentEvent
+            | TypeCode::StopEvent
+            | TypeCode::StartEncryptionEvent
+    )
+}
+
+impl EventProcessor {
+    fn new(mut session: session::ParseSession, mut options: IteratorOptions) -> Self {
+        let name_case = options.table_name_case;
+        options.catalog = options
+            .catalog
+            .map(|(catalog, policy)| (catalog.with_name_case(name_case), policy));
+        options.decode.raw_json.set_name_case(name_case);
+        options.decode.projection.set_name_case(name_case);
+        options.decode.value_decoders.set_name_case(name_case);
+        options.include_tables = options.include_tables.map(|include| {
+            include
+                .into_iter()
+                .map(|(schema, tables)| {
+                    let tables = tables.iter().map(|t| name_case.key(t).into_owned());
+                    (name_case.key(&schema).into_owned(), tables.collect())
+                })
+                .collect()
+        });
+        options.exclude_schemas = std::mem::take(&mut options.exclude_schemas)
+            .iter()
+            .map(|schema| name_case.key(schema).into_owned())
+            .collect();
+        session.table_map = std::mem::take(&mut session.table_map).with_name_case(name_case);
+        let waiting_for_start = options.start_after.is_some();
+        EventProcessor {
+            session,
+            skipping_transaction: waiting_for_start,
+            waiting_for_start,
+            pacer: if options.pace_to_realtime {
+                Some(pacing::Pacer::new())
+            } else {
+                None
+            },
+            column_stats: if options.collect_column_stats {
+                Some(column_stats::StatsCollector::new())
+            } else {
+                None
+            },
+            payload_events: VecDeque::new(),
+            split_events: VecDeque::new(),
+            lazy_decode: if options.lazy_rows {
+                Some(Arc::new(options.decode.clone()))
+            } else {
+                None
+            },
+            positions: PositionTracker::default(),
+            options,
+        }
+    }
+
+    /// Where to resume reading to continue after `event`, given the offset of the next raw event
+    /// (if reading hasn't stopped)
+    fn position_after(&mut self, event: &BinlogEvent, next_offset: Option<u64>) -> BinlogPosition {
+        // the rest of a split event or transaction payload would be lost by resuming after the
+        // event it came from
+        let pending = !self.split_events.is_empty() || !self.payload_events.is_empty();
+        let end = match next_offset {
+            _ if pending => event.offset,
+            Some(end) => end,
+            None => event.offset + u64::from(event.event_length),
+        };
+        self.positions.position(event, end, &self.session)
+    }
+
+    fn rows_event(
+        &self,
+        header: &event::EventHeader,
+        table_id: u64,
+        rows: Vec<event::RowEvent>,
+        row_count: Option<usize>,
+    ) -> BinlogEvent {
+        let maybe_table = self.session.table_map.get(table_id);
+        BinlogEvent {
+            table_ref: maybe_table.map(|a| {
+                table_map::TableRef::new(a.schema_name.as_str(), a.table_name.as_str(), table_id)
+                    .with_format(self.options.table_ref_format)
+            }),
+            rows,
+            row_count,
+            columns: maybe_table.and_then(|t| t.named_descriptors()),
+            column_types: maybe_table.map(|t| Arc::clone(&t.columns)),
+            ..self.binlog_event(header)
+        }
+    }
+
+    /// Whether a rows event's table passes `include_tables` and `exclude_schemas`. Events whose
+    /// table map is missing are kept, since there is nothing to filter them by.
+    fn table_selected(&self, event: &event::Event) -> bool {
+        let table = match event
+            .rows_table_id()
+            .and_then(|table_id| self.session.table_map.get(table_id))
+        {
+            Some(table) => table,
+            None => return true,
+        };
+        let name_case = self.options.table_name_case;
+        let schema = name_case.key(&table.schema_name);
+        if self.options.exclude_schemas.contains(schema.as_ref()) {
+            return false;
+        }
+        match self.options.include_tables {
+            Some(ref include) => include
+                .get(schema.as_ref())
+                .is_some_and(|tables| tables.contains(name_case.key(&table.table_name).as_ref())),
+            None => true,
+        }
+    }
+
+    /// Whether the current transaction comes before the one configured with
+    /// `start_after_gtid(_set)`, noting when that transaction is reached
+    fn before_start(&mut self) -> bool {
+        if !self.waiting_for_start {
+            return false;
+        }
+        match (&self.options.start_after, self.session.current_gtid()) {
+            (Some(StartAfter::Gtid(last)), Some(gtid)) => {
+                if gtid == last {
+                    self.waiting_for_start = false;
+                }
+                true
+            }
+            (Some(StartAfter::GtidSet(set)), Some(gtid)) if !set.contains(gtid) => {
+                self.waiting_for_start = false;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Track the commit lag of a transaction from its GTID event
+    fn start_transaction(
+        &mut self,
+        header: &event::EventHeader,
+        commit_info: &event::GtidCommitInfo,
+    ) {
+        if self.options.active_file {
+            let committed = commit_info
+                .original_commit_timestamp
+                .unwrap_or(u64::from(header.timestamp) * 1_000_000);
+            self.session
+                .stats
+                .record_commit_lag(committed, std::time::SystemTime::now());
+        }
+    }
+
+    /// An event in the current transaction, with none of the optional fields set
+    fn binlog_event(&self, header: &event::EventHeader) -> BinlogEvent {
+        BinlogEvent {
+            offset: header.offset,
+            type_code: header.type_code,
+            timestamp: header.timestamp,
+            gtid: self.session.current_gtid().cloned(),
+            anonymous: self.session.anonymous_transaction(),
+            logical_timestamp: self.session.logical_timestamp(),
+            commit_info: self.session.commit_info().copied(),
+            table_ref: None,
+            rows: Vec::new(),
+            query: None,
+            query_context: None,
+            transaction_marker: None,
+            xid: None,
+            incident: None,
+            rotate: None,
+            custom: None,
+            lazy_rows: None,
+            event_length: header.event_length,
+            server_id: header.server_id,
+            flags: header.flags,
+            next_position: header.next_position,
+            row_count: Some(0),
+            columns: None,
+            column_types: None,
+            split: None,
+            position: None,
+        }
+    }
+
+    /// Split a rows event into parts of at most `max_rows` rows, queueing all but the first
+    fn split_rows_event(
+        &mut self,
+        header: &event::EventHeader,
+        event: BinlogEvent,
+        max_rows: usize,
+    ) -> BinlogEvent {
+        if event.rows.len() <= max_rows {
+            return event;
+        }
+        let count = event.rows.len().div_ceil(max_rows) as u32;
+        let mut rows = event.rows.into_iter();
+        for index in 0..count {
+            let rows: Vec<_> = rows.by_ref().take(max_rows).collect();
+            let part = BinlogEvent {
+                table_ref: event.table_ref.clone(),
+                columns: event.columns.clone(),
+                column_types: event.column_types.clone(),
+                row_count: Some(rows.len()),
+                rows,
+                split: Some(EventSplit { index, count }),
+                ..self.binlog_event(header)
+            };
+            self.split_events.push_back(part);
+        }
+        self.split_events.pop_front().unwrap()
+    }
+
+    /// Decode one raw event, returning `None` if it doesn't produce a [`BinlogEvent`] (some
+    /// events only update the session, and events in skipped transactions are dropped)
+    fn process(
+        &mut self,
+        event: Arc<event::Event>,
+    ) -> Option<Result<BinlogEvent, EventParseError>> {
+        let type_code = event.type_code();
+        self.session.observe_raw(&event);
+        if matches!(
+            type_code,
+            TypeCode::GtidLogEvent | TypeCode::GtidTaggedLogEvent | TypeCode::AnonymousGtidLogEvent
+        ) {
+            if let Some(ref mut pacer) = self.pacer {
+                pacer.wait_for(event.timestamp());
+            }
+        }
+        let skipped = self.skipping_transaction && !between_transactions(type_code);
+        match type_code {
+            // the end of the skipped transaction; a transaction with a GTID is also ended by the
+            // next GTID event, but one without has only its commit
+            TypeCode::XidEvent | TypeCode::XaPrepareLogEvent if skipped => {
+                self.skipping_transaction = false;
+                return None;
+            }
+            TypeCode::QueryEvent if skipped => {
+                if let Ok(Some(EventData::QueryEvent { query, .. })) = event.inner(None) {
+                    if matches!(
+                        event::TransactionMarker::from_query(&query),
+                        Some(event::TransactionMarker::Commit | event::TransactionMarker::Rollback)
+                    ) {
+                        self.skipping_transaction = false;
+                    }
+                }
+                return None;
+            }
+            // nothing else in a skipped transaction is emitted, but its Table Map events still
+            // update the table map
+            TypeCode::TableMapEvent => {}
+            _ if skipped => return None,
+            t if t.is_rows_event() && !self.table_selected(&event) => return None,
+            _ => {}
+        }
+        if let Some(decoder) = self
+            .options
+            .event_decoders
+            .get(type_code)
+            .filter(|_| !skipped)
+        {
+            return match decoder.decode(&event) {
+                Ok(Some(value)) => Some(Ok(BinlogEvent {
+                    custom: Some(value),
+                    ..self.binlog_event(&event.event_header())
+                })),
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            };
+        }
+        if self.options.strict_table_map {
+            if let Some(table_id) = event.rows_table_id() {
+                if self.session.table_map.get(table_id).is_none() {
+                    return Some(Err(EventParseError::UnknownTableId {
+                        table_id,
+                        offset: event.offset(),
+                    }));
+                }
+            }
+        }
+        if (self.options.summaries_only || self.options.metadata_only)
+            && event.type_code().is_rows_event()
+        {
+            let table_map = Some(&self.session.table_map);
+            let summary = if self.options.metadata_only {
+                event.estimate_rows(table_map, &self.options.decode)
+            } else {
+                event.rows_summary(table_map, &self.options.decode)
+            };
+            return match summary {
+                Ok(Some(summary)) => Some(Ok(self.rows_event(
+                    &event.event_header(),
+                    summary.table_id,
+                    Vec::new(),
+                    summary.row_count,
+                ))),
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            };
+        }
+        let event = match self.lazy_decode {
+            Some(ref decode) if event.type_code().is_rows_event() => {
+                let header = event.event_header();
+                match event::LazyRows::new(event, &self.session.table_map, decode.clone()) {
+                    Ok(lazy) => {
+                        let table_id = lazy.table_id();
+                        return Some(Ok(BinlogEvent {
+                            lazy_rows: Some(lazy),
+                            ..self.rows_event(&header, table_id, Vec::new(), None)
+                        }));
+                    }
+                    // without a table map there are no rows to decode
+                    Err(event) => event,
+                }
+            }
+            _ => event,
+        };
+        let header = event.event_header();
+        let data =
+            match event.inner_with_options(Some(&self.session.table_map), &self.options.decode) {
+                Ok(data) => data,
                 Err(e) => return Some(Err(e)),
             };
-            let offset = event.offset();
-            match event.inner(Some(&self.table_map)) {
-                Ok(Some(e)) => match e {
-                    EventData::GtidLogEvent {
-                        uuid,
-                        coordinate,
-                        last_committed,
-                        sequence_number,
-                        ..
-                    } => {
-                        self.current_gtid = Some(Gtid(uuid, coordinate));
-                        if let (Some(last_committed), Some(sequence_number)) =
-                            (last_committed, sequence_number)
-                        {
-                            self.logical_timestamp = Some(LogicalTimestamp {
-                                last_committed,
-                                sequence_number,
-                            });
-                        } else {
-                            self.logical_timestamp = None;
+        if let Some(ref data) = data {
+            self.session.observe(&header, data);
+        }
+        match data {
+            Some(e) => match e {
+                EventData::GtidLogEvent { commit_info, .. } => {
+                    let executed = match (
+                        self.options.executed_gtids.as_ref(),
+                        self.session.current_gtid(),
+                    ) {
+                        (Some(executed), Some(gtid)) => executed.contains(gtid),
+                        _ => false,
+                    };
+                    self.skipping_transaction = self.before_start() || executed;
+                    self.start_transaction(&header, &commit_info);
+                }
+                EventData::AnonymousGtidLogEvent { commit_info, .. } => {
+                    self.skipping_transaction = self.before_start();
+                    self.start_transaction(&header, &commit_info);
+                }
+                EventData::TableMapEvent {
+                    table_id,
+                    schema_name,
+                    table_name,
+                    columns,
+                    mut descriptors,
+                    ..
+                } => {
+                    let name_case = self.options.table_name_case;
+                    let schema_name = name_case.apply(schema_name);
+                    let table_name = name_case.apply(table_name);
+                    let (schema_key, table_key) =
+                        (name_case.key(&schema_name), name_case.key(&table_name));
+                    if let Some(ref provider) = self.options.schema_provider {
+                        table_map::apply_schema_provider(
+                            provider.as_ref(),
+                            &schema_key,
+                            &table_key,
+                            &mut descriptors,
+                        );
+                    }
+                    if let Some((ref catalog, policy)) =
+                        self.options.catalog.as_ref().filter(|_| !skipped)
+                    {
+                        if let Err(mismatch) = catalog.check(&schema_key, &table_key, &columns) {
+                            match policy {
+                                catalog::MismatchPolicy::Error => {
+                                    return Some(Err(EventParseError::SchemaMismatch(Box::new(
+                                        mismatch,
+                                    ))))
+                                }
+                                catalog::MismatchPolicy::Warn => {
+                                    diagnostics::report(
+                                        self.options.diagnostics.as_ref(),
+                                        diagnostics::Diagnostic::SchemaMismatch(&mismatch),
+                                    );
+                                }
+                            }
                         }
                     }
-                    EventData::TableMapEvent {
+                    self.session.table_map.handle_with_descriptors(
                         table_id,
                         schema_name,
                         table_name,
                         columns,
-                        ..
-                    } => {
-                        self.table_map
-                            .handle(table_id, schema_name, table_name, columns);
-                    }
-                    EventData::QueryEvent { query, .. } => {
-                        return Some(Ok(BinlogEvent {
-                            offset,
-                            type_code: event.type_code(),
-                            timestamp: event.timestamp(),
-                            gtid: self.current_gtid,
-                            logical_timestamp: self.logical_timestamp,
-                            table_name: None,
-                            schema_name: None,
-                            rows: Vec::new(),
-                            query: Some(query),
-                        }))
+                        descriptors,
+                    );
+                }
+                EventData::QueryEvent {
+                    query,
+                    schema,
+                    thread_id,
+                    exec_time,
+                    error_code,
+                    ..
+                } => {
+                    return Some(Ok(BinlogEvent {
+                        transaction_marker: event::TransactionMarker::from_query(&query),
+                        query: Some(query),
+                        query_context: Some(event::QueryContext {
+                            schema,
+                            thread_id,
+                            exec_time,
+                            error_code,
+                        }),
+                        ..self.binlog_event(&header)
+                    }))
+                }
+                EventData::WriteRowsEvent { table_id, rows }
+                | EventData::UpdateRowsEvent { table_id, rows }
+                | EventData::DeleteRowsEvent { table_id, rows } => {
+                    if let (Some(collector), Some(table)) = (
+                        self.column_stats.as_mut(),
+                        self.session.table_map.get(table_id),
+                    ) {
+                        collector.record(&table.schema_name, &table.table_name, &rows);
                     }
-                    EventData::WriteRowsEvent { table_id, rows }
-                    | EventData::UpdateRowsEvent { table_id, rows }
-                    | EventData::DeleteRowsEvent { table_id, rows } => {
-                        let maybe_table = self.table_map.get(table_id);
-                        let message = BinlogEvent {
-                            offset,
-                            type_code: event.type_code(),
-                            timestamp: event.timestamp(),
-                            gtid: self.current_gtid,
-                            logical_timestamp: self.logical_timestamp,
-                            table_name: maybe_table.as_ref().map(|a| a.table_name.to_owned()),
-                            schema_name: maybe_table.as_ref().map(|a| a.schema_name.to_owned()),
-                            rows,
-                            query: None,
+                    let row_count = Some(rows.len());
+                    let mut binlog_event = self.rows_event(&header, table_id, rows, row_count);
+                    if self.options.changed_columns_only {
+                        let columns = binlog_event.columns.clone();
+                        let is_key = |i: usize| {
+                            columns
+                                .as_ref()
+                                .and_then(|columns| columns.get(i))
+                                .is_some_and(|column| column.primary_key)
                         };
-                        return Some(Ok(message));
-                    }
-                    u => {
-                        eprintln!("unhandled event: {:?}", u);
+                        for row in binlog_event.rows.iter_mut() {
+                            row.retain_changed_columns(is_key);
+                        }
                     }
-                },
-                Ok(None) => {
-                    // this event doesn't have an inner type, which means we don't currently
-                    // care about it. Example: PreviousGtidEvent
+                    return Some(Ok(match self.options.max_rows_per_event {
+                        Some(max_rows) => self.split_rows_event(&header, binlog_event, max_rows),
+                        None => binlog_event,
+                    }));
                 }
-                Err(e) => return Some(Err(e)),
+                EventData::TransactionPayloadEvent { events, .. } => {
+                    self.payload_events.extend(
+                        events
+                            .into_iter()
+                            .map(|inner| inner.with_offset(header.offset)),
+                    );
+                }
+                EventData::XidEvent { xid } => {
+                    return Some(Ok(BinlogEvent {
+                        xid: Some(xid),
+                        transaction_marker: Some(event::TransactionMarker::Commit),
+                        ..self.binlog_event(&header)
+                    }))
+                }
+                EventData::IncidentEvent {
+                    incident_type,
+                    message,
+                } => {
+                    return Some(Ok(BinlogEvent {
+                        incident: Some(event::Incident {
+                            incident_type,
+                            message,
+                        }),
+                        ..self.binlog_event(&header)
+                    }))
+                }
+                EventData::RotateEvent {
+                    position,
+                    next_log_name,
+                } => {
+                    return Some(Ok(BinlogEvent {
+                        rotate: Some(event::Rotation {
+                            next_log_name,
+                            position,
+                        }),
+                        ..self.binlog_event(&header)
+                    }))
+                }
+                // tracked by the session
+                EventData::PreviousGtidsEvent { .. }
+                | EventData::HeartbeatEvent { .. }
+                | EventData::FormatDescriptionEvent { .. }
+                | EventData::IntvarEvent { .. }
+                | EventData::RandEvent { .. }
+                | EventData::UserVarEvent { .. }
+                | EventData::XaPrepareEvent { .. }
+                | EventData::ViewChangeEvent { .. }
+                | EventData::StartEncryptionEvent { .. } => {}
+            },
+            None => {
+                // this event doesn't have an inner type, which means we don't currently
+                // care about it. Example: StopEvent
+                diagnostics::report(
+                    self.options.diagnostics.as_ref(),
+                    diagnostics::Diagnostic::UnhandledEvent {
+                        type_code: header.type_code,
+                        offset: header.offset,
+                    },
+                );
             }
         }
         None
@@ -189,7 +1364,9 @@ impl<BR: Read + Seek> Iterator for EventIterator<BR> {
 /// Builder to configure Binary Log reading
 pub struct BinlogFileParserBuilder<BR: Read + Seek> {
     bf: binlog_file::BinlogFile<BR>,
-    start_position: Option<u64>,
+    files: Option<sequence::FileSequence<BR>>,
+    session: session::ParseSession,
+    options: IteratorOptions,
 }
 
 impl BinlogFileParserBuilder<File> {
@@ -198,7 +1375,100 @@ impl BinlogFileParserBuilder<File> {
         let bf = binlog_file::BinlogFile::try_from_path(file_name.as_ref())?;
         Ok(BinlogFileParserBuilder {
             bf,
-            start_position: None,
+            files: None,
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
+        })
+    }
+
+    /// Construct a new BinlogFileParserBuilder which reads every binlog file (named
+    /// `<base>.NNNNNN`) in a directory, in order, as one continuous stream.
+    ///
+    /// While reading, each file is checked to end with a Rotate event naming the next file, and
+    /// each file's PreviousGtidsLogEvent is checked against the GTIDs executed by the files before
+    /// it; the iterator returns an error and stops if either check fails. Any start position
+    /// applies to the first file only.
+    pub fn try_from_directory<P: AsRef<Path>>(dir: P) -> Result<Self, BinlogParseError> {
+        Self::try_from_files(binlog_file::discover_directory(dir)?)
+    }
+
+    /// Construct a new BinlogFileParserBuilder which reads every binlog file listed in a binlog
+    /// index file (e.g. `mysql-bin.index`), in order, as one continuous stream, with the same
+    /// checks as [`try_from_directory`](Self::try_from_directory)
+    pub fn try_from_index<P: AsRef<Path>>(index: P) -> Result<Self, BinlogParseError> {
+        Self::try_from_files(binlog_file::BinlogIndex::open(index)?.into_files())
+    }
+
+    fn try_from_files(files: Vec<PathBuf>) -> Result<Self, BinlogParseError> {
+        let open: fn(&Path) -> Result<binlog_file::BinlogFile<File>, BinlogParseError> =
+            |path| binlog_file::BinlogFile::try_from_path(path);
+        let (files, first) = sequence::FileSequence::new(files.into(), open)
+            .expect("the list of binlog files is never empty");
+        let bf = binlog_file::BinlogFile::try_from_path(first)?;
+        Ok(BinlogFileParserBuilder {
+            bf,
+            files: Some(files),
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
+        })
+    }
+
+    /// Construct a new BinlogFileParserBuilder which starts with the given binlog file (or the
+    /// first binlog file in the given directory) and, at the end of each file, continues with
+    /// the file named by its Rotate event.
+    ///
+    /// Reading stops at a file which doesn't end with a Rotate event, or whose Rotate event names
+    /// a file which doesn't exist (yet). Each file's PreviousGtidsLogEvent is checked as for
+    /// [`try_from_directory`](Self::try_from_directory).
+    pub fn try_following_rotations<P: AsRef<Path>>(path: P) -> Result<Self, BinlogParseError> {
+        let path = path.as_ref();
+        let first = if path.is_dir() {
+            binlog_file::discover_directory(path)?.swap_remove(0)
+        } else {
+            path.to_owned()
+        };
+        let bf = binlog_file::BinlogFile::try_from_path(&first)?;
+        let open: fn(&Path) -> Result<binlog_file::BinlogFile<File>, BinlogParseError> =
+            |path| binlog_file::BinlogFile::try_from_path(path);
+        Ok(BinlogFileParserBuilder {
+            bf,
+            files: Some(sequence::FileSequence::following(first, open)),
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl BinlogFileParserBuilder<std::io::Cursor<memmap2::Mmap>> {
+    /// Construct a new BinlogFileParserBuilder which reads a memory mapping of the file at the
+    /// given path; see [`BinlogFile::try_from_mmap`](binlog_file::BinlogFile::try_from_mmap)
+    pub fn try_from_mmap<P: AsRef<Path>>(file_name: P) -> Result<Self, BinlogParseError> {
+        let bf = binlog_file::BinlogFile::try_from_mmap(file_name)?;
+        Ok(BinlogFileParserBuilder {
+            bf,
+            files: None,
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
+        })
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl BinlogFileParserBuilder<encryption::DecryptingReader<File>> {
+    /// Construct a new BinlogFileParserBuilder from an encrypted binlog; see
+    /// [`BinlogFile::try_from_encrypted_path`](binlog_file::BinlogFile::try_from_encrypted_path)
+    pub fn try_from_encrypted_path<P, K>(file_name: P, keys: &K) -> Result<Self, BinlogParseError>
+    where
+        P: AsRef<Path>,
+        K: encryption::KeyProvider + ?Sized,
+    {
+        let bf = binlog_file::BinlogFile::try_from_encrypted_path(file_name, keys)?;
+        Ok(BinlogFileParserBuilder {
+            bf,
+            files: None,
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
         })
     }
 }
@@ -209,22 +1479,442 @@ impl<BR: Read + Seek> BinlogFileParserBuilder<BR> {
         let bf = binlog_file::BinlogFile::try_from_reader(r)?;
         Ok(BinlogFileParserBuilder {
             bf,
-            start_position: None,
+            files: None,
+            session: session::ParseSession::new(),
+            options: IteratorOptions::default(),
         })
     }
 
     /// Set the start position to begin emitting events. NOTE: The beginning of the binlog will
     /// always be read first for the FDE. NOTE: Column mappings may be incorrect if you use this
-    /// functionality, as TMEs may be missed.
+    /// functionality, as TMEs may be missed; use [`resume_from`](Self::resume_from) to avoid
+    /// that.
     pub fn start_position(mut self, pos: u64) -> Self {
-        self.start_position = Some(pos);
+        self.options.start_position = Some(pos);
         self
     }
 
-    /// Consume this builder, returning an iterator of [`BinlogEvent`] structs
-    pub fn build(self) -> EventIterator<BR> {
-        EventIterator::new(self.bf, self.start_position)
-    }
+    /// Continue from a [`BinlogPosition`] taken from a previously-read event, emitting the events
+    /// after it. The file is read from its start up to the position to learn the tables and
+    /// transaction of the events which follow it, but nothing before the position is emitted.
+    ///
+    /// When reading a sequence of files, reading begins with the position's file. Otherwise the
+    /// position is assumed to be in the file being read.
+    ///
+    /// ## Errors
+    ///
+    /// - [`BinlogParseError::PositionFileNotFound`] if the position's file isn't the file being
+    ///   read or in the sequence of files
+    pub fn resume_from(mut self, position: &BinlogPosition) -> Result<Self, BinlogParseError> {
+        if let Some(ref name) = position.file_name {
+            let not_found = || BinlogParseError::PositionFileNotFound(name.clone());
+            match self.files {
+                Some(ref mut files) => {
+                    let path = files.skip_to(name).ok_or_else(not_found)?;
+                    if self.bf.file_name() != Some(path.as_path()) {
+                        self.bf = files.open(&path)?;
+                    }
+                }
+                None => {
+                    let current = self.bf.file_name().and_then(Path::file_name);
+                    if current.is_some_and(|current| current != name.as_str()) {
+                        return Err(not_found());
+                    }
+                }
+            }
+        }
+        self.options.resume_offset = Some(position.offset);
+        Ok(self)
+    }
+
+    /// Stop before the first event at or after this position, as `mysqlbinlog --stop-position`
+    /// does. When reading a sequence of files, the position is in the last file (or, when
+    /// [following rotations](Self::try_following_rotations), in whichever file reaches it
+    /// first).
+    pub fn stop_position(mut self, pos: u64) -> Self {
+        self.options.stop_position = Some(pos);
+        self
+    }
+
+    /// Stop before the first event whose timestamp is at or after this one (in seconds since the
+    /// epoch), as `mysqlbinlog --stop-datetime` does
+    pub fn stop_timestamp(mut self, timestamp: u32) -> Self {
+        self.options.stop_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Keep the rows of each rows event undecoded, to be decoded one at a time by
+    /// [`BinlogEvent::rows_iter`], so that huge events (such as bulk loads) are never held in
+    /// memory as values all at once. Lazily-read events have an empty `rows`, no `row_count`,
+    /// and are neither [split](Self::max_rows_per_event) nor included in
+    /// [column statistics](Self::collect_column_stats).
+    pub fn lazy_rows(mut self, lazy: bool) -> Self {
+        self.options.lazy_rows = lazy;
+        self
+    }
+
+    /// Only count the rows in each rows event rather than decoding their values. Emitted
+    /// [`BinlogEvent`]s will have an empty `rows` vector but an accurate `row_count`, which makes
+    /// this mode suitable for monitoring agents that only need [`BinlogEvent::summary`]. Events
+    /// whose table layout isn't known have no `row_count`.
+    pub fn summaries_only(mut self, summaries_only: bool) -> Self {
+        self.options.summaries_only = summaries_only;
+        self
+    }
+
+    /// Like [`summaries_only`](Self::summaries_only), but rather than stepping through every row
+    /// of a rows event, only measure its first row and [estimate](event::Event::estimate_rows)
+    /// `row_count` from it. Reading a rows event then costs the same however many rows it has,
+    /// for tools which only need to know which tables changed when.
+    pub fn metadata_only(mut self, metadata_only: bool) -> Self {
+        self.options.metadata_only = metadata_only;
+        self
+    }
+
+    /// Check every TableMapEvent against an expected schema catalog, either failing or warning
+    /// (according to `policy`) when a table's columns don't match
+    pub fn expected_catalog(
+        mut self,
+        catalog: catalog::SchemaCatalog,
+        policy: catalog::MismatchPolicy,
+    ) -> Self {
+        self.options.catalog = Some((catalog, policy));
+        self
+    }
+
+    /// Verify the CRC32 checksum of every event. An event is only returned once its checksum is
+    /// known to match; a corrupt event is replaced by [`EventParseError::ChecksumMismatch`]. By
+    /// default each event is verified on a worker thread while it is decoded, if there is more
+    /// than one CPU; see [`checksum_threads`](Self::checksum_threads).
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.options.checksum_threads = if verify {
+            let default_threads = std::thread::available_parallelism()
+                .map(|n| usize::from(n.get() > 1))
+                .unwrap_or(0);
+            Some(self.options.checksum_threads.unwrap_or(default_threads))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Verify events in binlogs whose FormatDescriptionEvent names checksum algorithm `id` (which
+    /// MySQL itself never writes) with `checksum`. Verifying a binlog which uses an algorithm
+    /// with no registered implementation returns
+    /// [`EventParseError::UnsupportedChecksumAlgorithm`] and leaves its events unverified.
+    pub fn checksum_algorithm<C: checksum::Checksum + 'static>(
+        mut self,
+        id: u8,
+        checksum: C,
+    ) -> Self {
+        self.options.checksums.insert(id, Arc::new(checksum));
+        self
+    }
+
+    /// Verify checksums on a worker thread, overlapping with decoding each event, if `threads`
+    /// is nonzero (only one thread is used), or inline on the iterating thread before each event
+    /// is decoded if it is zero. Implies `verify_checksums(true)`.
+    pub fn checksum_threads(mut self, threads: usize) -> Self {
+        self.options.checksum_threads = Some(threads);
+        self
+    }
+
+    /// Skip events which fail to decode, as long as the error is
+    /// [recoverable](EventParseError::is_recoverable), rather than returning the error. Skipped
+    /// events are counted in [`ParserStats::events_skipped`](stats::ParserStats::events_skipped).
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.options.error_policy = if lenient {
+            ErrorPolicy::SkipEvent
+        } else {
+            ErrorPolicy::Abort
+        };
+        self
+    }
+
+    /// Set what happens after a [recoverable](EventParseError::is_recoverable) error: `SkipEvent`
+    /// is the same as [`lenient`](Self::lenient), and `SkipTransaction` also drops the rest of
+    /// the transaction the event was in. Skipped events (but not the rest of their transactions)
+    /// are counted in [`ParserStats::events_skipped`](stats::ParserStats::events_skipped).
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.options.error_policy = policy;
+        self
+    }
+
+    /// Pass each [`Diagnostic`](diagnostics::Diagnostic) (such as an event skipped under
+    /// [`lenient`](Self::lenient)) to `handler`, rather than logging it (with the `log` feature)
+    /// or discarding it
+    pub fn on_diagnostic<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&diagnostics::Diagnostic<'_>) + Send + Sync + 'static,
+    {
+        self.options.diagnostics = Some(diagnostics::DiagnosticHandler::new(handler));
+        self
+    }
+
+    /// When an event can't be read because the file is corrupt, scan forward for the next
+    /// plausible event (checking its header and checksum) and continue from there, rather than
+    /// stopping. Each unreadable range is returned as an
+    /// [`EventParseError::SkippedRange`] error; anything in it, including whole transactions and
+    /// Table Map events, is lost.
+    pub fn recover_corruption(mut self, recover: bool) -> Self {
+        self.options.recover_corruption = recover;
+        self
+    }
+
+    /// Begin emitting events with the transaction after the one with this GTID; nothing is
+    /// emitted if it is never found. Unlike [`start_position`](Self::start_position), every event
+    /// before it is still read, so Table Map events are tracked.
+    pub fn start_after_gtid(mut self, gtid: Gtid) -> Self {
+        self.options.start_after = Some(StartAfter::Gtid(gtid));
+        self
+    }
+
+    /// Begin emitting events with the first transaction whose GTID is not in `gtids`, such as a
+    /// checkpoint of the transactions already handled. Unlike
+    /// [`executed_gtids`](Self::executed_gtids), transactions after that one are emitted whether
+    /// or not they are in `gtids`.
+    pub fn start_after_gtid_set(mut self, gtids: GtidSet) -> Self {
+        self.options.start_after = Some(StartAfter::GtidSet(gtids));
+        self
+    }
+
+    /// Only emit rows events for these `(schema, table)` pairs (and those of any other calls to
+    /// this method). Rows events for other tables are dropped without decoding their rows.
+    pub fn include_tables(mut self, tables: &[(&str, &str)]) -> Self {
+        let include = self.options.include_tables.get_or_insert_with(HashMap::new);
+        for (schema, table) in tables {
+            include
+                .entry((*schema).to_owned())
+                .or_default()
+                .insert((*table).to_owned());
+        }
+        self
+    }
+
+    /// Drop rows events for tables in these schemas without decoding their rows, even if
+    /// they are [included](Self::include_tables)
+    pub fn exclude_schemas(mut self, schemas: &[&str]) -> Self {
+        self.options
+            .exclude_schemas
+            .extend(schemas.iter().map(|schema| (*schema).to_owned()));
+        self
+    }
+
+    /// Skip every transaction whose GTID is in `executed`, as a replica resuming from its
+    /// `gtid_executed` would. Table maps in skipped transactions are still tracked.
+    pub fn executed_gtids(mut self, executed: GtidSet) -> Self {
+        self.options.executed_gtids = Some(executed);
+        self
+    }
+
+    /// Fail with [`EventTooLarge`](errors::EventParseError::EventTooLarge) rather than reading an
+    /// event longer than `bytes`. A corrupt event length can otherwise make the parser try to
+    /// read (and allocate) gigabytes; MySQL never writes an event longer than its
+    /// `max_allowed_packet`, which is at most 1GiB. The limit also applies to the decompressed
+    /// contents of transaction payloads.
+    pub fn max_event_size(mut self, bytes: u32) -> Self {
+        self.options.max_event_size = Some(bytes);
+        self.options.decode.max_event_size = Some(bytes);
+        self
+    }
+
+    /// Fail with [`ValueTooLarge`](errors::ColumnParseError::ValueTooLarge) rather than decoding
+    /// a string, blob, JSON, or geometry value longer than `bytes`
+    pub fn max_value_size(mut self, bytes: usize) -> Self {
+        self.options.decode.max_value_size = Some(bytes);
+        self
+    }
+
+    /// Choose what happens when a rows event has a different number of columns than its table
+    /// map; see [`ColumnCountPolicy`](event::ColumnCountPolicy)
+    pub fn column_count_policy(mut self, policy: event::ColumnCountPolicy) -> Self {
+        self.options.decode.column_count_policy = policy;
+        self
+    }
+
+    /// Sleep before each transaction so that transactions are emitted with the same spacing as
+    /// they were originally committed with (to the one-second resolution of event timestamps).
+    /// Useful for replaying archived binlogs against a replica with a realistic load pattern.
+    pub fn pace_to_realtime(mut self, pace: bool) -> Self {
+        self.options.pace_to_realtime = pace;
+        self
+    }
+
+    /// Decode the values of one column with a custom [`ValueDecoder`](value::ValueDecoder).
+    /// Columns are identified by their zero-based position in the table or by their name (see
+    /// [`ProjectedColumn`](event::ProjectedColumn)).
+    pub fn value_decoder<C, D>(
+        mut self,
+        schema_name: &str,
+        table_name: &str,
+        column: C,
+        decoder: D,
+    ) -> Self
+    where
+        C: Into<event::ProjectedColumn>,
+        D: value::ValueDecoder + 'static,
+    {
+        self.options
+            .decode
+            .value_decoders
+            .register(schema_name, table_name, column, decoder);
+        self
+    }
+
+    /// Decode events of type `type_code` with `decoder`, which returns the value to emit as the
+    /// event's [`custom`](BinlogEvent::custom), or `None` to drop it. This adds support for
+    /// vendor-specific events (which arrive as [`TypeCode::OtherUnknown`]), and can replace the
+    /// decoding of types this crate handles, but events given to a decoder aren't otherwise
+    /// processed: replacing the decoding of Table Map or GTID events leaves the events which
+    /// depend on them without a table or transaction.
+    pub fn event_decoder<D: event::EventDecoder + 'static>(
+        mut self,
+        type_code: TypeCode,
+        decoder: D,
+    ) -> Self {
+        self.options.event_decoders.register(type_code, decoder);
+        self
+    }
+
+    /// Return the values of a JSON column (or, if `column` is `None`, of every JSON column in the
+    /// table) as their raw binary encoding in a [`MySQLValue::Blob`](value::MySQLValue::Blob)
+    /// rather than decoding them. Decoding large JSON documents is expensive, so this is worth
+    /// doing for columns whose values aren't needed. Columns are identified by their zero-based
+    /// position in the table.
+    pub fn raw_json(mut self, schema_name: &str, table_name: &str, column: Option<usize>) -> Self {
+        self.options
+            .decode
+            .raw_json
+            .insert(schema_name, table_name, column);
+        self
+    }
+
+    /// Only decode these columns of a table's rows, skipping over the rest without decoding them
+    /// or allocating their values; the other columns are `None` in each row, as if they hadn't
+    /// been logged. Columns are given by their zero-based position or by name (see
+    /// [`ProjectedColumn`](event::ProjectedColumn)). Calling this again for the same table adds
+    /// to its columns.
+    pub fn project_columns<C, I>(mut self, schema_name: &str, table_name: &str, columns: I) -> Self
+    where
+        C: Into<event::ProjectedColumn>,
+        I: IntoIterator<Item = C>,
+    {
+        for column in columns {
+            self.options
+                .decode
+                .projection
+                .insert(schema_name, table_name, column);
+        }
+        self
+    }
+
+    /// Accumulate per-column statistics (null rate, min/max, approximate distinct count) for
+    /// every row decoded; see [`EventIterator::column_stats`]. Has no effect on rows which are
+    /// not decoded because of [`summaries_only`](Self::summaries_only).
+    pub fn collect_column_stats(mut self, collect: bool) -> Self {
+        self.options.collect_column_stats = collect;
+        self
+    }
+
+    /// Describe the columns of tables whose Table Map events don't include column names (see
+    /// [`BinlogEvent::columns`])
+    pub fn schema_provider<P: table_map::SchemaProvider + 'static>(mut self, provider: P) -> Self {
+        self.options.schema_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Split rows events with more than `max_rows` rows into several [`BinlogEvent`]s, each
+    /// marked with its position in the [`split`](BinlogEvent::split). Has no effect on rows which
+    /// are not decoded because of [`summaries_only`](Self::summaries_only). A `max_rows` of zero
+    /// means no limit, so events are not split.
+    pub fn max_rows_per_event(mut self, max_rows: usize) -> Self {
+        self.options.max_rows_per_event = Some(max_rows).filter(|max_rows| *max_rows > 0);
+        self
+    }
+
+    /// Remove the columns which an update didn't change from both of its images (leaving them
+    /// `None`, as if they hadn't been logged), except for primary key columns in the
+    /// before-image, which are kept to identify the row. The primary key is only known with
+    /// `binlog_row_metadata=FULL` or a [`schema_provider`](Self::schema_provider). Has no effect
+    /// on [lazily-read](Self::lazy_rows) rows.
+    pub fn changed_columns_only(mut self, changed_only: bool) -> Self {
+        self.options.changed_columns_only = changed_only;
+        self
+    }
+
+    /// Fail with [`UnknownTableId`](EventParseError::UnknownTableId) on a rows event for a table
+    /// whose TableMapEvent hasn't been read, rather than returning it with no rows. This happens
+    /// when reading starts partway through a transaction (as with
+    /// [`start_position`](Self::start_position)) and no table map has been
+    /// [restored](Self::restore_table_map).
+    pub fn strict_table_map(mut self, strict: bool) -> Self {
+        self.options.strict_table_map = strict;
+        self
+    }
+
+    /// Treat an I/O error reading the next event (including a truncated event) as the end of the
+    /// file, as older versions of this crate did, rather than returning it. This makes a disk
+    /// fault or a truncated file indistinguishable from a clean end of file, so is only for
+    /// callers which rely on that behavior.
+    pub fn io_errors_end_stream(mut self, end_stream: bool) -> Self {
+        self.options.io_errors_end_stream = end_stream;
+        self
+    }
+
+    /// Read a binlog which the server may still be writing to. When the iterator catches up with
+    /// the writer (including when the last event in the file is only partially written) it
+    /// returns `None`, and calling `next` again later resumes from the same position rather than
+    /// reporting the partial event as an error. After a RotateEvent, the iterator moves on to the
+    /// next file as usual if it is reading a directory.
+    pub fn active_file(mut self, active: bool) -> Self {
+        self.options.active_file = active;
+        self
+    }
+
+    /// Like [`active_file`](Self::active_file), but rather than returning `None` when the
+    /// iterator catches up with the writer, wait for more events, checking every `poll_interval`,
+    /// like `tail -f`. When reading a sequence of files, a Rotate event to a file which doesn't
+    /// exist yet is waited for too, so that reading carries on into each new file the server
+    /// starts. Iteration only ends at a [stop position](Self::stop_position) or
+    /// [timestamp](Self::stop_timestamp), or at the Rotate event of a single file.
+    pub fn follow(mut self, poll_interval: Duration) -> Self {
+        self.options.active_file = true;
+        self.options.follow = Some(poll_interval);
+        self
+    }
+
+    /// Choose how the [`TableRef`](table_map::TableRef) on each emitted event is serialized
+    pub fn table_ref_format(mut self, format: table_map::TableRefFormat) -> Self {
+        self.options.table_ref_format = format;
+        self
+    }
+
+    /// How schema and table names are compared and reported; see [`table_map::TableNameCase`].
+    /// Useful when reading from servers with different `lower_case_table_names` settings.
+    pub fn table_name_case(mut self, name_case: table_map::TableNameCase) -> Self {
+        self.options.table_name_case = name_case;
+        self
+    }
+
+    /// Continue from the state of a previous iterator (see [`EventIterator::into_session`]),
+    /// keeping its table map, GTID tracking, and statistics
+    pub fn session(mut self, session: session::ParseSession) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Start with the tables of a previously saved [`TableMap`](table_map::TableMap) (see
+    /// [`EventIterator::table_map`]), so that rows events after a
+    /// [`start_position`](Self::start_position) can be decoded without their Table Map events
+    pub fn restore_table_map(mut self, table_map: table_map::TableMap) -> Self {
+        self.session.table_map = table_map;
+        self
+    }
+
+    /// Consume this builder, returning an iterator of [`BinlogEvent`] structs
+    pub fn build(self) -> EventIterator<BR> {
+        EventIterator::new(self.bf, self.files, self.session, self.options)
+    }
 }
 
 /// Parse events from an object implementing the [`std::io::Read`] trait
@@ -247,14 +1937,32 @@ pub fn parse_file<P: AsRef<Path>>(file_name: P) -> Result<EventIterator<File>, B
     BinlogFileParserBuilder::try_from_path(file_name).map(|b| b.build())
 }
 
+/// parse all events in a binlog file and the files it rotates into; see
+/// [`BinlogFileParserBuilder::try_following_rotations`]
+///
+/// ## Errors
+///
+/// - returns an immediate error if the first file could not be opened or if it does not contain a valid Format Desciptor Event
+/// - each call to the iterator can return an error if there is an I/O or parsing error, or if the files are not continuous
+pub fn parse_directory<P: AsRef<Path>>(
+    path: P,
+) -> Result<BinlogDirectoryIterator, BinlogParseError> {
+    BinlogFileParserBuilder::try_following_rotations(path).map(|b| b.build())
+}
+
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
 
     use bigdecimal::BigDecimal;
 
-    use super::{parse_file, parse_reader};
-    use crate::event::TypeCode;
+    use super::{
+        parse_directory, parse_file, parse_reader, BinlogEvent, BinlogFileParserBuilder, EventSplit,
+    };
+    use crate::column_types::ColumnType;
+    use crate::errors::{BinlogParseError, ColumnParseError, EventParseError};
+    use crate::event::{IncidentType, RowEvent, TypeCode};
+    use crate::table_map::ColumnDescriptor;
     use crate::value::MySQLValue;
 
     #[test]
@@ -263,29 +1971,136 @@ mod tests {
             .unwrap()
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
-        assert_eq!(results.len(), 5);
+        assert_eq!(results.len(), 7);
         assert_eq!(results[0].type_code, TypeCode::QueryEvent);
         assert_eq!(results[0].query, Some("CREATE TABLE foo(id BIGINT AUTO_INCREMENT PRIMARY KEY, val_decimal DECIMAL(10, 5) NOT NULL, comment VARCHAR(255) NOT NULL)".to_owned()));
-        assert_eq!(results[2].timestamp, 1550192291);
         assert_eq!(
-            results[2].gtid.unwrap().to_string(),
-            "87cee3a4-6b31-11e7-bdfd-0d98d6698870:14918"
+            results[0].query_context,
+            Some(crate::event::QueryContext {
+                schema: "bltest".to_owned(),
+                thread_id: 472,
+                exec_time: 0,
+                error_code: 0,
+            })
+        );
+        assert_eq!(results[0].as_ddl().unwrap().schema, "bltest");
+        assert_eq!(results[0].transaction_marker, None);
+        assert_eq!(
+            results[1].transaction_marker,
+            Some(crate::event::TransactionMarker::Begin)
         );
         assert_eq!(
-            results[2].schema_name.as_ref().map(|s| s.as_str()),
-            Some("bltest")
+            results[3].transaction_marker,
+            Some(crate::event::TransactionMarker::Commit)
         );
+        assert_eq!(results[2].timestamp, 1550192291);
+        assert_eq!(results[2].server_id, 36431);
+        assert_eq!(results[2].event_length, 66);
+        assert_eq!(results[2].next_position, 718);
+        assert_eq!(results[2].flags, 0);
         assert_eq!(
-            results[2].table_name.as_ref().map(|s| s.as_str()),
-            Some("foo")
+            results[2].gtid.unwrap().to_string(),
+            "87cee3a4-6b31-11e7-bdfd-0d98d6698870:14918"
         );
-        let cols = results[2].rows[0].cols().unwrap();
+        let table_ref = results[2].table_ref.as_ref().unwrap();
+        assert_eq!(table_ref.schema, "bltest");
+        assert_eq!(table_ref.table, "foo");
+        assert_eq!(table_ref.table_id, 203);
+        let cols = results[2].rows[0].cols();
         assert_matches!(cols[0], Some(MySQLValue::SignedInteger(1)));
         assert_matches!(cols[1], Some(MySQLValue::Decimal(_)));
         if let Some(MySQLValue::Decimal(ref d)) = cols[1] {
             assert_eq!(*d, "0.1".parse::<BigDecimal>().unwrap());
         }
         assert_matches!(cols[2], Some(MySQLValue::String(_)));
+        assert_eq!(results[3].type_code, TypeCode::XidEvent);
+        assert_eq!(results[3].gtid, results[2].gtid);
+        assert!(results[3].xid.is_some());
+        assert!(results[3].rows.is_empty());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let events = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let serialized = serde_json::to_value(&events).unwrap();
+        let deserialized: Vec<BinlogEvent> = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&deserialized).unwrap(), serialized);
+        assert_eq!(deserialized[2].gtid, events[2].gtid);
+        assert_eq!(deserialized[2].table_ref, events[2].table_ref);
+        assert_eq!(deserialized[2].row_count, Some(1));
+
+        // deleted rows are told apart from inserted ones by the event's type
+        let mut serialized = serde_json::to_value(&events[2]).unwrap();
+        serialized["type_code"] = serde_json::json!("DELETE_ROWS_EVENT_V2");
+        let deleted: BinlogEvent = serde_json::from_value(serialized).unwrap();
+        assert_matches!(deleted.rows[0], RowEvent::DeletedRow { .. });
+    }
+
+    #[test]
+    fn test_commit_info() {
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut binlog = original[..194].to_vec();
+        // the first GTID event, as written by MySQL 8.0
+        let mut gtid = original[194 + 19..259 - 4].to_vec();
+        gtid.extend_from_slice(&(1_600_000_000_000_001u64 | (1 << 55)).to_le_bytes()[..7]);
+        gtid.extend_from_slice(&1_600_000_000_000_000u64.to_le_bytes()[..7]);
+        gtid.extend_from_slice(&[0xfc, 0x2c, 0x01]);
+        gtid.extend_from_slice(&80036u32.to_le_bytes());
+        push_event(&mut binlog, 33, &gtid);
+        binlog.extend_from_slice(&original[259..]);
+        let results = parse_reader(std::io::Cursor::new(binlog))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let commit_info = results[0].commit_info.unwrap();
+        assert_eq!(
+            commit_info.original_commit_timestamp,
+            Some(1_600_000_000_000_000)
+        );
+        assert_eq!(
+            commit_info.immediate_commit_timestamp,
+            Some(1_600_000_000_000_001)
+        );
+        assert_eq!(commit_info.transaction_length, Some(300));
+        assert_eq!(commit_info.original_server_version, Some(80036));
+        // the following transactions were written by MySQL 5.7
+        assert!(results[1].commit_info.is_none());
+    }
+
+    #[test]
+    fn test_metadata_only() {
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .metadata_only(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        assert!(results[2].rows.is_empty());
+        assert_eq!(results[2].row_count, Some(1));
+        assert_eq!(
+            results[2].table_ref.as_ref().unwrap().qualified_name(),
+            "bltest.foo"
+        );
+    }
+
+    #[test]
+    fn test_format_description() {
+        use crate::event::ChecksumAlgorithm;
+
+        let iter = parse_file("test_data/bin-log.000001").unwrap();
+        let description = iter.format_description().unwrap();
+        assert_eq!(description.binlog_version, 4);
+        assert_eq!(description.server_version, "5.7.24-27-log");
+        assert_eq!(description.create_timestamp, 0);
+        assert_eq!(description.checksum_algorithm, ChecksumAlgorithm::CRC32);
+        assert_eq!(
+            description.version().flavor,
+            crate::server_version::ServerFlavor::Percona
+        );
     }
 
     #[test]
@@ -295,8 +2110,1295 @@ mod tests {
             .unwrap()
             .collect::<Result<Vec<_>, _>>()
             .unwrap();
-        assert_eq!(results.len(), 5);
+        assert_eq!(results.len(), 7);
         assert_eq!(results[0].type_code, TypeCode::QueryEvent);
         assert_eq!(results[0].query, Some("CREATE TABLE foo(id BIGINT AUTO_INCREMENT PRIMARY KEY, val_decimal DECIMAL(10, 5) NOT NULL, comment VARCHAR(255) NOT NULL)".to_owned()));
     }
+
+    #[test]
+    fn test_summaries_only() {
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .summaries_only(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        assert!(results[2].rows.is_empty());
+        let summary = results[2].summary();
+        assert_eq!(summary.type_code, TypeCode::WriteRowsEventV2);
+        assert_eq!(summary.row_count, Some(1));
+        assert_eq!(summary.byte_size, 66);
+        assert_eq!(summary.table_ref.unwrap().qualified_name(), "bltest.foo");
+
+        // without the Table Map event, the rows can't be counted
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .summaries_only(true)
+            .start_position(results[2].offset)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results[0].type_code, TypeCode::WriteRowsEventV2);
+        assert_eq!(results[0].row_count, None);
+        let summary = serde_json::to_value(results[0].summary()).unwrap();
+        assert_eq!(summary["row_count"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_io_errors() {
+        use crate::errors::ErrorKind;
+        use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+        /// Fails every read past the first rows event
+        struct FailingReader(Cursor<Vec<u8>>);
+
+        impl Read for FailingReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.position() >= 652 {
+                    return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+                }
+                self.0.read(buf)
+            }
+        }
+
+        impl Seek for FailingReader {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let builder = || {
+            BinlogFileParserBuilder::try_from_reader(FailingReader(Cursor::new(data.clone())))
+                .unwrap()
+        };
+        let results = builder().build().collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_matches!(results[2], Err(ref e) if e.kind() == ErrorKind::Io);
+
+        let results = builder().io_errors_end_stream(true).build();
+        assert_eq!(results.map(Result::unwrap).count(), 2);
+
+        // a file which ends partway through an event header is truncated, not finished
+        let truncated = parse_reader(Cursor::new(data[..660].to_vec()))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_matches!(truncated[2], Err(ref e) if e.kind() == ErrorKind::Corruption);
+    }
+
+    #[test]
+    fn test_active_file() {
+        use std::io::Write;
+
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        // stop part of the way through the second rows event
+        file.write_all(&data[..980]).unwrap();
+        file.flush().unwrap();
+        let mut iter = BinlogFileParserBuilder::try_from_path(file.path())
+            .unwrap()
+            .active_file(true)
+            .build();
+        let first = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(first.len(), 5);
+        assert!(iter.next().is_none());
+        // the test file was written years ago
+        let lag = iter.stats().commit_lag_micros.unwrap();
+        assert!(lag > 365 * 24 * 3600 * 1_000_000);
+        assert!(iter.stats().max_commit_lag_micros >= lag);
+
+        file.write_all(&data[980..]).unwrap();
+        file.flush().unwrap();
+        let rest = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].offset, 942);
+        assert_eq!(rest[0].rows.len(), 1);
+        assert_eq!(rest[1].type_code, TypeCode::XidEvent);
+
+        // without active mode, the partial event is an error
+        let truncated = parse_reader(std::io::Cursor::new(data[..980].to_vec()))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>();
+        assert_matches!(truncated, Err(EventParseError::Io(_)));
+    }
+
+    #[test]
+    fn test_follow() {
+        use std::time::Duration;
+
+        let mut first = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut first, 4, &rotate_body("bin-log.000002"));
+        let dir = binlog_directory(&[("bin-log.000001", first[..980].to_vec())]);
+        let mut iter = BinlogFileParserBuilder::try_following_rotations(dir.path())
+            .unwrap()
+            .follow(Duration::from_millis(5))
+            .build();
+        let path = dir.path().to_owned();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            std::fs::write(path.join("bin-log.000001"), &first).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+            // a new file is only picked up once it has been completely written
+            std::fs::write(path.join("partial"), second_binlog()).unwrap();
+            std::fs::rename(path.join("partial"), path.join("bin-log.000002")).unwrap();
+        });
+        let results = iter
+            .by_ref()
+            .take(15)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        writer.join().unwrap();
+        assert_eq!(results[5].offset, 942);
+        assert_eq!(results[7].type_code, TypeCode::RotateEvent);
+        assert_eq!(results[14].type_code, TypeCode::XidEvent);
+        assert_eq!(
+            results[14].position.as_ref().unwrap().file_name.as_deref(),
+            Some("bin-log.000002")
+        );
+    }
+
+    #[test]
+    fn test_typed_views() {
+        use crate::view::{DdlKind, RowChangeKind};
+
+        let results = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let ddl = results[0].as_ddl().unwrap();
+        assert_eq!(ddl.kind, DdlKind::Create);
+        assert!(ddl.query.starts_with("CREATE TABLE foo"));
+        assert!(results[0].as_row_change().is_none());
+
+        assert_eq!(results[1].as_query().unwrap().query, "BEGIN");
+        assert!(results[1].as_ddl().is_none());
+
+        let change = results[2].as_row_change().unwrap();
+        assert_eq!(change.kind, RowChangeKind::Insert);
+        assert_eq!(change.table.qualified_name(), "bltest.foo");
+        assert_eq!(change.rows.len(), 1);
+        assert_eq!(change.offset, 652);
+        assert!(results[2].as_query().is_none());
+    }
+
+    #[test]
+    fn test_table_name_case() {
+        use crate::catalog::{MismatchPolicy, SchemaCatalog};
+        use crate::table_map::TableNameCase;
+
+        // both tables are named `Foo` (as from a server with lower_case_table_names=0)
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        for tme in &[598, 888] {
+            data[tme + 36] = b'F';
+        }
+        let catalog = SchemaCatalog::new().with_table("bltest", "foo", Vec::new());
+        let read = |name_case| {
+            BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone()))
+                .unwrap()
+                .table_name_case(name_case)
+                .expected_catalog(catalog.clone(), MismatchPolicy::Error)
+                .build()
+                .collect::<Vec<_>>()
+        };
+
+        let results = read(TableNameCase::Preserve);
+        let table_ref = results[2].as_ref().unwrap().table_ref.as_ref().unwrap();
+        assert_eq!(table_ref.table, "Foo");
+
+        let results = read(TableNameCase::CaseInsensitive);
+        assert!(matches!(
+            results[2],
+            Err(EventParseError::SchemaMismatch(ref m)) if m.table_name == "foo"
+        ));
+
+        let results = read(TableNameCase::Lowercase);
+        assert!(results[2].is_err());
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone()))
+            .unwrap()
+            .table_name_case(TableNameCase::Lowercase)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results[2].table_ref.as_ref().unwrap().table, "foo");
+    }
+
+    #[test]
+    fn test_expected_catalog() {
+        use crate::catalog::{MismatchPolicy, SchemaCatalog};
+        use crate::column_types::ColumnType;
+        use crate::errors::EventParseError;
+
+        let matching = SchemaCatalog::new().with_table(
+            "bltest",
+            "foo",
+            vec![
+                ColumnType::LongLong,
+                ColumnType::NewDecimal(10, 5),
+                ColumnType::VarChar(765),
+            ],
+        );
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(matching, MismatchPolicy::Error)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+
+        let mismatched = SchemaCatalog::new().with_table(
+            "bltest",
+            "foo",
+            vec![ColumnType::LongLong, ColumnType::NewDecimal(10, 5)],
+        );
+        let err = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(mismatched, MismatchPolicy::Error)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+        assert_matches!(err, EventParseError::SchemaMismatch(ref m) if m.actual.len() == 3);
+    }
+
+    #[test]
+    fn test_source_identity() {
+        let mut iter = parse_file("test_data/bin-log.000001").unwrap();
+        assert!(iter.source_identity().is_none());
+        for event in iter.by_ref() {
+            event.unwrap();
+        }
+        let identity = iter.source_identity().unwrap();
+        assert_eq!(identity.server_id, 36431);
+        assert_eq!(
+            identity.server_uuid.unwrap().to_string(),
+            "87cee3a4-6b31-11e7-bdfd-0d98d6698870"
+        );
+        assert_eq!(iter.sources_seen().len(), 1);
+    }
+
+    #[test]
+    fn test_verify_checksums() {
+        for threads in [0, 2] {
+            let mut iter = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+                .unwrap()
+                .checksum_threads(threads)
+                .build();
+            assert_eq!(iter.by_ref().count(), 7);
+            let stats = iter.stats();
+            assert_eq!(stats.events_read, 13);
+            assert_eq!(stats.bytes_read, 1039 - 123);
+            assert_eq!(stats.checksums_verified, 13);
+            assert_eq!(stats.checksum_failures, 0);
+            assert_eq!(stats.checksums_unverified, 0);
+        }
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        // corrupt a byte in the text of the first query event
+        data[0x150] ^= 0xff;
+        for threads in [0, 2] {
+            let results =
+                BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone()))
+                    .unwrap()
+                    .checksum_threads(threads)
+                    .build()
+                    .collect::<Vec<_>>();
+            // the mismatch is returned in place of the corrupt event
+            assert_eq!(results.len(), 7);
+            assert_matches!(
+                results[0],
+                Err(EventParseError::ChecksumMismatch { offset: 259, .. })
+            );
+            assert!(results[1..].iter().all(|r| r.is_ok()));
+        }
+    }
+
+    #[test]
+    fn test_fde_checksum_fixtures() {
+        // (file, events with verified checksums)
+        let fixtures = [
+            ("5.5-no-checksum.000001", 0),
+            ("5.6-crc32.000001", 13),
+            ("5.6-none.000001", 0),
+            ("5.6-undef.000001", 0),
+            ("relay-mixed.000001", 1),
+        ];
+        for &(name, verified) in &fixtures {
+            let mut iter =
+                BinlogFileParserBuilder::try_from_path(format!("test_data/fde/{}", name))
+                    .unwrap()
+                    .verify_checksums(true)
+                    .build();
+            let results = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(results.len(), 7, "{}", name);
+            assert_eq!(results[6].xid, Some(11096), "{}", name);
+            let stats = iter.stats();
+            assert_eq!(stats.checksums_verified, verified, "{}", name);
+            assert_eq!(stats.checksum_failures, 0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_checksum_algorithm() {
+        use crate::checksum::Checksum;
+        use byteorder::ByteOrder;
+
+        #[derive(Debug)]
+        struct Sum;
+
+        impl Checksum for Sum {
+            fn compute(&self, header: &[u8], body: &[u8]) -> u32 {
+                header.iter().chain(body).fold(0u32, |sum, b| {
+                    sum.wrapping_mul(31).wrapping_add(u32::from(*b))
+                })
+            }
+        }
+
+        // re-checksum the test file with algorithm 0xf0
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        data[123 - 5] = 0xf0;
+        let mut offset = 4;
+        while offset < data.len() {
+            let end = offset + byteorder::LittleEndian::read_u32(&data[offset + 9..]) as usize;
+            let checksum = Sum.compute(&data[offset..end - 4], &[]);
+            data[end - 4..end].copy_from_slice(&checksum.to_le_bytes());
+            offset = end;
+        }
+
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone()))
+            .unwrap()
+            .verify_checksums(true)
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 8);
+        assert_matches!(
+            results[0],
+            Err(EventParseError::UnsupportedChecksumAlgorithm(0xf0))
+        );
+
+        for threads in [0, 2] {
+            let mut iter = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(&data))
+                .unwrap()
+                .checksum_algorithm(0xf0, Sum)
+                .checksum_threads(threads)
+                .build();
+            assert_eq!(
+                iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap().len(),
+                7
+            );
+            let stats = iter.stats();
+            assert_eq!(stats.checksums_verified, 13);
+            assert_eq!(stats.checksum_failures, 0);
+        }
+    }
+
+    // append an event with a valid checksum to an in-memory binlog
+    fn push_event(binlog: &mut Vec<u8>, type_code: u8, body: &[u8]) {
+        let offset = binlog.len() as u32;
+        let length = 19 + body.len() as u32 + 4;
+        let start = binlog.len();
+        binlog.extend_from_slice(&0u32.to_le_bytes());
+        binlog.push(type_code);
+        binlog.extend_from_slice(&36431u32.to_le_bytes());
+        binlog.extend_from_slice(&length.to_le_bytes());
+        binlog.extend_from_slice(&(offset + length).to_le_bytes());
+        binlog.extend_from_slice(&0u16.to_le_bytes());
+        binlog.extend_from_slice(body);
+        let checksum = crate::event::compute_checksum(&binlog[start..], &[]);
+        binlog.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    fn rotate_body(next_file: &str) -> Vec<u8> {
+        let mut body = 4u64.to_le_bytes().to_vec();
+        body.extend_from_slice(next_file.as_bytes());
+        body
+    }
+
+    // a binlog with the same transactions as the test file, but whose PreviousGtidsLogEvent
+    // includes every transaction in the test file
+    fn second_binlog() -> Vec<u8> {
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut binlog = original[..123].to_vec();
+        let mut previous_gtids = 1u64.to_le_bytes().to_vec();
+        previous_gtids.extend_from_slice(&original[123 + 19 + 8..123 + 19 + 24]);
+        previous_gtids.extend_from_slice(&1u64.to_le_bytes());
+        previous_gtids.extend_from_slice(&1u64.to_le_bytes());
+        previous_gtids.extend_from_slice(&14920u64.to_le_bytes());
+        push_event(&mut binlog, 35, &previous_gtids);
+        binlog.extend_from_slice(&original[194..]);
+        binlog
+    }
+
+    #[test]
+    fn test_previous_gtids() {
+        let mut iter = parse_file("test_data/bin-log.000001").unwrap();
+        assert!(iter.previous_gtids().is_none());
+        let first = iter.next().unwrap().unwrap();
+        let previous = iter.previous_gtids().unwrap();
+        assert_eq!(previous.len(), 14916);
+        assert!(!previous.contains(&first.gtid.unwrap()));
+
+        let mut iter = parse_reader(std::io::Cursor::new(second_binlog())).unwrap();
+        iter.next().unwrap().unwrap();
+        let previous = iter.previous_gtids().unwrap();
+        assert_eq!(previous.len(), 14919);
+        assert!(previous.contains(&first.gtid.unwrap()));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut iter = parse_file("test_data/bin-log.000001").unwrap();
+        assert_eq!(iter.by_ref().count(), 7);
+        let session = iter.into_session();
+        assert_eq!(session.stats().events_read, 13);
+        assert_eq!(session.gtids_seen().len(), 3);
+
+        // a file which continues the last transaction, with no Table Map event of its own
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut binlog = original[..123].to_vec();
+        binlog.extend_from_slice(&original[942..1039]);
+        let without_session = parse_reader(std::io::Cursor::new(binlog.clone()))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(without_session.table_ref.is_none());
+        assert!(without_session.gtid.is_none());
+
+        let mut iter = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(binlog))
+            .unwrap()
+            .session(session)
+            .build();
+        let event = iter.next().unwrap().unwrap();
+        assert_eq!(event.table_ref.unwrap().table, "foo");
+        assert_eq!(event.rows.len(), 1);
+        assert_eq!(event.gtid.unwrap().sequence(), 14919);
+        assert_eq!(iter.by_ref().count(), 1);
+        assert_eq!(iter.stats().events_read, 15);
+    }
+
+    fn binlog_directory(files: &[(&str, Vec<u8>)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.path().join(name), contents).unwrap();
+        }
+        std::fs::write(dir.path().join("bin-log.index"), b"").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_directory() {
+        let mut first = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut first, 4, &rotate_body("bin-log.000002"));
+        let dir = binlog_directory(&[
+            ("bin-log.000001", first.clone()),
+            ("bin-log.000002", second_binlog()),
+        ]);
+        let results = BinlogFileParserBuilder::try_from_directory(dir.path())
+            .unwrap()
+            .verify_checksums(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 15);
+        assert_eq!(
+            results[7].rotate,
+            Some(crate::event::Rotation {
+                next_log_name: "bin-log.000002".to_owned(),
+                position: 4
+            })
+        );
+        assert_eq!(results[13].rows.len(), 1);
+        assert_eq!(results[14].type_code, TypeCode::XidEvent);
+
+        // the second file doesn't account for the transactions in the first
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let dir = binlog_directory(&[
+            ("bin-log.000001", first),
+            ("bin-log.000002", original.clone()),
+        ]);
+        let results = BinlogFileParserBuilder::try_from_directory(dir.path())
+            .unwrap()
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 9);
+        assert_matches!(
+            results[8],
+            Err(EventParseError::PreviousGtidsMismatch { ref expected, .. })
+                if expected.to_string() == "87cee3a4-6b31-11e7-bdfd-0d98d6698870:1-14919"
+        );
+
+        // the first file doesn't rotate into the second
+        let dir = binlog_directory(&[
+            ("bin-log.000001", original.clone()),
+            ("bin-log.000002", second_binlog()),
+        ]);
+        let results = BinlogFileParserBuilder::try_from_directory(dir.path())
+            .unwrap()
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 8);
+        assert_matches!(results[7], Err(EventParseError::MissingRotate { .. }));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut first = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut first, 4, &rotate_body("bin-log.000002"));
+        let dir = binlog_directory(&[
+            ("bin-log.000001", first),
+            ("bin-log.000002", second_binlog()),
+        ]);
+        std::fs::write(
+            dir.path().join("bin-log.index"),
+            "./bin-log.000001\n./bin-log.000002\n",
+        )
+        .unwrap();
+        let results = BinlogFileParserBuilder::try_from_index(dir.path().join("bin-log.index"))
+            .unwrap()
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 15);
+    }
+
+    #[test]
+    fn test_event_decoder() {
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut data, 200, b"vendor data");
+        let builder = || {
+            BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone())).unwrap()
+        };
+        let events = builder().build().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(events.len(), 7);
+
+        let events = builder()
+            .event_decoder(
+                TypeCode::OtherUnknown(200),
+                |event: &crate::event::Event| {
+                    let text = String::from_utf8_lossy(event.data()).into_owned();
+                    Ok(Some(serde_json::Value::String(text)))
+                },
+            )
+            .event_decoder(TypeCode::XidEvent, |_: &crate::event::Event| Ok(None))
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[5].type_code, TypeCode::OtherUnknown(200));
+        assert_eq!(events[5].custom, Some("vendor data".into()));
+    }
+
+    #[test]
+    fn test_following_rotations() {
+        // rotation isn't limited to the next sequence number or the same base name
+        let mut first = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut first, 4, &rotate_body("other-log.000007"));
+        let dir = binlog_directory(&[
+            ("bin-log.000001", first.clone()),
+            ("other-log.000007", second_binlog()),
+            ("bin-log.000002", second_binlog()),
+        ]);
+        let results = parse_directory(dir.path().join("bin-log.000001"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 15);
+        assert_eq!(results[14].type_code, TypeCode::XidEvent);
+
+        // starting from a directory, and stopping at a rotation to a file which doesn't exist
+        let dir = binlog_directory(&[("bin-log.000001", first)]);
+        let mut iter = parse_directory(dir.path()).unwrap();
+        assert_eq!(
+            iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap().len(),
+            8
+        );
+        assert_eq!(iter.stats().events_read, 14);
+    }
+
+    #[test]
+    fn test_directory_sequence_gap() {
+        use crate::errors::BinlogParseError;
+
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let dir = binlog_directory(&[
+            ("bin-log.000001", original.clone()),
+            ("bin-log.000003", original),
+        ]);
+        assert_matches!(
+            BinlogFileParserBuilder::try_from_directory(dir.path()).err(),
+            Some(BinlogParseError::SequenceGap { .. })
+        );
+        let dir = binlog_directory(&[]);
+        assert_matches!(
+            BinlogFileParserBuilder::try_from_directory(dir.path()).err(),
+            Some(BinlogParseError::NoBinlogFiles(_))
+        );
+    }
+
+    #[test]
+    fn test_lenient() {
+        use crate::catalog::{MismatchPolicy, SchemaCatalog};
+
+        let catalog = SchemaCatalog::new().with_table("bltest", "foo", Vec::new());
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(catalog.clone(), MismatchPolicy::Error)
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 2);
+
+        let mut iter = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(catalog.clone(), MismatchPolicy::Error)
+            .lenient(true)
+            .build();
+        let results = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(results.len(), 7);
+        assert_eq!(iter.stats().events_skipped, 2);
+
+        // the rows and commit of both transactions are dropped along with their table maps
+        let mut iter = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .expected_catalog(catalog, MismatchPolicy::Error)
+            .error_policy(crate::errors::ErrorPolicy::SkipTransaction)
+            .build();
+        let results = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            results.iter().map(|e| e.offset).collect::<Vec<_>>(),
+            vec![259, 524, 814]
+        );
+        assert_eq!(iter.stats().events_skipped, 2);
+    }
+
+    #[test]
+    fn test_stop() {
+        let offsets = |builder: BinlogFileParserBuilder<std::fs::File>| {
+            builder
+                .build()
+                .map(|e| e.unwrap().offset)
+                .collect::<Vec<_>>()
+        };
+        let builder =
+            || BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap();
+        assert_eq!(offsets(builder().stop_position(718)), vec![259, 524, 652]);
+        assert_eq!(
+            offsets(builder().stop_position(719)),
+            vec![259, 524, 652, 718]
+        );
+        assert_eq!(
+            offsets(builder().stop_timestamp(1550192300)),
+            vec![259, 524, 652, 718]
+        );
+        assert_eq!(
+            offsets(builder().stop_timestamp(1550192281)),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    fn test_recover_corruption() {
+        // the length of the WriteRows event at 652 is too short to hold its header
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        data[652 + 9..652 + 13].copy_from_slice(&5u32.to_le_bytes());
+        let results = parse_reader(std::io::Cursor::new(data.clone()))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_matches!(results[2], Err(EventParseError::Io(_)));
+
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data.clone()))
+            .unwrap()
+            .recover_corruption(true)
+            .lenient(true)
+            .build()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 7);
+        assert_matches!(
+            results[2],
+            Err(EventParseError::SkippedRange {
+                start: 652,
+                end: 718
+            })
+        );
+        assert_eq!(results[3].as_ref().unwrap().type_code, TypeCode::XidEvent);
+        assert_eq!(results[5].as_ref().unwrap().rows.len(), 1);
+
+        // a truncated final event is skipped to the end of the file, without lenient mode too
+        let results =
+            BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data[..1000].to_vec()))
+                .unwrap()
+                .recover_corruption(true)
+                .build()
+                .collect::<Vec<_>>();
+        assert_eq!(results.len(), 6);
+        assert_matches!(results[2], Err(EventParseError::SkippedRange { .. }));
+        assert_matches!(
+            results[5],
+            Err(EventParseError::SkippedRange {
+                start: 942,
+                end: 1000
+            })
+        );
+    }
+
+    #[test]
+    fn test_resume_from() {
+        let events = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let position = events[2].position.clone().unwrap();
+        assert_eq!(position.file_name.as_deref(), Some("bin-log.000001"));
+        assert_eq!(position.offset, events[3].offset);
+        // the transaction of the rows event isn't complete until its commit
+        let gtid = events[2].gtid.unwrap();
+        assert!(!position.gtid_set.contains(&gtid));
+        let commit = events
+            .iter()
+            .find(|e| e.gtid == Some(gtid) && e.xid.is_some())
+            .unwrap();
+        assert!(commit.position.as_ref().unwrap().gtid_set.contains(&gtid));
+
+        let serialized = serde_json::to_string(&position).unwrap();
+        let resumed = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .resume_from(&serde_json::from_str(&serialized).unwrap())
+            .unwrap()
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(resumed.len(), events.len() - 3);
+        for (resumed, original) in resumed.iter().zip(&events[3..]) {
+            assert_eq!(resumed.offset, original.offset);
+            assert_eq!(resumed.gtid, original.gtid);
+            assert_eq!(resumed.table_ref, original.table_ref);
+            assert_eq!(resumed.position, original.position);
+        }
+
+        // resuming between a Table Map event and the rows event which uses it
+        let before_rows = super::BinlogPosition {
+            offset: 652,
+            ..position.clone()
+        };
+        let resumed = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .resume_from(&before_rows)
+            .unwrap()
+            .build()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.offset, 652);
+        assert_eq!(resumed.gtid, Some(gtid));
+        assert_eq!(resumed.table_ref, events[2].table_ref);
+        assert_eq!(resumed.rows.len(), 1);
+
+        let elsewhere = super::BinlogPosition {
+            file_name: Some("bin-log.000002".to_owned()),
+            ..position
+        };
+        assert_matches!(
+            BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+                .unwrap()
+                .resume_from(&elsewhere)
+                .err(),
+            Some(BinlogParseError::PositionFileNotFound(_))
+        );
+    }
+
+    #[test]
+    fn test_restore_table_map() {
+        let mut iter = parse_file("test_data/bin-log.000001").unwrap();
+        let rows_event = iter.by_ref().nth(2).unwrap().unwrap();
+        let snapshot = serde_json::to_string(iter.table_map()).unwrap();
+
+        let from_offset = || {
+            BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+                .unwrap()
+                .start_position(rows_event.offset)
+        };
+        // without the Table Map event, the rows can't be decoded
+        let missing = from_offset().build().next().unwrap().unwrap();
+        assert!(missing.rows.is_empty());
+
+        let restored = from_offset()
+            .restore_table_map(serde_json::from_str(&snapshot).unwrap())
+            .build()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.offset, rows_event.offset);
+        assert_eq!(restored.table_ref, rows_event.table_ref);
+        assert_eq!(restored.rows.len(), 1);
+        assert_eq!(restored.rows[0].cols(), rows_event.rows[0].cols());
+    }
+
+    #[test]
+    fn test_project_columns() {
+        let builder =
+            || BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap();
+        let first_row = |builder: BinlogFileParserBuilder<std::fs::File>| {
+            let mut events = builder.build().filter_map(|e| e.ok());
+            events.find(|e| !e.rows.is_empty()).unwrap().rows[0].clone()
+        };
+        let row = first_row(builder().project_columns("bltest", "foo", [0, 2]));
+        assert_eq!(
+            *row.cols(),
+            [
+                Some(MySQLValue::SignedInteger(1)),
+                None,
+                Some(MySQLValue::String("zero point one".to_owned())),
+            ]
+        );
+        // the test data has no column names
+        let row = first_row(builder().project_columns("bltest", "foo", ["id"]));
+        assert_eq!(*row.cols(), [None, None, None]);
+        let row = first_row(builder().project_columns("bltest", "other", [0]));
+        assert!(row.cols().iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_strict_table_map() {
+        let strict = || {
+            BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+                .unwrap()
+                .strict_table_map(true)
+        };
+        let err = strict().start_position(652).build().next().unwrap().err();
+        assert_matches!(
+            err,
+            Some(EventParseError::UnknownTableId {
+                table_id: 203,
+                offset: 652
+            })
+        );
+        // from the start of the file, every table map is known
+        assert_eq!(strict().build().filter(|e| e.is_err()).count(), 0);
+    }
+
+    #[test]
+    fn test_size_limits() {
+        // a corrupt length which would otherwise be read as a 4GiB event
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        data[652 + 9..652 + 13].copy_from_slice(&0xffff_0000u32.to_le_bytes());
+        let results = parse_reader(std::io::Cursor::new(data.clone()))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_matches!(results[2], Err(EventParseError::Io(_)));
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(data))
+            .unwrap()
+            .max_event_size(1 << 20)
+            .build()
+            .collect::<Vec<_>>();
+        assert_matches!(
+            results[2],
+            Err(EventParseError::EventTooLarge {
+                offset: 652,
+                length: 0xffff_0000,
+                limit: 0x10_0000
+            })
+        );
+
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .max_value_size(1)
+            .build()
+            .collect::<Vec<_>>();
+        assert_matches!(
+            results[2],
+            Err(EventParseError::ColumnParseError(
+                ColumnParseError::ValueTooLarge { limit: 1, .. }
+            ))
+        );
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .max_value_size(1024)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+    }
+
+    #[test]
+    fn test_ignores_next_position() {
+        use byteorder::ByteOrder;
+
+        // relay logs (and files over 4GiB) have next_position fields which don't match the
+        // event's position in the file
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut offset = 4;
+        while offset < data.len() {
+            let length = byteorder::LittleEndian::read_u32(&data[offset + 9..]);
+            data[offset + 13..offset + 17].copy_from_slice(&0u32.to_le_bytes());
+            offset += length as usize;
+        }
+        let results = parse_reader(std::io::Cursor::new(data))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        assert_eq!(results[5].offset, 942);
+    }
+
+    #[test]
+    fn test_executed_gtids() {
+        let executed = "87cee3a4-6b31-11e7-bdfd-0d98d6698870:1-14918"
+            .parse()
+            .unwrap();
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .executed_gtids(executed)
+            // nothing from the skipped transaction is emitted, even with a custom decoder
+            .event_decoder(TypeCode::TableMapEvent, |e: &crate::event::Event| {
+                Ok(Some(serde_json::json!(e.offset())))
+            })
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].query.as_deref(), Some("BEGIN"));
+        assert_eq!(results[1].custom, Some(serde_json::json!(888)));
+        let results = &results[1..];
+        assert_eq!(
+            results[1].gtid.unwrap().to_string(),
+            "87cee3a4-6b31-11e7-bdfd-0d98d6698870:14919"
+        );
+        assert_eq!(results[1].rows.len(), 1);
+    }
+
+    #[test]
+    fn test_start_after_gtid() {
+        let offsets = |builder: BinlogFileParserBuilder<std::fs::File>| {
+            builder
+                .build()
+                .map(|e| e.unwrap().offset)
+                .collect::<Vec<_>>()
+        };
+        let builder =
+            || BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap();
+        let gtid = |s: &str| s.parse::<crate::gtid::Gtid>().unwrap();
+        assert_eq!(
+            offsets(builder().start_after_gtid(gtid("87cee3a4-6b31-11e7-bdfd-0d98d6698870:14918"))),
+            vec![814, 942, 1008]
+        );
+        assert_eq!(
+            offsets(builder().start_after_gtid(gtid("87cee3a4-6b31-11e7-bdfd-0d98d6698870:99"))),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            offsets(
+                builder().start_after_gtid_set(
+                    "87cee3a4-6b31-11e7-bdfd-0d98d6698870:1-14917:14919"
+                        .parse()
+                        .unwrap()
+                )
+            ),
+            vec![524, 652, 718, 814, 942, 1008]
+        );
+    }
+
+    #[test]
+    fn test_lazy_rows() {
+        let eager = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let lazy = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .lazy_rows(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(lazy.len(), eager.len());
+        for (lazy, eager) in lazy.iter().zip(&eager) {
+            assert_eq!(lazy.lazy_rows.is_some(), lazy.type_code.is_rows_event());
+            assert!(lazy.rows.is_empty());
+            assert_eq!(lazy.table_ref, eager.table_ref);
+            let lazy_rows = lazy.rows_iter().collect::<Result<Vec<_>, _>>().unwrap();
+            let eager_rows = eager.rows_iter().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(
+                serde_json::to_value(&lazy_rows).unwrap(),
+                serde_json::to_value(&eager_rows).unwrap()
+            );
+        }
+        assert_eq!(lazy[2].lazy_rows.as_ref().unwrap().table_id(), 203);
+        assert_eq!(lazy[2].rows_iter().count(), 1);
+        let borrowed = lazy[2].lazy_rows.as_ref().unwrap().rows_ref().unwrap();
+        assert_eq!(borrowed.rows.len(), 1);
+    }
+
+    #[test]
+    fn test_table_filters() {
+        let offsets = |builder: BinlogFileParserBuilder<std::fs::File>| {
+            builder
+                .build()
+                .map(|e| e.unwrap().offset)
+                .collect::<Vec<_>>()
+        };
+        let builder =
+            || BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001").unwrap();
+        let all = vec![259, 524, 652, 718, 814, 942, 1008];
+        let without_rows = vec![259, 524, 718, 814, 1008];
+        assert_eq!(offsets(builder().include_tables(&[("bltest", "foo")])), all);
+        assert_eq!(
+            offsets(builder().include_tables(&[("bltest", "bar"), ("other", "foo")])),
+            without_rows
+        );
+        assert_eq!(
+            offsets(
+                builder()
+                    .table_name_case(crate::table_map::TableNameCase::CaseInsensitive)
+                    .include_tables(&[("BLTest", "FOO")])
+            ),
+            all
+        );
+        assert_eq!(offsets(builder().exclude_schemas(&["other"])), all);
+        assert_eq!(
+            offsets(
+                builder()
+                    .include_tables(&[("bltest", "foo")])
+                    .exclude_schemas(&["bltest"])
+            ),
+            without_rows
+        );
+    }
+
+    #[test]
+    fn test_column_stats() {
+        let mut iter = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .collect_column_stats(true)
+            .build();
+        for event in &mut iter {
+            event.unwrap();
+        }
+        let table = iter.column_stats().unwrap().table("bltest", "foo").unwrap();
+        assert_eq!(table.row_images, 2);
+        assert_eq!(table.columns.len(), 3);
+        assert_eq!(table.columns[0].nulls, 0);
+        assert_eq!(table.columns[0].approx_distinct(), 2);
+        assert!(table.columns[1].min.is_some());
+        assert!(table.columns[2].min.is_none());
+    }
+
+    #[test]
+    fn test_value_decoder() {
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .value_decoder(
+                "bltest",
+                "foo",
+                2,
+                |_: &ColumnType, value: MySQLValue| match value {
+                    MySQLValue::String(s) => Ok(MySQLValue::Custom {
+                        type_name: "length".to_owned(),
+                        value: s.len().into(),
+                    }),
+                    _ => Err(ColumnParseError::CustomDecoder("not a string".into())),
+                },
+            )
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let cols = results[2].rows[0].cols();
+        assert_matches!(cols[0], Some(MySQLValue::SignedInteger(1)));
+        assert_matches!(
+            cols[2],
+            Some(MySQLValue::Custom { ref type_name, ref value })
+                if type_name == "length" && *value == 14
+        );
+
+        // by name, once the table map has column names
+        let mut provider = std::collections::HashMap::new();
+        provider.insert(
+            ("bltest".to_owned(), "foo".to_owned()),
+            ["id", "val_decimal", "comment"]
+                .iter()
+                .map(|name| ColumnDescriptor {
+                    name: Some(name.to_string()),
+                    ..ColumnDescriptor::default()
+                })
+                .collect(),
+        );
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .schema_provider(provider)
+            .value_decoder("bltest", "foo", "ID", |_: &ColumnType, _: MySQLValue| {
+                Ok(MySQLValue::Null)
+            })
+            .lazy_rows(true)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let rows = results[2]
+            .rows_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_matches!(rows[0].cols()[0], Some(MySQLValue::Null));
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_transaction_payload() {
+        // rewrite the last transaction in the test file as a compressed payload
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut payload = Vec::new();
+        for (start, end) in [(814, 888), (888, 942), (942, 1008), (1008, 1039)] {
+            let mut inner = original[start..end - 4].to_vec();
+            inner[9..13].copy_from_slice(&(end as u32 - start as u32 - 4).to_le_bytes());
+            payload.extend_from_slice(&inner);
+        }
+        let compressed = zstd::encode_all(payload.as_slice(), 0).unwrap();
+        let mut body = vec![0x01, 0x01, compressed.len() as u8, 0x02, 0x01, 0x00, 0x00];
+        body.extend_from_slice(&compressed);
+        let mut binlog = original[..814].to_vec();
+        push_event(&mut binlog, 40, &body);
+        let results = parse_reader(std::io::Cursor::new(binlog))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        assert_eq!(results[4].query.as_deref(), Some("BEGIN"));
+        assert_eq!(results[5].type_code, TypeCode::WriteRowsEventV2);
+        assert_eq!(results[5].offset, 814);
+        assert_eq!(results[5].table_ref.as_ref().unwrap().table, "foo");
+        assert_eq!(results[5].rows.len(), 1);
+        assert_eq!(results[6].xid, Some(results[3].xid.unwrap() + 1));
+    }
+
+    #[test]
+    fn test_incident() {
+        let mut binlog = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut body = 1u16.to_le_bytes().to_vec();
+        body.push(11);
+        body.extend_from_slice(b"LOST_EVENTS");
+        push_event(&mut binlog, 26, &body);
+        let results = parse_reader(std::io::Cursor::new(binlog))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 8);
+        let incident = results[7].incident.as_ref().unwrap();
+        assert_eq!(incident.incident_type, IncidentType::LostEvents);
+        assert_eq!(incident.message, "LOST_EVENTS");
+        assert_eq!(results[7].offset, 1039);
+        assert!(results[..7].iter().all(|e| e.incident.is_none()));
+    }
+
+    #[test]
+    fn test_anonymous_transactions() {
+        // rewrite both transactions as if they were committed with gtid_mode=OFF
+        let mut data = std::fs::read("test_data/bin-log.000001").unwrap();
+        for &gtid_event in &[459, 749] {
+            data[gtid_event + 4] = 34;
+            for b in &mut data[gtid_event + 20..gtid_event + 44] {
+                *b = 0;
+            }
+        }
+        let results = parse_reader(std::io::Cursor::new(data))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 7);
+        // the CREATE TABLE still has a GTID
+        assert!(results[0].gtid.is_some());
+        assert!(!results[0].anonymous);
+        for event in &results[1..] {
+            assert_eq!(event.gtid, None);
+            assert!(event.anonymous);
+        }
+        let first = results[1].logical_timestamp.unwrap();
+        let second = results[4].logical_timestamp.unwrap();
+        assert_eq!(second.sequence_number, first.sequence_number + 1);
+        assert_eq!(
+            serde_json::to_value(&results[0]).unwrap().get("anonymous"),
+            None
+        );
+        assert_eq!(
+            serde_json::to_value(&results[1]).unwrap()["anonymous"],
+            serde_json::json!(true)
+        );
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let mut binlog = std::fs::read("test_data/bin-log.000001").unwrap();
+        push_event(&mut binlog, 27, b"bin-log.000001");
+        let mut iter = parse_reader(std::io::Cursor::new(binlog)).unwrap();
+        assert_eq!(iter.by_ref().count(), 7);
+        let heartbeat = iter.last_heartbeat().unwrap();
+        assert_eq!(heartbeat.log_name, "bin-log.000001");
+        assert_eq!(heartbeat.position, 1039 + 19 + 14 + 4);
+    }
+
+    #[test]
+    fn test_max_rows_per_event() {
+        // combine the rows of both inserts in the test file into a single event
+        let original = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut body = original[942 + 19..1008 - 4].to_vec();
+        body.extend_from_slice(&original[942 + 19 + 12..1008 - 4]);
+        body.extend_from_slice(&original[942 + 19 + 12..1008 - 4]);
+        let mut binlog = original[..942].to_vec();
+        push_event(&mut binlog, 30, &body);
+        let results =
+            BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(binlog.clone()))
+                .unwrap()
+                .max_rows_per_event(2)
+                .build()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(results.len(), 7);
+        let (first, second) = (&results[5], &results[6]);
+        assert_eq!(first.split, Some(EventSplit { index: 0, count: 2 }));
+        assert_eq!(second.split, Some(EventSplit { index: 1, count: 2 }));
+        assert_eq!((first.rows.len(), second.rows.len()), (2, 1));
+        assert_eq!(second.row_count, Some(1));
+        assert_eq!(first.offset, second.offset);
+        assert_eq!(first.gtid, second.gtid);
+        assert_eq!(second.table_ref.as_ref().unwrap().table, "foo");
+        assert_eq!(results[2].split, None);
+
+        // zero means no limit
+        let results = BinlogFileParserBuilder::try_from_reader(std::io::Cursor::new(binlog))
+            .unwrap()
+            .max_rows_per_event(0)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[5].split, None);
+        assert_eq!(results[5].rows.len(), 3);
+    }
+
+    #[test]
+    fn test_schema_provider() {
+        let names = ["id", "val_decimal", "comment"];
+        let mut provider = std::collections::HashMap::new();
+        provider.insert(
+            ("bltest".to_owned(), "foo".to_owned()),
+            names
+                .iter()
+                .map(|name| ColumnDescriptor {
+                    name: Some(name.to_string()),
+                    ..ColumnDescriptor::default()
+                })
+                .collect(),
+        );
+        let results = BinlogFileParserBuilder::try_from_path("test_data/bin-log.000001")
+            .unwrap()
+            .schema_provider(provider)
+            .build()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let columns = results[2].columns.as_ref().unwrap();
+        assert_eq!(columns[2].name.as_deref(), Some("comment"));
+        assert!(!columns[2].nullable);
+        assert!(results[0].columns.is_none());
+        assert!(parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .all(|e| e.unwrap().columns.is_none()));
+    }
 }
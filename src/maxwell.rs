@@ -0,0 +1,231 @@
+//! Converting [`BinlogEvent`]s into the flat JSON records written by
+//! [Maxwell](https://maxwells-daemon.io/dataformat/), for pipelines which already consume them.
+//!
+//! Each changed row becomes one [`MaxwellRecord`]. Like Maxwell, [`maxwell_records`] holds back
+//! the rows of a transaction until it commits, so that every record carries the transaction's
+//! `xid` and the last one is marked with `commit: true`.
+//!
+//! Column names come from the table's [`ColumnDescriptor`]s, so the binlog must be written with
+//! `binlog_row_metadata=FULL` or read with a
+//! [`SchemaProvider`](crate::table_map::SchemaProvider); columns whose names aren't known are
+//! named by their position, as `_0`, `_1`, and so on.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::errors::EventParseError;
+use crate::event::{RowEvent, TransactionMarker};
+use crate::table_map::ColumnDescriptor;
+use crate::value::{format_utc, MySQLValue};
+use crate::BinlogEvent;
+
+/// The kind of change a [`MaxwellRecord`] describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxwellType {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// One changed row in Maxwell's format
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaxwellRecord {
+    pub database: String,
+    pub table: String,
+    #[serde(rename = "type")]
+    pub kind: MaxwellType,
+    /// When the change was committed, in seconds since the epoch
+    pub ts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xid: Option<u64>,
+    /// Set on the last row of each transaction
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub commit: bool,
+    /// The row after the change (before it, for deletes)
+    pub data: Map<String, Value>,
+    /// For updates, the previous values of the columns which changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Map<String, Value>>,
+}
+
+/// Iterator adapter returned by [`maxwell_records`]
+pub struct MaxwellRecords<I> {
+    inner: I,
+    // the rows of the current transaction, which are held back until it commits
+    transaction: Vec<MaxwellRecord>,
+    ready: VecDeque<MaxwellRecord>,
+}
+
+impl<I> MaxwellRecords<I> {
+    /// Release the rows of the current transaction
+    fn finish_transaction(&mut self, xid: Option<u64>, committed: bool) {
+        if let Some(last) = self.transaction.last_mut() {
+            last.commit = committed;
+        }
+        for mut record in self.transaction.drain(..) {
+            record.xid = xid;
+            self.ready.push_back(record);
+        }
+    }
+}
+
+impl<I> Iterator for MaxwellRecords<I>
+where
+    I: Iterator<Item = Result<BinlogEvent, EventParseError>>,
+{
+    type Item = Result<MaxwellRecord, EventParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.ready.pop_front() {
+                return Some(Ok(record));
+            }
+            let event = match self.inner.next() {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    // the stream ended part-way through a transaction
+                    self.finish_transaction(None, false);
+                    return self.ready.pop_front().map(Ok);
+                }
+            };
+            if let Err(e) = records(&event, &mut self.transaction) {
+                return Some(Err(e));
+            }
+            match event.transaction_marker {
+                Some(TransactionMarker::Commit) => self.finish_transaction(event.xid, true),
+                // only changes to non-transactional tables are logged before a ROLLBACK, and
+                // those weren't rolled back
+                Some(TransactionMarker::Rollback) => self.finish_transaction(None, true),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Convert a stream of [`BinlogEvent`]s into Maxwell records
+pub fn maxwell_records<I: IntoIterator>(events: I) -> MaxwellRecords<I::IntoIter> {
+    MaxwellRecords {
+        inner: events.into_iter(),
+        transaction: Vec::new(),
+        ready: VecDeque::new(),
+    }
+}
+
+/// Append a record for each row of a rows event to `out`
+fn records(event: &BinlogEvent, out: &mut Vec<MaxwellRecord>) -> Result<(), EventParseError> {
+    let table = match event.table_ref {
+        Some(ref table) => table,
+        None => return Ok(()),
+    };
+    let columns = event.columns.as_deref().unwrap_or(&[]);
+    for row in event.rows_iter() {
+        let row = row?;
+        let (kind, data, old) = match *row {
+            RowEvent::NewRow { ref cols } => (MaxwellType::Insert, image(cols, columns), None),
+            RowEvent::DeletedRow { ref cols } => (MaxwellType::Delete, image(cols, columns), None),
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => {
+                let data = image(after_cols, columns);
+                let old = image(before_cols, columns)
+                    .into_iter()
+                    .filter(|(name, value)| data.get(name) != Some(value))
+                    .collect();
+                (MaxwellType::Update, data, Some(old))
+            }
+        };
+        out.push(MaxwellRecord {
+            database: table.schema.clone(),
+            table: table.table.clone(),
+            kind,
+            ts: event.timestamp,
+            xid: None,
+            commit: false,
+            data,
+            old,
+        });
+    }
+    Ok(())
+}
+
+fn image(cols: &[Option<MySQLValue>], columns: &[ColumnDescriptor]) -> Map<String, Value> {
+    cols.iter()
+        .enumerate()
+        .filter_map(|(i, value)| {
+            let value = value.as_ref()?;
+            let descriptor = columns.get(i);
+            let name = descriptor
+                .and_then(|d| d.name.clone())
+                .unwrap_or_else(|| format!("_{}", i));
+            Some((name, to_json(value, descriptor)))
+        })
+        .collect()
+}
+
+/// Represent a value as Maxwell does
+fn to_json(value: &MySQLValue, descriptor: Option<&ColumnDescriptor>) -> Value {
+    match *value {
+        MySQLValue::SignedInteger(i) => i.into(),
+        MySQLValue::UnsignedInteger(u) => u.into(),
+        MySQLValue::Float(f) => f64::from(f).into(),
+        MySQLValue::Double(f) => f.into(),
+        MySQLValue::String(ref s) => s.clone().into(),
+        MySQLValue::Enum(index) => index.into(),
+        MySQLValue::EnumString { ref name, .. } => name.clone().into(),
+        MySQLValue::Set(bits) => match descriptor.and_then(|d| d.set_members(value)) {
+            Some(members) => members.into(),
+            None => bits.into(),
+        },
+        MySQLValue::Bit { value, .. } => value.into(),
+        MySQLValue::Blob(ref blob) | MySQLValue::Bytes(ref blob) => base64::encode(&blob.0).into(),
+        MySQLValue::Year(year) => year.into(),
+        MySQLValue::Date { .. } | MySQLValue::Time { .. } | MySQLValue::DateTime { .. } => {
+            value.to_temporal_string().map_or(Value::Null, Value::from)
+        }
+        MySQLValue::Timestamp {
+            unix_time,
+            subsecond,
+            fsp,
+        } => format_utc(i64::from(unix_time), subsecond * 1_000, fsp, ' ').into(),
+        MySQLValue::Json(ref document) => document.clone(),
+        MySQLValue::JsonDiff(ref diffs) => serde_json::to_value(diffs).unwrap_or(Value::Null),
+        MySQLValue::Decimal(ref decimal) => {
+            bigdecimal::ToPrimitive::to_f64(decimal).map_or(Value::Null, Value::from)
+        }
+        MySQLValue::Geometry { ref wkb, .. } => base64::encode(&wkb.0).into(),
+        MySQLValue::Custom { ref value, .. } => value.clone(),
+        MySQLValue::Null => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{maxwell_records, MaxwellType};
+    use crate::parse_file;
+
+    #[test]
+    fn test_maxwell_records() {
+        let records = maxwell_records(parse_file("test_data/bin-log.000001").unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!(record.kind, MaxwellType::Insert);
+        assert!(record.commit);
+        assert!(record.xid.is_some());
+
+        // the test binlog doesn't name its columns
+        let serialized = serde_json::to_value(record).unwrap();
+        assert_eq!(serialized["database"], "bltest");
+        assert_eq!(serialized["table"], "foo");
+        assert_eq!(serialized["type"], "insert");
+        assert_eq!(serialized["data"]["_0"], 1);
+        assert_eq!(serialized["data"]["_1"], 0.1);
+        assert!(serialized.get("old").is_none());
+    }
+}
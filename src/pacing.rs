@@ -0,0 +1,60 @@
+//! Replaying events with the same spacing they were originally written with.
+
+use std::time::{Duration, Instant};
+
+/// Tracks the offset between event timestamps and the wall clock
+pub(crate) struct Pacer {
+    // the first event timestamp seen, and when we saw it
+    origin: Option<(u32, Instant)>,
+}
+
+impl Pacer {
+    pub(crate) fn new() -> Self {
+        Pacer { origin: None }
+    }
+
+    /// How long to wait before emitting a transaction which started at `timestamp` (in seconds
+    /// since the epoch), given that it is now `now`
+    pub(crate) fn delay_for(&mut self, timestamp: u32, now: Instant) -> Option<Duration> {
+        let (origin_timestamp, origin_instant) = *self.origin.get_or_insert((timestamp, now));
+        // timestamps can go backwards (e.g. after a clock adjustment on the source); there's no
+        // sensible way to honor that, so just don't wait
+        let elapsed = Duration::from_secs(u64::from(timestamp.checked_sub(origin_timestamp)?));
+        (origin_instant + elapsed)
+            .checked_duration_since(now)
+            .filter(|delay| !delay.is_zero())
+    }
+
+    /// Block until a transaction which started at `timestamp` should be emitted
+    pub(crate) fn wait_for(&mut self, timestamp: u32) {
+        if let Some(delay) = self.delay_for(timestamp, Instant::now()) {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Pacer;
+
+    #[test]
+    fn test_delay_for() {
+        let mut pacer = Pacer::new();
+        let start = Instant::now();
+        assert_eq!(pacer.delay_for(1000, start), None);
+        assert_eq!(
+            pacer.delay_for(1002, start + Duration::from_millis(500)),
+            Some(Duration::from_millis(1500))
+        );
+        // we're already running behind
+        assert_eq!(pacer.delay_for(1003, start + Duration::from_secs(4)), None);
+        // the source clock went backwards
+        assert_eq!(pacer.delay_for(999, start + Duration::from_secs(5)), None);
+        assert_eq!(
+            pacer.delay_for(1010, start + Duration::from_secs(5)),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
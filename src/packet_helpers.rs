@@ -25,7 +25,25 @@ pub(crate) fn read_variable_length_integer<R: Read>(r: &mut R) -> io::Result<i64
     } else if first == 0xfe {
         r.read_i64::<LittleEndian>()
     } else {
-        unreachable!();
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid length-encoded integer marker {:#x}", first),
+        ))
+    }
+}
+
+/// Read an unsigned length-encoded ("packed") integer, as used by the fields MySQL 8.0 added to
+/// GTID and transaction payload events
+pub(crate) fn read_packed_unsigned<R: Read>(r: &mut R) -> io::Result<u64> {
+    match r.read_u8()? {
+        first @ 0..=0xfa => Ok(u64::from(first)),
+        0xfc => Ok(u64::from(r.read_u16::<LittleEndian>()?)),
+        0xfd => Ok(u64::from(read_uint24(r)?)),
+        0xfe => r.read_u64::<LittleEndian>(),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid packed integer marker {:#x}", other),
+        )),
     }
 }
 
@@ -46,7 +64,12 @@ pub(crate) fn read_known_length_integer_be<R: Read>(r: &mut R, bytes: usize) ->
             }
         }
         4 => i64::from(r.read_i32::<BigEndian>()?),
-        _ => unimplemented!(),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid integer length {}", bytes),
+            ))
+        }
     })
 }
 
@@ -62,6 +85,41 @@ pub(crate) fn read_int24<R: Read>(r: &mut R) -> io::Result<i32> {
     Ok(LittleEndian::read_i32(&buf))
 }
 
+/// Read an unsigned integer in the variable-length encoding used by the `mysql::serialization`
+/// library (MySQL 8.3+): the number of trailing one bits in the first byte gives the number of
+/// additional bytes, and the value follows the length marker in little-endian order.
+pub(crate) fn read_serialized_unsigned<R: Read>(r: &mut R) -> io::Result<u64> {
+    let first = r.read_u8()?;
+    let num_bytes = first.trailing_ones() as usize + 1;
+    if num_bytes == 9 {
+        return r.read_u64::<LittleEndian>();
+    }
+    let mut buf = [0u8; 8];
+    buf[0] = first;
+    r.read_exact(&mut buf[1..num_bytes])?;
+    Ok(LittleEndian::read_u64(&buf) >> num_bytes)
+}
+
+/// Read a zigzag-encoded signed integer in the `mysql::serialization` variable-length encoding
+pub(crate) fn read_serialized_signed<R: Read>(r: &mut R) -> io::Result<i64> {
+    let value = read_serialized_unsigned(r)?;
+    Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+}
+
+/// Write an unsigned integer in the `mysql::serialization` variable-length encoding
+pub(crate) fn write_serialized_unsigned(w: &mut Vec<u8>, value: u64) {
+    match (1..=8).find(|n| value < 1u64 << (7 * n)) {
+        Some(num_bytes) => {
+            let encoded = (value << num_bytes) | ((1 << (num_bytes - 1)) - 1);
+            w.extend_from_slice(&encoded.to_le_bytes()[..num_bytes]);
+        }
+        None => {
+            w.push(0xff);
+            w.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
 pub(crate) fn read_one_byte_length_prefixed_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
     let length = r.read_u8()?;
     read_nbytes(r, length)
@@ -76,7 +134,12 @@ pub(crate) fn read_var_byte_length_prefixed_bytes<R: Read>(
     r: &mut R,
     pl: u8,
 ) -> io::Result<Vec<u8>> {
-    let len = match pl {
+    let len = read_var_byte_length(r, pl)?;
+    read_nbytes(r, len)
+}
+
+pub(crate) fn read_var_byte_length<R: Read>(r: &mut R, pl: u8) -> io::Result<usize> {
+    Ok(match pl {
         1 => r.read_u8()? as usize,
         2 => r.read_u16::<LittleEndian>()? as usize,
         3 => {
@@ -86,9 +149,13 @@ pub(crate) fn read_var_byte_length_prefixed_bytes<R: Read>(
         }
         4 => r.read_u32::<LittleEndian>()? as usize,
         8 => r.read_u64::<LittleEndian>()? as usize,
-        l => unreachable!("got unexpected length {0:?}", l),
-    };
-    read_nbytes(r, len)
+        l => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid length prefix size {}", l),
+            ))
+        }
+    })
 }
 
 pub(crate) fn read_one_byte_length_prefixed_string<R: Read>(r: &mut R) -> io::Result<String> {
@@ -101,15 +168,40 @@ pub(crate) fn read_two_byte_length_prefixed_string<R: Read>(r: &mut R) -> io::Re
     Ok(String::from_utf8_lossy(&buf).into_owned())
 }
 
+/// The most memory allocated up front for a length read from the data. Longer values are read
+/// into a buffer which grows as the bytes actually arrive, so a corrupt length fails with an EOF
+/// rather than an enormous allocation.
+pub(crate) const MAX_PREALLOCATION: usize = 1 << 20;
+
 pub(crate) fn read_nbytes<R: Read, S: Into<usize>>(
     r: &mut R,
     desired_bytes: S,
 ) -> io::Result<Vec<u8>> {
-    let mut into = vec![0u8; desired_bytes.into()];
-    r.read_exact(&mut into)?;
+    let desired_bytes = desired_bytes.into();
+    if desired_bytes <= MAX_PREALLOCATION {
+        let mut into = vec![0u8; desired_bytes];
+        r.read_exact(&mut into)?;
+        return Ok(into);
+    }
+    let mut into = Vec::with_capacity(MAX_PREALLOCATION);
+    r.take(desired_bytes as u64).read_to_end(&mut into)?;
+    if into.len() != desired_bytes {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
     Ok(into)
 }
 
+pub(crate) fn skip_nbytes<R: Read>(r: &mut R, bytes: usize) -> io::Result<()> {
+    let skipped = io::copy(&mut r.take(bytes as u64), &mut io::sink())?;
+    if skipped != bytes as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "unexpected end of data while skipping",
+        ));
+    }
+    Ok(())
+}
+
 pub(crate) fn read_variable_length_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
     let mut byte = 0x80;
     let mut length = 0usize;
@@ -119,7 +211,10 @@ pub(crate) fn read_variable_length_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u
         length |= ((byte & 0x7f) as usize) << shbits;
         shbits += 7;
         if shbits >= 57 {
-            panic!("illegal shift, shbits={}", shbits);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "variable-length integer is too long",
+            ));
         }
     }
     read_nbytes(r, length)
@@ -131,6 +226,25 @@ pub(crate) fn read_variable_length_string<R: Read>(r: &mut R) -> io::Result<Stri
 }
 
 const DECIMAL_DIGITS_PER_INTEGER: u8 = 9;
+/// The largest precision of a DECIMAL column
+pub(crate) const MAX_DECIMAL_PRECISION: u8 = 65;
+const COMPRESSED_BYTE_MAP: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+/// Number of bytes used to store a NEWDECIMAL with the given precision and scale, or None if
+/// the scale is larger than the precision
+pub(crate) fn decimal_binary_size(precision: u8, decimal: u8) -> Option<usize> {
+    let integral = precision.checked_sub(decimal)?;
+    let uncompressed_integers = (integral / DECIMAL_DIGITS_PER_INTEGER) as usize;
+    let uncompressed_decimals = (decimal / DECIMAL_DIGITS_PER_INTEGER) as usize;
+    let compressed_integers = (integral % DECIMAL_DIGITS_PER_INTEGER) as usize;
+    let compressed_decimals = (decimal % DECIMAL_DIGITS_PER_INTEGER) as usize;
+    Some(
+        uncompressed_integers * 4
+            + COMPRESSED_BYTE_MAP[compressed_integers]
+            + uncompressed_decimals * 4
+            + COMPRESSED_BYTE_MAP[compressed_decimals],
+    )
+}
 
 pub(crate) fn read_new_decimal<R: Read>(
     r: &mut R,
@@ -140,7 +254,14 @@ pub(crate) fn read_new_decimal<R: Read>(
     // like every other binlog parser's implementation, this code
     // is a transliteration of https://github.com/jeremycole/mysql_binlog/blob/master/lib/mysql_binlog/binlog_field_parser.rb#L233
     // because this format is bananas
-    let compressed_byte_map = [0usize, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+    let invalid = DecimalParseError::InvalidPrecision {
+        precision,
+        scale: decimal,
+    };
+    if precision == 0 || precision > MAX_DECIMAL_PRECISION {
+        return Err(invalid);
+    }
+    let bytes_to_read = decimal_binary_size(precision, decimal).ok_or(invalid)?;
     let integral = precision - decimal;
     let uncompressed_integers: usize = (integral / DECIMAL_DIGITS_PER_INTEGER).into();
     let uncompressed_decimals: usize = (decimal / DECIMAL_DIGITS_PER_INTEGER).into();
@@ -149,24 +270,23 @@ pub(crate) fn read_new_decimal<R: Read>(
     let compressed_decimals: usize =
         decimal as usize - (uncompressed_decimals * DECIMAL_DIGITS_PER_INTEGER as usize);
 
-    let bytes_to_read: usize = uncompressed_integers * 4
-        + compressed_byte_map[compressed_integers]
-        + uncompressed_decimals * 4
-        + compressed_byte_map[compressed_decimals];
-
     let mut buf = read_nbytes(r, bytes_to_read)?;
 
     let mut components = Vec::new();
 
-    let is_negative = (buf[0] & 0x80) == 0;
-    buf[0] ^= 0x80;
+    let sign = buf.first_mut().ok_or(DecimalParseError::InvalidPrecision {
+        precision,
+        scale: decimal,
+    })?;
+    let is_negative = (*sign & 0x80) == 0;
+    *sign ^= 0x80;
     if is_negative {
         components.push("-".to_owned());
     }
     let mut r = Cursor::new(buf);
     // if there's a compressed integral part, read it
     if compressed_integers != 0 {
-        let to_read = compressed_byte_map[compressed_integers];
+        let to_read = COMPRESSED_BYTE_MAP[compressed_integers];
         components.push(read_known_length_integer_be(&mut r, to_read)?.to_string())
     }
     for _ in 0..uncompressed_integers {
@@ -178,7 +298,7 @@ pub(crate) fn read_new_decimal<R: Read>(
     }
     if compressed_decimals != 0 {
         components.push(
-            read_known_length_integer_be(&mut r, compressed_byte_map[compressed_decimals])?
+            read_known_length_integer_be(&mut r, COMPRESSED_BYTE_MAP[compressed_decimals])?
                 .to_string(),
         )
     }
@@ -186,11 +306,24 @@ pub(crate) fn read_new_decimal<R: Read>(
     Ok(decimal)
 }
 
+/// Number of bytes used to store the fractional-seconds part of a temporal value
+pub(crate) fn datetime_subsecond_bytes(pack_length: u8) -> usize {
+    match pack_length {
+        1 | 2 => 1,
+        3 | 4 => 2,
+        5 | 6 => 3,
+        _ => 0,
+    }
+}
+
+/// Read the fractional-seconds part of a temporal value, in microseconds. MySQL stores it in 1,
+/// 2, or 3 bytes (for a precision of 1-2, 3-4, and 5-6 respectively) as hundredths,
+/// ten-thousandths, and millionths of a second.
 pub(crate) fn read_datetime_subsecond_part<R: Read>(r: &mut R, pack_length: u8) -> io::Result<u32> {
     Ok(match pack_length {
         0 => 0u32,
-        1 | 2 => read_known_length_integer_be(r, 1)? as u32,
-        3 | 4 => read_known_length_integer_be(r, 2)? as u32,
+        1 | 2 => read_known_length_integer_be(r, 1)? as u32 * 10_000,
+        3 | 4 => read_known_length_integer_be(r, 2)? as u32 * 100,
         5 | 6 => read_known_length_integer_be(r, 3)? as u32,
         _ => 0u32,
     })
@@ -200,10 +333,27 @@ pub(crate) fn read_datetime_subsecond_part<R: Read>(r: &mut R, pack_length: u8)
 mod tests {
     use std::io::Cursor;
 
+    use assert_matches::assert_matches;
     use bigdecimal::BigDecimal;
 
     use super::read_new_decimal;
+    use super::read_packed_unsigned;
     use super::read_var_byte_length_prefixed_bytes;
+    use crate::errors::DecimalParseError;
+
+    #[test]
+    fn test_read_packed_unsigned() {
+        assert_eq!(read_packed_unsigned(&mut &[0xfa][..]).unwrap(), 250);
+        assert_eq!(
+            read_packed_unsigned(&mut &[0xfc, 0x2c, 0x01][..]).unwrap(),
+            300
+        );
+        assert_eq!(
+            read_packed_unsigned(&mut &[0xfd, 0x00, 0x00, 0x01][..]).unwrap(),
+            0x10000
+        );
+        assert!(read_packed_unsigned(&mut &[0xfb][..]).is_err());
+    }
 
     #[test]
     fn test_read_new_decimal() {
@@ -225,6 +375,12 @@ mod tests {
             read_new_decimal(&mut uut, 10, 5).expect("should parse"),
             expected
         );
+        for (precision, scale) in [(0, 0), (5, 10), (66, 0)] {
+            assert_matches!(
+                read_new_decimal(&mut Cursor::new(vec![0x80; 32]), precision, scale),
+                Err(DecimalParseError::InvalidPrecision { .. })
+            );
+        }
     }
 
     #[test]
@@ -253,4 +409,31 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_serialized_varlen() {
+        use super::{read_serialized_signed, read_serialized_unsigned, write_serialized_unsigned};
+
+        for value in &[
+            0,
+            1,
+            127,
+            128,
+            14918,
+            1 << 49,
+            (1 << 56) - 1,
+            1 << 56,
+            u64::MAX,
+        ] {
+            let mut buf = Vec::new();
+            write_serialized_unsigned(&mut buf, *value);
+            assert_eq!(
+                read_serialized_unsigned(&mut buf.as_slice()).unwrap(),
+                *value
+            );
+        }
+        // zigzag: 3 => 6, -3 => 5
+        assert_eq!(read_serialized_signed(&mut [0x0c_u8].as_ref()).unwrap(), 3);
+        assert_eq!(read_serialized_signed(&mut [0x0a_u8].as_ref()).unwrap(), -3);
+    }
 }
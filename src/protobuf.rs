@@ -0,0 +1,215 @@
+//! Encoding [`BinlogEvent`]s as Protocol Buffers messages.
+//!
+//! Only available with the `protobuf` feature. Events are encoded as the `BinlogEvent` message
+//! of [`SCHEMA`] (also at `proto/binlog_event.proto` in this crate's source), which describes
+//! the values of every table, so consumers need no per-table schema.
+
+use serde_json::Value as JsonValue;
+
+use crate::errors::EventParseError;
+use crate::event::RowEvent;
+use crate::value::MySQLValue;
+use crate::BinlogEvent;
+
+/// The `.proto` definition of the messages written by [`encode_event`]
+pub const SCHEMA: &str = include_str!("../proto/binlog_event.proto");
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+/// A message being encoded
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn write_varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.0.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.0.push(n as u8);
+    }
+
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        self.write_varint(field << 3 | wire_type);
+    }
+
+    fn uint64(&mut self, field: u64, n: u64) {
+        self.tag(field, VARINT);
+        self.write_varint(n);
+    }
+
+    fn int64(&mut self, field: u64, n: i64) {
+        self.uint64(field, n as u64);
+    }
+
+    fn sint64(&mut self, field: u64, n: i64) {
+        self.uint64(field, ((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn float(&mut self, field: u64, f: f32) {
+        self.tag(field, FIXED32);
+        self.0.extend_from_slice(&f.to_le_bytes());
+    }
+
+    fn double(&mut self, field: u64, f: f64) {
+        self.tag(field, FIXED64);
+        self.0.extend_from_slice(&f.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.tag(field, LENGTH_DELIMITED);
+        self.write_varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn message(&mut self, field: u64, message: Message) {
+        self.bytes(field, &message.0);
+    }
+
+    /// Like `bytes`, but leaves out an empty string, as proto3 does for default values
+    fn string(&mut self, field: u64, s: &str) {
+        if !s.is_empty() {
+            self.bytes(field, s.as_bytes());
+        }
+    }
+}
+
+fn value(value: Option<&MySQLValue>) -> Message {
+    let mut m = Message::default();
+    let value = match value {
+        Some(value) => value,
+        None => return m,
+    };
+    match *value {
+        MySQLValue::Null => m.uint64(1, 1),
+        MySQLValue::SignedInteger(i) => m.sint64(2, i),
+        MySQLValue::UnsignedInteger(u) => m.uint64(3, u),
+        MySQLValue::Float(f) => m.float(4, f),
+        MySQLValue::Double(f) => m.double(5, f),
+        MySQLValue::String(ref s) => m.bytes(6, s.as_bytes()),
+        MySQLValue::Blob(ref b) | MySQLValue::Bytes(ref b) => m.bytes(7, &b.0),
+        MySQLValue::Decimal(ref d) => m.bytes(8, d.to_string().as_bytes()),
+        MySQLValue::Json(ref document) => m.bytes(9, document.to_string().as_bytes()),
+        MySQLValue::JsonDiff(ref diffs) => {
+            let diffs = serde_json::to_value(diffs).unwrap_or(JsonValue::Null);
+            m.bytes(9, diffs.to_string().as_bytes())
+        }
+        MySQLValue::Custom { ref value, .. } => m.bytes(9, value.to_string().as_bytes()),
+        MySQLValue::Date { .. } | MySQLValue::Time { .. } | MySQLValue::DateTime { .. } => {
+            let formatted = value.to_temporal_string().unwrap_or_default();
+            m.bytes(10, formatted.as_bytes())
+        }
+        MySQLValue::Timestamp { .. } => {
+            let (seconds, nanos) = value.as_datetime64().unwrap_or_default();
+            m.int64(11, seconds * 1_000_000 + i64::from(nanos / 1_000))
+        }
+        MySQLValue::EnumString { ref name, .. } => m.bytes(12, name.as_bytes()),
+        MySQLValue::Enum(index) => m.uint64(13, index as u64),
+        MySQLValue::Set(bits) => m.uint64(14, bits),
+        MySQLValue::Bit { value, .. } => m.uint64(15, value),
+        MySQLValue::Year(year) => m.uint64(16, u64::from(year)),
+        MySQLValue::Geometry { srid, ref wkb } => {
+            let mut geometry = Message::default();
+            geometry.uint64(1, u64::from(srid));
+            geometry.bytes(2, &wkb.0);
+            m.message(17, geometry)
+        }
+    }
+    m
+}
+
+fn row(cols: &[Option<MySQLValue>]) -> Message {
+    let mut m = Message::default();
+    for col in cols {
+        m.message(1, value(col.as_ref()));
+    }
+    m
+}
+
+/// Encode an event as a `BinlogEvent` message
+pub fn encode_event(event: &BinlogEvent) -> Result<Vec<u8>, EventParseError> {
+    let mut m = Message::default();
+    let type_code = match serde_json::to_value(event.type_code) {
+        Ok(JsonValue::String(name)) => name,
+        _ => format!("{:?}", event.type_code),
+    };
+    m.string(1, &type_code);
+    if event.timestamp != 0 {
+        m.uint64(2, u64::from(event.timestamp));
+    }
+    if let Some(gtid) = event.gtid {
+        m.string(3, &gtid.to_string());
+    }
+    if let Some(ref table) = event.table_ref {
+        m.string(4, &table.schema);
+        m.string(5, &table.table);
+        m.uint64(6, table.table_id);
+    }
+    if let Some(ref columns) = event.columns {
+        for column in columns.iter() {
+            m.bytes(7, column.name.as_deref().unwrap_or("").as_bytes());
+        }
+    }
+    for row_event in event.rows_iter() {
+        let row_event = row_event?;
+        let mut change = Message::default();
+        match *row_event {
+            RowEvent::NewRow { ref cols } => change.message(2, row(cols)),
+            RowEvent::DeletedRow { ref cols } => change.message(1, row(cols)),
+            RowEvent::UpdatedRow {
+                ref before_cols,
+                ref after_cols,
+            } => {
+                change.message(1, row(before_cols));
+                change.message(2, row(after_cols));
+            }
+        }
+        m.message(8, change);
+    }
+    if let Some(ref query) = event.query {
+        m.string(9, query);
+    }
+    if let Some(xid) = event.xid {
+        m.uint64(10, xid);
+    }
+    m.uint64(11, event.offset);
+    m.uint64(12, u64::from(event.server_id));
+    Ok(m.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_event, value};
+    use crate::parse_file;
+    use crate::value::MySQLValue;
+
+    #[test]
+    fn test_value() {
+        assert!(value(None).0.is_empty());
+        assert_eq!(value(Some(&MySQLValue::Null)).0, [0x08, 0x01]);
+        assert_eq!(value(Some(&MySQLValue::SignedInteger(-1))).0, [0x10, 0x01]);
+        assert_eq!(
+            value(Some(&MySQLValue::String("hi".to_owned()))).0,
+            [0x32, 0x02, b'h', b'i']
+        );
+    }
+
+    #[test]
+    fn test_encode_event() {
+        let events = parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let encoded = encode_event(&events[2]).unwrap();
+        let type_code = b"WRITE_ROWS_EVENT_V2";
+        assert_eq!(encoded[..2], [0x0a, type_code.len() as u8]);
+        assert_eq!(&encoded[2..2 + type_code.len()], type_code);
+        // a row change with an after-image, whose first value is 1
+        let rows = encoded.windows(2).position(|w| w[0] == 0x42).unwrap();
+        assert_eq!(encoded[rows + 2], 0x12);
+        assert_eq!(encoded[rows + 4..rows + 8], [0x0a, 0x02, 0x10, 0x02]);
+    }
+}
@@ -0,0 +1,231 @@
+//! Reconciliation of binlog events against snapshots of a table, for detecting drift.
+//!
+//! A [`Reconciler`] starts from a snapshot of a table's contents, applies every change to the
+//! table in a stretch of binlog, and then compares the result with a snapshot taken at the end
+//! of that stretch. Any row the binlog can't explain (one which was changed out-of-band, or by
+//! transactions missing from the binlogs) is reported as a [`Discrepancy`].
+//!
+//! This requires that the binlogs were written with `binlog_row_image=FULL`. Rows are compared by
+//! their JSON serialization, so snapshot values must be of the same
+//! [`MySQLValue`](crate::value::MySQLValue) variants that the binlog decodes to (e.g. `DECIMAL`
+//! columns as [`MySQLValue::Decimal`](crate::value::MySQLValue::Decimal) with the column's
+//! scale).
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::event::RowData;
+use crate::BinlogEvent;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// Why a row could not be explained by the binlog
+pub enum DiscrepancyKind {
+    /// The binlog inserted a row whose key was already present
+    DuplicateInsert,
+    /// The binlog updated or deleted a row which was not present
+    MissingRow,
+    /// The binlog updated or deleted a row, but its before-image didn't match the row
+    BeforeImageMismatch { expected: Value, actual: Value },
+    /// The end snapshot has a row which the binlog never inserted
+    Unexplained { actual: Value },
+    /// The end snapshot is missing a row which the binlog never deleted
+    Vanished { expected: Value },
+    /// The end snapshot has a different version of a row than the binlog produced
+    Changed { expected: Value, actual: Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// A row which could not be explained by the binlog
+pub struct Discrepancy {
+    /// The values of the primary key columns
+    pub key: Value,
+    /// Offset of the event which exposed the discrepancy, or `None` if it was found by comparing
+    /// with the end snapshot
+    pub offset: Option<u64>,
+    #[serde(flatten)]
+    pub kind: DiscrepancyKind,
+}
+
+/// Applies binlog events for one table on top of a start snapshot; see the
+/// [module documentation](self)
+pub struct Reconciler {
+    schema_name: String,
+    table_name: String,
+    key_columns: Vec<usize>,
+    // serialized key -> (key, row)
+    rows: HashMap<String, (Value, Value)>,
+    discrepancies: Vec<Discrepancy>,
+}
+
+impl Reconciler {
+    /// Start reconciling a table from a snapshot of its rows. `key_columns` are the zero-based
+    /// positions of the primary key columns.
+    pub fn new<S, T, I>(schema_name: S, table_name: T, key_columns: Vec<usize>, start: I) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+        I: IntoIterator<Item = RowData>,
+    {
+        let mut reconciler = Reconciler {
+            schema_name: schema_name.into(),
+            table_name: table_name.into(),
+            key_columns,
+            rows: HashMap::new(),
+            discrepancies: Vec::new(),
+        };
+        for row in start {
+            let (id, key) = reconciler.key(&row);
+            reconciler.rows.insert(id, (key, row_value(&row)));
+        }
+        reconciler
+    }
+
+    fn key(&self, row: &RowData) -> (String, Value) {
+        let key = Value::Array(
+            self.key_columns
+                .iter()
+                .map(|&i| match row.get(i) {
+                    Some(value) => serde_json::to_value(value).unwrap_or(Value::Null),
+                    None => Value::Null,
+                })
+                .collect(),
+        );
+        (key.to_string(), key)
+    }
+
+    fn report(&mut self, key: Value, offset: Option<u64>, kind: DiscrepancyKind) {
+        self.discrepancies.push(Discrepancy { key, offset, kind });
+    }
+
+    fn insert(&mut self, row: &RowData, offset: u64) {
+        let (id, key) = self.key(row);
+        if self.rows.contains_key(&id) {
+            self.report(key.clone(), Some(offset), DiscrepancyKind::DuplicateInsert);
+        }
+        self.rows.insert(id, (key, row_value(row)));
+    }
+
+    fn remove(&mut self, row: &RowData, offset: u64) {
+        let (id, key) = self.key(row);
+        let actual = row_value(row);
+        match self.rows.remove(&id) {
+            None => self.report(key, Some(offset), DiscrepancyKind::MissingRow),
+            Some((_, expected)) if expected != actual => self.report(
+                key,
+                Some(offset),
+                DiscrepancyKind::BeforeImageMismatch { expected, actual },
+            ),
+            Some(_) => {}
+        }
+    }
+
+    /// Apply a rows event, if it modifies this table
+    pub fn apply(&mut self, event: &BinlogEvent) {
+        match event.table_ref {
+            Some(ref t) if t.schema == self.schema_name && t.table == self.table_name => {}
+            _ => return,
+        }
+        for row in &event.rows {
+            if let Some(before) = row.before_cols() {
+                self.remove(before, event.offset);
+            }
+            if let Some(after) = row.after_cols() {
+                self.insert(after, event.offset);
+            }
+        }
+    }
+
+    /// Compare the result of applying the binlog with a snapshot of the table's rows at the end,
+    /// returning every discrepancy found (including those found while applying events)
+    pub fn finish<I: IntoIterator<Item = RowData>>(mut self, end: I) -> Vec<Discrepancy> {
+        let mut expected = std::mem::take(&mut self.rows);
+        for row in end {
+            let (id, key) = self.key(&row);
+            let actual = row_value(&row);
+            match expected.remove(&id) {
+                None => self.report(key, None, DiscrepancyKind::Unexplained { actual }),
+                Some((_, expected)) if expected != actual => {
+                    self.report(key, None, DiscrepancyKind::Changed { expected, actual })
+                }
+                Some(_) => {}
+            }
+        }
+        let mut vanished = expected.into_iter().collect::<Vec<_>>();
+        vanished.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, (key, expected)) in vanished {
+            self.report(key, None, DiscrepancyKind::Vanished { expected });
+        }
+        self.discrepancies
+    }
+}
+
+fn row_value(row: &RowData) -> Value {
+    serde_json::to_value(row).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiscrepancyKind, Reconciler};
+    use crate::event::{RowData, RowEvent};
+    use crate::parse_file;
+    use crate::value::MySQLValue;
+
+    fn binlog_rows() -> Vec<RowData> {
+        parse_file("test_data/bin-log.000001")
+            .unwrap()
+            .flat_map(|e| e.unwrap().rows)
+            .map(|row| match row {
+                RowEvent::NewRow { cols } => cols,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut reconciler = Reconciler::new("bltest", "foo", vec![0], Vec::new());
+        for event in parse_file("test_data/bin-log.000001").unwrap() {
+            reconciler.apply(&event.unwrap());
+        }
+        let mut end = binlog_rows();
+        assert_eq!(end.len(), 2);
+        // the second insert never made it, and a row was written out-of-band
+        end.truncate(1);
+        end.push(vec![
+            Some(MySQLValue::SignedInteger(3)),
+            None,
+            Some(MySQLValue::String("three".to_owned())),
+        ]);
+        let discrepancies = reconciler.finish(end);
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(
+            discrepancies[0].key,
+            serde_json::json!([{"SignedInteger": 3}])
+        );
+        assert!(matches!(
+            discrepancies[0].kind,
+            DiscrepancyKind::Unexplained { .. }
+        ));
+        assert_eq!(
+            discrepancies[1].key,
+            serde_json::json!([{"SignedInteger": 2}])
+        );
+        assert!(matches!(
+            discrepancies[1].kind,
+            DiscrepancyKind::Vanished { .. }
+        ));
+
+        // starting from a snapshot which already has the rows means the inserts are suspicious
+        let mut reconciler = Reconciler::new("bltest", "foo", vec![0], binlog_rows());
+        for event in parse_file("test_data/bin-log.000001").unwrap() {
+            reconciler.apply(&event.unwrap());
+        }
+        let discrepancies = reconciler.finish(binlog_rows());
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(discrepancies[0].offset, Some(652));
+        assert_eq!(discrepancies[0].kind, DiscrepancyKind::DuplicateInsert);
+    }
+}
@@ -0,0 +1,195 @@
+//! Reading a sequence of binlog files (e.g. a whole directory, or every file reached by following
+//! Rotate events) as one continuous stream.
+
+use std::collections::VecDeque;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::binlog_file::BinlogFile;
+use crate::errors::{BinlogParseError, EventParseError};
+use crate::event::{Event, EventData, TypeCode};
+use crate::gtid::{Gtid, GtidSet};
+
+pub(crate) struct FileSequence<BR: Read + Seek> {
+    current: PathBuf,
+    remaining: VecDeque<PathBuf>,
+    // once `remaining` is exhausted, open the file named by the Rotate event, if it exists
+    follow_rotations: bool,
+    open: fn(&Path) -> Result<BinlogFile<BR>, BinlogParseError>,
+    // the file named by the Rotate event at the end of the current file
+    rotate_target: Option<String>,
+    // PreviousGtids of the current file, if we read it, plus everything executed since
+    executed: Option<GtidSet>,
+}
+
+impl<BR: Read + Seek> FileSequence<BR> {
+    pub(crate) fn new(
+        mut files: VecDeque<PathBuf>,
+        open: fn(&Path) -> Result<BinlogFile<BR>, BinlogParseError>,
+    ) -> Option<(Self, PathBuf)> {
+        let first = files.pop_front()?;
+        let sequence = FileSequence {
+            current: first.clone(),
+            remaining: files,
+            follow_rotations: false,
+            open,
+            rotate_target: None,
+            executed: None,
+        };
+        Some((sequence, first))
+    }
+
+    /// A sequence starting at `first`, which finds each next file from the Rotate event at the
+    /// end of the one before it
+    pub(crate) fn following(
+        first: PathBuf,
+        open: fn(&Path) -> Result<BinlogFile<BR>, BinlogParseError>,
+    ) -> Self {
+        FileSequence {
+            current: first,
+            remaining: VecDeque::new(),
+            follow_rotations: true,
+            open,
+            rotate_target: None,
+            executed: None,
+        }
+    }
+
+    /// Track the events which let us check continuity between files
+    pub(crate) fn observe(&mut self, event: &Event) -> Result<(), EventParseError> {
+        match event.type_code() {
+            TypeCode::RotateEvent => {
+                if let Some(EventData::RotateEvent { next_log_name, .. }) = event.inner(None)? {
+                    self.rotate_target = Some(next_log_name);
+                }
+            }
+            TypeCode::PreviousGtidsLogEvent => {
+                let previous = GtidSet::read_from(&mut event.data().as_slice())?;
+                if let Some(expected) = self.executed.take() {
+                    if expected != previous {
+                        return Err(EventParseError::PreviousGtidsMismatch {
+                            file: self.current.clone(),
+                            expected,
+                            actual: previous,
+                        });
+                    }
+                }
+                self.executed = Some(previous);
+            }
+            TypeCode::GtidLogEvent | TypeCode::GtidTaggedLogEvent => {
+                if let (
+                    Some(executed),
+                    Some(EventData::GtidLogEvent {
+                        uuid,
+                        tag,
+                        coordinate,
+                        ..
+                    }),
+                ) = (self.executed.as_mut(), event.inner(None)?)
+                {
+                    executed.insert(&Gtid::new(uuid, tag, coordinate));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Skip ahead to the file with the given name, returning its path, or `None` if it isn't in
+    /// the sequence
+    pub(crate) fn skip_to(&mut self, name: &str) -> Option<PathBuf> {
+        let is_named = |path: &Path| path.file_name().and_then(|n| n.to_str()) == Some(name);
+        if is_named(&self.current) {
+            return Some(self.current.clone());
+        }
+        while let Some(next) = self.remaining.pop_front() {
+            if is_named(&next) {
+                self.current = next.clone();
+                return Some(next);
+            }
+        }
+        if self.follow_rotations {
+            let next = self.current.with_file_name(Path::new(name).file_name()?);
+            if next.is_file() {
+                self.current = next.clone();
+                return Some(next);
+            }
+        }
+        None
+    }
+
+    /// Once the listed files run out, continue with the file named by each Rotate event
+    pub(crate) fn follow_rotations(&mut self) {
+        self.follow_rotations = true;
+    }
+
+    /// Whether the current file rotated to one which doesn't exist yet
+    pub(crate) fn awaiting_rotation(&self) -> bool {
+        self.follow_rotations && self.remaining.is_empty() && self.rotate_target.is_some()
+    }
+
+    pub(crate) fn open(&self, path: &Path) -> Result<BinlogFile<BR>, BinlogParseError> {
+        (self.open)(path)
+    }
+
+    /// Whether the current file is the last one known of. When following rotations, every file
+    /// is the last until it rotates.
+    pub(crate) fn is_last(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    /// Open the next file in the sequence, checking that the current one rotated into it.
+    /// Returns `None` once every file has been read.
+    pub(crate) fn advance(&mut self) -> Option<Result<BinlogFile<BR>, EventParseError>> {
+        let next = match self.remaining.pop_front() {
+            Some(next) => next,
+            None if self.follow_rotations => self.rotated_file()?,
+            None => return None,
+        };
+        let result = self.check_rotate(&next).and_then(|()| {
+            (self.open)(&next).map_err(|e| EventParseError::NextFile {
+                file: next.clone(),
+                inner: Box::new(e),
+            })
+        });
+        if result.is_err() {
+            // the stream is no longer continuous, so don't try to read any further
+            self.remaining.clear();
+        }
+        self.current = next;
+        self.rotate_target = None;
+        Some(result)
+    }
+
+    /// The file named by the current file's Rotate event, in the same directory, if it exists
+    /// yet
+    fn rotated_file(&self) -> Option<PathBuf> {
+        // only ever look beside the current file, whatever the event says
+        let name = Path::new(self.rotate_target.as_ref()?).file_name()?;
+        let next = self.current.with_file_name(name);
+        if next.is_file() {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    fn check_rotate(&self, next: &Path) -> Result<(), EventParseError> {
+        let expected = match self.rotate_target {
+            Some(ref expected) => expected,
+            None => {
+                return Err(EventParseError::MissingRotate {
+                    file: self.current.clone(),
+                })
+            }
+        };
+        if next.file_name().and_then(|n| n.to_str()) != Some(expected.as_str()) {
+            return Err(EventParseError::UnexpectedNextFile {
+                file: self.current.clone(),
+                expected: expected.to_owned(),
+                found: next.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
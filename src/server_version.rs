@@ -0,0 +1,138 @@
+//! Versions of the servers which write binlogs, and the features they support.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Which distribution of MySQL a server is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerFlavor {
+    MySql,
+    /// Percona Server, whose versions have a build number after the MySQL version they're based
+    /// on (e.g. `8.0.36-28`)
+    Percona,
+    MariaDb,
+}
+
+/// A server version, as parsed from a FormatDescriptionEvent (e.g. `8.0.36-log`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub flavor: ServerFlavor,
+}
+
+impl ServerVersion {
+    /// Parse a version string. Missing or malformed numbers are read as zero, so this never
+    /// fails; the flavor is MySQL unless the string says otherwise.
+    pub fn parse(version: &str) -> Self {
+        // old clients can't handle a major version of 10, so MariaDB may prefix its version
+        let version = match version.strip_prefix("5.5.5-") {
+            Some(rest) if rest.to_ascii_lowercase().contains("mariadb") => rest,
+            _ => version,
+        };
+        let mut numbers = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|n| n.parse::<u32>().unwrap_or(0));
+        let (major, minor, patch) = (
+            numbers.next().unwrap_or(0),
+            numbers.next().unwrap_or(0),
+            numbers.next().unwrap_or(0),
+        );
+        // the first suffix after the version proper, e.g. `27` in `5.7.24-27-log`
+        let suffix = version
+            .split_once('-')
+            .map(|(_, rest)| rest.split('-').next().unwrap_or(""));
+        let flavor = if version.to_ascii_lowercase().contains("mariadb") {
+            ServerFlavor::MariaDb
+        } else if suffix.is_some_and(|s| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit())) {
+            ServerFlavor::Percona
+        } else {
+            ServerFlavor::MySql
+        };
+        ServerVersion {
+            major,
+            minor,
+            patch,
+            flavor,
+        }
+    }
+
+    /// A version in the numeric form of MySQL's `immediate_server_version` and
+    /// `original_server_version` (e.g. `80036`), which doesn't say which flavor it is
+    pub fn from_number(version: u32) -> Self {
+        ServerVersion {
+            major: version / 10000,
+            minor: version / 100 % 100,
+            patch: version % 100,
+            flavor: ServerFlavor::MySql,
+        }
+    }
+
+    /// Whether this is at least the given version, ignoring the flavor
+    pub fn at_least(&self, major: u32, minor: u32, patch: u32) -> bool {
+        (self.major, self.minor, self.patch) >= (major, minor, patch)
+    }
+
+    /// Whether the server's FormatDescriptionEvent says which checksum algorithm its events use
+    /// (MySQL 5.6.1+, MariaDB 5.3+)
+    pub fn supports_checksums(&self) -> bool {
+        match self.flavor {
+            ServerFlavor::MariaDb => self.at_least(5, 3, 0),
+            ServerFlavor::MySql | ServerFlavor::Percona => self.at_least(5, 6, 1),
+        }
+    }
+
+    /// Whether the server writes MySQL-style GTID events (MySQL 5.6.5+). MariaDB has GTIDs of its
+    /// own, which this crate doesn't decode.
+    pub fn supports_gtids(&self) -> bool {
+        self.flavor != ServerFlavor::MariaDb && self.at_least(5, 6, 5)
+    }
+
+    /// Whether the server can write column names and other metadata into Table Map events
+    /// (`binlog_row_metadata=FULL`, MySQL 8.0.1+)
+    pub fn supports_row_metadata(&self) -> bool {
+        self.flavor != ServerFlavor::MariaDb && self.at_least(8, 0, 1)
+    }
+
+    /// Whether the server can log partial updates of JSON columns (MySQL 8.0.3+)
+    pub fn supports_partial_json(&self) -> bool {
+        self.flavor != ServerFlavor::MariaDb && self.at_least(8, 0, 3)
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ServerFlavor, ServerVersion};
+
+    #[test]
+    fn test_parse() {
+        let mysql = ServerVersion::parse("8.0.36-log");
+        assert_eq!((mysql.major, mysql.minor, mysql.patch), (8, 0, 36));
+        assert_eq!(mysql.flavor, ServerFlavor::MySql);
+        assert!(mysql.supports_row_metadata());
+
+        let percona = ServerVersion::parse("5.7.24-27-log");
+        assert_eq!(percona.flavor, ServerFlavor::Percona);
+        assert!(percona.supports_checksums() && percona.supports_gtids());
+        assert!(!percona.supports_partial_json());
+
+        let mariadb = ServerVersion::parse("10.6.12-MariaDB-log");
+        assert_eq!(mariadb.flavor, ServerFlavor::MariaDb);
+        assert!(mariadb.supports_checksums() && !mariadb.supports_gtids());
+        assert_eq!(ServerVersion::parse("5.5.5-10.6.12-MariaDB"), mariadb);
+
+        assert!(!ServerVersion::parse("5.6.0").supports_checksums());
+        assert!(!ServerVersion::parse("").supports_checksums());
+        assert_eq!(ServerVersion::from_number(80036).to_string(), "8.0.36");
+    }
+}
@@ -0,0 +1,298 @@
+//! State which carries over from one binlog file (or stream) to the next.
+//!
+//! A [`ParseSession`] holds everything learned from the events read so far: the framing described
+//! by the most recent FormatDescriptionEvent, the table map, the current transaction's GTID, and
+//! the parser statistics. Every [`EventIterator`](crate::EventIterator) has one; it can be taken
+//! with [`into_session`](crate::EventIterator::into_session) and handed to the next iterator
+//! with [`BinlogFileParserBuilder::session`](crate::BinlogFileParserBuilder::session), or used
+//! directly to read events from a replication stream with [`read_event`](ParseSession::read_event)
+//! and [`decode`](ParseSession::decode).
+
+use std::io::Read;
+
+use crate::errors::EventParseError;
+use crate::event::{
+    ChecksumAlgorithm, DecodedEvent, Event, EventData, EventFormat, EventHeader, FormatDescription,
+    GtidCommitInfo, Heartbeat, TypeCode,
+};
+use crate::gtid::{Gtid, GtidSet};
+use crate::stats::ParserStats;
+use crate::table_map::TableMap;
+use crate::{LogicalTimestamp, SourceIdentity};
+
+#[derive(Default)]
+/// Parser state shared by consecutive binlog files and streams; see the
+/// [module documentation](self)
+pub struct ParseSession {
+    format: Option<EventFormat>,
+    format_description: Option<FormatDescription>,
+    pub(crate) table_map: TableMap,
+    current_gtid: Option<Gtid>,
+    // the current transaction began with an AnonymousGtidLogEvent
+    anonymous_transaction: bool,
+    logical_timestamp: Option<LogicalTimestamp>,
+    commit_info: Option<GtidCommitInfo>,
+    gtids_seen: GtidSet,
+    previous_gtids: Option<GtidSet>,
+    source: Option<SourceIdentity>,
+    sources_seen: Vec<SourceIdentity>,
+    last_heartbeat: Option<Heartbeat>,
+    pub(crate) stats: ParserStats,
+}
+
+impl ParseSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How events are framed, from the most recent FormatDescriptionEvent (or `None` if none
+    /// has been read yet)
+    pub fn format(&self) -> Option<EventFormat> {
+        self.format
+    }
+
+    /// The most recent FormatDescriptionEvent, which describes the server which wrote the
+    /// current file or stream
+    pub fn format_description(&self) -> Option<&FormatDescription> {
+        self.format_description.as_ref()
+    }
+
+    /// The checksum algorithm of the current file or stream
+    pub fn checksum_algorithm(&self) -> Option<ChecksumAlgorithm> {
+        self.format.map(|f| f.checksum_algorithm)
+    }
+
+    /// The tables described by every Table Map event read so far
+    pub fn table_map(&self) -> &TableMap {
+        &self.table_map
+    }
+
+    /// The GTID of the transaction currently being read
+    pub fn current_gtid(&self) -> Option<&Gtid> {
+        self.current_gtid.as_ref()
+    }
+
+    /// The commit timestamps, length, and server versions of the transaction currently being
+    /// read, if its GTID event had any (MySQL 8.0+)
+    pub fn commit_info(&self) -> Option<&GtidCommitInfo> {
+        self.commit_info.as_ref()
+    }
+
+    /// Every GTID read so far in this session
+    pub fn gtids_seen(&self) -> &GtidSet {
+        &self.gtids_seen
+    }
+
+    /// The GTIDs executed before the start of the current binlog file, from its
+    /// PreviousGtidsLogEvent (or `None` if the file has none, or it hasn't been read yet)
+    pub fn previous_gtids(&self) -> Option<&GtidSet> {
+        self.previous_gtids.as_ref()
+    }
+
+    /// Identity of the server which originated the most recently read transaction
+    pub fn source_identity(&self) -> Option<&SourceIdentity> {
+        self.source.as_ref()
+    }
+
+    /// Every distinct source identity seen so far, in the order they were first encountered
+    pub fn sources_seen(&self) -> &[SourceIdentity] {
+        &self.sources_seen
+    }
+
+    /// The position reported by the most recent heartbeat, if any
+    pub fn last_heartbeat(&self) -> Option<&Heartbeat> {
+        self.last_heartbeat.as_ref()
+    }
+
+    /// Counters describing the work done in this session so far
+    pub fn stats(&self) -> ParserStats {
+        self.stats
+    }
+
+    /// Read one event, framed according to the most recent FormatDescriptionEvent. Events
+    /// received from a replication stream can be read with this once the one-byte OK marker is
+    /// stripped from each packet.
+    pub fn read_event<R: Read>(
+        &mut self,
+        reader: &mut R,
+        offset: u64,
+    ) -> Result<Event, EventParseError> {
+        let event = Event::read_with_format(reader, offset, &self.format.unwrap_or_default())?;
+        self.stats.events_read += 1;
+        self.stats.bytes_read += u64::from(event.event_length());
+        Ok(event)
+    }
+
+    /// Decode an event against this session's table map, updating the session with what it
+    /// describes (including tracking the table map from Table Map events)
+    pub fn decode(&mut self, event: Event) -> Result<DecodedEvent, EventParseError> {
+        self.observe_raw(&event);
+        let decoded = event.decode(&self.table_map)?;
+        if let Some(ref data) = decoded.data {
+            self.observe(&decoded.header, data);
+            if let EventData::TableMapEvent {
+                table_id,
+                schema_name,
+                table_name,
+                columns,
+                descriptors,
+                ..
+            } = data
+            {
+                self.table_map.handle_with_descriptors(
+                    *table_id,
+                    schema_name.clone(),
+                    table_name.clone(),
+                    columns.clone(),
+                    descriptors.clone(),
+                );
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Start reading a new binlog file, which begins with the FormatDescriptionEvent
+    /// `description`
+    pub(crate) fn start_file(&mut self, description: &FormatDescription) {
+        self.format = Some(description.format());
+        self.format_description = Some(description.clone());
+        self.previous_gtids = None;
+    }
+
+    pub(crate) fn logical_timestamp(&self) -> Option<LogicalTimestamp> {
+        self.logical_timestamp
+    }
+
+    pub(crate) fn anonymous_transaction(&self) -> bool {
+        self.anonymous_transaction
+    }
+
+    /// Track the source of events which identify their originating server
+    pub(crate) fn observe_raw(&mut self, event: &Event) {
+        let type_code = event.type_code();
+        if type_code.is_rows_event()
+            || matches!(
+                type_code,
+                TypeCode::QueryEvent | TypeCode::TableMapEvent | TypeCode::XidEvent
+            )
+        {
+            self.observe_source(event.server_id(), None);
+        }
+    }
+
+    /// Update the session from a decoded event. Table Map events are not tracked here, since
+    /// the iterator applies its options to them first.
+    pub(crate) fn observe(&mut self, header: &EventHeader, data: &EventData) {
+        match *data {
+            EventData::FormatDescriptionEvent { .. } => {
+                self.format_description = FormatDescription::from_data(data);
+                self.format = self.format_description.as_ref().map(|d| d.format());
+            }
+            EventData::GtidLogEvent {
+                uuid,
+                tag,
+                coordinate,
+                last_committed,
+                sequence_number,
+                commit_info,
+                ..
+            } => {
+                self.observe_source(header.server_id, Some(uuid));
+                self.set_commit_info(commit_info);
+                let gtid = Gtid::new(uuid, tag, coordinate);
+                self.gtids_seen.insert(&gtid);
+                self.current_gtid = Some(gtid);
+                self.anonymous_transaction = false;
+                self.set_logical_clock(last_committed.zip(sequence_number));
+            }
+            EventData::AnonymousGtidLogEvent {
+                last_committed,
+                sequence_number,
+                commit_info,
+                ..
+            } => {
+                self.set_commit_info(commit_info);
+                self.current_gtid = None;
+                self.anonymous_transaction = true;
+                self.set_logical_clock(last_committed.zip(sequence_number));
+            }
+            EventData::PreviousGtidsEvent { ref gtids } => {
+                self.previous_gtids = Some(gtids.clone());
+            }
+            EventData::HeartbeatEvent {
+                ref log_name,
+                position,
+            } => {
+                self.last_heartbeat = Some(Heartbeat {
+                    log_name: log_name.clone(),
+                    position,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn set_commit_info(&mut self, commit_info: GtidCommitInfo) {
+        self.commit_info = Some(commit_info).filter(|info| *info != GtidCommitInfo::default());
+    }
+
+    fn set_logical_clock(&mut self, logical_clock: Option<(u64, u64)>) {
+        self.logical_timestamp =
+            logical_clock.map(|(last_committed, sequence_number)| LogicalTimestamp {
+                last_committed,
+                sequence_number,
+            });
+    }
+
+    fn observe_source(&mut self, server_id: u32, server_uuid: Option<uuid::Uuid>) {
+        let identity = match (&self.source, server_uuid) {
+            (Some(current), None) if current.server_id == server_id => return,
+            (_, server_uuid) => SourceIdentity {
+                server_id,
+                server_uuid,
+            },
+        };
+        if !self.sources_seen.contains(&identity) {
+            self.sources_seen.push(identity);
+        }
+        self.source = Some(identity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::ParseSession;
+    use crate::event::{ChecksumAlgorithm, EventData, TypeCode};
+
+    #[test]
+    fn test_stream_session() {
+        // read the test binlog as a stream of events, without a BinlogFile
+        let data = std::fs::read("test_data/bin-log.000001").unwrap();
+        let mut session = ParseSession::new();
+        let mut cursor = Cursor::new(&data[4..]);
+        let mut offset = 4;
+        let mut rows = 0;
+        while (cursor.position() as usize) < data.len() - 4 {
+            let event = session.read_event(&mut cursor, offset).unwrap();
+            offset = event.end_offset();
+            let type_code = event.type_code();
+            let decoded = session.decode(event).unwrap();
+            if type_code == TypeCode::WriteRowsEventV2 {
+                assert!(matches!(
+                    decoded.data,
+                    Some(EventData::WriteRowsEvent { ref rows, .. }) if rows.len() == 1
+                ));
+                rows += 1;
+            }
+        }
+        assert_eq!(rows, 2);
+        assert_eq!(session.checksum_algorithm(), Some(ChecksumAlgorithm::CRC32));
+        assert_eq!(session.stats().events_read, 14);
+        assert_eq!(session.table_map().find("bltest", "foo").unwrap().0, 203);
+        assert_eq!(session.gtids_seen().len(), 3);
+        assert_eq!(session.current_gtid().unwrap().sequence(), 14919);
+        assert_eq!(session.previous_gtids().unwrap().len(), 14916);
+    }
+}
@@ -0,0 +1,262 @@
+//! Writing [`BinlogEvent`]s to their destination.
+//!
+//! A [`Sink`] receives events one at a time; [`pump`] drives an iterator of events into one,
+//! flushing it in batches. The built-in sinks write newline-delimited JSON to any writer (such as
+//! a file or stdout) or, with the `kafka` feature, produce each event to a Kafka topic.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::errors::{EventParseError, SinkError};
+use crate::BinlogEvent;
+
+/// A destination for events
+pub trait Sink {
+    /// Write an event. Sinks may buffer events until they are flushed.
+    fn write(&mut self, event: &BinlogEvent) -> Result<(), SinkError>;
+
+    /// Make sure every event written so far has reached its destination
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(())
+    }
+}
+
+/// Writes each event as a line of JSON
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        NdjsonSink { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl NdjsonSink<BufWriter<File>> {
+    /// Write events to a new file at `path`, replacing it if it exists
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(NdjsonSink::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl NdjsonSink<BufWriter<io::Stdout>> {
+    /// Write events to stdout. Output is buffered until the sink is flushed.
+    pub fn stdout() -> Self {
+        NdjsonSink::new(BufWriter::new(io::stdout()))
+    }
+}
+
+impl<W: Write> Sink for NdjsonSink<W> {
+    fn write(&mut self, event: &BinlogEvent) -> Result<(), SinkError> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SinkError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub use self::kafka::KafkaSink;
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use rdkafka::config::ClientConfig;
+    use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+    use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+    use rdkafka::util::Timeout;
+    use rdkafka::ClientContext;
+
+    use super::Sink;
+    use crate::errors::SinkError;
+    use crate::BinlogEvent;
+
+    /// Remembers the first message which couldn't be delivered, so that it can be reported by
+    /// the next flush
+    #[derive(Default)]
+    struct DeliveryContext {
+        failure: Mutex<Option<KafkaError>>,
+    }
+
+    impl ClientContext for DeliveryContext {}
+
+    impl ProducerContext for DeliveryContext {
+        type DeliveryOpaque = ();
+
+        fn delivery(&self, result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+            if let Err((ref e, _)) = *result {
+                let mut failure = self.failure.lock().unwrap();
+                if failure.is_none() {
+                    *failure = Some(e.clone());
+                }
+            }
+        }
+    }
+
+    /// Produces each event to a Kafka topic as JSON.
+    ///
+    /// Row events are keyed by `schema.table`, so that the changes to each table stay in order
+    /// within a single partition; other events have no key. Production is asynchronous: delivery
+    /// failures are returned by the next [`flush`](Sink::flush).
+    pub struct KafkaSink {
+        producer: BaseProducer<DeliveryContext>,
+        topic: String,
+    }
+
+    impl KafkaSink {
+        /// Connect to the cluster at `brokers` (a comma-separated list of `host:port`)
+        pub fn new(brokers: &str, topic: impl Into<String>) -> Result<Self, SinkError> {
+            let mut config = ClientConfig::new();
+            config.set("bootstrap.servers", brokers);
+            KafkaSink::with_config(&config, topic)
+        }
+
+        /// Connect using a producer configured by `config`
+        pub fn with_config(
+            config: &ClientConfig,
+            topic: impl Into<String>,
+        ) -> Result<Self, SinkError> {
+            Ok(KafkaSink {
+                producer: config.create_with_context(DeliveryContext::default())?,
+                topic: topic.into(),
+            })
+        }
+    }
+
+    impl Sink for KafkaSink {
+        fn write(&mut self, event: &BinlogEvent) -> Result<(), SinkError> {
+            let payload = serde_json::to_vec(event)?;
+            let key = event
+                .table_ref
+                .as_ref()
+                .map(|table| format!("{}.{}", table.schema, table.table));
+            let mut record = BaseRecord::to(&self.topic).payload(&payload);
+            if let Some(ref key) = key {
+                record = record.key(key);
+            }
+            loop {
+                match self.producer.send(record) {
+                    Ok(()) => break,
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), rejected)) => {
+                        // wait for some of the queued messages to be delivered
+                        self.producer.poll(Duration::from_millis(100));
+                        record = rejected;
+                    }
+                    Err((e, _)) => return Err(e.into()),
+                }
+            }
+            self.producer.poll(Duration::ZERO);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), SinkError> {
+            self.producer.flush(Timeout::Never)?;
+            match self.producer.context().failure.lock().unwrap().take() {
+                Some(e) => Err(e.into()),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// When [`pump`] flushes its sink, in addition to after the last event
+#[derive(Debug, Clone, Default)]
+pub struct PumpOptions {
+    /// Flush after every this many events
+    pub batch_size: Option<usize>,
+    /// Flush after writing an event if this long has passed since the last flush
+    pub flush_interval: Option<Duration>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// What was written by [`pump`]
+pub struct PumpSummary {
+    pub events: u64,
+    pub flushes: u64,
+}
+
+/// Write every event from `events` to `sink`, flushing it as directed by `options` and once the
+/// events run out.
+///
+/// If reading an event fails, the events written before it are flushed and the error is
+/// returned.
+pub fn pump<I, S>(events: I, sink: &mut S, options: &PumpOptions) -> Result<PumpSummary, SinkError>
+where
+    I: IntoIterator<Item = Result<BinlogEvent, EventParseError>>,
+    S: Sink + ?Sized,
+{
+    let mut summary = PumpSummary::default();
+    let mut unflushed = 0;
+    let mut last_flush = Instant::now();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                sink.flush()?;
+                return Err(e.into());
+            }
+        };
+        sink.write(&event)?;
+        summary.events += 1;
+        unflushed += 1;
+        let batch_full = options.batch_size.is_some_and(|size| unflushed >= size);
+        let interval_elapsed = options
+            .flush_interval
+            .is_some_and(|interval| last_flush.elapsed() >= interval);
+        if batch_full || interval_elapsed {
+            sink.flush()?;
+            summary.flushes += 1;
+            unflushed = 0;
+            last_flush = Instant::now();
+        }
+    }
+    if unflushed > 0 {
+        sink.flush()?;
+        summary.flushes += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pump, NdjsonSink, PumpOptions, PumpSummary};
+    use crate::parse_file;
+
+    #[test]
+    fn test_pump() {
+        let mut sink = NdjsonSink::new(Vec::new());
+        let options = PumpOptions {
+            batch_size: Some(3),
+            ..Default::default()
+        };
+        let summary = pump(
+            parse_file("test_data/bin-log.000001").unwrap(),
+            &mut sink,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            summary,
+            PumpSummary {
+                events: 7,
+                flushes: 3
+            }
+        );
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        let event: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(event["type_code"], "WRITE_ROWS_EVENT_V2");
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+/// Counters describing the work done by an [`EventIterator`](crate::EventIterator)
+pub struct ParserStats {
+    /// Number of raw events read from the binlog (not including the initial FDE)
+    pub events_read: u64,
+    /// Number of events whose checksum has been verified
+    pub checksums_verified: u64,
+    /// Number of events whose checksum was not verified, either because verification is
+    /// disabled or because the event carries no checksum
+    pub checksums_unverified: u64,
+    /// Number of events whose checksum did not match their contents
+    pub checksum_failures: u64,
+    /// Number of events skipped because of a recoverable error (only in lenient mode, or with
+    /// an [`ErrorPolicy`](crate::errors::ErrorPolicy) other than `Abort`)
+    pub events_skipped: u64,
+    /// Number of bytes of events copied out of the binlog (not including the initial FDE)
+    pub bytes_read: u64,
+    /// Number of allocations made while producing events. Only counted with the `alloc-stats`
+    /// feature, when a [`CountingAllocator`](crate::alloc_stats) is the global allocator.
+    pub allocations: u64,
+    /// Number of bytes allocated while producing events; counted along with `allocations`
+    pub bytes_allocated: u64,
+    /// How long after it was committed the most recent transaction was read, in microseconds.
+    /// Only measured when reading an [`active_file`](crate::BinlogFileParserBuilder::active_file).
+    /// Uses the commit time on the originating server where the binlog records it (MySQL 8.0),
+    /// and the event timestamp (which only has second precision) otherwise.
+    pub commit_lag_micros: Option<u64>,
+    /// The largest `commit_lag_micros` seen so far
+    pub max_commit_lag_micros: u64,
+}
+
+impl ParserStats {
+    /// Record that a transaction committed at `commit_micros` (since the epoch) was read at `now`
+    pub(crate) fn record_commit_lag(&mut self, commit_micros: u64, now: SystemTime) {
+        let now_micros = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        // the source's clock may be ahead of ours
+        let lag = now_micros.saturating_sub(commit_micros);
+        self.commit_lag_micros = Some(lag);
+        self.max_commit_lag_micros = self.max_commit_lag_micros.max(lag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::ParserStats;
+
+    #[test]
+    fn test_commit_lag() {
+        let mut stats = ParserStats::default();
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        stats.record_commit_lag(998_500_000, now);
+        assert_eq!(stats.commit_lag_micros, Some(1_500_000));
+        stats.record_commit_lag(999_900_000, now);
+        assert_eq!(stats.commit_lag_micros, Some(100_000));
+        assert_eq!(stats.max_commit_lag_micros, 1_500_000);
+        stats.record_commit_lag(1_000_100_000, now);
+        assert_eq!(stats.commit_lag_micros, Some(0));
+    }
+}
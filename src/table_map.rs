@@ -1,20 +1,354 @@
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
 
 use crate::column_types::ColumnType;
+use crate::value::MySQLValue;
+
+/// How a [`TableRef`] is serialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableRefFormat {
+    /// An object with `schema`, `table`, and `table_id` fields
+    #[default]
+    Structured,
+    /// A single `"schema.table"` string
+    Qualified,
+}
+
+/// How schema and table names are compared, for pipelines reading from servers with different
+/// `lower_case_table_names` settings (which may write the same table's name with different case)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableNameCase {
+    /// Names are used exactly as the server wrote them
+    #[default]
+    Preserve,
+    /// Names are lowercased as they are read, so every event, lookup, and statistic uses the
+    /// lowercase name
+    Lowercase,
+    /// Names are reported as the server wrote them, but compared case-insensitively wherever they
+    /// are looked up: in [`TableMap::find`], the
+    /// [`SchemaCatalog`](crate::catalog::SchemaCatalog), and
+    /// [`raw_json`](crate::BinlogFileParserBuilder::raw_json) selections
+    CaseInsensitive,
+}
+
+impl TableNameCase {
+    /// The form of a name used to look it up: lowercase unless names are preserved
+    pub fn key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            TableNameCase::Preserve => Cow::Borrowed(name),
+            TableNameCase::Lowercase | TableNameCase::CaseInsensitive => {
+                Cow::Owned(name.to_lowercase())
+            }
+        }
+    }
+
+    /// The form of a name used in events
+    pub(crate) fn apply(&self, name: String) -> String {
+        match self {
+            TableNameCase::Lowercase => name.to_lowercase(),
+            TableNameCase::Preserve | TableNameCase::CaseInsensitive => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The table a rows event refers to.
+///
+/// Equality and hashing consider the schema, table, and table id (but not the serialization
+/// format), so a `TableRef` can be used directly as a routing key.
+pub struct TableRef {
+    pub schema: String,
+    pub table: String,
+    /// The id assigned to the table by the server. This changes whenever the table definition is
+    /// evicted from the server's table cache, so it is not a stable identifier across files.
+    pub table_id: u64,
+    format: TableRefFormat,
+}
 
-#[derive(Debug)]
+impl TableRef {
+    pub fn new<S: Into<String>, T: Into<String>>(schema: S, table: T, table_id: u64) -> Self {
+        TableRef {
+            schema: schema.into(),
+            table: table.into(),
+            table_id,
+            format: TableRefFormat::default(),
+        }
+    }
+
+    /// Change how this reference is serialized
+    pub fn with_format(mut self, format: TableRefFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The schema-qualified name of this table, e.g. `db.table`
+    pub fn qualified_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl PartialEq for TableRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.table_id == other.table_id && self.schema == other.schema && self.table == other.table
+    }
+}
+
+impl Eq for TableRef {}
+
+impl Hash for TableRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.schema.hash(state);
+        self.table.hash(state);
+        self.table_id.hash(state);
+    }
+}
+
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.schema, self.table)
+    }
+}
+
+impl serde::Serialize for TableRef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.format {
+            TableRefFormat::Structured => {
+                let mut state = serializer.serialize_struct("TableRef", 3)?;
+                state.serialize_field("schema", &self.schema)?;
+                state.serialize_field("table", &self.table)?;
+                state.serialize_field("table_id", &self.table_id)?;
+                state.end()
+            }
+            TableRefFormat::Qualified => serializer.collect_str(self),
+        }
+    }
+}
+
+/// Either serialization of a [`TableRef`]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedTableRef {
+    Structured {
+        schema: String,
+        table: String,
+        table_id: u64,
+    },
+    Qualified(String),
+}
+
+/// Reads either format. A `"schema.table"` string has no table id, so its `table_id` is 0.
+impl<'de> Deserialize<'de> for TableRef {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match SerializedTableRef::deserialize(deserializer)? {
+            SerializedTableRef::Structured {
+                schema,
+                table,
+                table_id,
+            } => Ok(TableRef::new(schema, table, table_id)),
+            SerializedTableRef::Qualified(name) => match name.split_once('.') {
+                Some((schema, table)) => {
+                    Ok(TableRef::new(schema, table, 0).with_format(TableRefFormat::Qualified))
+                }
+                None => Err(serde::de::Error::custom(format!(
+                    "table name {:?} is not schema-qualified",
+                    name
+                ))),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// What a Table Map event says about a column beyond its type. Apart from `nullable`, these
+/// come from the optional metadata which MySQL 8.0 writes when `binlog_row_metadata=FULL`, and
+/// are unset for older servers.
+pub struct ColumnDescriptor {
+    pub name: Option<String>,
+    pub nullable: bool,
+    /// Whether a numeric column is `UNSIGNED`
+    pub unsigned: Option<bool>,
+    /// Collation id of a character, `ENUM`, or `SET` column
+    pub charset: Option<u64>,
+    /// The permitted values of an `ENUM` or `SET` column
+    pub values: Option<Vec<String>>,
+    /// The geometry type of a spatial column
+    pub geometry_type: Option<u64>,
+    pub primary_key: bool,
+    /// If only a prefix of this column is part of the primary key, the length of that prefix
+    pub primary_key_prefix: Option<u64>,
+    /// Whether the column is visible (MySQL 8.0.23+ supports invisible columns)
+    pub visible: Option<bool>,
+}
+
+impl ColumnDescriptor {
+    /// The names of the members of a [`MySQLValue::Set`], if the server wrote the names of
+    /// the column's permitted values (with `binlog_row_metadata=FULL`) or a
+    /// [`SchemaProvider`] supplied them. Returns `None` for any other value.
+    pub fn set_members(&self, value: &MySQLValue) -> Option<Vec<String>> {
+        let bits = match *value {
+            MySQLValue::Set(bits) => bits,
+            _ => return None,
+        };
+        let values = self.values.as_ref()?;
+        Some(
+            values
+                .iter()
+                .take(64)
+                .enumerate()
+                .filter(|(i, _)| bits & (1 << i) != 0)
+                .map(|(_, name)| name.clone())
+                .collect(),
+        )
+    }
+
+    /// Replace a [`MySQLValue::Enum`] with a [`MySQLValue::EnumString`] if the names of the
+    /// column's permitted values are known. Index 0 is MySQL's empty error value; other values
+    /// (including out-of-range indices) are returned unchanged.
+    pub fn resolve_enum(&self, value: MySQLValue) -> MySQLValue {
+        let index = match value {
+            MySQLValue::Enum(index) => index,
+            other => return other,
+        };
+        let name = match (index, self.values.as_ref()) {
+            (0, Some(_)) => Some(String::new()),
+            (i, Some(values)) if i > 0 => values.get(i as usize - 1).cloned(),
+            _ => None,
+        };
+        match name {
+            Some(name) => MySQLValue::EnumString { name, index },
+            None => value,
+        }
+    }
+
+    /// Fill in anything this descriptor doesn't know from another description of the same column
+    fn merge(&mut self, other: &ColumnDescriptor) {
+        if self.name.is_none() {
+            self.name = other.name.clone();
+        }
+        self.unsigned = self.unsigned.or(other.unsigned);
+        self.charset = self.charset.or(other.charset);
+        if self.values.is_none() {
+            self.values = other.values.clone();
+        }
+        self.geometry_type = self.geometry_type.or(other.geometry_type);
+        if !self.primary_key && other.primary_key {
+            self.primary_key = true;
+            self.primary_key_prefix = other.primary_key_prefix;
+        }
+        self.visible = self.visible.or(other.visible);
+    }
+}
+
+/// A source of column descriptions for servers which don't write them into Table Map events
+/// (MySQL 5.6 and 5.7, or 8.0 without `binlog_row_metadata=FULL`); for example, one backed by
+/// `information_schema` or a DDL dump. Register it with
+/// [`BinlogFileParserBuilder::schema_provider`](crate::BinlogFileParserBuilder::schema_provider).
+///
+/// The provider is consulted for every Table Map event, so implementations which do any I/O
+/// should cache their results. Unless names are preserved (see [`TableNameCase`]), the provider
+/// is passed lowercased names.
+pub trait SchemaProvider: fmt::Debug + Send + Sync {
+    /// Descriptors for each column of a table, in table order, or `None` if the table is unknown
+    fn columns(&self, schema_name: &str, table_name: &str) -> Option<Vec<ColumnDescriptor>>;
+}
+
+impl SchemaProvider for HashMap<(String, String), Vec<ColumnDescriptor>> {
+    fn columns(&self, schema_name: &str, table_name: &str) -> Option<Vec<ColumnDescriptor>> {
+        self.get(&(schema_name.to_owned(), table_name.to_owned()))
+            .cloned()
+    }
+}
+
+/// Fill in column descriptors from a schema provider. If the provider's idea of the table has a
+/// different number of columns than the binlog's, it is out of date and is ignored.
+pub(crate) fn apply_schema_provider(
+    provider: &dyn SchemaProvider,
+    schema_name: &str,
+    table_name: &str,
+    descriptors: &mut [ColumnDescriptor],
+) {
+    if let Some(provided) = provider.columns(schema_name, table_name) {
+        if provided.len() == descriptors.len() {
+            for (descriptor, provided) in descriptors.iter_mut().zip(provided.iter()) {
+                descriptor.merge(provided);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Opaque reference to a table map, intended to be consumed by [`Event`]
 pub struct SingleTableMap {
     pub(crate) schema_name: String,
     pub(crate) table_name: String,
-    pub(crate) columns: Vec<ColumnType>,
+    pub(crate) columns: Arc<[ColumnType]>,
+    pub(crate) descriptors: Arc<[ColumnDescriptor]>,
+}
+
+impl SingleTableMap {
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn columns(&self) -> &[ColumnType] {
+        &self.columns
+    }
+
+    /// A descriptor for each column, in table order
+    pub fn descriptors(&self) -> &[ColumnDescriptor] {
+        &self.descriptors
+    }
+
+    /// The position of the column with the given name, if the server wrote column names
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.descriptors
+            .iter()
+            .position(|d| d.name.as_deref() == Some(name))
+    }
+
+    /// The column descriptors, shared, if any column has a name
+    pub(crate) fn named_descriptors(&self) -> Option<Arc<[ColumnDescriptor]>> {
+        if self.descriptors.iter().any(|d| d.name.is_some()) {
+            Some(Arc::clone(&self.descriptors))
+        } else {
+            None
+        }
+    }
 }
 
 /// A MySQL binary log includes Table Map events; the first time a table is referenced in a given
 /// binlog, a TME will be emitted describing the fields of that table and assigning them to a
 /// binlog-unique identifier. The TableMap object is used to keep track of that mapping.
+///
+/// A TableMap can be serialized as a snapshot of the tables mapped so far, and restored with
+/// [`BinlogFileParserBuilder::restore_table_map`](crate::BinlogFileParserBuilder::restore_table_map)
+/// to decode the rows events after a checkpoint in the middle of a file, whose Table Map events
+/// came before it. The [`TableNameCase`] isn't serialized; the builder's applies to the restored
+/// map.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct TableMap {
     inner: BTreeMap<u64, SingleTableMap>,
+    #[serde(skip)]
+    name_case: TableNameCase,
 }
 
 impl Default for TableMap {
@@ -27,20 +361,41 @@ impl TableMap {
     pub fn new() -> Self {
         TableMap {
             inner: BTreeMap::new(),
+            name_case: TableNameCase::default(),
         }
     }
 
+    /// Change how the names of tables are stored and compared
+    pub fn with_name_case(mut self, name_case: TableNameCase) -> Self {
+        self.name_case = name_case;
+        self
+    }
+
     pub fn handle(
         &mut self,
         table_id: u64,
         schema_name: String,
         table_name: String,
         columns: Vec<ColumnType>,
+    ) {
+        let descriptors = vec![ColumnDescriptor::default(); columns.len()];
+        self.handle_with_descriptors(table_id, schema_name, table_name, columns, descriptors);
+    }
+
+    /// Like [`handle`](Self::handle), including the column descriptors from the Table Map event
+    pub fn handle_with_descriptors(
+        &mut self,
+        table_id: u64,
+        schema_name: String,
+        table_name: String,
+        columns: Vec<ColumnType>,
+        descriptors: Vec<ColumnDescriptor>,
     ) {
         let map = SingleTableMap {
-            schema_name,
-            table_name,
-            columns,
+            schema_name: self.name_case.apply(schema_name),
+            table_name: self.name_case.apply(table_name),
+            columns: columns.into(),
+            descriptors: descriptors.into(),
         };
         self.inner.insert(table_id, map);
     }
@@ -48,4 +403,144 @@ impl TableMap {
     pub fn get(&self, table_id: u64) -> Option<&SingleTableMap> {
         self.inner.get(&table_id)
     }
+
+    /// Find the most recently mapped table with the given name, returning its table id
+    pub fn find(&self, schema_name: &str, table_name: &str) -> Option<(u64, &SingleTableMap)> {
+        let (schema_key, table_key) = (
+            self.name_case.key(schema_name),
+            self.name_case.key(table_name),
+        );
+        self.inner
+            .iter()
+            .rev()
+            .find(|(_, t)| {
+                self.name_case.key(&t.schema_name) == schema_key
+                    && self.name_case.key(&t.table_name) == table_key
+            })
+            .map(|(&table_id, t)| (table_id, t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use super::{
+        apply_schema_provider, ColumnDescriptor, TableMap, TableNameCase, TableRef, TableRefFormat,
+    };
+    use crate::value::MySQLValue;
+
+    #[test]
+    fn test_set_members() {
+        let mut descriptor = ColumnDescriptor::default();
+        assert_eq!(descriptor.set_members(&MySQLValue::Set(1)), None);
+        descriptor.values = Some(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]);
+        assert_eq!(
+            descriptor.set_members(&MySQLValue::Set(0b101)),
+            Some(vec!["a".to_owned(), "c".to_owned()])
+        );
+        assert_eq!(descriptor.set_members(&MySQLValue::Set(0)), Some(vec![]));
+        assert_eq!(descriptor.set_members(&MySQLValue::Enum(1)), None);
+    }
+
+    #[test]
+    fn test_resolve_enum() {
+        let mut descriptor = ColumnDescriptor::default();
+        assert!(matches!(
+            descriptor.resolve_enum(MySQLValue::Enum(1)),
+            MySQLValue::Enum(1)
+        ));
+        descriptor.values = Some(vec!["small".to_owned(), "large".to_owned()]);
+        let resolved = descriptor.resolve_enum(MySQLValue::Enum(2));
+        assert_eq!(resolved.enum_index(), Some(2));
+        assert_eq!(
+            serde_json::to_value(&resolved).unwrap(),
+            serde_json::json!({"EnumString": "large"})
+        );
+        assert!(matches!(
+            descriptor.resolve_enum(MySQLValue::Enum(0)),
+            MySQLValue::EnumString { ref name, index: 0 } if name.is_empty()
+        ));
+        assert!(matches!(
+            descriptor.resolve_enum(MySQLValue::Enum(3)),
+            MySQLValue::Enum(3)
+        ));
+        assert!(matches!(
+            descriptor.resolve_enum(MySQLValue::Set(1)),
+            MySQLValue::Set(1)
+        ));
+    }
+
+    #[test]
+    fn test_table_ref() {
+        let table_ref = TableRef::new("bltest", "foo", 203);
+        assert_eq!(
+            serde_json::to_value(&table_ref).unwrap(),
+            serde_json::json!({"schema": "bltest", "table": "foo", "table_id": 203})
+        );
+        let qualified = table_ref.clone().with_format(TableRefFormat::Qualified);
+        assert_eq!(
+            serde_json::to_value(&qualified).unwrap(),
+            serde_json::json!("bltest.foo")
+        );
+        let mut set = HashSet::new();
+        set.insert(table_ref);
+        assert!(set.contains(&qualified));
+    }
+
+    #[test]
+    fn test_name_case() {
+        let mut table_map = TableMap::new();
+        table_map.handle(1, "Db".to_owned(), "Foo".to_owned(), Vec::new());
+        assert!(table_map.find("Db", "Foo").is_some());
+        assert!(table_map.find("db", "foo").is_none());
+
+        let mut table_map = TableMap::new().with_name_case(TableNameCase::CaseInsensitive);
+        table_map.handle(1, "Db".to_owned(), "Foo".to_owned(), Vec::new());
+        table_map.handle(2, "db".to_owned(), "FOO".to_owned(), Vec::new());
+        let (table_id, table) = table_map.find("DB", "foo").unwrap();
+        assert_eq!(table_id, 2);
+        assert_eq!(table.table_name(), "FOO");
+
+        let mut table_map = TableMap::new().with_name_case(TableNameCase::Lowercase);
+        table_map.handle(1, "Db".to_owned(), "Foo".to_owned(), Vec::new());
+        assert_eq!(table_map.get(1).unwrap().table_name(), "foo");
+        assert!(table_map.find("DB", "FOO").is_some());
+    }
+
+    #[test]
+    fn test_schema_provider() {
+        let mut provider = HashMap::new();
+        provider.insert(
+            ("bltest".to_owned(), "foo".to_owned()),
+            vec![
+                ColumnDescriptor {
+                    name: Some("id".to_owned()),
+                    unsigned: Some(true),
+                    ..ColumnDescriptor::default()
+                },
+                ColumnDescriptor {
+                    name: Some("comment".to_owned()),
+                    ..ColumnDescriptor::default()
+                },
+            ],
+        );
+        let mut descriptors = vec![
+            ColumnDescriptor {
+                unsigned: Some(false),
+                ..ColumnDescriptor::default()
+            },
+            ColumnDescriptor::default(),
+        ];
+        apply_schema_provider(&provider, "bltest", "foo", &mut descriptors);
+        assert_eq!(descriptors[0].name.as_deref(), Some("id"));
+        // what the binlog says takes precedence
+        assert_eq!(descriptors[0].unsigned, Some(false));
+        assert_eq!(descriptors[1].name.as_deref(), Some("comment"));
+        // an out of date schema is ignored
+        let mut descriptors = vec![ColumnDescriptor::default(); 3];
+        apply_schema_provider(&provider, "bltest", "foo", &mut descriptors);
+        assert_eq!(descriptors[0].name, None);
+    }
 }
@@ -1,8 +1,8 @@
-use std::io::{Result, Seek, SeekFrom};
+use std::io::{Result, Seek};
 
 pub trait Tell: Seek {
     fn tell(&mut self) -> Result<u64> {
-        self.seek(SeekFrom::Current(0))
+        self.stream_position()
     }
 }
 
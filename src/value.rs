@@ -1,8 +1,17 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug)]
+use crate::column_types::ColumnType;
+use crate::errors::ColumnParseError;
+use crate::event::{get_table, rekey_tables, ProjectedColumn};
+use crate::jsonb;
+use crate::table_map::{ColumnDescriptor, SingleTableMap, TableNameCase};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// Wrapper for the SQL BLOB (Binary Large OBject) and TEXT types
 ///
 /// Serializes as Base64
@@ -24,26 +33,75 @@ impl Serialize for Blob {
     }
 }
 
-#[derive(Debug, Serialize)]
+impl<'de> Deserialize<'de> for Blob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let serialized = String::deserialize(deserializer)?;
+        base64::decode(serialized)
+            .map(Blob)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 /// Normalized representation of types which are present in MySQL
 pub enum MySQLValue {
     SignedInteger(i64),
+    /// A value of an `UNSIGNED` integer column. Integer columns are only known to be unsigned
+    /// with `binlog_row_metadata=FULL` or a
+    /// [`SchemaProvider`](crate::table_map::SchemaProvider); otherwise they are decoded as
+    /// [`SignedInteger`](MySQLValue::SignedInteger), and unsigned values too large for the signed
+    /// type come out negative.
+    UnsignedInteger(u64),
     Float(f32),
     Double(f64),
     String(String),
+    /// An `ENUM` value, as the (1-based) index of its member. Resolved to an
+    /// [`EnumString`](MySQLValue::EnumString) when the members' names are known.
     Enum(i16),
+    /// An `ENUM` value whose member name is known (from `binlog_row_metadata=FULL` or a
+    /// [`SchemaProvider`](crate::table_map::SchemaProvider)). Serializes as just the name; see
+    /// [`MySQLValue::enum_index`] for the raw index. The index is lost when serialized, so a
+    /// deserialized `EnumString` has an index of 0.
+    #[serde(
+        serialize_with = "serialize_enum_string",
+        deserialize_with = "deserialize_enum_string"
+    )]
+    EnumString {
+        name: String,
+        index: i16,
+    },
+    /// A `SET` value: bit `n` is set if the set contains its `n`th member. See
+    /// [`ColumnDescriptor::set_members`](crate::table_map::ColumnDescriptor::set_members) for the
+    /// members' names.
+    Set(u64),
+    /// A `BIT(length)` value
+    Bit {
+        value: u64,
+        length: u16,
+    },
     Blob(Blob),
+    /// A string which couldn't be safely decoded in its column's character set (or a binary
+    /// string); see [`charset`](crate::charset)
+    Bytes(Blob),
     Year(u32),
     Date {
         year: u32,
         month: u32,
         day: u32,
     },
+    /// `subseconds` is the fractional part in microseconds, whatever the fractional-seconds
+    /// precision (`fsp`) of the column; likewise `subsecond` for `DateTime` and `Timestamp`.
+    /// A `TIME` is a duration between -838:59:59 and 838:59:59; `negative` gives its sign.
     Time {
+        negative: bool,
         hours: u32,
         minutes: u32,
         seconds: u32,
         subseconds: u32,
+        fsp: u8,
     },
     DateTime {
         year: u32,
@@ -53,21 +111,450 @@ pub enum MySQLValue {
         minute: u32,
         second: u32,
         subsecond: u32,
+        fsp: u8,
     },
     Json(serde_json::Value),
+    /// The after-image of a partially-updated JSON column whose before-image wasn't logged; see
+    /// [`json_diff`](crate::json_diff)
+    JsonDiff(Vec<crate::json_diff::JsonDiff>),
     Decimal(bigdecimal::BigDecimal),
     Timestamp {
         unix_time: i32,
         subsecond: u32,
+        fsp: u8,
+    },
+    /// A spatial value: the SRID followed by the geometry in WKB format. With the `geo` feature,
+    /// [`MySQLValue::geometry`] decodes the WKB.
+    Geometry {
+        srid: u32,
+        wkb: Blob,
+    },
+    /// A value produced by a user-supplied [`ValueDecoder`]
+    Custom {
+        type_name: String,
+        value: serde_json::Value,
     },
     Null,
 }
 
+/// A value borrowed from the buffer of the event it was read from, produced by
+/// [`Event::rows_ref`](crate::event::Event::rows_ref). Strings, blobs, and JSON documents are
+/// not copied; everything else is decoded as a [`MySQLValue`].
+#[derive(Debug, Clone)]
+pub enum MySQLValueRef<'a> {
+    /// A string which is valid in its column's character set (UTF-8 if the character set isn't
+    /// known)
+    String(&'a str),
+    /// A string which couldn't be decoded without copying it; see [`MySQLValue::Bytes`]
+    Bytes(&'a [u8]),
+    Blob(&'a [u8]),
+    /// A `JSON` document in MySQL's binary (JSONB) encoding, undecoded
+    Json(&'a [u8]),
+    Owned(MySQLValue),
+}
+
+impl MySQLValueRef<'_> {
+    /// Copy this value into the [`MySQLValue`] the owned decoder would have produced (JSON
+    /// documents are always decoded, even if selected as raw JSON)
+    pub fn to_owned_value(&self) -> Result<MySQLValue, ColumnParseError> {
+        Ok(match *self {
+            MySQLValueRef::String(s) => MySQLValue::String(s.to_owned()),
+            MySQLValueRef::Bytes(b) => MySQLValue::Bytes(b.to_vec().into()),
+            MySQLValueRef::Blob(b) => MySQLValue::Blob(b.to_vec().into()),
+            MySQLValueRef::Json(b) => MySQLValue::Json(jsonb::parse(b.to_vec())?),
+            MySQLValueRef::Owned(ref value) => value.clone(),
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            MySQLValueRef::String(s) => Some(s),
+            MySQLValueRef::Owned(MySQLValue::String(ref s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, MySQLValueRef::Owned(MySQLValue::Null))
+    }
+}
+
+/// Custom decoding for the values of a particular column, registered with
+/// [`BinlogFileParserBuilder::value_decoder`](crate::BinlogFileParserBuilder::value_decoder).
+///
+/// The decoder receives each non-null value as decoded by this crate (so a `VARBINARY` column
+/// arrives as a [`MySQLValue::Blob`]) and returns the value to emit in its place, typically a
+/// [`MySQLValue::Custom`].
+pub trait ValueDecoder: Send + Sync {
+    fn decode(
+        &self,
+        column_type: &ColumnType,
+        value: MySQLValue,
+    ) -> Result<MySQLValue, ColumnParseError>;
+}
+
+impl<F> ValueDecoder for F
+where
+    F: Fn(&ColumnType, MySQLValue) -> Result<MySQLValue, ColumnParseError> + Send + Sync,
+{
+    fn decode(
+        &self,
+        column_type: &ColumnType,
+        value: MySQLValue,
+    ) -> Result<MySQLValue, ColumnParseError> {
+        self(column_type, value)
+    }
+}
+
+type RegisteredDecoder = (ProjectedColumn, Arc<dyn ValueDecoder>);
+
+/// The decoder for each column of a table, by position
+pub(crate) type ColumnDecoders = [Option<Arc<dyn ValueDecoder>>];
+
+// resolved tables are forgotten once this many are cached, since table ids are never reused for
+// the same table once it changes
+const MAX_RESOLVED_TABLES: usize = 1024;
+
+/// The decoders of one table, as resolved against its table map
+struct ResolvedTable {
+    // identifies the table map the decoders were resolved against
+    descriptors: Arc<[ColumnDescriptor]>,
+    decoders: Option<Arc<ColumnDecoders>>,
+}
+
+/// The [`ValueDecoder`]s registered for each column, keyed by schema, table, and column. Columns
+/// given by name are only found if the table map has column names (`binlog_row_metadata=FULL`,
+/// or a [`SchemaProvider`](crate::table_map::SchemaProvider)).
+#[derive(Default)]
+pub struct ValueDecoders {
+    tables: HashMap<(String, String), Vec<RegisteredDecoder>>,
+    name_case: TableNameCase,
+    // the decoders of each table read so far, by table id
+    resolved: Mutex<HashMap<u64, ResolvedTable>>,
+}
+
+impl ValueDecoders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a decoder for a column, replacing any previous registration
+    pub fn register<C: Into<ProjectedColumn>, D: ValueDecoder + 'static>(
+        &mut self,
+        schema_name: &str,
+        table_name: &str,
+        column: C,
+        decoder: D,
+    ) {
+        let column = column.into();
+        let columns = self
+            .tables
+            .entry((
+                self.name_case.key(schema_name).into_owned(),
+                self.name_case.key(table_name).into_owned(),
+            ))
+            .or_default();
+        columns.retain(|(registered, _)| *registered != column);
+        columns.push((column, Arc::new(decoder)));
+        self.forget_resolved();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Change how table names are compared, including for tables already registered
+    pub fn set_name_case(&mut self, name_case: TableNameCase) {
+        self.name_case = name_case;
+        self.tables = rekey_tables(std::mem::take(&mut self.tables), name_case);
+        self.forget_resolved();
+    }
+
+    fn forget_resolved(&mut self) {
+        self.resolved
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// The decoders registered for the columns of a table, by position; resolved once for each
+    /// table map
+    pub(crate) fn for_table(
+        &self,
+        table_id: u64,
+        table: &SingleTableMap,
+    ) -> Option<Arc<ColumnDecoders>> {
+        if self.tables.is_empty() {
+            return None;
+        }
+        let mut resolved = self.resolved.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = resolved.get(&table_id) {
+            if Arc::ptr_eq(&cached.descriptors, &table.descriptors) {
+                return cached.decoders.clone();
+            }
+        }
+        let decoders = self.resolve(table);
+        if resolved.len() >= MAX_RESOLVED_TABLES {
+            resolved.clear();
+        }
+        resolved.insert(
+            table_id,
+            ResolvedTable {
+                descriptors: Arc::clone(&table.descriptors),
+                decoders: decoders.clone(),
+            },
+        );
+        decoders
+    }
+
+    fn resolve(&self, table: &SingleTableMap) -> Option<Arc<ColumnDecoders>> {
+        let registered = get_table(
+            &self.tables,
+            self.name_case,
+            &table.schema_name,
+            &table.table_name,
+        )?;
+        let mut decoders = vec![None; table.columns.len()];
+        let mut found = false;
+        for (column, decoder) in registered {
+            if let Some(slot) = column.position(table).and_then(|i| decoders.get_mut(i)) {
+                *slot = Some(Arc::clone(decoder));
+                found = true;
+            }
+        }
+        if found {
+            Some(decoders.into())
+        } else {
+            None
+        }
+    }
+}
+
+// the resolved decoders are a cache, which is left behind
+impl Clone for ValueDecoders {
+    fn clone(&self) -> Self {
+        ValueDecoders {
+            tables: self.tables.clone(),
+            name_case: self.name_case,
+            resolved: Mutex::default(),
+        }
+    }
+}
+
+impl fmt::Debug for ValueDecoders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set()
+            .entries(self.tables.iter().flat_map(|((schema, table), columns)| {
+                columns.iter().map(move |(column, _)| match column {
+                    ProjectedColumn::Index(index) => format!("{}.{}[{}]", schema, table, index),
+                    ProjectedColumn::Name(name) => format!("{}.{}.{}", schema, table, name),
+                })
+            }))
+            .finish()
+    }
+}
+
+/// Number of days between 1970-01-01 and the given proleptic Gregorian date
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The proleptic Gregorian date `days` after 1970-01-01, as `(year, month, day)`; the inverse
+/// of [`days_from_civil`]
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Format a UNIX time as a UTC date and time, with `fsp` fractional digits and `separator`
+/// between the date and the time (e.g. `2000-02-29T12:00:00.120`)
+pub(crate) fn format_utc(unix_time: i64, nanos: u32, fsp: u8, separator: char) -> String {
+    let (year, month, day) = civil_from_days(unix_time.div_euclid(86_400));
+    let time = unix_time.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        separator,
+        time / 3_600,
+        time / 60 % 60,
+        time % 60,
+        format_fraction(nanos, fsp)
+    )
+}
+
+fn format_fraction(nanos: u32, fsp: u8) -> String {
+    if fsp == 0 {
+        String::new()
+    } else {
+        let digits = format!("{:09}", nanos);
+        format!(".{}", &digits[..usize::from(fsp.min(9))])
+    }
+}
+
+fn serialize_enum_string<S: Serializer>(
+    name: &str,
+    _index: &i16,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(name)
+}
+
+fn deserialize_enum_string<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(String, i16), D::Error> {
+    Ok((String::deserialize(deserializer)?, 0))
+}
+
 impl MySQLValue {
+    /// The raw index of an `ENUM` value, whether or not its name was resolved
+    pub fn enum_index(&self) -> Option<i16> {
+        match *self {
+            MySQLValue::Enum(index) | MySQLValue::EnumString { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The fractional-seconds part of a `Time`, `DateTime`, or `Timestamp`, in nanoseconds
+    pub fn subsecond_nanos(&self) -> Option<u32> {
+        match *self {
+            MySQLValue::Time {
+                subseconds: micros, ..
+            }
+            | MySQLValue::DateTime {
+                subsecond: micros, ..
+            }
+            | MySQLValue::Timestamp {
+                subsecond: micros, ..
+            } => Some(micros * 1_000),
+            _ => None,
+        }
+    }
+
+    /// The fractional-seconds precision (0-6) of a temporal value's source column
+    pub fn fsp(&self) -> Option<u8> {
+        match *self {
+            MySQLValue::Time { fsp, .. }
+            | MySQLValue::DateTime { fsp, .. }
+            | MySQLValue::Timestamp { fsp, .. } => Some(fsp),
+            _ => None,
+        }
+    }
+
+    /// Represent a `DateTime` or `Timestamp` as `(seconds since the UNIX epoch, nanoseconds)`,
+    /// analogous to a `DateTime64`. `DateTime` values carry no time zone and are interpreted as
+    /// UTC.
+    pub fn as_datetime64(&self) -> Option<(i64, u32)> {
+        let nanos = self.subsecond_nanos()?;
+        match *self {
+            MySQLValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                ..
+            } => {
+                let days = days_from_civil(i64::from(year), month, day);
+                let seconds = days * 86_400
+                    + i64::from(hour) * 3_600
+                    + i64::from(minute) * 60
+                    + i64::from(second);
+                Some((seconds, nanos))
+            }
+            MySQLValue::Timestamp { unix_time, .. } => Some((i64::from(unix_time), nanos)),
+            _ => None,
+        }
+    }
+
+    /// Format a temporal value the way MySQL would, with exactly as many fractional digits as
+    /// the column's precision (so trailing zeros are preserved)
+    pub fn to_temporal_string(&self) -> Option<String> {
+        let nanos = self.subsecond_nanos().unwrap_or(0);
+        match *self {
+            MySQLValue::Date { year, month, day } => {
+                Some(format!("{:04}-{:02}-{:02}", year, month, day))
+            }
+            MySQLValue::Time {
+                negative,
+                hours,
+                minutes,
+                seconds,
+                fsp,
+                ..
+            } => Some(format!(
+                "{}{:02}:{:02}:{:02}{}",
+                if negative { "-" } else { "" },
+                hours,
+                minutes,
+                seconds,
+                format_fraction(nanos, fsp)
+            )),
+            MySQLValue::DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                fsp,
+                ..
+            } => Some(format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                format_fraction(nanos, fsp)
+            )),
+            MySQLValue::Timestamp { unix_time, fsp, .. } => {
+                Some(format!("{}{}", unix_time, format_fraction(nanos, fsp)))
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "geo")]
+    /// Decode the WKB of a [`MySQLValue::Geometry`]; returns `None` for any other value
+    pub fn geometry(
+        &self,
+    ) -> Option<Result<crate::geometry::Geometry, crate::errors::GeometryParseError>> {
+        match *self {
+            MySQLValue::Geometry { ref wkb, .. } => {
+                Some(crate::geometry::Geometry::from_wkb(&wkb.0))
+            }
+            _ => None,
+        }
+    }
+
     /// Turn this type into a serde_json::Value
     ///
     /// Tries to avoid round-tripping through Serialize if it can
-    pub(crate) fn as_value(&self) -> Result<Cow<serde_json::Value>, serde_json::error::Error> {
+    pub(crate) fn as_value(&self) -> Result<Cow<'_, serde_json::Value>, serde_json::error::Error> {
         match *self {
             MySQLValue::Json(ref j) => Ok(Cow::Borrowed(j)),
             MySQLValue::Null => Ok(Cow::Owned(serde_json::Value::Null)),
@@ -75,3 +562,127 @@ impl MySQLValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{MySQLValue, ValueDecoders};
+    use crate::column_types::ColumnType;
+    use crate::errors::ColumnParseError;
+    use crate::table_map::{ColumnDescriptor, TableMap};
+
+    fn table_map(names: &[&str]) -> TableMap {
+        let mut table_map = TableMap::new();
+        let descriptors = names
+            .iter()
+            .map(|name| ColumnDescriptor {
+                name: Some(name.to_string()),
+                ..ColumnDescriptor::default()
+            })
+            .collect();
+        table_map.handle_with_descriptors(
+            1,
+            "db".to_owned(),
+            "t".to_owned(),
+            vec![ColumnType::LongLong; names.len()],
+            descriptors,
+        );
+        table_map
+    }
+
+    #[test]
+    fn test_value_decoders_for_table() {
+        let decoder = |_: &ColumnType, value: MySQLValue| Ok::<_, ColumnParseError>(value);
+        let mut decoders = ValueDecoders::new();
+        decoders.register("db", "t", "B", decoder);
+        decoders.register("db", "t", 2, decoder);
+        decoders.register("db", "t", "missing", decoder);
+
+        let first = table_map(&["a", "b", "c"]);
+        let resolved = decoders.for_table(1, first.get(1).unwrap()).unwrap();
+        let present: Vec<bool> = resolved.iter().map(Option::is_some).collect();
+        assert_eq!(present, [false, true, true]);
+        // resolved once for each table map
+        let again = decoders.for_table(1, first.get(1).unwrap()).unwrap();
+        assert!(Arc::ptr_eq(&resolved, &again));
+
+        // the table id now maps a different version of the table
+        let second = table_map(&["b", "a", "d"]);
+        let resolved = decoders.for_table(1, second.get(1).unwrap()).unwrap();
+        let present: Vec<bool> = resolved.iter().map(Option::is_some).collect();
+        assert_eq!(present, [true, false, true]);
+
+        assert!(ValueDecoders::new()
+            .for_table(1, first.get(1).unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_datetime64() {
+        let value = MySQLValue::DateTime {
+            year: 2019,
+            month: 2,
+            day: 15,
+            hour: 0,
+            minute: 58,
+            second: 11,
+            subsecond: 500_000,
+            fsp: 2,
+        };
+        assert_eq!(value.subsecond_nanos(), Some(500_000_000));
+        assert_eq!(value.as_datetime64(), Some((1550192291, 500_000_000)));
+        assert_eq!(
+            value.to_temporal_string().as_deref(),
+            Some("2019-02-15 00:58:11.50")
+        );
+    }
+
+    #[test]
+    fn test_fractional_precision() {
+        let value = MySQLValue::Timestamp {
+            unix_time: 1,
+            subsecond: 5,
+            fsp: 6,
+        };
+        assert_eq!(value.subsecond_nanos(), Some(5_000));
+        assert_eq!(value.to_temporal_string().as_deref(), Some("1.000005"));
+        let value = MySQLValue::Time {
+            negative: false,
+            hours: 1,
+            minutes: 2,
+            seconds: 3,
+            subseconds: 120_000,
+            fsp: 3,
+        };
+        assert_eq!(value.to_temporal_string().as_deref(), Some("01:02:03.120"));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let values = vec![
+            MySQLValue::SignedInteger(-3),
+            MySQLValue::String("foo".to_owned()),
+            MySQLValue::Blob(vec![0u8, 255, 7].into()),
+            MySQLValue::Decimal("12.50".parse().unwrap()),
+            MySQLValue::Bit {
+                value: 5,
+                length: 3,
+            },
+            MySQLValue::Json(serde_json::json!({"a": [1, null]})),
+            MySQLValue::Null,
+        ];
+        let serialized = serde_json::to_value(&values).unwrap();
+        let deserialized: Vec<MySQLValue> = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&deserialized).unwrap(), serialized);
+        assert_matches::assert_matches!(deserialized[2], MySQLValue::Blob(ref b) if b.0 == [0, 255, 7]);
+
+        let value = MySQLValue::EnumString {
+            name: "large".to_owned(),
+            index: 3,
+        };
+        let deserialized: MySQLValue =
+            serde_json::from_value(serde_json::to_value(&value).unwrap()).unwrap();
+        assert_matches::assert_matches!(deserialized, MySQLValue::EnumString { ref name, index: 0 } if name == "large");
+    }
+}
@@ -0,0 +1,145 @@
+//! Typed views of a [`BinlogEvent`](crate::BinlogEvent) for each kind of event, returned by
+//! [`as_row_change`](crate::BinlogEvent::as_row_change),
+//! [`as_query`](crate::BinlogEvent::as_query), and [`as_ddl`](crate::BinlogEvent::as_ddl).
+//!
+//! Unlike the `BinlogEvent` itself, the fields which are always present for a given kind of event
+//! are not optional.
+
+use serde::Serialize;
+
+use crate::event::{RowEvent, TypeCode};
+use crate::gtid::Gtid;
+use crate::table_map::TableRef;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// The kind of change made by a rows event
+pub enum RowChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl RowChangeKind {
+    pub(crate) fn from_type_code(type_code: TypeCode) -> Option<Self> {
+        match type_code {
+            TypeCode::WriteRowsEventV1 | TypeCode::WriteRowsEventV2 => Some(RowChangeKind::Insert),
+            TypeCode::UpdateRowsEventV1
+            | TypeCode::UpdateRowsEventV2
+            | TypeCode::PartialUpdateRowsEvent => Some(RowChangeKind::Update),
+            TypeCode::DeleteRowsEventV1 | TypeCode::DeleteRowsEventV2 => {
+                Some(RowChangeKind::Delete)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+/// A rows event: one or more rows inserted into, updated in, or deleted from a single table
+pub struct RowChange<'a> {
+    pub kind: RowChangeKind,
+    pub table: &'a TableRef,
+    /// The decoded rows; empty if only summaries were requested
+    pub rows: &'a [RowEvent],
+    pub gtid: Option<Gtid>,
+    pub timestamp: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+/// A statement logged as a QueryEvent, including the `BEGIN` which starts each transaction
+pub struct Query<'a> {
+    pub query: &'a str,
+    /// The default database the statement ran in (empty if there was none)
+    pub schema: &'a str,
+    pub gtid: Option<Gtid>,
+    pub timestamp: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// The kind of schema change made by a DDL statement
+pub enum DdlKind {
+    Create,
+    Alter,
+    Drop,
+    Rename,
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+/// A QueryEvent containing a statement which changes the schema
+pub struct Ddl<'a> {
+    pub kind: DdlKind,
+    pub query: &'a str,
+    /// The default database, which unqualified table names in the statement refer to
+    pub schema: &'a str,
+    pub gtid: Option<Gtid>,
+    pub timestamp: u32,
+    pub offset: u64,
+}
+
+/// Skip whitespace and comments (which the server preserves, e.g. for `/* ... */` hints) at the
+/// start of a statement
+fn strip_leading_comments(mut query: &str) -> &str {
+    loop {
+        query = query.trim_start();
+        if let Some(rest) = query.strip_prefix("/*") {
+            match rest.find("*/") {
+                Some(end) => query = &rest[end + 2..],
+                None => return "",
+            }
+        } else if query.starts_with("--") || query.starts_with('#') {
+            match query.find('\n') {
+                Some(end) => query = &query[end + 1..],
+                None => return "",
+            }
+        } else {
+            return query;
+        }
+    }
+}
+
+impl DdlKind {
+    /// Classify a statement by its first keyword, returning `None` if it is not DDL
+    pub(crate) fn from_query(query: &str) -> Option<Self> {
+        let keyword = strip_leading_comments(query)
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()?;
+        let kind = match keyword.to_ascii_uppercase().as_str() {
+            "CREATE" => DdlKind::Create,
+            "ALTER" => DdlKind::Alter,
+            "DROP" => DdlKind::Drop,
+            "RENAME" => DdlKind::Rename,
+            "TRUNCATE" => DdlKind::Truncate,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DdlKind;
+
+    #[test]
+    fn test_ddl_kind() {
+        assert_eq!(
+            DdlKind::from_query("CREATE TABLE foo(id INT)"),
+            Some(DdlKind::Create)
+        );
+        assert_eq!(
+            DdlKind::from_query("/* hint */ alter table foo add column bar int"),
+            Some(DdlKind::Alter)
+        );
+        assert_eq!(
+            DdlKind::from_query("-- comment\n  DROP TABLE `foo`"),
+            Some(DdlKind::Drop)
+        );
+        assert_eq!(DdlKind::from_query("BEGIN"), None);
+        assert_eq!(DdlKind::from_query("/* unterminated"), None);
+        assert_eq!(DdlKind::from_query(""), None);
+    }
+}